@@ -21,6 +21,14 @@ pub enum HookTrigger {
     AgentSpawn,
     /// Triggered per user message submission
     UserPromptSubmit,
+    /// Triggered before a tool is used, able to allow, deny or rewrite the tool use
+    PreToolUse,
+    /// Triggered after a tool is used
+    PostToolUse,
+    /// Triggered when the assistant finishes responding to the user
+    Stop,
+    /// Triggered when the chat session ends
+    SessionEnd,
 }
 
 impl Display for HookTrigger {
@@ -28,6 +36,10 @@ impl Display for HookTrigger {
         match self {
             HookTrigger::AgentSpawn => write!(f, "agentSpawn"),
             HookTrigger::UserPromptSubmit => write!(f, "userPromptSubmit"),
+            HookTrigger::PreToolUse => write!(f, "preToolUse"),
+            HookTrigger::PostToolUse => write!(f, "postToolUse"),
+            HookTrigger::Stop => write!(f, "stop"),
+            HookTrigger::SessionEnd => write!(f, "sessionEnd"),
         }
     }
 }
@@ -61,6 +73,11 @@ pub struct Hook {
     #[serde(default = "Hook::default_cache_ttl_seconds")]
     pub cache_ttl_seconds: u64,
 
+    /// Glob pattern of the names of the tools that trigger the hook, such as `fs_write` or
+    /// `@git/*`. Only used by preToolUse and postToolUse hooks, which match every tool if unset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub matcher: Option<String>,
+
     #[schemars(skip)]
     #[serde(default, skip_serializing)]
     pub source: Source,
@@ -73,10 +90,22 @@ impl Hook {
             timeout_ms: Self::default_timeout_ms(),
             max_output_size: Self::default_max_output_size(),
             cache_ttl_seconds: Self::default_cache_ttl_seconds(),
+            matcher: None,
             source,
         }
     }
 
+    /// Whether the hook is triggered by the tool with the given name, see [Self::matcher].
+    pub fn matches_tool(&self, tool_name: &str) -> bool {
+        let Some(matcher) = &self.matcher else {
+            return true;
+        };
+        globset::Glob::new(matcher).map_or_else(
+            |_invalid| matcher == tool_name,
+            |glob| glob.compile_matcher().is_match(tool_name),
+        )
+    }
+
     fn default_timeout_ms() -> u64 {
         DEFAULT_TIMEOUT_MS
     }
@@ -89,3 +118,26 @@ impl Hook {
         DEFAULT_CACHE_TTL_SECONDS
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_matches_tool() {
+        let mut hook = Hook::new("true".to_string(), Source::Agent);
+        assert!(hook.matches_tool("fs_write"));
+
+        hook.matcher = Some("fs_write".to_string());
+        assert!(hook.matches_tool("fs_write"));
+        assert!(!hook.matches_tool("fs_read"));
+
+        hook.matcher = Some("@git/*".to_string());
+        assert!(hook.matches_tool("@git/git_status"));
+        assert!(!hook.matches_tool("@github/list_issues"));
+
+        hook.matcher = Some("fs_*".to_string());
+        assert!(hook.matches_tool("fs_read"));
+        assert!(!hook.matches_tool("execute_bash"));
+    }
+}
@@ -1,6 +1,7 @@
 pub mod hook;
 mod legacy;
 mod mcp_config;
+pub mod permission;
 mod root_command_args;
 mod wrapper_types;
 
@@ -63,6 +64,7 @@ use crate::cli::agent::hook::{
     Hook,
     HookTrigger,
 };
+use crate::cli::agent::permission::Permissions;
 use crate::database::settings::Setting;
 use crate::os::Os;
 use crate::util::{
@@ -145,10 +147,19 @@ pub struct Agent {
     /// List of tools the agent is explicitly allowed to use
     #[serde(default)]
     pub allowed_tools: HashSet<String>,
+    /// Rules such as \"fs_write(src/**)\" or \"execute_bash(cargo test *)\" deciding which tool
+    /// uses are allowed or denied without asking the user. These take precedence over
+    /// allowedTools and toolsSettings
+    #[serde(default, skip_serializing_if = "Permissions::is_empty")]
+    pub permissions: Permissions,
     /// Files to include in the agent's context
     #[serde(default)]
     pub resources: Vec<ResourcePath>,
-    /// Commands to run when a chat session is created
+    /// Directories the agent works in, shared with MCP servers that ask for their roots. When
+    /// empty, the directories of the file resources are used instead
+    #[serde(default)]
+    pub roots: Vec<String>,
+    /// Commands to run at specific trigger points of a chat session
     #[serde(default)]
     pub hooks: HashMap<HookTrigger, Vec<Hook>>,
     /// Settings for specific tools. These are mostly for native tools. The actual schema differs by
@@ -181,10 +192,12 @@ impl Default for Agent {
                 set.extend(default_approve);
                 set
             },
+            permissions: Default::default(),
             resources: vec!["file://AmazonQ.md", "file://README.md", "file://.amazonq/rules/**/*.md"]
                 .into_iter()
                 .map(Into::into)
                 .collect::<Vec<_>>(),
+            roots: Default::default(),
             hooks: Default::default(),
             tools_settings: Default::default(),
             use_legacy_mcp_json: true,
@@ -241,12 +254,90 @@ impl Agent {
         Ok(())
     }
 
+    /// Returns the directories MCP servers may operate in: `cwd`, followed by the [Self::roots]
+    /// of the agent or, if there are none, the directories of its file resources. Directories
+    /// inside another root are left out.
+    pub fn workspace_roots(&self, cwd: &Path, home: Option<&Path>) -> Vec<PathBuf> {
+        let resolve = |path: &str| -> Option<PathBuf> {
+            let path = match path.strip_prefix("~/") {
+                Some(rest) => home?.join(rest),
+                None => PathBuf::from(path),
+            };
+            Some(cwd.join(path))
+        };
+        let candidates = if self.roots.is_empty() {
+            self.resources
+                .iter()
+                .filter_map(|resource| resource.strip_prefix("file://"))
+                .filter_map(|path| {
+                    // Globs can only be resolved to the directory before the first pattern.
+                    let prefix = match path.find(['*', '?', '[']) {
+                        Some(idx) => &path[..idx],
+                        None => path,
+                    };
+                    let path = resolve(prefix)?;
+                    if prefix.ends_with('/') || path.is_dir() {
+                        Some(path)
+                    } else {
+                        path.parent().map(Path::to_path_buf)
+                    }
+                })
+                .collect::<Vec<_>>()
+        } else {
+            self.roots.iter().filter_map(|root| resolve(root)).collect()
+        };
+
+        let mut roots = vec![cwd.to_path_buf()];
+        for candidate in candidates {
+            let candidate = normalize_path(&candidate);
+            if roots.iter().any(|root| candidate.starts_with(root)) {
+                continue;
+            }
+            roots.retain(|root| !root.starts_with(&candidate));
+            roots.push(candidate);
+        }
+        roots
+    }
+
     pub fn to_str_pretty(&self) -> eyre::Result<String> {
         let mut agent_clone = self.clone();
         agent_clone.freeze();
         Ok(serde_json::to_string_pretty(&agent_clone)?)
     }
 
+    /// Adds `rule` to the allow rules of [Self::permissions] and saves it to the config file of the
+    /// agent.
+    pub async fn persist_allow_rule(&mut self, os: &Os, rule: &str) -> eyre::Result<()> {
+        self.update_allow_rules(os, |rules| {
+            if !rules.iter().any(|r| r == rule) {
+                rules.push(rule.to_string());
+            }
+        })
+        .await
+    }
+
+    /// Removes `rule` from the allow rules of [Self::permissions], both in this session and in the
+    /// config file of the agent.
+    pub async fn revoke_allow_rule(&mut self, os: &Os, rule: &str) -> eyre::Result<()> {
+        self.update_allow_rules(os, |rules| rules.retain(|r| r != rule)).await
+    }
+
+    /// Applies `update` to the allow rules of the agent as written in its config file, so that
+    /// changes made only for this session (e.g. trusted tools) are not saved along with it.
+    async fn update_allow_rules(&mut self, os: &Os, update: impl Fn(&mut Vec<String>)) -> eyre::Result<()> {
+        let Some(path) = self.path.clone() else {
+            bail!("Agent {} has no config file to save permission rules to", self.name);
+        };
+
+        let content = os.fs.read(&path).await?;
+        let mut agent = serde_json::from_slice::<Agent>(&content)?;
+        update(&mut agent.permissions.allow);
+        os.fs.write(&path, agent.to_str_pretty()?).await?;
+
+        update(&mut self.permissions.allow);
+        Ok(())
+    }
+
     /// Retrieves an agent by name. It does so via first seeking the given agent under local dir,
     /// and falling back to global dir if it does not exist in local.
     pub async fn get_agent_by_name(os: &Os, agent_name: &str) -> eyre::Result<(Agent, PathBuf)> {
@@ -310,6 +401,10 @@ pub enum PermissionEvalResult {
     Allow,
     Ask,
     Deny,
+    /// Allowed by the given rule of [Agent::permissions]
+    AllowedByRule(String),
+    /// Denied by the given rule of [Agent::permissions]
+    DeniedByRule(String),
 }
 
 #[derive(Clone, Default, Debug)]
@@ -719,6 +814,7 @@ impl Agents {
             "execute_bash" => "trust read-only commands".dark_grey(),
             #[cfg(windows)]
             "execute_cmd" => "trust read-only commands".dark_grey(),
+            "background_process" => "trust all operations other than write".dark_grey(),
             "use_aws" => "trust read-only commands".dark_grey(),
             "report_issue" => "trusted".dark_green().bold(),
             "thinking" => "trusted (prerelease)".dark_green().bold(),
@@ -797,6 +893,21 @@ async fn load_legacy_mcp_config(os: &Os) -> eyre::Result<Option<McpServerConfig>
     })
 }
 
+/// Resolves `.` and `..` components without touching the file system.
+fn normalize_path(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            std::path::Component::CurDir => {},
+            std::path::Component::ParentDir => {
+                normalized.pop();
+            },
+            component => normalized.push(component),
+        }
+    }
+    normalized
+}
+
 fn default_schema() -> String {
     "https://raw.githubusercontent.com/aws/amazon-q-developer-cli/refs/heads/main/schemas/agent-v1.json".into()
 }
@@ -920,6 +1031,62 @@ mod tests {
         assert_eq!(result.unwrap_err().to_string(), "No agent with name nonexistent found");
     }
 
+    #[test]
+    fn test_workspace_roots() {
+        let cwd = Path::new("/work/project");
+        let home = Path::new("/home/user");
+        let agent = Agent {
+            resources: vec![
+                "file://README.md".into(),
+                "file://.amazonq/rules/**/*.md".into(),
+                "file://~/prompts/*.md".into(),
+                "file://../shared/notes.md".into(),
+            ],
+            ..Default::default()
+        };
+        assert_eq!(agent.workspace_roots(cwd, Some(home)), vec![
+            PathBuf::from("/work/project"),
+            PathBuf::from("/home/user/prompts"),
+            PathBuf::from("/work/shared"),
+        ]);
+
+        let agent = Agent {
+            roots: vec!["/work".to_string(), "~/docs".to_string()],
+            ..agent
+        };
+        assert_eq!(agent.workspace_roots(cwd, Some(home)), vec![
+            PathBuf::from("/work"),
+            PathBuf::from("/home/user/docs"),
+        ]);
+    }
+
+    #[tokio::test]
+    async fn test_persist_allow_rule() {
+        let os = Os::new().await.unwrap();
+        let path = PathBuf::from("/agents/some_agent.json");
+        os.fs.create_dir_all("/agents").await.unwrap();
+        os.fs.write(&path, INPUT).await.unwrap();
+
+        let mut agent = serde_json::from_str::<Agent>(INPUT).unwrap();
+        agent.path = Some(path.clone());
+        // Session only changes are not saved along with the rule.
+        agent.allowed_tools.insert("fs_write".to_string());
+
+        agent.persist_allow_rule(&os, "fs_write(src/**)").await.unwrap();
+        agent.persist_allow_rule(&os, "fs_write(src/**)").await.unwrap();
+        assert_eq!(agent.permissions.allow, vec!["fs_write(src/**)".to_string()]);
+        let saved = serde_json::from_slice::<Agent>(&os.fs.read(&path).await.unwrap()).unwrap();
+        assert_eq!(saved.permissions.allow, vec!["fs_write(src/**)".to_string()]);
+        assert!(!saved.allowed_tools.contains("fs_write"));
+
+        agent.revoke_allow_rule(&os, "fs_write(src/**)").await.unwrap();
+        assert!(agent.permissions.allow.is_empty());
+        let saved = serde_json::from_slice::<Agent>(&os.fs.read(&path).await.unwrap()).unwrap();
+        assert!(saved.permissions.allow.is_empty());
+
+        assert!(Agent::default().persist_allow_rule(&os, "fs_read").await.is_err());
+    }
+
     #[test]
     fn test_validate_agent_name() {
         // Valid names
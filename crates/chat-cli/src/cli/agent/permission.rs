@@ -0,0 +1,399 @@
+use std::path::{
+    Component,
+    Path,
+    PathBuf,
+};
+
+use schemars::JsonSchema;
+use serde::{
+    Deserialize,
+    Serialize,
+};
+
+use super::PermissionEvalResult;
+use crate::util::MCP_SERVER_TOOL_DELIMITER;
+
+/// Rules deciding which tool uses are allowed or denied without asking the user.
+///
+/// A rule is a tool name optionally followed by a pattern on its arguments in parentheses, such
+/// as `fs_write(src/**)`, `execute_bash(cargo test *)` or `@github/create_issue(repo=aws/*)`. Tool
+/// names may be globs, and `@server_name` matches every tool of an MCP server. A rule followed by
+/// `in <directory>`, such as `execute_bash(cargo test *) in /repo`, only applies while the current
+/// directory is within that directory.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, Eq, PartialEq, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct Permissions {
+    /// Rules of the tool uses that are allowed without asking the user
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub allow: Vec<String>,
+    /// Rules of the tool uses that are rejected. These take precedence over allow rules
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub deny: Vec<String>,
+}
+
+impl Permissions {
+    pub fn is_empty(&self) -> bool {
+        self.allow.is_empty() && self.deny.is_empty()
+    }
+
+    /// Evaluates the rules against a use of the tool named `tool_name`, as written in agent
+    /// configs. `matches_args` decides whether the arguments of the tool use match the pattern of
+    /// a rule, and is told whether the rule is an allow rule.
+    ///
+    /// Returns [None] if no rule matches, in which case the tool settings of the agent apply.
+    pub fn eval(
+        &self,
+        tool_name: &str,
+        cwd: &Path,
+        matches_args: impl Fn(&str, RuleEffect) -> bool,
+    ) -> Option<PermissionEvalResult> {
+        let find = |rules: &[String], effect: RuleEffect| {
+            rules
+                .iter()
+                .find(|rule| {
+                    let rule = PermissionRule::parse(rule);
+                    rule.matches_tool(tool_name)
+                        && rule.matches_dir(cwd)
+                        && rule.pattern.is_none_or(|pattern| matches_args(pattern, effect))
+                })
+                .cloned()
+        };
+
+        if let Some(rule) = find(&self.deny, RuleEffect::Deny) {
+            return Some(PermissionEvalResult::DeniedByRule(rule));
+        }
+        find(&self.allow, RuleEffect::Allow).map(PermissionEvalResult::AllowedByRule)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RuleEffect {
+    Allow,
+    Deny,
+}
+
+impl RuleEffect {
+    /// Matches `pattern` against every value a tool use operates on, such as the paths it reads.
+    /// Deny rules match if any of the values match, allow rules only if all of them do.
+    pub fn matches_glob<'a>(self, pattern: &str, values: impl IntoIterator<Item = &'a str>) -> bool {
+        let Ok(glob) = globset::Glob::new(pattern) else {
+            return false;
+        };
+        let matcher = glob.compile_matcher();
+        let mut values = values.into_iter().peekable();
+        if values.peek().is_none() {
+            return false;
+        }
+        match self {
+            RuleEffect::Allow => values.all(|value| matcher.is_match(value)),
+            RuleEffect::Deny => values.any(|value| matcher.is_match(value)),
+        }
+    }
+
+    /// Like [Self::matches_glob], but for paths: `pattern` and `paths` are both normalized with
+    /// [normalize_path] first, so that `src/../../.bashrc` does not match `src/**` and
+    /// `~/.ssh/id_rsa` matches `/home/user/.ssh/**`.
+    pub fn matches_path_glob<'a>(
+        self,
+        pattern: &str,
+        paths: impl IntoIterator<Item = &'a str>,
+        cwd: &Path,
+        home: Option<&Path>,
+    ) -> bool {
+        let pattern = normalize_path(pattern, cwd, home);
+        let paths = paths
+            .into_iter()
+            .map(|path| normalize_path(path, cwd, home))
+            .collect::<Vec<_>>();
+        self.matches_glob(&pattern, paths.iter().map(String::as_str))
+    }
+}
+
+/// Makes `path` absolute against `cwd`, expanding a leading `~` to `home`, and resolves `.` and
+/// `..` without touching the file system.
+///
+/// Patterns starting with a glob, such as `**/*.rs`, match anywhere and are left relative.
+pub fn normalize_path(path: &str, cwd: &Path, home: Option<&Path>) -> String {
+    let mut components = Path::new(path).components().peekable();
+    let mut res = PathBuf::new();
+    match components.peek() {
+        Some(Component::Normal(first)) if *first == "~" => {
+            if let Some(home) = home {
+                res.push(home);
+                components.next();
+            }
+        },
+        Some(Component::Normal(first)) if first.to_str().is_some_and(|s| s.contains(['*', '?', '[', '{'])) => {},
+        Some(Component::Normal(_) | Component::CurDir | Component::ParentDir) => res.push(cwd),
+        _ => {},
+    }
+    for component in components {
+        match component {
+            Component::CurDir => {},
+            Component::ParentDir => {
+                res.pop();
+            },
+            component => res.push(component),
+        }
+    }
+    res.to_string_lossy().into_owned()
+}
+
+/// Suggests a rule allowing `tool_name` to access every file under the deepest directory that
+/// contains all of `paths`, such as `fs_write(src/**)`. A single path in the current directory is
+/// allowed by itself.
+pub fn directory_rule<'a>(tool_name: &str, paths: impl IntoIterator<Item = &'a str>) -> Option<String> {
+    let paths = paths.into_iter().collect::<Vec<_>>();
+    let mut common: Option<PathBuf> = None;
+    for path in &paths {
+        let parent = Path::new(path).parent().unwrap_or(Path::new(""));
+        common = Some(match common {
+            None => parent.to_path_buf(),
+            Some(common) => common
+                .components()
+                .zip(parent.components())
+                .take_while(|(a, b)| a == b)
+                .map(|(a, _)| a)
+                .collect(),
+        });
+    }
+
+    let common = common?;
+    match common.to_str()? {
+        "" | "/" | "~" => match paths.as_slice() {
+            [path] => Some(format!("{tool_name}({path})")),
+            _ => None,
+        },
+        dir => Some(format!("{tool_name}({}/**)", dir.trim_end_matches('/'))),
+    }
+}
+
+/// A parsed rule of [Permissions].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PermissionRule<'a> {
+    pub tool: &'a str,
+    pub pattern: Option<&'a str>,
+    /// The directory the rule is limited to, as in `fs_read in /repo`.
+    pub dir: Option<&'a str>,
+}
+
+impl<'a> PermissionRule<'a> {
+    pub fn parse(rule: &'a str) -> Self {
+        let rule = rule.trim();
+        let (rule, dir) = match rule.rsplit_once(" in ") {
+            Some((scoped, dir)) if !rule.ends_with(')') && (scoped.ends_with(')') || !scoped.contains('(')) => {
+                (scoped.trim(), Some(dir.trim()))
+            },
+            _ => (rule, None),
+        };
+        match rule.split_once('(') {
+            Some((tool, pattern)) if rule.ends_with(')') => Self {
+                tool: tool.trim(),
+                pattern: Some(pattern[..pattern.len() - 1].trim()),
+                dir,
+            },
+            _ => Self {
+                tool: rule,
+                pattern: None,
+                dir,
+            },
+        }
+    }
+
+    /// Whether the rule applies in the current directory `cwd`, which is always the case for rules
+    /// not limited to a directory.
+    pub fn matches_dir(&self, cwd: &Path) -> bool {
+        self.dir.is_none_or(|dir| {
+            let dir = normalize_path(dir, cwd, dirs::home_dir().as_deref());
+            cwd.starts_with(dir)
+        })
+    }
+
+    /// Whether the rule applies to the tool named `tool_name`, such as `fs_read` or
+    /// `@git/git_status`.
+    pub fn matches_tool(&self, tool_name: &str) -> bool {
+        if self.tool == tool_name {
+            return true;
+        }
+        if let (Some(server), Some(name)) = (self.tool.strip_prefix('@'), tool_name.strip_prefix('@')) {
+            if !server.contains(MCP_SERVER_TOOL_DELIMITER) {
+                return name
+                    .split_once(MCP_SERVER_TOOL_DELIMITER)
+                    .is_some_and(|(tool_server, _)| tool_server == server);
+            }
+        }
+        globset::Glob::new(self.tool).is_ok_and(|glob| glob.compile_matcher().is_match(tool_name))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_rule() {
+        assert_eq!(PermissionRule::parse("fs_read"), PermissionRule {
+            tool: "fs_read",
+            pattern: None,
+            dir: None,
+        });
+        assert_eq!(PermissionRule::parse("execute_bash(cargo test *)"), PermissionRule {
+            tool: "execute_bash",
+            pattern: Some("cargo test *"),
+            dir: None,
+        });
+        assert_eq!(
+            PermissionRule::parse("@github/create_issue(repo=aws/*)"),
+            PermissionRule {
+                tool: "@github/create_issue",
+                pattern: Some("repo=aws/*"),
+                dir: None,
+            }
+        );
+        assert_eq!(
+            PermissionRule::parse("execute_bash(cargo test *) in /repo"),
+            PermissionRule {
+                tool: "execute_bash",
+                pattern: Some("cargo test *"),
+                dir: Some("/repo"),
+            }
+        );
+        assert_eq!(PermissionRule::parse("@git in /repo"), PermissionRule {
+            tool: "@git",
+            pattern: None,
+            dir: Some("/repo"),
+        });
+        assert_eq!(PermissionRule::parse("execute_bash(grep in *)"), PermissionRule {
+            tool: "execute_bash",
+            pattern: Some("grep in *"),
+            dir: None,
+        });
+    }
+
+    #[test]
+    fn test_matches_tool() {
+        assert!(PermissionRule::parse("fs_write(src/**)").matches_tool("fs_write"));
+        assert!(!PermissionRule::parse("fs_write").matches_tool("fs_read"));
+        assert!(PermissionRule::parse("@git").matches_tool("@git/git_status"));
+        assert!(!PermissionRule::parse("@git").matches_tool("@github/list_issues"));
+        assert!(PermissionRule::parse("@git/git_*").matches_tool("@git/git_status"));
+        assert!(PermissionRule::parse("*").matches_tool("use_aws"));
+    }
+
+    #[test]
+    fn test_matches_dir() {
+        let rule = PermissionRule::parse("execute_bash(cargo test *) in /repo");
+        assert!(rule.matches_dir(Path::new("/repo")));
+        assert!(rule.matches_dir(Path::new("/repo/crates/cli")));
+        assert!(!rule.matches_dir(Path::new("/repository")));
+        assert!(!rule.matches_dir(Path::new("/other")));
+        assert!(PermissionRule::parse("execute_bash(cargo test *)").matches_dir(Path::new("/other")));
+    }
+
+    #[test]
+    fn test_eval() {
+        let permissions = Permissions {
+            allow: vec![
+                "fs_write(src/**)".to_string(),
+                "@git".to_string(),
+                "execute_bash(cargo test *) in /repo".to_string(),
+            ],
+            deny: vec!["fs_write(src/secrets/**)".to_string()],
+        };
+        let paths = |paths: &'static [&'static str]| {
+            move |pattern: &str, effect: RuleEffect| effect.matches_glob(pattern, paths.iter().copied())
+        };
+        let cwd = Path::new("/repo");
+
+        assert_eq!(
+            permissions.eval("fs_write", cwd, paths(&["src/main.rs"])),
+            Some(PermissionEvalResult::AllowedByRule("fs_write(src/**)".to_string()))
+        );
+        assert_eq!(
+            permissions.eval("fs_write", cwd, paths(&["src/main.rs", "src/secrets/key.pem"])),
+            Some(PermissionEvalResult::DeniedByRule(
+                "fs_write(src/secrets/**)".to_string()
+            ))
+        );
+        assert_eq!(
+            permissions.eval("fs_write", cwd, paths(&["src/main.rs", "Cargo.toml"])),
+            None
+        );
+        assert_eq!(
+            permissions.eval("@git/git_status", cwd, paths(&[])),
+            Some(PermissionEvalResult::AllowedByRule("@git".to_string()))
+        );
+        assert_eq!(permissions.eval("execute_bash", cwd, paths(&["ls"])), None);
+        let command = |pattern: &str, _| pattern == "cargo test *";
+        assert_eq!(
+            permissions.eval("execute_bash", cwd, command),
+            Some(PermissionEvalResult::AllowedByRule(
+                "execute_bash(cargo test *) in /repo".to_string()
+            ))
+        );
+        assert_eq!(permissions.eval("execute_bash", Path::new("/other"), command), None);
+    }
+
+    #[test]
+    fn test_normalize_path() {
+        let cwd = Path::new("/repo");
+        let home = Some(Path::new("/home/user"));
+        assert_eq!(normalize_path("src/main.rs", cwd, home), "/repo/src/main.rs");
+        assert_eq!(normalize_path("./src/../Cargo.toml", cwd, home), "/repo/Cargo.toml");
+        assert_eq!(normalize_path("src/../../.bashrc", cwd, home), "/.bashrc");
+        assert_eq!(normalize_path("~/.ssh/id_rsa", cwd, home), "/home/user/.ssh/id_rsa");
+        assert_eq!(normalize_path("~/.ssh/id_rsa", cwd, None), "~/.ssh/id_rsa");
+        assert_eq!(normalize_path("/etc/../etc/hosts", cwd, home), "/etc/hosts");
+        assert_eq!(normalize_path("src/**", cwd, home), "/repo/src/**");
+        assert_eq!(normalize_path("**/*.rs", cwd, home), "**/*.rs");
+    }
+
+    #[test]
+    fn test_matches_path_glob() {
+        let cwd = Path::new("/home/user/repo");
+        let home = Some(Path::new("/home/user"));
+        let matches = |effect: RuleEffect, pattern: &str, paths: &[&str]| {
+            effect.matches_path_glob(pattern, paths.iter().copied(), cwd, home)
+        };
+
+        // Traversal out of an allowed directory.
+        assert!(matches(RuleEffect::Allow, "src/**", &["src/main.rs"]));
+        assert!(matches(RuleEffect::Allow, "src/**", &["/home/user/repo/src/main.rs"]));
+        assert!(!matches(RuleEffect::Allow, "src/**", &["src/../../.bashrc"]));
+        assert!(!matches(RuleEffect::Allow, "src/**", &[
+            "src/main.rs",
+            "src/../Cargo.toml"
+        ]));
+
+        // Home directory in either the rule or the path.
+        assert!(matches(RuleEffect::Deny, "/home/user/.ssh/**", &["~/.ssh/id_rsa"]));
+        assert!(matches(RuleEffect::Deny, "~/.ssh/**", &["/home/user/.ssh/id_rsa"]));
+        assert!(matches(RuleEffect::Deny, "~/.ssh/**", &["../.ssh/id_rsa"]));
+        assert!(matches(RuleEffect::Deny, "/etc/**", &[
+            "src/main.rs",
+            "/tmp/../etc/passwd"
+        ]));
+        assert!(!matches(RuleEffect::Deny, "~/.ssh/**", &["src/.ssh/id_rsa"]));
+
+        assert!(matches(RuleEffect::Allow, "**/*.rs", &["src/main.rs", "/tmp/x.rs"]));
+    }
+
+    #[test]
+    fn test_directory_rule() {
+        assert_eq!(
+            directory_rule("fs_write", ["src/cli/mod.rs", "src/main.rs"]),
+            Some("fs_write(src/**)".to_string())
+        );
+        assert_eq!(
+            directory_rule("fs_read", ["/repo/src/lib.rs"]),
+            Some("fs_read(/repo/src/**)".to_string())
+        );
+        assert_eq!(
+            directory_rule("fs_write", ["Cargo.toml"]),
+            Some("fs_write(Cargo.toml)".to_string())
+        );
+        assert_eq!(directory_rule("fs_write", ["Cargo.toml", "src/main.rs"]), None);
+        assert_eq!(directory_rule("fs_write", ["/etc/hosts", "/home/user/.bashrc"]), None);
+        assert_eq!(directory_rule("fs_write", []), None);
+    }
+}
@@ -0,0 +1,391 @@
+use std::collections::{
+    BTreeMap,
+    BTreeSet,
+};
+use std::path::{
+    Path,
+    PathBuf,
+};
+
+use eyre::Result;
+use time::OffsetDateTime;
+use tracing::warn;
+
+use super::tools::Tool;
+use crate::database::settings::Setting;
+use crate::os::Os;
+
+/// Maximum number of characters of the prompt stored with a checkpoint.
+const MAX_PROMPT_PREVIEW_LEN: usize = 80;
+/// Files larger than this are not captured when snapshotting a watched tree.
+const MAX_WATCHED_FILE_SIZE: u64 = 1024 * 1024;
+/// Maximum number of files captured when snapshotting a watched tree.
+const MAX_WATCHED_FILES: usize = 10_000;
+/// Maximum total size of the files captured when snapshotting a watched tree.
+const MAX_WATCHED_TOTAL_SIZE: u64 = 64 * 1024 * 1024;
+
+/// Records the original content of every file modified by a tool, grouped by the user turn that
+/// modified it, so that the workspace can be rolled back to the state before any turn.
+#[derive(Debug, Clone, Default)]
+pub struct CheckpointStore {
+    /// The current turn, incremented on every user prompt.
+    turn: usize,
+    /// Preview of the prompt that started the current turn.
+    prompt: String,
+    checkpoints: Vec<Checkpoint>,
+}
+
+/// The files modified during a single turn.
+#[derive(Debug, Clone)]
+pub struct Checkpoint {
+    pub turn: usize,
+    pub prompt: String,
+    pub timestamp: OffsetDateTime,
+    /// The content of each file before the turn first modified it, [None] if the file did not
+    /// exist.
+    pub files: BTreeMap<PathBuf, Option<String>>,
+}
+
+/// A file modified during a turn, see [CheckpointStore::diff].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileChange {
+    pub path: PathBuf,
+    pub before: Option<String>,
+    pub after: Option<String>,
+}
+
+impl CheckpointStore {
+    /// Starts a new turn. Files snapshotted from now on are restored by `/checkpoint restore` for
+    /// this turn.
+    pub fn begin_turn(&mut self, prompt: &str) {
+        self.turn += 1;
+        let prompt = prompt.lines().next().unwrap_or_default().trim();
+        self.prompt = match prompt.char_indices().nth(MAX_PROMPT_PREVIEW_LEN) {
+            Some((i, _)) => format!("{}...", &prompt[..i]),
+            None => prompt.to_string(),
+        };
+    }
+
+    /// Creates a store from the parts returned by [Self::turn], [Self::prompt] and
+    /// [Self::checkpoints], such as when loading a saved conversation.
+    pub fn from_parts(turn: usize, prompt: String, checkpoints: Vec<Checkpoint>) -> Self {
+        Self {
+            turn,
+            prompt,
+            checkpoints,
+        }
+    }
+
+    pub fn turn(&self) -> usize {
+        self.turn
+    }
+
+    pub fn prompt(&self) -> &str {
+        &self.prompt
+    }
+
+    pub fn checkpoints(&self) -> &[Checkpoint] {
+        &self.checkpoints
+    }
+
+    pub fn get(&self, turn: usize) -> Option<&Checkpoint> {
+        self.checkpoints.iter().find(|c| c.turn == turn)
+    }
+
+    /// Records the current content of `paths` unless the current turn has already done so.
+    pub async fn snapshot(&mut self, os: &Os, paths: impl IntoIterator<Item = PathBuf>) {
+        for path in paths {
+            if self.get(self.turn).is_some_and(|c| c.files.contains_key(&path)) {
+                continue;
+            }
+            let original = if os.fs.exists(&path) {
+                match os.fs.read_to_string(&path).await {
+                    Ok(content) => Some(content),
+                    Err(err) => {
+                        warn!(?err, ?path, "unable to snapshot file, it will not be restorable");
+                        continue;
+                    },
+                }
+            } else {
+                None
+            };
+            self.record(path, original);
+        }
+    }
+
+    /// Snapshots the files `tool` may modify before it runs. For `execute_bash`, whose changes are
+    /// found by comparing the files before and after, a snapshot of the current directory is
+    /// returned to pass to [Self::record_tool_changes].
+    pub async fn snapshot_tool(&mut self, os: &Os, tool: &Tool) -> Option<TreeSnapshot> {
+        match tool {
+            Tool::FsWrite(fs_write) => {
+                self.snapshot(os, fs_write.paths(os)).await;
+                None
+            },
+            Tool::ExecuteCommand(_)
+                if os
+                    .database
+                    .settings
+                    .get_bool(Setting::ChatCheckpointExecuteBash)
+                    .unwrap_or(false) =>
+            {
+                let cwd = os.env.current_dir().ok()?;
+                Some(TreeSnapshot::capture(os, cwd).await)
+            },
+            _ => None,
+        }
+    }
+
+    /// Records the files modified by `tool` after it ran, see [Self::snapshot_tool]. Subagents
+    /// record the files their own tools modify, which are added to the current turn.
+    pub async fn record_tool_changes(&mut self, os: &Os, tool: &Tool, tree_snapshot: Option<TreeSnapshot>) {
+        if let Some(before) = tree_snapshot {
+            if let Ok(cwd) = os.env.current_dir() {
+                let after = TreeSnapshot::capture(os, cwd).await;
+                self.record_tree_changes(&before, &after);
+            }
+        }
+        if let Tool::UseSubagent(use_subagent) = tool {
+            if let Some(context) = &use_subagent.context {
+                let subagent_checkpoints = context.checkpoints.lock().await;
+                for (path, original) in subagent_checkpoints.restore_plan(0) {
+                    self.record(path, original);
+                }
+            }
+        }
+    }
+
+    /// Records the files changed between two snapshots of a watched tree.
+    pub fn record_tree_changes(&mut self, before: &TreeSnapshot, after: &TreeSnapshot) {
+        for (path, content) in &before.files {
+            if after.files.get(path) != Some(content) {
+                self.record(path.clone(), Some(content.clone()));
+            }
+        }
+        for path in after.files.keys() {
+            // Files that existed but were not captured must not be deleted on restore.
+            if !before.files.contains_key(path) && !before.skipped.contains(path) {
+                self.record(path.clone(), None);
+            }
+        }
+    }
+
+    /// Returns the changes made to each file during `turn`, or [None] if the turn did not modify
+    /// any files.
+    pub async fn diff(&self, os: &Os, turn: usize) -> Option<Vec<FileChange>> {
+        let checkpoint = self.get(turn)?;
+        let mut changes = Vec::with_capacity(checkpoint.files.len());
+        for (path, before) in &checkpoint.files {
+            // The state after the turn is whatever the next turn to touch the file started from.
+            let after = match self
+                .checkpoints
+                .iter()
+                .filter(|c| c.turn > turn)
+                .find_map(|c| c.files.get(path))
+            {
+                Some(content) => content.clone(),
+                None => os.fs.read_to_string(path).await.ok(),
+            };
+            if *before != after {
+                changes.push(FileChange {
+                    path: path.clone(),
+                    before: before.clone(),
+                    after,
+                });
+            }
+        }
+        Some(changes)
+    }
+
+    /// Returns the content that every file modified during or after `turn` must be restored to.
+    pub fn restore_plan(&self, turn: usize) -> BTreeMap<PathBuf, Option<String>> {
+        let mut plan = BTreeMap::new();
+        for checkpoint in self.checkpoints.iter().filter(|c| c.turn >= turn) {
+            for (path, content) in &checkpoint.files {
+                plan.entry(path.clone()).or_insert_with(|| content.clone());
+            }
+        }
+        plan
+    }
+
+    /// Rolls every file back to its state before `turn` and drops the checkpoints of `turn` and
+    /// later. Returns the restored paths.
+    pub async fn restore(&mut self, os: &Os, turn: usize) -> Result<Vec<PathBuf>> {
+        let plan = self.restore_plan(turn);
+        for (path, content) in &plan {
+            match content {
+                Some(content) => {
+                    if let Some(parent) = path.parent() {
+                        os.fs.create_dir_all(parent).await?;
+                    }
+                    os.fs.write(path, content).await?;
+                },
+                None if os.fs.exists(path) => os.fs.remove_file(path).await?,
+                None => (),
+            }
+        }
+        self.checkpoints.retain(|c| c.turn < turn);
+        Ok(plan.into_keys().collect())
+    }
+
+    fn current(&mut self) -> &mut Checkpoint {
+        if self.checkpoints.last().is_none_or(|c| c.turn != self.turn) {
+            self.checkpoints.push(Checkpoint {
+                turn: self.turn,
+                prompt: self.prompt.clone(),
+                timestamp: OffsetDateTime::now_utc(),
+                files: BTreeMap::new(),
+            });
+        }
+        self.checkpoints.last_mut().expect("checkpoint was just pushed")
+    }
+
+    fn record(&mut self, path: PathBuf, original: Option<String>) {
+        self.current().files.entry(path).or_insert(original);
+    }
+}
+
+/// The text files under a directory, used to detect the changes made by `execute_bash`.
+///
+/// Ignored files (e.g. those in `.gitignore`), binary files, files larger than
+/// [MAX_WATCHED_FILE_SIZE], and files past [MAX_WATCHED_FILES] or [MAX_WATCHED_TOTAL_SIZE] are not
+/// captured.
+#[derive(Debug, Default)]
+pub struct TreeSnapshot {
+    files: BTreeMap<PathBuf, String>,
+    /// Files that exist but whose content was not captured.
+    skipped: BTreeSet<PathBuf>,
+}
+
+impl TreeSnapshot {
+    /// Walks and reads the files under `root` on a blocking thread.
+    pub async fn capture(os: &Os, root: impl AsRef<Path>) -> Self {
+        let root = os.fs.chroot_path(root);
+        tokio::task::spawn_blocking(move || Self::capture_blocking(&root))
+            .await
+            .unwrap_or_else(|err| {
+                warn!(?err, "failed to capture the files under the current directory");
+                Self::default()
+            })
+    }
+
+    fn capture_blocking(root: &Path) -> Self {
+        let mut snapshot = Self::default();
+        let mut captured_size = 0;
+        let walker = ignore::WalkBuilder::new(root)
+            .hidden(false)
+            .require_git(false)
+            .filter_entry(|entry| entry.file_name() != ".git")
+            .build();
+        for entry in walker.flatten() {
+            if !entry.file_type().is_some_and(|t| t.is_file()) {
+                continue;
+            }
+            let path = entry.into_path();
+            if snapshot.files.len() >= MAX_WATCHED_FILES {
+                snapshot.skipped.insert(path);
+                continue;
+            }
+            let content = std::fs::metadata(&path)
+                .ok()
+                .filter(|metadata| {
+                    metadata.len() <= MAX_WATCHED_FILE_SIZE && captured_size + metadata.len() <= MAX_WATCHED_TOTAL_SIZE
+                })
+                .and_then(|_| std::fs::read(&path).ok())
+                .and_then(|bytes| String::from_utf8(bytes).ok());
+            match content {
+                Some(content) => {
+                    captured_size += content.len() as u64;
+                    snapshot.files.insert(path, content);
+                },
+                None => {
+                    snapshot.skipped.insert(path);
+                },
+            }
+        }
+        snapshot
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_checkpoint_restore() {
+        let os = Os::new().await.unwrap();
+        let a = os.fs.chroot_path("/a.txt");
+        let b = os.fs.chroot_path("/b.txt");
+        os.fs.write(&a, "a1").await.unwrap();
+
+        let mut store = CheckpointStore::default();
+        store.begin_turn("first");
+        store.snapshot(&os, [a.clone()]).await;
+        os.fs.write(&a, "a2").await.unwrap();
+        // A second snapshot within the same turn keeps the original content.
+        store.snapshot(&os, [a.clone()]).await;
+        os.fs.write(&a, "a3").await.unwrap();
+
+        store.begin_turn("second, no changes");
+        store.begin_turn("third");
+        store.snapshot(&os, [a.clone(), b.clone()]).await;
+        os.fs.write(&a, "a4").await.unwrap();
+        os.fs.write(&b, "b1").await.unwrap();
+
+        assert_eq!(store.checkpoints().iter().map(|c| c.turn).collect::<Vec<_>>(), vec![
+            1, 3
+        ]);
+        assert!(store.get(2).is_none());
+
+        let changes = store.diff(&os, 1).await.unwrap();
+        assert_eq!(changes, vec![FileChange {
+            path: a.clone(),
+            before: Some("a1".to_string()),
+            after: Some("a3".to_string()),
+        }]);
+
+        // Restoring the third turn leaves the first turn's changes in place.
+        store.restore(&os, 3).await.unwrap();
+        assert_eq!(os.fs.read_to_string(&a).await.unwrap(), "a3");
+        assert!(!os.fs.exists(&b));
+        assert_eq!(store.checkpoints().len(), 1);
+
+        store.restore(&os, 1).await.unwrap();
+        assert_eq!(os.fs.read_to_string(&a).await.unwrap(), "a1");
+        assert!(store.checkpoints().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_checkpoint_tree_changes() {
+        let os = Os::new().await.unwrap();
+        os.fs.create_dir_all("/project").await.unwrap();
+        os.fs.write("/project/kept.txt", "kept").await.unwrap();
+        os.fs.write("/project/edited.txt", "before").await.unwrap();
+        os.fs.write("/project/deleted.txt", "deleted").await.unwrap();
+        os.fs.write("/project/binary.bin", [0xff, 0xfe]).await.unwrap();
+
+        let mut store = CheckpointStore::default();
+        store.begin_turn("run a script");
+        let before = TreeSnapshot::capture(&os, "/project").await;
+        os.fs.write("/project/edited.txt", "after").await.unwrap();
+        os.fs.remove_file("/project/deleted.txt").await.unwrap();
+        os.fs.write("/project/created.txt", "created").await.unwrap();
+        os.fs.write("/project/binary.bin", "now text").await.unwrap();
+        let after = TreeSnapshot::capture(&os, "/project").await;
+        store.record_tree_changes(&before, &after);
+
+        let files = &store.get(1).unwrap().files;
+        let name = |p: &PathBuf| p.file_name().unwrap().to_string_lossy().to_string();
+        assert_eq!(files.keys().map(name).collect::<Vec<_>>(), vec![
+            "created.txt",
+            "deleted.txt",
+            "edited.txt"
+        ]);
+
+        store.restore(&os, 1).await.unwrap();
+        assert_eq!(os.fs.read_to_string("/project/edited.txt").await.unwrap(), "before");
+        assert_eq!(os.fs.read_to_string("/project/deleted.txt").await.unwrap(), "deleted");
+        assert!(!os.fs.exists("/project/created.txt"));
+        assert_eq!(os.fs.read_to_string("/project/binary.bin").await.unwrap(), "now text");
+    }
+}
@@ -0,0 +1,249 @@
+use std::io::Write;
+
+use clap::Subcommand;
+use crossterm::style::{
+    self,
+    Attribute,
+    Color,
+    Stylize,
+};
+use crossterm::{
+    cursor,
+    execute,
+    queue,
+};
+use similar::{
+    ChangeTag,
+    TextDiff,
+};
+
+use crate::cli::chat::tools::format_path;
+use crate::cli::chat::{
+    ChatError,
+    ChatSession,
+    ChatState,
+};
+use crate::os::Os;
+
+#[deny(missing_docs)]
+#[derive(Debug, PartialEq, Subcommand)]
+#[command(
+    before_long_help = "Checkpoints record the content of every file before a tool modifies it, grouped by turn.
+A turn starts with each prompt you send and includes every tool the assistant runs in response.
+
+Notes:
+• Files modified by execute_bash are only recorded when chat.checkpointExecuteBash is enabled
+• Checkpoints are saved with the conversation by /save, but not kept when a session is resumed"
+)]
+pub enum CheckpointSubcommand {
+    /// List the turns that modified files
+    List,
+    /// Show the changes made to files during a turn
+    Diff { turn: usize },
+    /// Restore all files to their state before a turn
+    Restore {
+        turn: usize,
+        /// Skip the confirmation prompt
+        #[arg(short, long)]
+        force: bool,
+    },
+}
+
+impl CheckpointSubcommand {
+    pub async fn execute(self, os: &Os, session: &mut ChatSession) -> Result<ChatState, ChatError> {
+        let cwd = os.env.current_dir().unwrap_or_default();
+        match self {
+            Self::List => {
+                let checkpoints = session.conversation.checkpoints.checkpoints();
+                if checkpoints.is_empty() {
+                    execute!(
+                        session.stderr,
+                        style::SetForegroundColor(Color::DarkGrey),
+                        style::Print("\nNo files have been modified in this conversation.\n\n"),
+                        style::SetForegroundColor(Color::Reset)
+                    )?;
+                } else {
+                    queue!(session.stderr, style::Print("\n"))?;
+                    for checkpoint in checkpoints {
+                        queue!(
+                            session.stderr,
+                            style::SetAttribute(Attribute::Bold),
+                            style::Print(format!("Turn {}", checkpoint.turn)),
+                            style::SetAttribute(Attribute::Reset),
+                            style::SetForegroundColor(Color::DarkGrey),
+                            style::Print(format!(
+                                " {} ",
+                                checkpoint
+                                    .timestamp
+                                    .format(time::macros::format_description!(
+                                        "[month repr:short] [day] [hour]:[minute]"
+                                    ))
+                                    .unwrap_or_default()
+                            )),
+                            style::SetForegroundColor(Color::Reset),
+                            style::Print(format!("> {}\n", checkpoint.prompt)),
+                        )?;
+                        for (path, original) in &checkpoint.files {
+                            let status = if original.is_none() { " (created)" } else { "" };
+                            queue!(
+                                session.stderr,
+                                style::Print("  "),
+                                style::SetForegroundColor(Color::Green),
+                                style::Print(format_path(&cwd, path)),
+                                style::SetForegroundColor(Color::DarkGrey),
+                                style::Print(format!("{status}\n")),
+                                style::SetForegroundColor(Color::Reset),
+                            )?;
+                        }
+                    }
+                    execute!(session.stderr, style::Print("\n"))?;
+                }
+            },
+            Self::Diff { turn } => {
+                let Some(changes) = session.conversation.checkpoints.diff(os, turn).await else {
+                    return no_changes(session, format!("No files were modified during turn {turn}."));
+                };
+                queue!(session.stderr, style::Print("\n"))?;
+                for change in changes {
+                    let before = change.before.as_deref().unwrap_or_default();
+                    let after = change.after.as_deref().unwrap_or_default();
+                    let label = match (&change.before, &change.after) {
+                        (None, _) => " (created)",
+                        (_, None) => " (deleted)",
+                        _ => "",
+                    };
+                    queue!(
+                        session.stderr,
+                        style::SetAttribute(Attribute::Bold),
+                        style::Print(format_path(&cwd, &change.path)),
+                        style::SetAttribute(Attribute::Reset),
+                        style::Print(format!("{label}\n")),
+                    )?;
+                    let diff = TextDiff::from_lines(before, after);
+                    for hunk in diff.unified_diff().context_radius(3).iter_hunks() {
+                        queue!(
+                            session.stderr,
+                            style::SetForegroundColor(Color::Cyan),
+                            style::Print(format!("{}\n", hunk.header())),
+                        )?;
+                        for change in hunk.iter_changes() {
+                            let (sign, color) = match change.tag() {
+                                ChangeTag::Delete => ("-", Color::Red),
+                                ChangeTag::Insert => ("+", Color::Green),
+                                ChangeTag::Equal => (" ", Color::Reset),
+                            };
+                            queue!(
+                                session.stderr,
+                                style::SetForegroundColor(color),
+                                style::Print(sign),
+                                style::Print(change.value().trim_end_matches(['\r', '\n'])),
+                                style::Print("\n"),
+                            )?;
+                        }
+                    }
+                    queue!(
+                        session.stderr,
+                        style::SetForegroundColor(Color::Reset),
+                        style::Print("\n")
+                    )?;
+                }
+                session.stderr.flush()?;
+            },
+            Self::Restore { turn, force } => {
+                let plan = session.conversation.checkpoints.restore_plan(turn);
+                if plan.is_empty() {
+                    return no_changes(session, format!("No files were modified during or after turn {turn}."));
+                }
+
+                queue!(
+                    session.stderr,
+                    style::Print(format!(
+                        "\nThe following files will be restored to their state before turn {turn}:\n"
+                    )),
+                )?;
+                for (path, content) in &plan {
+                    let status = if content.is_none() { " (deleted)" } else { "" };
+                    queue!(
+                        session.stderr,
+                        style::Print("  "),
+                        style::SetForegroundColor(Color::Green),
+                        style::Print(format_path(&cwd, path)),
+                        style::SetForegroundColor(Color::DarkGrey),
+                        style::Print(format!("{status}\n")),
+                        style::SetForegroundColor(Color::Reset),
+                    )?;
+                }
+
+                if !force {
+                    execute!(
+                        session.stderr,
+                        style::SetForegroundColor(Color::DarkGrey),
+                        style::Print("\nAny changes made to these files since then will be lost. Continue? "),
+                        style::Print("["),
+                        style::SetForegroundColor(Color::Green),
+                        style::Print("y"),
+                        style::SetForegroundColor(Color::DarkGrey),
+                        style::Print("/"),
+                        style::SetForegroundColor(Color::Green),
+                        style::Print("n"),
+                        style::SetForegroundColor(Color::DarkGrey),
+                        style::Print("]:\n\n"),
+                        style::SetForegroundColor(Color::Reset),
+                        cursor::Show,
+                    )?;
+                    let user_input = session
+                        .read_user_input("> ".yellow().to_string().as_str(), true)
+                        .unwrap_or_default();
+                    if !["y", "Y"].contains(&user_input.as_str()) {
+                        return Ok(ChatState::PromptUser {
+                            skip_printing_tools: true,
+                        });
+                    }
+                }
+
+                match session.conversation.checkpoints.restore(os, turn).await {
+                    Ok(restored) => execute!(
+                        session.stderr,
+                        style::SetForegroundColor(Color::Green),
+                        style::Print(format!(
+                            "\n✔ Restored {} file(s) to their state before turn {turn}\n\n",
+                            restored.len()
+                        )),
+                        style::SetForegroundColor(Color::Reset)
+                    )?,
+                    Err(err) => execute!(
+                        session.stderr,
+                        style::SetForegroundColor(Color::Red),
+                        style::Print(format!("\nFailed to restore turn {turn}: {err}\n\n")),
+                        style::SetForegroundColor(Color::Reset)
+                    )?,
+                }
+            },
+        }
+
+        Ok(ChatState::PromptUser {
+            skip_printing_tools: true,
+        })
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::List => "list",
+            Self::Diff { .. } => "diff",
+            Self::Restore { .. } => "restore",
+        }
+    }
+}
+
+fn no_changes(session: &mut ChatSession, message: String) -> Result<ChatState, ChatError> {
+    execute!(
+        session.stderr,
+        style::SetForegroundColor(Color::Yellow),
+        style::Print(format!("\n{message}\n\n")),
+        style::SetForegroundColor(Color::Reset)
+    )?;
+
+    Ok(ChatState::PromptUser {
+        skip_printing_tools: true,
+    })
+}
@@ -0,0 +1,95 @@
+use clap::Args;
+use crossterm::execute;
+use crossterm::style::{
+    self,
+    Attribute,
+    Color,
+};
+
+use crate::cli::chat::transcript::{
+    ExportFormat,
+    ExportOptions,
+    export,
+};
+use crate::cli::chat::{
+    ChatError,
+    ChatSession,
+    ChatState,
+};
+use crate::os::Os;
+use crate::util::directories;
+
+#[deny(missing_docs)]
+#[derive(Debug, PartialEq, Args)]
+#[command(
+    before_long_help = "/export writes a readable transcript of the conversation, including the tool uses and their results,
+the context added by hooks and the time of each message. Unlike /save, the transcript cannot be loaded back.
+
+Formats
+• md: Markdown, for reading and sharing
+• html: a standalone web page
+• jsonl: one JSON object per line, for processing with other tools
+
+Saved conversations can also be exported with q chat export <SESSION_ID>"
+)]
+pub struct ExportArgs {
+    /// The file to write the transcript to
+    path: String,
+    /// The format of the transcript, guessed from the file extension if omitted
+    #[arg(long, value_enum)]
+    format: Option<ExportFormat>,
+    /// Leave out the environment of each prompt, and mask secrets and the home directory
+    #[arg(long)]
+    redact: bool,
+    /// Overwrite the file if it already exists
+    #[arg(short, long)]
+    force: bool,
+}
+
+impl ExportArgs {
+    pub async fn execute(self, os: &Os, session: &mut ChatSession) -> Result<ChatState, ChatError> {
+        if os.fs.exists(&self.path) && !self.force {
+            execute!(
+                session.stderr,
+                style::SetForegroundColor(Color::Red),
+                style::Print(format!(
+                    "\nFile at {} already exists. To overwrite, use -f or --force\n\n",
+                    &self.path
+                )),
+                style::SetAttribute(Attribute::Reset)
+            )?;
+            return Ok(ChatState::PromptUser {
+                skip_printing_tools: true,
+            });
+        }
+
+        let options = ExportOptions {
+            format: self.format.unwrap_or_else(|| ExportFormat::from_path(&self.path)),
+            redact: self.redact,
+            home_dir: directories::home_dir(os).ok(),
+        };
+        let result = match export(&session.conversation, &options) {
+            Ok(contents) => os.fs.write(&self.path, contents).await.map_err(|err| err.to_string()),
+            Err(err) => Err(err.to_string()),
+        };
+
+        match result {
+            Ok(()) => execute!(
+                session.stderr,
+                style::SetForegroundColor(Color::Green),
+                style::Print(format!("\n✔ Exported conversation to {}\n\n", &self.path)),
+                style::SetAttribute(Attribute::Reset)
+            )?,
+            Err(err) => execute!(
+                session.stderr,
+                style::SetForegroundColor(Color::Red),
+                style::Print(format!("\nFailed to export to {}: {err}\n\n", &self.path)),
+                style::SetAttribute(Attribute::Reset)
+            )?,
+        }
+
+        Ok(ChatState::PromptUser {
+            skip_printing_tools: true,
+        })
+    }
+}
@@ -0,0 +1,78 @@
+use clap::Args;
+use crossterm::execute;
+use crossterm::style::{
+    self,
+    Color,
+};
+
+use crate::cli::chat::sessions::short_id;
+use crate::cli::chat::{
+    ChatError,
+    ChatSession,
+    ChatState,
+};
+use crate::os::Os;
+
+#[deny(missing_docs)]
+#[derive(Debug, PartialEq, Args)]
+#[command(
+    before_long_help = "/fork copies the conversation into a new saved session and continues in the copy.
+The original conversation stays as it is, and can be resumed with q chat --resume-id or
+picked with q chat --resume."
+)]
+pub struct ForkArgs {
+    /// The title of the new session
+    #[arg(required = true)]
+    name: Vec<String>,
+}
+
+impl ForkArgs {
+    pub async fn execute(self, os: &Os, session: &mut ChatSession) -> Result<ChatState, ChatError> {
+        if session.conversation.history().is_empty() {
+            execute!(
+                session.stderr,
+                style::SetForegroundColor(Color::DarkGrey),
+                style::Print("\nThere is nothing to fork yet.\n\n"),
+                style::SetForegroundColor(Color::Reset)
+            )?;
+            return Ok(ChatState::PromptUser {
+                skip_printing_tools: true,
+            });
+        }
+
+        let name = self.name.join(" ");
+        let cwd = os.env.current_dir()?;
+        let forked = session.conversation.fork();
+        let saved = os
+            .database
+            .set_session(&cwd, &session.conversation)
+            .and_then(|_| os.database.set_session(&cwd, &forked))
+            .and_then(|_| os.database.rename_session(forked.conversation_id(), &name));
+        if let Err(err) = saved {
+            return Err(ChatError::Custom(
+                format!("Failed to save the forked conversation: {err}").into(),
+            ));
+        }
+
+        let original_id = short_id(session.conversation.conversation_id()).to_string();
+        session.conversation = forked;
+        execute!(
+            session.stderr,
+            style::SetForegroundColor(Color::Green),
+            style::Print(format!("\nForked the conversation into '{name}'")),
+            style::SetForegroundColor(Color::DarkGrey),
+            style::Print(format!(
+                " ({}). The original conversation is saved as {original_id}, resume it with ",
+                short_id(session.conversation.conversation_id())
+            )),
+            style::SetForegroundColor(Color::Green),
+            style::Print(format!("q chat --resume-id {original_id}")),
+            style::SetForegroundColor(Color::Reset),
+            style::Print("\n\n")
+        )?;
+
+        Ok(ChatState::PromptUser {
+            skip_printing_tools: true,
+        })
+    }
+}
@@ -28,10 +28,16 @@ use futures::stream::{
     FuturesUnordered,
     StreamExt,
 };
+use serde::{
+    Deserialize,
+    Serialize,
+};
+use serde_json::Value;
 use spinners::{
     Spinner,
     Spinners,
 };
+use tokio::io::AsyncWriteExt;
 
 use crate::cli::agent::hook::{
     Hook,
@@ -44,6 +50,125 @@ use crate::cli::chat::{
     ChatSession,
     ChatState,
 };
+use crate::os::Os;
+
+/// Exit code of a hook that blocks the action that triggered it, with its stderr as the reason.
+pub const BLOCKING_EXIT_CODE: i32 = 2;
+
+/// Describes the event that triggered a hook. Hooks receive it as JSON on stdin.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HookEvent {
+    pub hook_event_name: HookTrigger,
+    pub cwd: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub prompt: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tool_name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tool_input: Option<Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tool_response: Option<Value>,
+}
+
+impl HookEvent {
+    pub fn new(os: &Os, trigger: HookTrigger) -> Self {
+        Self {
+            hook_event_name: trigger,
+            cwd: os
+                .env
+                .current_dir()
+                .map(|cwd| cwd.to_string_lossy().to_string())
+                .unwrap_or_default(),
+            prompt: None,
+            tool_name: None,
+            tool_input: None,
+            tool_response: None,
+        }
+    }
+
+    pub fn with_prompt(mut self, prompt: Option<&str>) -> Self {
+        self.prompt = prompt.map(str::to_string);
+        self
+    }
+
+    pub fn with_tool(mut self, name: &str, input: Value) -> Self {
+        self.tool_name = Some(name.to_string());
+        self.tool_input = Some(input);
+        self
+    }
+
+    pub fn with_tool_response(mut self, response: Value) -> Self {
+        self.tool_response = Some(response);
+        self
+    }
+}
+
+/// The output of a hook that ran to completion.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum HookOutput {
+    /// The hook exited successfully with the given stdout.
+    Success(String),
+    /// The hook exited with [BLOCKING_EXIT_CODE] with the given reason.
+    Blocked(String),
+}
+
+impl HookOutput {
+    /// The structured response of the hook. Plain text stdout is treated as additional context.
+    pub fn response(&self) -> HookResponse {
+        match self {
+            HookOutput::Success(stdout) => HookResponse::parse(stdout).unwrap_or_else(|| HookResponse {
+                additional_context: (!stdout.is_empty()).then(|| stdout.clone()),
+                ..Default::default()
+            }),
+            HookOutput::Blocked(reason) => HookResponse {
+                decision: Some(HookDecision::Deny),
+                reason: Some(reason.clone()),
+                ..Default::default()
+            },
+        }
+    }
+}
+
+/// Structured output that hooks may write to stdout as a JSON object instead of plain text.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HookResponse {
+    pub decision: Option<HookDecision>,
+    pub reason: Option<String>,
+    /// Replaces the input of the tool, only used by preToolUse hooks.
+    pub tool_input: Option<Value>,
+    /// Text added to the context, only used by agentSpawn and userPromptSubmit hooks.
+    pub additional_context: Option<String>,
+    /// Paths of files whose content is added to the context, only used by agentSpawn and
+    /// userPromptSubmit hooks.
+    #[serde(default)]
+    pub attach_files: Vec<String>,
+}
+
+/// Ordered from the least to the most strict decision.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum HookDecision {
+    /// Use the tool without asking the user.
+    Allow,
+    /// Ask the user whether to use the tool.
+    Ask,
+    /// Reject the tool use, or the prompt for userPromptSubmit hooks.
+    #[serde(alias = "block")]
+    Deny,
+}
+
+impl HookResponse {
+    /// Parses the stdout of a hook, [None] if it is plain text.
+    pub fn parse(stdout: &str) -> Option<Self> {
+        let stdout = stdout.trim();
+        if !stdout.starts_with('{') {
+            return None;
+        }
+        serde_json::from_str(stdout).ok()
+    }
+}
 
 #[derive(Debug, Clone)]
 pub struct CachedHook {
@@ -62,20 +187,21 @@ impl HookExecutor {
         Self { cache: HashMap::new() }
     }
 
-    /// Run and cache [`Hook`]s. Any hooks that are already cached will be returned without
-    /// executing. Hooks that fail to execute will not be returned. Returned hook order is
-    /// undefined.
+    /// Run and cache [`Hook`]s, passing them `event` on stdin. Any hooks that are already cached
+    /// will be returned without executing. Hooks that fail to execute will not be returned.
+    /// Returned hook order is undefined.
     ///
     /// If `updates` is `Some`, progress on hook execution will be written to it.
     /// Errors encountered with write operations to `updates` are ignored.
     ///
-    /// Note: [`HookTrigger::AgentSpawn`] hooks never leave the cache.
+    /// Note: [`HookTrigger::AgentSpawn`] hooks never leave the cache, and hooks of other triggers
+    /// than [`HookTrigger::UserPromptSubmit`] are never cached.
     pub async fn run_hooks(
         &mut self,
         hooks: HashMap<HookTrigger, Vec<Hook>>,
         output: &mut impl Write,
-        prompt: Option<&str>,
-    ) -> Result<Vec<((HookTrigger, Hook), String)>, ChatError> {
+        event: &HookEvent,
+    ) -> Result<Vec<((HookTrigger, Hook), HookOutput)>, ChatError> {
         let mut cached = vec![];
         let mut futures = FuturesUnordered::new();
         for hook in hooks
@@ -83,10 +209,10 @@ impl HookExecutor {
             .flat_map(|(trigger, hooks)| hooks.into_iter().map(move |hook| (trigger, hook)))
         {
             if let Some(cache) = self.get_cache(&hook) {
-                cached.push((hook.clone(), cache.clone()));
+                cached.push((hook.clone(), HookOutput::Success(cache)));
                 continue;
             }
-            futures.push(self.run_hook(hook, prompt));
+            futures.push(self.run_hook(hook, event));
         }
 
         let mut complete = 0;
@@ -136,6 +262,17 @@ impl HookExecutor {
                     style::Print(format!(": {}\n", err)),
                 )?;
             }
+            if let Ok(HookOutput::Blocked(reason)) = &result {
+                queue!(
+                    output,
+                    style::SetForegroundColor(style::Color::Yellow),
+                    style::Print("✗ "),
+                    style::SetForegroundColor(style::Color::Blue),
+                    style::Print(&hook.1.command),
+                    style::ResetColor,
+                    style::Print(format!(" blocked {}: {}\n", hook.0, reason)),
+                )?;
+            }
 
             // Process results regardless of output enabled
             if let Ok(output) = result {
@@ -168,12 +305,19 @@ impl HookExecutor {
 
         // Fill cache with executed results, skipping what was already from cache
         for ((trigger, hook), output) in &results {
+            let HookOutput::Success(output) = output else {
+                continue;
+            };
+            let expiry = match trigger {
+                HookTrigger::AgentSpawn => None,
+                HookTrigger::UserPromptSubmit => Some(Instant::now() + Duration::from_secs(hook.cache_ttl_seconds)),
+                HookTrigger::PreToolUse | HookTrigger::PostToolUse | HookTrigger::Stop | HookTrigger::SessionEnd => {
+                    continue;
+                },
+            };
             self.cache.insert((*trigger, hook.clone()), CachedHook {
                 output: output.clone(),
-                expiry: match trigger {
-                    HookTrigger::AgentSpawn => None,
-                    HookTrigger::UserPromptSubmit => Some(Instant::now() + Duration::from_secs(hook.cache_ttl_seconds)),
-                },
+                expiry,
             });
         }
 
@@ -185,42 +329,33 @@ impl HookExecutor {
     async fn run_hook(
         &self,
         hook: (HookTrigger, Hook),
-        prompt: Option<&str>,
-    ) -> ((HookTrigger, Hook), Result<String>, Duration) {
+        event: &HookEvent,
+    ) -> ((HookTrigger, Hook), Result<HookOutput>, Duration) {
         let start_time = Instant::now();
 
         let command = &hook.1.command;
 
-        #[cfg(unix)]
-        let mut cmd = tokio::process::Command::new("bash");
-        #[cfg(unix)]
-        let cmd = cmd
-            .arg("-c")
-            .arg(command)
-            .stdin(Stdio::piped())
-            .stdout(Stdio::piped())
-            .stderr(Stdio::piped());
-
-        #[cfg(windows)]
-        let mut cmd = tokio::process::Command::new("cmd");
-        #[cfg(windows)]
-        let cmd = cmd
-            .arg("/C")
-            .arg(command)
-            .stdin(Stdio::piped())
-            .stdout(Stdio::piped())
-            .stderr(Stdio::piped());
+        let mut cmd = shell_command(command);
+        cmd.stdin(Stdio::piped()).stdout(Stdio::piped()).stderr(Stdio::piped());
 
         let timeout = Duration::from_millis(hook.1.timeout_ms);
 
         // Set USER_PROMPT environment variable if provided
-        if let Some(prompt) = prompt {
+        if let Some(prompt) = &event.prompt {
             // Sanitize the prompt to avoid issues with special characters
             let sanitized_prompt = sanitize_user_prompt(prompt);
             cmd.env("USER_PROMPT", sanitized_prompt);
         }
 
-        let command_future = cmd.output();
+        let command_future = async {
+            let mut child = cmd.spawn()?;
+            if let Some(mut stdin) = child.stdin.take() {
+                let input = serde_json::to_vec(event)?;
+                // The hook may exit without reading its input.
+                let _ = stdin.write_all(&input).await;
+            }
+            Ok::<_, eyre::Report>(child.wait_with_output().await?)
+        };
 
         // Run with timeout
         let result = match tokio::time::timeout(timeout, command_future).await {
@@ -236,7 +371,12 @@ impl HookExecutor {
                             ""
                         }
                     );
-                    Ok(stdout)
+                    Ok(HookOutput::Success(stdout))
+                } else if result.status.code() == Some(BLOCKING_EXIT_CODE) {
+                    let stderr = result.stderr.to_str_lossy();
+                    Ok(HookOutput::Blocked(
+                        truncate_safe(stderr.trim(), hook.1.max_output_size).to_string(),
+                    ))
                 } else {
                     Err(eyre!("command returned non-zero exit code: {}", result.status))
                 }
@@ -265,6 +405,22 @@ impl HookExecutor {
 }
 
 /// Sanitizes a string value to be used as an environment variable
+/// Creates a command that runs `command` with the system shell.
+pub fn shell_command(command: &str) -> tokio::process::Command {
+    #[cfg(unix)]
+    let mut cmd = tokio::process::Command::new("bash");
+    #[cfg(unix)]
+    cmd.arg("-c");
+
+    #[cfg(windows)]
+    let mut cmd = tokio::process::Command::new("cmd");
+    #[cfg(windows)]
+    cmd.arg("/C");
+
+    cmd.arg(command);
+    cmd
+}
+
 fn sanitize_user_prompt(input: &str) -> String {
     // Limit the size of input to first 4096 characters
     let truncated = if input.len() > 4096 { &input[0..4096] } else { input };
@@ -284,7 +440,9 @@ Refer to the documentation for how to configure hooks with your agent: https://g
 Notes:
 • Hooks are executed in parallel
 • 'conversation_start' hooks run on the first user prompt and are attached once to the conversation history sent to Amazon Q
-• 'per_prompt' hooks run on each user prompt and are attached to the prompt, but are not stored in conversation history"
+• 'per_prompt' hooks run on each user prompt and are attached to the prompt, but are not stored in conversation history
+• 'userPromptSubmit' hooks can block the prompt by exiting with code 2 or printing {\"decision\": \"block\"}
+• 'preToolUse' hooks can allow, deny or rewrite tool uses, and their output is not attached to the prompt"
 )]
 pub struct HooksArgs;
 
@@ -303,7 +461,10 @@ impl HooksArgs {
                 true => writeln!(&mut out, "<none>")?,
                 false => {
                     for hook in hooks {
-                        writeln!(&mut out, "  - {}", hook.command)?;
+                        match &hook.matcher {
+                            Some(matcher) => writeln!(&mut out, "  - [{matcher}] {}", hook.command)?,
+                            None => writeln!(&mut out, "  - {}", hook.command)?,
+                        }
                     }
                 },
             }
@@ -329,3 +490,90 @@ impl HooksArgs {
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hook_response_parse() {
+        assert_eq!(HookResponse::parse("plain text output"), None);
+        assert_eq!(HookResponse::parse("{ not json"), None);
+        assert_eq!(
+            HookResponse::parse(r#"{"decision": "deny", "reason": "no writes to .env"}"#),
+            Some(HookResponse {
+                decision: Some(HookDecision::Deny),
+                reason: Some("no writes to .env".to_string()),
+                ..Default::default()
+            })
+        );
+        assert_eq!(
+            HookResponse::parse(
+                r#"{"decision": "block", "additionalContext": "on call", "attachFiles": ["README.md"]}"#
+            ),
+            Some(HookResponse {
+                decision: Some(HookDecision::Deny),
+                additional_context: Some("on call".to_string()),
+                attach_files: vec!["README.md".to_string()],
+                ..Default::default()
+            })
+        );
+        assert_eq!(
+            HookResponse::parse(r#" {"toolInput": {"command": "ls"}} "#).and_then(|r| r.tool_input),
+            Some(serde_json::json!({ "command": "ls" }))
+        );
+        assert!(HookDecision::Deny > HookDecision::Ask && HookDecision::Ask > HookDecision::Allow);
+    }
+
+    #[test]
+    fn test_hook_output_response() {
+        assert_eq!(
+            HookOutput::Success("plain text output".to_string()).response(),
+            HookResponse {
+                additional_context: Some("plain text output".to_string()),
+                ..Default::default()
+            }
+        );
+        assert_eq!(HookOutput::Success(String::new()).response(), HookResponse::default());
+        assert_eq!(
+            HookOutput::Blocked("contains a secret".to_string()).response(),
+            HookResponse {
+                decision: Some(HookDecision::Deny),
+                reason: Some("contains a secret".to_string()),
+                ..Default::default()
+            }
+        );
+    }
+
+    #[cfg(not(windows))]
+    #[tokio::test]
+    async fn test_run_tool_hooks() {
+        let os = Os::new().await.unwrap();
+        let event =
+            HookEvent::new(&os, HookTrigger::PreToolUse).with_tool("fs_write", serde_json::json!({ "path": ".env" }));
+        let hooks = HashMap::from([(HookTrigger::PreToolUse, vec![
+            Hook::new("cat".to_string(), Default::default()),
+            Hook::new("echo 'no writes to .env' >&2; exit 2".to_string(), Default::default()),
+        ])]);
+
+        let mut executor = HookExecutor::new();
+        let mut output = vec![];
+        let results = executor.run_hooks(hooks.clone(), &mut output, &event).await.unwrap();
+        assert_eq!(results.len(), 2);
+        for ((_, hook), output) in &results {
+            match output {
+                HookOutput::Success(stdout) => {
+                    assert_eq!(hook.command, "cat");
+                    let stdin = serde_json::from_str::<Value>(stdout).unwrap();
+                    assert_eq!(stdin["hookEventName"], "preToolUse");
+                    assert_eq!(stdin["toolName"], "fs_write");
+                    assert_eq!(stdin["toolInput"]["path"], ".env");
+                },
+                HookOutput::Blocked(reason) => assert_eq!(reason, "no writes to .env"),
+            }
+        }
+
+        // Tool hooks are not cached.
+        assert!(executor.cache.is_empty());
+    }
+}
@@ -1,10 +1,20 @@
+use std::collections::HashMap;
 use std::io::Write;
 
-use clap::Args;
+use clap::{
+    Args,
+    Subcommand,
+};
+use crossterm::style::Color;
 use crossterm::{
+    execute,
     queue,
     style,
 };
+use dialoguer::{
+    Input,
+    Select,
+};
 
 use crate::cli::chat::tool_manager::LoadingRecord;
 use crate::cli::chat::{
@@ -15,10 +25,17 @@ use crate::cli::chat::{
 
 #[deny(missing_docs)]
 #[derive(Debug, PartialEq, Args)]
-pub struct McpArgs;
+pub struct McpArgs {
+    #[command(subcommand)]
+    subcommand: Option<McpSubcommand>,
+}
 
 impl McpArgs {
     pub async fn execute(self, session: &mut ChatSession) -> Result<ChatState, ChatError> {
+        if let Some(subcommand) = self.subcommand {
+            return subcommand.execute(session).await;
+        }
+
         let terminal_width = session.terminal_width();
         let still_loading = session
             .conversation
@@ -67,4 +84,212 @@ impl McpArgs {
             skip_printing_tools: true,
         })
     }
+
+    pub fn subcommand_name(&self) -> Option<&'static str> {
+        self.subcommand.as_ref().map(|s| s.name())
+    }
+}
+
+#[deny(missing_docs)]
+#[derive(Debug, PartialEq, Subcommand)]
+pub enum McpSubcommand {
+    /// Browse the resources of mcp servers and attach them to the context
+    Resources(ResourcesArgs),
+}
+
+impl McpSubcommand {
+    pub async fn execute(self, session: &mut ChatSession) -> Result<ChatState, ChatError> {
+        match self {
+            Self::Resources(args) => args.execute(session).await,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Resources(_) => "resources",
+        }
+    }
+}
+
+#[deny(missing_docs)]
+#[derive(Debug, PartialEq, Args)]
+pub struct ResourcesArgs {
+    #[command(subcommand)]
+    subcommand: Option<ResourcesSubcommand>,
+}
+
+#[deny(missing_docs)]
+#[derive(Debug, PartialEq, Subcommand)]
+pub enum ResourcesSubcommand {
+    /// Remove an attached resource of an mcp server from the context
+    Remove { server_name: String, uri: String },
+}
+
+/// An entry of the resource browser.
+enum ResourceItem {
+    Resource { server_name: String, uri: String },
+    Template { server_name: String, index: usize },
+}
+
+impl ResourcesArgs {
+    pub async fn execute(self, session: &mut ChatSession) -> Result<ChatState, ChatError> {
+        if let Some(ResourcesSubcommand::Remove { server_name, uri }) = self.subcommand {
+            match session.conversation.detach_resource(&server_name, &uri).await {
+                Some(_) => execute!(
+                    session.stderr,
+                    style::SetForegroundColor(Color::Green),
+                    style::Print(format!("\nRemoved {uri} of {server_name} from the context\n\n")),
+                    style::SetForegroundColor(Color::Reset)
+                )?,
+                None => execute!(
+                    session.stderr,
+                    style::SetForegroundColor(Color::Yellow),
+                    style::Print(format!(
+                        "\nNo resource with the uri {uri} is attached from the mcp server {server_name}\n\n"
+                    )),
+                    style::SetForegroundColor(Color::Reset)
+                )?,
+            }
+            return Ok(ChatState::PromptUser {
+                skip_printing_tools: true,
+            });
+        }
+
+        let servers = {
+            let mut servers = session
+                .conversation
+                .tool_manager
+                .resources
+                .lock()
+                .await
+                .clone()
+                .into_iter()
+                .collect::<Vec<_>>();
+            servers.sort_by(|(a, _), (b, _)| a.cmp(b));
+            servers
+        };
+
+        if !session.conversation.attached_resources.is_empty() {
+            queue!(session.stderr, style::Print("\nAttached resources:\n"))?;
+            for resource in &session.conversation.attached_resources {
+                queue!(
+                    session.stderr,
+                    style::Print(format!("- {} ", resource.uri)),
+                    style::SetForegroundColor(Color::DarkGrey),
+                    style::Print(format!("({})\n", resource.server_name)),
+                    style::SetForegroundColor(Color::Reset)
+                )?;
+            }
+        }
+
+        let mut items = Vec::new();
+        let mut labels = Vec::new();
+        for (server_name, resources) in &servers {
+            for resource in &resources.resources {
+                let attached = session
+                    .conversation
+                    .attached_resources
+                    .iter()
+                    .any(|r| r.server_name == *server_name && r.uri == resource.uri);
+                labels.push(format!(
+                    "{server_name}: {} ({}){}",
+                    resource.name,
+                    resource.uri,
+                    if attached { " (attached)" } else { "" }
+                ));
+                items.push(ResourceItem::Resource {
+                    server_name: server_name.clone(),
+                    uri: resource.uri.clone(),
+                });
+            }
+            for (index, template) in resources.templates.iter().enumerate() {
+                labels.push(format!("{server_name}: {} ({})", template.name, template.uri_template));
+                items.push(ResourceItem::Template {
+                    server_name: server_name.clone(),
+                    index,
+                });
+            }
+        }
+
+        if items.is_empty() {
+            execute!(
+                session.stderr,
+                style::SetForegroundColor(Color::DarkGrey),
+                style::Print("\nNo mcp server offers any resources.\n\n"),
+                style::SetForegroundColor(Color::Reset)
+            )?;
+            return Ok(ChatState::PromptUser {
+                skip_printing_tools: true,
+            });
+        }
+
+        queue!(session.stderr, style::Print("\n"))?;
+        session.stderr.flush()?;
+        let selection = match Select::with_theme(&crate::util::dialoguer_theme())
+            .with_prompt("Select a resource to attach to the context")
+            .items(&labels)
+            .default(0)
+            .interact_on_opt(&dialoguer::console::Term::stdout())
+        {
+            Ok(selection) => selection,
+            // Ctrl‑C -> Err(Interrupted)
+            Err(dialoguer::Error::IO(ref e)) if e.kind() == std::io::ErrorKind::Interrupted => None,
+            Err(e) => return Err(ChatError::Custom(format!("Failed to choose a resource: {e}").into())),
+        };
+
+        let (server_name, uri) = match selection.map(|i| &items[i]) {
+            None => {
+                return Ok(ChatState::PromptUser {
+                    skip_printing_tools: true,
+                });
+            },
+            Some(ResourceItem::Resource { server_name, uri }) => (server_name.clone(), uri.clone()),
+            Some(ResourceItem::Template { server_name, index }) => {
+                let template = servers
+                    .iter()
+                    .find(|(name, _)| name == server_name)
+                    .map(|(_, resources)| &resources.templates[*index])
+                    .expect("template is listed");
+                let mut values = HashMap::new();
+                for variable in template.variables() {
+                    let value = match Input::<String>::with_theme(&crate::util::dialoguer_theme())
+                        .with_prompt(&variable)
+                        .allow_empty(true)
+                        .interact_text()
+                    {
+                        Ok(value) => value,
+                        Err(dialoguer::Error::IO(ref e)) if e.kind() == std::io::ErrorKind::Interrupted => {
+                            return Ok(ChatState::PromptUser {
+                                skip_printing_tools: true,
+                            });
+                        },
+                        Err(e) => return Err(ChatError::Custom(format!("Failed to read {variable}: {e}").into())),
+                    };
+                    if !value.is_empty() {
+                        values.insert(variable, value);
+                    }
+                }
+                (server_name.clone(), template.expand(&values))
+            },
+        };
+
+        match session.conversation.attach_resource(&server_name, &uri).await {
+            Ok(()) => execute!(
+                session.stderr,
+                style::SetForegroundColor(Color::Green),
+                style::Print(format!("\nAttached {uri} to the context\n\n")),
+                style::SetForegroundColor(Color::Reset)
+            )?,
+            Err(err) => execute!(
+                session.stderr,
+                style::SetForegroundColor(Color::Red),
+                style::Print(format!("\nFailed to attach {uri}: {err}\n\n")),
+                style::SetForegroundColor(Color::Reset)
+            )?,
+        }
+
+        Ok(ChatState::PromptUser {
+            skip_printing_tools: true,
+        })
+    }
 }
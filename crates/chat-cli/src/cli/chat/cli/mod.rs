@@ -1,32 +1,42 @@
+pub mod checkpoint;
 pub mod clear;
 pub mod compact;
 pub mod context;
 pub mod editor;
+pub mod export;
+pub mod fork;
 pub mod hooks;
 #[cfg(feature = "knowledge")]
 pub mod knowledge;
 pub mod mcp;
 pub mod model;
 pub mod persist;
+pub mod processes;
 pub mod profile;
 pub mod prompts;
+pub mod rewind;
 pub mod subscribe;
 pub mod tools;
 pub mod usage;
 
+use checkpoint::CheckpointSubcommand;
 use clap::Parser;
 use clear::ClearArgs;
 use compact::CompactArgs;
 use context::ContextSubcommand;
 use editor::EditorArgs;
+use export::ExportArgs;
+use fork::ForkArgs;
 use hooks::HooksArgs;
 #[cfg(feature = "knowledge")]
 use knowledge::KnowledgeSubcommand;
 use mcp::McpArgs;
 use model::ModelArgs;
 use persist::PersistSubcommand;
+use processes::ProcessesArgs;
 use profile::AgentSubcommand;
 use prompts::PromptsArgs;
+use rewind::RewindArgs;
 use tools::ToolsArgs;
 
 use crate::cli::chat::cli::subscribe::SubscribeArgs;
@@ -67,8 +77,17 @@ pub enum SlashCommand {
     PromptEditor(EditorArgs),
     /// Summarize the conversation to free up context space
     Compact(CompactArgs),
+    /// View and restore the files modified by tools in each turn
+    #[command(subcommand)]
+    Checkpoint(CheckpointSubcommand),
+    /// Drop the last exchanges from the conversation and edit the prompt again
+    Rewind(RewindArgs),
+    /// Continue in a copy of the conversation saved as a new session
+    Fork(ForkArgs),
     /// View tools and permissions
     Tools(ToolsArgs),
+    /// View and kill the processes started in the background by execute_bash
+    Processes(ProcessesArgs),
     /// Create a new Github issue or make a feature request
     Issue(issue::IssueArgs),
     /// View and retrieve prompts
@@ -77,7 +96,7 @@ pub enum SlashCommand {
     Hooks(HooksArgs),
     /// Show current session's context window usage
     Usage(UsageArgs),
-    /// See mcp server loaded
+    /// See mcp server loaded and browse their resources
     Mcp(McpArgs),
     /// Select a model for the current conversation session
     Model(ModelArgs),
@@ -85,6 +104,8 @@ pub enum SlashCommand {
     Subscribe(SubscribeArgs),
     #[command(flatten)]
     Persist(PersistSubcommand),
+    /// Export the conversation as a Markdown, HTML or JSONL transcript
+    Export(ExportArgs),
     // #[command(flatten)]
     // Root(RootSubcommand),
 }
@@ -123,7 +144,11 @@ impl SlashCommand {
             Self::Knowledge(subcommand) => subcommand.execute(os, session).await,
             Self::PromptEditor(args) => args.execute(session).await,
             Self::Compact(args) => args.execute(os, session).await,
-            Self::Tools(args) => args.execute(session).await,
+            Self::Checkpoint(subcommand) => subcommand.execute(os, session).await,
+            Self::Rewind(args) => args.execute(os, session).await,
+            Self::Fork(args) => args.execute(os, session).await,
+            Self::Tools(args) => args.execute(os, session).await,
+            Self::Processes(args) => args.execute(session).await,
             Self::Issue(args) => {
                 if let Err(err) = args.execute(os).await {
                     return Err(ChatError::Custom(err.to_string().into()));
@@ -140,6 +165,7 @@ impl SlashCommand {
             Self::Model(args) => args.execute(os, session).await,
             Self::Subscribe(args) => args.execute(os, session).await,
             Self::Persist(subcommand) => subcommand.execute(os, session).await,
+            Self::Export(args) => args.execute(os, session).await,
             // Self::Root(subcommand) => {
             //     if let Err(err) = subcommand.execute(os, database, telemetry).await {
             //         return Err(ChatError::Custom(err.to_string().into()));
@@ -163,7 +189,11 @@ impl SlashCommand {
             Self::Knowledge(_) => "knowledge",
             Self::PromptEditor(_) => "editor",
             Self::Compact(_) => "compact",
+            Self::Checkpoint(_) => "checkpoint",
+            Self::Rewind(_) => "rewind",
+            Self::Fork(_) => "fork",
             Self::Tools(_) => "tools",
+            Self::Processes(_) => "processes",
             Self::Issue(_) => "issue",
             Self::Prompts(_) => "prompts",
             Self::Hooks(_) => "hooks",
@@ -175,6 +205,7 @@ impl SlashCommand {
                 PersistSubcommand::Save { .. } => "save",
                 PersistSubcommand::Load { .. } => "load",
             },
+            Self::Export(_) => "export",
         }
     }
 
@@ -182,10 +213,13 @@ impl SlashCommand {
         match self {
             SlashCommand::Agent(sub) => Some(sub.name()),
             SlashCommand::Context(sub) => Some(sub.name()),
+            SlashCommand::Checkpoint(sub) => Some(sub.name()),
             #[cfg(feature = "knowledge")]
             SlashCommand::Knowledge(sub) => Some(sub.name()),
             SlashCommand::Tools(arg) => arg.subcommand_name(),
+            SlashCommand::Processes(arg) => arg.subcommand_name(),
             SlashCommand::Prompts(arg) => arg.subcommand_name(),
+            SlashCommand::Mcp(arg) => arg.subcommand_name(),
             _ => None,
         }
     }
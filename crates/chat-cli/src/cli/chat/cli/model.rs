@@ -14,6 +14,7 @@ use crate::auth::builder_id::{
     BuilderIdToken,
     TokenType,
 };
+use crate::cli::chat::token_counter::TokenizerKind;
 use crate::cli::chat::{
     ChatError,
     ChatSession,
@@ -28,6 +29,8 @@ pub struct ModelOption {
     pub model_id: &'static str,
     /// Size of the model's context window, in tokens
     pub context_window_tokens: usize,
+    /// Tokenizer used to count the tokens sent to the model
+    pub tokenizer: TokenizerKind,
 }
 
 const MODEL_OPTIONS: [ModelOption; 2] = [
@@ -35,11 +38,13 @@ const MODEL_OPTIONS: [ModelOption; 2] = [
         name: "claude-4-sonnet",
         model_id: "CLAUDE_SONNET_4_20250514_V1_0",
         context_window_tokens: 200_000,
+        tokenizer: TokenizerKind::Bpe,
     },
     ModelOption {
         name: "claude-3.7-sonnet",
         model_id: "CLAUDE_3_7_SONNET_20250219_V1_0",
         context_window_tokens: 200_000,
+        tokenizer: TokenizerKind::Bpe,
     },
 ];
 
@@ -47,6 +52,7 @@ const GPT_OSS_120B: ModelOption = ModelOption {
     name: "openai-gpt-oss-120b-preview",
     model_id: "OPENAI_GPT_OSS_120B_1_0",
     context_window_tokens: 128_000,
+    tokenizer: TokenizerKind::Bpe,
 };
 
 #[deny(missing_docs)]
@@ -170,3 +176,17 @@ pub fn context_window_tokens(model_id: Option<&str>) -> usize {
         .find(|m| m.model_id == model_id)
         .map_or(DEFAULT_CONTEXT_WINDOW_LENGTH, |m| m.context_window_tokens)
 }
+
+/// Returns the tokenizer to count tokens with for the given model_id, estimating from the length
+/// of the text for models that are not known.
+pub fn tokenizer_kind(model_id: Option<&str>) -> TokenizerKind {
+    let Some(model_id) = model_id else {
+        return TokenizerKind::default();
+    };
+
+    MODEL_OPTIONS
+        .iter()
+        .chain(std::iter::once(&GPT_OSS_120B))
+        .find(|m| m.model_id == model_id)
+        .map_or(TokenizerKind::CharRatio, |m| m.tokenizer)
+}
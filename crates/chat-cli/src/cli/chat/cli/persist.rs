@@ -6,11 +6,11 @@ use crossterm::style::{
     Color,
 };
 
-use crate::cli::ConversationState;
 use crate::cli::chat::{
     ChatError,
     ChatSession,
     ChatState,
+    conversation_file,
 };
 use crate::os::Os;
 
@@ -51,7 +51,7 @@ impl PersistSubcommand {
 
         match self {
             Self::Save { path, force } => {
-                let contents = tri!(serde_json::to_string_pretty(&session.conversation), "export to", &path);
+                let contents = tri!(conversation_file::to_json(&session.conversation), "export to", &path);
                 if os.fs.exists(&path) && !force {
                     execute!(
                         session.stderr,
@@ -93,14 +93,9 @@ impl PersistSubcommand {
                     tri!(original_result, "import from", &path)
                 };
 
-                let mut new_state: ConversationState = tri!(serde_json::from_str(&contents), "import from", &path);
-                std::mem::swap(&mut new_state.tool_manager, &mut session.conversation.tool_manager);
-                std::mem::swap(
-                    &mut new_state.context_manager,
-                    &mut session.conversation.context_manager,
-                );
-                std::mem::swap(&mut new_state.agents, &mut session.conversation.agents);
-                session.conversation = new_state;
+                let loaded = tri!(conversation_file::from_json(&contents), "import from", &path);
+                let outdated = loaded.version < conversation_file::CURRENT_VERSION;
+                let issues = loaded.restore_into(&mut session.conversation);
 
                 execute!(
                     session.stderr,
@@ -108,6 +103,24 @@ impl PersistSubcommand {
                     style::Print(format!("\n✔ Imported conversation state from {}\n\n", &path)),
                     style::SetAttribute(Attribute::Reset)
                 )?;
+                if outdated {
+                    execute!(
+                        session.stderr,
+                        style::SetForegroundColor(Color::DarkGrey),
+                        style::Print("The file was saved in an earlier format, /save it again to update it.\n\n"),
+                        style::SetAttribute(Attribute::Reset)
+                    )?;
+                }
+                if !issues.is_empty() {
+                    execute!(
+                        session.stderr,
+                        style::SetForegroundColor(Color::Yellow),
+                        style::Print("Some parts of the file could not be loaded:\n"),
+                        style::Print(issues.iter().map(|issue| format!("  • {issue}\n")).collect::<String>()),
+                        style::SetAttribute(Attribute::Reset),
+                        style::Print("\n")
+                    )?;
+                }
             },
         }
 
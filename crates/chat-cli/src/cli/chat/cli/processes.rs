@@ -0,0 +1,155 @@
+use std::io::Write;
+
+use clap::{
+    Args,
+    Subcommand,
+};
+use crossterm::style::{
+    self,
+    Attribute,
+    Color,
+};
+use crossterm::{
+    execute,
+    queue,
+};
+
+use crate::cli::chat::tools::execute::ProcessStatus;
+use crate::cli::chat::{
+    ChatError,
+    ChatSession,
+    ChatState,
+};
+
+#[deny(missing_docs)]
+#[derive(Debug, PartialEq, Args)]
+pub struct ProcessesArgs {
+    #[command(subcommand)]
+    subcommand: Option<ProcessesSubcommand>,
+}
+
+impl ProcessesArgs {
+    pub async fn execute(self, session: &mut ChatSession) -> Result<ChatState, ChatError> {
+        if let Some(subcommand) = self.subcommand {
+            return subcommand.execute(session).await;
+        }
+
+        let processes = session.background_processes.list();
+        if processes.is_empty() {
+            execute!(
+                session.stderr,
+                style::SetForegroundColor(Color::DarkGrey),
+                style::Print("\nNo background processes have been started in this session.\n\n"),
+                style::SetForegroundColor(Color::Reset)
+            )?;
+            return Ok(ChatState::PromptUser {
+                skip_printing_tools: true,
+            });
+        }
+
+        queue!(
+            session.stderr,
+            style::SetAttribute(Attribute::Bold),
+            style::Print(format!(
+                "\n{:<4} {:<8} {:<12} {:<9} Command\n",
+                "Id", "Pid", "Status", "Elapsed"
+            )),
+            style::SetAttribute(Attribute::Reset),
+        )?;
+        for process in processes {
+            let status = process.status();
+            let color = match status {
+                ProcessStatus::Running => Color::Green,
+                ProcessStatus::Exited(Some(0)) => Color::DarkGrey,
+                ProcessStatus::Exited(_) => Color::Red,
+            };
+            let pid = process.pid.map(|pid| pid.to_string()).unwrap_or_default();
+            queue!(
+                session.stderr,
+                style::Print(format!("{:<4} {pid:<8} ", process.id)),
+                style::SetForegroundColor(color),
+                style::Print(format!("{:<12} ", status.to_string())),
+                style::SetForegroundColor(Color::Reset),
+                style::Print(format!(
+                    "{:<9} {}\n",
+                    format_elapsed(process.started.elapsed().as_secs()),
+                    process.command.lines().next().unwrap_or_default()
+                )),
+            )?;
+        }
+        queue!(
+            session.stderr,
+            style::SetForegroundColor(Color::DarkGrey),
+            style::Print("\nRunning processes are killed when the session ends.\n\n"),
+            style::SetForegroundColor(Color::Reset)
+        )?;
+        session.stderr.flush()?;
+
+        Ok(ChatState::PromptUser {
+            skip_printing_tools: true,
+        })
+    }
+
+    pub fn subcommand_name(&self) -> Option<&'static str> {
+        self.subcommand.as_ref().map(|s| s.name())
+    }
+}
+
+#[deny(missing_docs)]
+#[derive(Debug, PartialEq, Subcommand)]
+pub enum ProcessesSubcommand {
+    /// Kill a background process and any processes it started
+    Kill { id: usize },
+}
+
+impl ProcessesSubcommand {
+    pub async fn execute(self, session: &mut ChatSession) -> Result<ChatState, ChatError> {
+        match self {
+            Self::Kill { id } => {
+                let Some(process) = session.background_processes.get(id) else {
+                    execute!(
+                        session.stderr,
+                        style::SetForegroundColor(Color::Yellow),
+                        style::Print(format!("\nThere is no background process with id {id}.\n\n")),
+                        style::SetForegroundColor(Color::Reset)
+                    )?;
+                    return Ok(ChatState::PromptUser {
+                        skip_printing_tools: true,
+                    });
+                };
+                match process.kill().await {
+                    Ok(status) => execute!(
+                        session.stderr,
+                        style::SetForegroundColor(Color::Green),
+                        style::Print(format!("\n✔ Process {id} {status}\n\n")),
+                        style::SetForegroundColor(Color::Reset)
+                    )?,
+                    Err(err) => execute!(
+                        session.stderr,
+                        style::SetForegroundColor(Color::Red),
+                        style::Print(format!("\nFailed to kill process {id}: {err}\n\n")),
+                        style::SetForegroundColor(Color::Reset)
+                    )?,
+                }
+            },
+        }
+
+        Ok(ChatState::PromptUser {
+            skip_printing_tools: true,
+        })
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Kill { .. } => "kill",
+        }
+    }
+}
+
+fn format_elapsed(secs: u64) -> String {
+    match secs {
+        0..60 => format!("{secs}s"),
+        60..3600 => format!("{}m{}s", secs / 60, secs % 60),
+        _ => format!("{}h{}m", secs / 3600, secs % 3600 / 60),
+    }
+}
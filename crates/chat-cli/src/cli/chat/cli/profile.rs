@@ -68,7 +68,6 @@ pub enum AgentSubcommand {
     #[command(hide = true)]
     Delete { name: String },
     /// Switch to the specified agent
-    #[command(hide = true)]
     Set { name: String },
     /// Show agent config schema
     Schema,
@@ -177,11 +176,35 @@ impl AgentSubcommand {
                     style::SetForegroundColor(Color::Reset)
                 )?;
             },
-            Self::Set { .. } | Self::Delete { .. } => {
+            Self::Set { name } => {
+                let previous = agents.get_active().cloned();
+                if let Err(err) = session.conversation.switch_agent(os, &name).await {
+                    _print_err!(err);
+                    return Ok(ChatState::PromptUser {
+                        skip_printing_tools: true,
+                    });
+                }
+                execute!(
+                    session.stderr,
+                    style::SetForegroundColor(Color::Green),
+                    style::Print(format!("\n✔ Switched to agent {name}\n")),
+                    style::SetForegroundColor(Color::Reset),
+                )?;
+                let current = session.conversation.agents.get_active();
+                if previous.as_ref().map(|a| (&a.mcp_servers, &a.tools)) != current.map(|a| (&a.mcp_servers, &a.tools))
+                {
+                    execute!(
+                        session.stderr,
+                        style::SetForegroundColor(Color::Yellow),
+                        style::Print("The MCP servers and tools of this agent take effect on next launch\n"),
+                        style::SetForegroundColor(Color::Reset),
+                    )?;
+                }
+                execute!(session.stderr, style::Print("\n"))?;
+            },
+            Self::Delete { .. } => {
                 // As part of the agent implementation, we are disabling the ability to
-                // switch / create profile after a session has started.
-                // TODO: perhaps revive this after we have a decision on profile create /
-                // switch
+                // delete profile after a session has started.
                 let global_path = if let Ok(path) = chat_global_agent_path(os) {
                     path.to_str().unwrap_or("default global agent path").to_string()
                 } else {
@@ -191,7 +214,7 @@ impl AgentSubcommand {
                     session.stderr,
                     style::SetForegroundColor(Color::Yellow),
                     style::Print(format!(
-                        "To make changes or create agents, please do so via create the corresponding config in {}, where you would also find an example config for your reference.\nTo switch agent, launch another instance of q chat with --agent.\n\n",
+                        "To make changes or create agents, please do so via create the corresponding config in {}, where you would also find an example config for your reference.\n\n",
                         global_path
                     )),
                     style::SetAttribute(Attribute::Reset)
@@ -0,0 +1,110 @@
+use clap::Args;
+use crossterm::execute;
+use crossterm::style::{
+    self,
+    Color,
+};
+
+use crate::cli::chat::util::select_item;
+use crate::cli::chat::{
+    ChatError,
+    ChatSession,
+    ChatState,
+};
+use crate::os::Os;
+
+#[deny(missing_docs)]
+#[derive(Debug, PartialEq, Args)]
+#[command(
+    before_long_help = "/rewind drops the last exchanges from the conversation history, so that you can take
+the conversation in another direction. An exchange is a prompt you sent together with
+the response and every tool the assistant ran for it.
+
+The prompt of the earliest exchange dropped is put back in the input for you to edit.
+Files modified by tools are not restored, use /checkpoint restore for that."
+)]
+pub struct RewindArgs {
+    /// The number of exchanges to drop. Without it, pick the exchange to rewind to from a list
+    count: Option<usize>,
+}
+
+impl RewindArgs {
+    pub async fn execute(self, os: &Os, session: &mut ChatSession) -> Result<ChatState, ChatError> {
+        let exchanges = session.conversation.exchanges();
+        let total = exchanges.len();
+        if total == 0 {
+            execute!(
+                session.stderr,
+                style::SetForegroundColor(Color::DarkGrey),
+                style::Print("\nThere is nothing to rewind yet.\n\n"),
+                style::SetForegroundColor(Color::Reset)
+            )?;
+            return Ok(ChatState::PromptUser {
+                skip_printing_tools: true,
+            });
+        }
+
+        let count = match self.count {
+            Some(count) => count,
+            None => {
+                // Most recent first, numbered by how many exchanges are dropped when selected.
+                let items = exchanges
+                    .iter()
+                    .rev()
+                    .enumerate()
+                    .map(|(i, (_, prompt))| format!("{}. {}", i + 1, prompt.lines().next().unwrap_or_default()))
+                    .collect::<Vec<_>>();
+                match select_item("Rewind to before", &items)
+                    .map_err(|err| ChatError::Custom(err.to_string().into()))?
+                {
+                    Some(i) => i + 1,
+                    None => {
+                        return Ok(ChatState::PromptUser {
+                            skip_printing_tools: true,
+                        });
+                    },
+                }
+            },
+        };
+
+        let Some(prompt) = session.conversation.rewind(count) else {
+            execute!(
+                session.stderr,
+                style::SetForegroundColor(Color::Red),
+                style::Print(format!(
+                    "\nCannot rewind {count} exchanges, the conversation has {total}.\n\n"
+                )),
+                style::SetForegroundColor(Color::Reset)
+            )?;
+            return Ok(ChatState::PromptUser {
+                skip_printing_tools: true,
+            });
+        };
+        session.conversation.save_session(os);
+        session.input_source.set_buffer(&prompt);
+
+        let exchanges = if count == 1 { "exchange" } else { "exchanges" };
+        execute!(
+            session.stderr,
+            style::SetForegroundColor(Color::Green),
+            style::Print(format!("\nRewound {count} {exchanges}.")),
+            style::SetForegroundColor(Color::DarkGrey),
+            style::Print(" Edit the prompt below and press enter to send it again.\n"),
+        )?;
+        if !session.conversation.checkpoints.checkpoints().is_empty() {
+            execute!(
+                session.stderr,
+                style::Print("Files modified by tools were not restored, see /checkpoint list.\n"),
+            )?;
+        }
+        execute!(
+            session.stderr,
+            style::SetForegroundColor(Color::Reset),
+            style::Print("\n")
+        )?;
+
+        Ok(ChatState::PromptUser {
+            skip_printing_tools: true,
+        })
+    }
+}
@@ -33,6 +33,7 @@ use crate::cli::chat::{
     ChatState,
     TRUST_ALL_TEXT,
 };
+use crate::os::Os;
 use crate::util::consts::MCP_SERVER_TOOL_DELIMITER;
 
 #[deny(missing_docs)]
@@ -43,9 +44,9 @@ pub struct ToolsArgs {
 }
 
 impl ToolsArgs {
-    pub async fn execute(self, session: &mut ChatSession) -> Result<ChatState, ChatError> {
+    pub async fn execute(self, os: &Os, session: &mut ChatSession) -> Result<ChatState, ChatError> {
         if let Some(subcommand) = self.subcommand {
-            return subcommand.execute(session).await;
+            return subcommand.execute(os, session).await;
         }
 
         // No subcommand - print the current tools and their permissions.
@@ -141,6 +142,47 @@ impl ToolsArgs {
             );
         }
 
+        if let Some(permissions) = session
+            .conversation
+            .agents
+            .get_active()
+            .map(|agent| &agent.permissions)
+            .filter(|permissions| !permissions.is_empty())
+        {
+            let allow_rules = permissions
+                .allow
+                .iter()
+                .enumerate()
+                .fold(String::new(), |mut acc, (i, rule)| {
+                    acc.push_str(&format!("{:>3}. {rule}\n", i + 1));
+                    acc
+                });
+            let deny_rules = permissions.deny.iter().fold(String::new(), |mut acc, rule| {
+                acc.push_str(&format!("   - {rule}\n"));
+                acc
+            });
+            queue!(
+                session.stderr,
+                style::SetAttribute(Attribute::Bold),
+                style::Print("Permission rules:\n"),
+                style::SetAttribute(Attribute::Reset),
+            )?;
+            if !allow_rules.is_empty() {
+                queue!(
+                    session.stderr,
+                    style::Print("  Allow "),
+                    style::SetForegroundColor(Color::DarkGrey),
+                    style::Print("(remove with /tools revoke <rule or number>)\n"),
+                    style::SetForegroundColor(Color::Reset),
+                    style::Print(allow_rules),
+                )?;
+            }
+            if !deny_rules.is_empty() {
+                queue!(session.stderr, style::Print("  Deny\n"), style::Print(deny_rules))?;
+            }
+            queue!(session.stderr, style::Print("\n"))?;
+        }
+
         let loading = session.conversation.tool_manager.pending_clients().await;
         if !loading.is_empty() {
             queue!(
@@ -203,10 +245,15 @@ pub enum ToolsSubcommand {
     TrustAll,
     /// Reset all tools to default permission levels
     Reset,
+    /// Remove an allow rule from the agent config, given the rule or its number in /tools
+    Revoke {
+        #[arg(required = true)]
+        rule: String,
+    },
 }
 
 impl ToolsSubcommand {
-    pub async fn execute(self, session: &mut ChatSession) -> Result<ChatState, ChatError> {
+    pub async fn execute(self, os: &Os, session: &mut ChatSession) -> Result<ChatState, ChatError> {
         // Here we need to obtain the list of host tool names
         let existing_custom_tools = session
             .conversation
@@ -369,6 +416,7 @@ impl ToolsSubcommand {
                     if let (Ok(orig_agent), Some(active_agent)) = (result, session.conversation.agents.get_active_mut())
                     {
                         active_agent.allowed_tools = orig_agent.allowed_tools;
+                        active_agent.permissions = orig_agent.permissions;
                     }
                 } else if session
                     .conversation
@@ -389,6 +437,38 @@ impl ToolsSubcommand {
                     style::SetForegroundColor(Color::Reset),
                 )?;
             },
+            Self::Revoke { rule } => {
+                let Some(agent) = session.conversation.agents.get_active_mut() else {
+                    return Err(ChatError::Custom("No active agent".into()));
+                };
+                let rule = match rule.parse::<usize>() {
+                    Ok(n) => n.checked_sub(1).and_then(|i| agent.permissions.allow.get(i)).cloned(),
+                    Err(_) => agent.permissions.allow.iter().find(|r| **r == rule).cloned(),
+                };
+
+                match rule {
+                    Some(rule) => match agent.revoke_allow_rule(os, &rule).await {
+                        Ok(()) => queue!(
+                            session.stderr,
+                            style::SetForegroundColor(Color::Green),
+                            style::Print(format!("\nRemoved the rule {rule} from the agent {}.", agent.name)),
+                            style::SetForegroundColor(Color::Reset),
+                        )?,
+                        Err(err) => queue!(
+                            session.stderr,
+                            style::SetForegroundColor(Color::Red),
+                            style::Print(format!("\nFailed to remove the rule {rule}: {err}")),
+                            style::SetForegroundColor(Color::Reset),
+                        )?,
+                    },
+                    None => queue!(
+                        session.stderr,
+                        style::SetForegroundColor(Color::Red),
+                        style::Print("\nNo such allow rule. Run /tools to see the rules of the agent."),
+                        style::SetForegroundColor(Color::Reset),
+                    )?,
+                }
+            },
         };
 
         session.stderr.flush()?;
@@ -405,6 +485,7 @@ impl ToolsSubcommand {
             ToolsSubcommand::Untrust { .. } => "untrust",
             ToolsSubcommand::TrustAll => "trust-all",
             ToolsSubcommand::Reset => "reset",
+            ToolsSubcommand::Revoke { .. } => "revoke",
         }
     }
 }
@@ -13,6 +13,9 @@ pub const MAX_USER_MESSAGE_SIZE: usize = 400_000;
 
 pub const DUMMY_TOOL_NAME: &str = "dummy";
 
+/// Maximum number of bytes of an MCP resource attached to the conversation context
+pub const MAX_RESOURCE_SIZE: usize = 100_000;
+
 pub const MAX_NUMBER_OF_IMAGES_PER_REQUEST: usize = 10;
 
 /// In bytes - 10 MB
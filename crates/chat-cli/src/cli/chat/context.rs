@@ -22,7 +22,11 @@ use crate::cli::agent::hook::{
     HookTrigger,
 };
 use crate::cli::chat::ChatError;
-use crate::cli::chat::cli::hooks::HookExecutor;
+use crate::cli::chat::cli::hooks::{
+    HookEvent,
+    HookExecutor,
+    HookOutput,
+};
 use crate::os::Os;
 
 #[derive(Debug, Clone)]
@@ -239,18 +243,23 @@ impl ContextManager {
         Ok(())
     }
 
-    /// Run all the currently enabled hooks from both the global and profile contexts.
+    /// Run all the currently enabled hooks from both the global and profile contexts that are
+    /// triggered by `event`.
     /// # Returns
     /// A vector containing pairs of a [`Hook`] definition and its execution output
     pub async fn run_hooks(
         &mut self,
-        trigger: HookTrigger,
+        event: &HookEvent,
         output: &mut impl Write,
-        prompt: Option<&str>,
-    ) -> Result<Vec<((HookTrigger, Hook), String)>, ChatError> {
+    ) -> Result<Vec<((HookTrigger, Hook), HookOutput)>, ChatError> {
         let mut hooks = self.hooks.clone();
-        hooks.retain(|t, _| *t == trigger);
-        self.hook_executor.run_hooks(hooks, output, prompt).await
+        hooks.retain(|t, _| *t == event.hook_event_name);
+        if let Some(tool_name) = &event.tool_name {
+            for hooks in hooks.values_mut() {
+                hooks.retain(|hook| hook.matches_tool(tool_name));
+            }
+        }
+        self.hook_executor.run_hooks(hooks, output, event).await
     }
 }
 
@@ -20,11 +20,21 @@ use tracing::{
     warn,
 };
 
+use super::checkpoint::CheckpointStore;
 use super::cli::compact::CompactStrategy;
-use super::cli::model::context_window_tokens;
+use super::cli::hooks::{
+    HookDecision,
+    HookEvent,
+    HookOutput,
+};
+use super::cli::model::{
+    context_window_tokens,
+    tokenizer_kind,
+};
 use super::consts::{
     DUMMY_TOOL_NAME,
     MAX_CONVERSATION_STATE_HISTORY_LEN,
+    MAX_RESOURCE_SIZE,
 };
 use super::context::{
     ContextManager,
@@ -37,9 +47,11 @@ use super::message::{
 };
 use super::parser::RequestMetadata;
 use super::token_counter::{
-    CharCount,
-    CharCounter,
-    TokenCounter,
+    CountTokens,
+    EntryTokenCount,
+    TokenCount,
+    TokenCountCache,
+    TokenizerKind,
 };
 use super::tool_manager::ToolManager;
 use super::tools::{
@@ -47,8 +59,13 @@ use super::tools::{
     QueuedTool,
     ToolOrigin,
     ToolSpec,
+    sanitize_path_tool_arg,
+};
+use super::util::{
+    serde_value_to_document,
+    truncate_safe,
+    truncate_safe_in_place,
 };
-use super::util::serde_value_to_document;
 use crate::api_client::model::{
     ChatMessage,
     ConversationState as FigConversationState,
@@ -64,11 +81,16 @@ use crate::cli::agent::hook::{
     HookTrigger,
 };
 use crate::cli::chat::ChatError;
-use crate::mcp_client::Prompt;
+use crate::mcp_client::{
+    Prompt,
+    ResourceReadResult,
+};
 use crate::os::Os;
 
 const CONTEXT_ENTRY_START_HEADER: &str = "--- CONTEXT ENTRY BEGIN ---\n";
 const CONTEXT_ENTRY_END_HEADER: &str = "--- CONTEXT ENTRY END ---\n\n";
+/// Maximum length in bytes of [ConversationState::title].
+const MAX_TITLE_LEN: usize = 80;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct HistoryEntry {
@@ -76,6 +98,37 @@ pub struct HistoryEntry {
     assistant: AssistantMessage,
     #[serde(default)]
     request_metadata: Option<RequestMetadata>,
+    #[serde(skip)]
+    token_counts: TokenCountCache,
+}
+
+impl HistoryEntry {
+    pub fn new(user: UserMessage, assistant: AssistantMessage, request_metadata: Option<RequestMetadata>) -> Self {
+        Self {
+            user,
+            assistant,
+            request_metadata,
+            token_counts: TokenCountCache::default(),
+        }
+    }
+
+    pub fn user(&self) -> &UserMessage {
+        &self.user
+    }
+
+    pub fn assistant(&self) -> &AssistantMessage {
+        &self.assistant
+    }
+
+    pub fn request_metadata(&self) -> Option<&RequestMetadata> {
+        self.request_metadata.as_ref()
+    }
+
+    /// Returns the tokens of the messages counted with `tokenizer`, only counting them again if
+    /// they have changed since the last call.
+    pub fn token_count(&self, tokenizer: TokenizerKind) -> EntryTokenCount {
+        self.token_counts.get(tokenizer, &self.user, &self.assistant)
+    }
 }
 
 /// Tracks state related to an ongoing conversation.
@@ -102,6 +155,9 @@ pub struct ConversationState {
     pub tool_manager: ToolManager,
     /// Cached value representing the length of the user context message.
     context_message_length: Option<usize>,
+    /// Cached count of the tokens of the context messages.
+    #[serde(skip)]
+    context_message_tokens: Option<TokenCount>,
     /// Stores the latest conversation summary created by /compact
     latest_summary: Option<(String, RequestMetadata)>,
     #[serde(skip)]
@@ -109,6 +165,25 @@ pub struct ConversationState {
     /// Model explicitly selected by the user in this conversation state via `/model`.
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub model: Option<String>,
+    /// Snapshots of the files modified by tools, used by `/checkpoint`. They are kept out of
+    /// sessions, which are saved after every response, and only saved by `/save`.
+    #[serde(skip)]
+    pub checkpoints: CheckpointStore,
+    /// Resources from MCP servers included in the context, attached with `/mcp resources`.
+    #[serde(default)]
+    pub attached_resources: Vec<AttachedResource>,
+    /// Whether the conversation is kept out of the database, as for subagents.
+    #[serde(skip)]
+    pub ephemeral: bool,
+}
+
+/// A resource of an MCP server included in the conversation context.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct AttachedResource {
+    pub server_name: String,
+    pub uri: String,
+    /// The contents of the resource when it was last read.
+    pub content: String,
 }
 
 impl ConversationState {
@@ -147,9 +222,13 @@ impl ConversationState {
             context_manager,
             tool_manager,
             context_message_length: None,
+            context_message_tokens: None,
             latest_summary: None,
             agents,
             model: current_model_id,
+            checkpoints: CheckpointStore::default(),
+            attached_resources: Vec::new(),
+            ephemeral: false,
         }
     }
 
@@ -193,16 +272,76 @@ impl ConversationState {
                 let assistant = candidate_asst.take().unwrap();
                 let user = candidate_user.take().unwrap();
                 self.append_assistant_transcript(&assistant);
-                self.history.push_back(HistoryEntry {
-                    user,
-                    assistant,
-                    request_metadata: None,
-                });
+                self.history.push_back(HistoryEntry::new(user, assistant, None));
             }
         }
         Some(last_msg.content.to_string())
     }
 
+    /// Reads the resource `uri` from the MCP server `server_name` and includes it in the context
+    /// of every following request, subscribing to updates if the server supports them.
+    pub async fn attach_resource(&mut self, server_name: &str, uri: &str) -> eyre::Result<()> {
+        let Some(client) = self.tool_manager.clients.get(server_name) else {
+            eyre::bail!("No MCP server named {server_name} is loaded");
+        };
+        let content = resource_content(client.read_resource(uri).await?);
+        if client.supports_resource_subscriptions().await {
+            if let Err(err) = client.subscribe_resource(uri).await {
+                warn!(?err, server_name, uri, "failed to subscribe to resource");
+            }
+        }
+
+        let resource = AttachedResource {
+            server_name: server_name.to_string(),
+            uri: uri.to_string(),
+            content,
+        };
+        match self
+            .attached_resources
+            .iter_mut()
+            .find(|r| r.server_name == server_name && r.uri == uri)
+        {
+            Some(existing) => *existing = resource,
+            None => self.attached_resources.push(resource),
+        }
+        Ok(())
+    }
+
+    /// Removes the resource `uri` of the MCP server `server_name` from the context. Returns the
+    /// removed resource, if any.
+    pub async fn detach_resource(&mut self, server_name: &str, uri: &str) -> Option<AttachedResource> {
+        let index = self
+            .attached_resources
+            .iter()
+            .position(|r| r.server_name == server_name && r.uri == uri)?;
+        let resource = self.attached_resources.remove(index);
+        if let Some(client) = self.tool_manager.clients.get(&resource.server_name) {
+            if client.supports_resource_subscriptions().await {
+                if let Err(err) = client.unsubscribe_resource(uri).await {
+                    warn!(?err, uri, "failed to unsubscribe from resource");
+                }
+            }
+        }
+        Some(resource)
+    }
+
+    /// Reads the attached resources that servers have reported as changed again.
+    async fn refresh_attached_resources(&mut self) {
+        let updated = self.tool_manager.take_updated_resources().await;
+        for resource in &mut self.attached_resources {
+            if !updated.contains(&(resource.server_name.clone(), resource.uri.clone())) {
+                continue;
+            }
+            let Some(client) = self.tool_manager.clients.get(&resource.server_name) else {
+                continue;
+            };
+            match client.read_resource(&resource.uri).await {
+                Ok(result) => resource.content = resource_content(result),
+                Err(err) => warn!(?err, uri = resource.uri, "failed to refresh attached resource"),
+            }
+        }
+    }
+
     pub fn next_user_message(&self) -> Option<&UserMessage> {
         self.next_message.as_ref()
     }
@@ -239,14 +378,65 @@ impl ConversationState {
         let next_user_message = self.next_message.take().expect("next user message should exist");
 
         self.append_assistant_transcript(&message);
-        self.history.push_back(HistoryEntry {
-            user: next_user_message,
-            assistant: message,
-            request_metadata,
-        });
+        self.history
+            .push_back(HistoryEntry::new(next_user_message, message, request_metadata));
 
+        self.save_session(os);
+    }
+
+    /// Saves the conversation as a session of the current directory, unless it is ephemeral.
+    pub fn save_session(&self, os: &Os) {
+        if self.ephemeral {
+            return;
+        }
         if let Ok(cwd) = std::env::current_dir() {
-            os.database.set_conversation_by_path(cwd, self).ok();
+            os.database.set_session(cwd, self).ok();
+        }
+    }
+
+    /// Returns the index in the history and the prompt of every exchange, oldest first. An
+    /// exchange starts with a prompt of the user and includes the tool uses made in response.
+    pub fn exchanges(&self) -> Vec<(usize, &str)> {
+        self.history
+            .iter()
+            .enumerate()
+            .filter_map(|(i, entry)| entry.user.prompt().map(|prompt| (i, prompt)))
+            .collect()
+    }
+
+    /// Drops the last `count` exchanges from the history and returns the prompt of the earliest
+    /// one dropped, or [None] if the history has fewer exchanges.
+    pub fn rewind(&mut self, count: usize) -> Option<String> {
+        let exchanges = self.exchanges();
+        let (index, prompt) = match exchanges.len().checked_sub(count) {
+            Some(i) if count > 0 => exchanges[i],
+            _ => return None,
+        };
+        let prompt = prompt.to_string();
+
+        self.history.truncate(index);
+        self.next_message = None;
+        self.enforce_conversation_invariants();
+        Some(prompt)
+    }
+
+    /// Replaces the id, history and summary of the conversation with those of a conversation
+    /// loaded with `/load`. The next message is dropped.
+    pub fn restore(&mut self, conversation_id: String, history: VecDeque<HistoryEntry>, summary: Option<String>) {
+        self.conversation_id = conversation_id;
+        self.history = history;
+        self.next_message = None;
+        self.context_message_length = None;
+        self.context_message_tokens = None;
+        self.latest_summary = summary.map(|summary| (summary, RequestMetadata::default()));
+        self.enforce_conversation_invariants();
+    }
+
+    /// Returns a copy of the conversation under a new id, to continue separately from this one.
+    pub fn fork(&self) -> Self {
+        Self {
+            conversation_id: uuid::Uuid::new_v4().to_string(),
+            ..self.clone()
         }
     }
 
@@ -255,6 +445,17 @@ impl ConversationState {
         self.conversation_id.as_ref()
     }
 
+    /// Returns a title for the conversation taken from the first line of its first prompt, or an
+    /// empty string if there is none.
+    pub fn title(&self) -> String {
+        let prompt = self
+            .history
+            .iter()
+            .find_map(|entry| entry.user.prompt())
+            .unwrap_or_default();
+        truncate_safe(prompt.lines().next().unwrap_or_default().trim(), MAX_TITLE_LEN).to_string()
+    }
+
     /// Returns the message id associated with the last assistant message, if present.
     ///
     /// This is equivalent to `utterance_id` in the Q API.
@@ -398,19 +599,29 @@ impl ConversationState {
     ) -> Result<BackendConversationState<'_>, ChatError> {
         self.update_state(false).await;
         self.enforce_conversation_invariants();
+        self.refresh_attached_resources().await;
 
         // Run hooks and add to conversation start and next user message.
         let mut agent_spawn_context = None;
         if let Some(cm) = self.context_manager.as_mut() {
             let user_prompt = self.next_message.as_ref().and_then(|m| m.prompt());
-            let agent_spawn = cm.run_hooks(HookTrigger::AgentSpawn, output, user_prompt).await?;
-            agent_spawn_context = format_hook_context(&agent_spawn, HookTrigger::AgentSpawn);
+            let event = HookEvent::new(os, HookTrigger::AgentSpawn).with_prompt(user_prompt);
+            let agent_spawn = cm.run_hooks(&event, output).await?;
+            agent_spawn_context = format_hook_context(os, &agent_spawn, HookTrigger::AgentSpawn).await;
 
             if let (true, Some(next_message)) = (run_perprompt_hooks, self.next_message.as_mut()) {
-                let per_prompt = cm
-                    .run_hooks(HookTrigger::UserPromptSubmit, output, next_message.prompt())
-                    .await?;
-                if let Some(ctx) = format_hook_context(&per_prompt, HookTrigger::UserPromptSubmit) {
+                let event = HookEvent::new(os, HookTrigger::UserPromptSubmit).with_prompt(next_message.prompt());
+                let per_prompt = cm.run_hooks(&event, output).await?;
+
+                // Only prompts written by the user can be blocked, tool results are always sent.
+                if next_message.prompt().is_some() {
+                    if let Some(reason) = hook_block_reason(&per_prompt) {
+                        self.next_message = None;
+                        return Err(ChatError::PromptBlocked(reason));
+                    }
+                }
+
+                if let Some(ctx) = format_hook_context(os, &per_prompt, HookTrigger::UserPromptSubmit).await {
                     next_message.additional_context = ctx;
                 }
             }
@@ -543,6 +754,19 @@ impl ConversationState {
         self.latest_summary = Some((summary, request_metadata));
     }
 
+    /// Makes the agent `name` the active one, replacing the context files and hooks with its own
+    /// and sharing its roots with MCP servers. The MCP servers themselves stay loaded.
+    pub async fn switch_agent(&mut self, os: &Os, name: &str) -> eyre::Result<()> {
+        let agent = self.agents.switch(name)?.clone();
+        self.context_manager =
+            ContextManager::from_agent(&agent, calc_max_context_files_size(self.model.as_deref())).ok();
+        self.context_message_length = None;
+        self.context_message_tokens = None;
+        self.tool_manager.update_roots(os, Some(&agent)).await;
+        *self.tool_manager.agent.lock().await = agent;
+        Ok(())
+    }
+
     pub fn current_profile(&self) -> Option<&str> {
         if let Some(cm) = self.context_manager.as_ref() {
             Some(cm.current_profile.as_str())
@@ -597,6 +821,17 @@ impl ConversationState {
             }
         }
 
+        if !self.attached_resources.is_empty() {
+            context_content.push_str(CONTEXT_ENTRY_START_HEADER);
+            for resource in &self.attached_resources {
+                context_content.push_str(&format!(
+                    "[{} from MCP server {}]\n{}\n",
+                    resource.uri, resource.server_name, resource.content
+                ));
+            }
+            context_content.push_str(CONTEXT_ENTRY_END_HEADER);
+        }
+
         if let Some(context) = additional_context {
             context_content.push_str(&context);
         }
@@ -609,14 +844,10 @@ impl ConversationState {
             self.context_message_length = Some(context_content.len());
             let user = UserMessage::new_prompt(context_content);
             let assistant = AssistantMessage::new_response(None, "I will fully incorporate this information when generating my responses, and explicitly acknowledge relevant parts of the summary when answering questions.".into());
-            (
-                Some(vec![HistoryEntry {
-                    user,
-                    assistant,
-                    request_metadata: None,
-                }]),
-                dropped_context_files,
-            )
+            let entry = HistoryEntry::new(user, assistant, None);
+            let EntryTokenCount { user, assistant } = entry.token_count(tokenizer_kind(self.model.as_deref()));
+            self.context_message_tokens = Some(user + assistant);
+            (Some(vec![entry]), dropped_context_files)
         } else {
             (None, dropped_context_files)
         }
@@ -627,20 +858,41 @@ impl ConversationState {
         self.context_message_length
     }
 
-    /// Calculate the total character count in the conversation
-    pub async fn calculate_char_count(&mut self, os: &Os) -> Result<CharCount, ChatError> {
+    /// Calculate the total token count in the conversation
+    pub async fn calculate_token_count(&mut self, os: &Os) -> Result<TokenCount, ChatError> {
         Ok(self
             .backend_conversation_state(os, false, &mut vec![])
             .await?
-            .char_count())
+            .calculate_conversation_size()
+            .total())
+    }
+
+    /// Estimates the token count of the request for the next user message from the sendable
+    /// history and the last context message, without running any hooks.
+    pub fn estimate_request_token_count(&self) -> TokenCount {
+        let tokenizer = tokenizer_kind(self.model.as_deref());
+        let history = self
+            .history
+            .range(self.valid_history_range.0..self.valid_history_range.1)
+            .map(|entry| {
+                let EntryTokenCount { user, assistant } = entry.token_count(tokenizer);
+                user + assistant
+            })
+            .sum::<TokenCount>();
+        let next_message = self
+            .next_message
+            .as_ref()
+            .map(|msg| msg.count_tokens(tokenizer.tokenizer()))
+            .unwrap_or_default();
+        history + next_message + self.context_message_tokens.unwrap_or_default()
     }
 
     /// Get the current token warning level
     pub async fn get_token_warning_level(&mut self, os: &Os) -> Result<TokenWarningLevel, ChatError> {
-        let total_chars = self.calculate_char_count(os).await?;
-        let max_chars = TokenCounter::token_to_chars(context_window_tokens(self.model.as_deref()));
+        let total_tokens = self.calculate_token_count(os).await?;
+        let max_tokens = context_window_tokens(self.model.as_deref());
 
-        Ok(if *total_chars >= max_chars {
+        Ok(if *total_tokens >= max_tokens {
             TokenWarningLevel::Critical
         } else {
             TokenWarningLevel::None
@@ -704,33 +956,30 @@ impl BackendConversationStateImpl<'_, std::collections::vec_deque::Iter<'_, Hist
     }
 
     pub fn calculate_conversation_size(&self) -> ConversationSize {
-        let mut user_chars = 0;
-        let mut assistant_chars = 0;
-        let mut context_chars = 0;
+        let tokenizer = tokenizer_kind(self.model_id);
+        let mut user_tokens = TokenCount::default();
+        let mut assistant_tokens = TokenCount::default();
+        let mut context_tokens = TokenCount::default();
 
-        // Count the chars used by the messages in the history.
+        // Count the tokens used by the messages in the history.
         // this clone is cheap
         let history = self.history.clone();
-        for HistoryEntry { user, assistant, .. } in history {
-            user_chars += *user.char_count();
-            assistant_chars += *assistant.char_count();
+        for entry in history {
+            let EntryTokenCount { user, assistant } = entry.token_count(tokenizer);
+            user_tokens += user;
+            assistant_tokens += assistant;
         }
 
-        // Add any chars from context messages, if available.
-        context_chars += self
-            .context_messages
-            .as_ref()
-            .map(|v| {
-                v.iter().fold(0, |acc, HistoryEntry { user, assistant, .. }| {
-                    acc + *user.char_count() + *assistant.char_count()
-                })
-            })
-            .unwrap_or_default();
+        // Add any tokens from context messages, if available.
+        for entry in self.context_messages.iter().flatten() {
+            let EntryTokenCount { user, assistant } = entry.token_count(tokenizer);
+            context_tokens += user + assistant;
+        }
 
         ConversationSize {
-            context_messages: context_chars.into(),
-            user_messages: user_chars.into(),
-            assistant_messages: assistant_chars.into(),
+            context_messages: context_tokens,
+            user_messages: user_tokens,
+            assistant_messages: assistant_tokens,
         }
     }
 }
@@ -738,9 +987,16 @@ impl BackendConversationStateImpl<'_, std::collections::vec_deque::Iter<'_, Hist
 /// Reflects a detailed accounting of the context window utilization for a given conversation.
 #[derive(Debug, Clone, Copy)]
 pub struct ConversationSize {
-    pub context_messages: CharCount,
-    pub user_messages: CharCount,
-    pub assistant_messages: CharCount,
+    pub context_messages: TokenCount,
+    pub user_messages: TokenCount,
+    pub assistant_messages: TokenCount,
+}
+
+impl ConversationSize {
+    /// Returns the tokens used by all of the messages.
+    pub fn total(&self) -> TokenCount {
+        self.context_messages + self.user_messages + self.assistant_messages
+    }
 }
 
 /// Converts a list of user/assistant message pairs into a flattened list of ChatMessage.
@@ -755,12 +1011,12 @@ where
     })
 }
 
-/// Character count warning levels for conversation size
+/// Token count warning levels for conversation size
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum TokenWarningLevel {
     /// No warning, conversation is within normal limits
     None,
-    /// Critical level - at single warning threshold (600K characters)
+    /// Critical level - at single warning threshold (the context window of the model)
     Critical,
 }
 
@@ -772,14 +1028,56 @@ impl From<InputSchema> for ToolInputSchema {
     }
 }
 
+/// Returns why the prompt is blocked if any of `hook_results` blocks it.
+fn hook_block_reason(hook_results: &[((HookTrigger, Hook), HookOutput)]) -> Option<String> {
+    let responses = hook_results
+        .iter()
+        .map(|(_, output)| output.response())
+        .filter(|response| response.decision == Some(HookDecision::Deny))
+        .collect::<Vec<_>>();
+    if responses.is_empty() {
+        return None;
+    }
+
+    let reasons = responses
+        .into_iter()
+        .filter_map(|response| response.reason)
+        .collect::<Vec<_>>();
+    Some(match reasons.is_empty() {
+        true => "no reason was given".to_string(),
+        false => reasons.join("; "),
+    })
+}
+
 /// Formats hook output to be used within context blocks (e.g., in context messages or in new user
-/// prompts).
+/// prompts). This includes the additional context of the hooks along with the content of the files
+/// they attach.
 ///
 /// # Returns
 /// [Option::Some] if `hook_results` is not empty and at least one hook has content. Otherwise,
 /// [Option::None]
-fn format_hook_context(hook_results: &[((HookTrigger, Hook), String)], trigger: HookTrigger) -> Option<String> {
-    if hook_results.iter().all(|(_, content)| content.is_empty()) {
+async fn format_hook_context(
+    os: &Os,
+    hook_results: &[((HookTrigger, Hook), HookOutput)],
+    trigger: HookTrigger,
+) -> Option<String> {
+    let mut outputs = Vec::new();
+    for ((_, hook), output) in hook_results.iter().filter(|((h_trigger, _), _)| *h_trigger == trigger) {
+        let response = output.response();
+        if let Some(context) = response.additional_context.filter(|c| !c.is_empty()) {
+            outputs.push(context);
+        }
+        for path in response.attach_files {
+            match os.fs.read_to_string(sanitize_path_tool_arg(os, &path)).await {
+                Ok(mut content) => {
+                    truncate_safe_in_place(&mut content, hook.max_output_size, "\n[file truncated]");
+                    outputs.push(format!("[{path}]\n{content}"));
+                },
+                Err(err) => warn!(?err, %path, "failed to read a file attached by a hook"),
+            }
+        }
+    }
+    if outputs.is_empty() {
         return None;
     }
 
@@ -792,7 +1090,7 @@ fn format_hook_context(hook_results: &[((HookTrigger, Hook), String)], trigger:
     }
     context_content.push_str("\n\n");
 
-    for (_, output) in hook_results.iter().filter(|((h_trigger, _), _)| *h_trigger == trigger) {
+    for output in outputs {
         context_content.push_str(&format!("{output}\n\n"));
     }
     context_content.push_str(CONTEXT_ENTRY_END_HEADER);
@@ -944,8 +1242,30 @@ fn enforce_tool_use_history_invariants(history: &mut VecDeque<HistoryEntry>, too
     }
 }
 
+/// Converts the contents of a resource to the text included in the context. Binary contents are
+/// only described.
+fn resource_content(result: ResourceReadResult) -> String {
+    let mut content = result
+        .contents
+        .into_iter()
+        .map(|contents| match (contents.text, contents.blob) {
+            (Some(text), _) => text,
+            (None, blob) => format!(
+                "[binary content of type {}, {} bytes base64 encoded]",
+                contents.mime_type.as_deref().unwrap_or("unknown"),
+                blob.map_or(0, |b| b.len())
+            ),
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+    truncate_safe_in_place(&mut content, MAX_RESOURCE_SIZE, "\n[resource truncated]");
+    content
+}
+
 #[cfg(test)]
 mod tests {
+    use std::path::Path;
+
     use super::super::message::AssistantToolUse;
     use super::*;
     use crate::api_client::model::{
@@ -1206,4 +1526,227 @@ mod tests {
             conversation.set_next_user_message(i.to_string()).await;
         }
     }
+
+    #[tokio::test]
+    async fn test_conversation_state_with_attached_resources() {
+        let mut os = Os::new().await.unwrap();
+        let mut tool_manager = ToolManager::default();
+        let mut conversation = ConversationState::new(
+            "fake_conv_id",
+            Agents::default(),
+            tool_manager.load_tools(&mut os, &mut vec![]).await.unwrap(),
+            tool_manager,
+            None,
+        )
+        .await;
+        conversation.attached_resources.push(AttachedResource {
+            server_name: "docs".to_string(),
+            uri: "docs://readme".to_string(),
+            content: "resource content".to_string(),
+        });
+
+        conversation.set_next_user_message("hello".to_string()).await;
+        let s = conversation
+            .as_sendable_conversation_state(&os, &mut vec![], true)
+            .await
+            .unwrap();
+        match &s.history.as_ref().unwrap()[0] {
+            ChatMessage::UserInputMessage(user) => {
+                assert!(
+                    user.content
+                        .contains("[docs://readme from MCP server docs]\nresource content")
+                );
+            },
+            ChatMessage::AssistantResponseMessage(_) => panic!("Expected the first message to be from the user"),
+        }
+
+        // Servers may expose the same uri, each is detached on its own.
+        conversation.attached_resources.push(AttachedResource {
+            server_name: "mirror".to_string(),
+            uri: "docs://readme".to_string(),
+            content: "mirrored content".to_string(),
+        });
+        assert!(conversation.detach_resource("other", "docs://readme").await.is_none());
+        let detached = conversation.detach_resource("mirror", "docs://readme").await.unwrap();
+        assert_eq!(detached.content, "mirrored content");
+        assert_eq!(conversation.attached_resources.len(), 1);
+        assert_eq!(conversation.attached_resources[0].server_name, "docs");
+    }
+
+    #[test]
+    fn test_resource_content() {
+        let result = serde_json::from_value::<ResourceReadResult>(serde_json::json!({
+            "contents": [
+                { "uri": "file:///a.txt", "mimeType": "text/plain", "text": "hello" },
+                { "uri": "file:///b.png", "mimeType": "image/png", "blob": "aGVsbG8=" },
+            ]
+        }))
+        .unwrap();
+        assert_eq!(
+            resource_content(result),
+            "hello\n[binary content of type image/png, 8 bytes base64 encoded]"
+        );
+
+        let result = ResourceReadResult {
+            contents: vec![crate::mcp_client::ResourceReadContents {
+                uri: "file:///big.txt".to_string(),
+                mime_type: None,
+                text: Some("a".repeat(MAX_RESOURCE_SIZE * 2)),
+                blob: None,
+            }],
+        };
+        let content = resource_content(result);
+        assert_eq!(content.len(), MAX_RESOURCE_SIZE);
+        assert!(content.ends_with("[resource truncated]"));
+    }
+
+    #[tokio::test]
+    async fn test_hook_context_and_block_reason() {
+        let os = Os::new().await.unwrap();
+        os.fs.write("/notes.md", "remember the milk").await.unwrap();
+
+        let hook = |command: &str| {
+            (
+                HookTrigger::UserPromptSubmit,
+                Hook::new(command.to_string(), Default::default()),
+            )
+        };
+        let results = vec![
+            (hook("a"), HookOutput::Success("plain text".to_string())),
+            (
+                hook("b"),
+                HookOutput::Success(r#"{"additionalContext": "structured", "attachFiles": ["/notes.md"]}"#.to_string()),
+            ),
+        ];
+        let context = format_hook_context(&os, &results, HookTrigger::UserPromptSubmit)
+            .await
+            .unwrap();
+        assert!(context.contains("plain text"));
+        assert!(context.contains("structured"));
+        assert!(context.contains("[/notes.md]\nremember the milk"));
+        assert!(
+            format_hook_context(&os, &results, HookTrigger::AgentSpawn)
+                .await
+                .is_none()
+        );
+        assert_eq!(hook_block_reason(&results), None);
+
+        let results = vec![
+            (hook("a"), HookOutput::Blocked("contains a secret".to_string())),
+            (
+                hook("b"),
+                HookOutput::Success(r#"{"decision": "block", "reason": "too long"}"#.to_string()),
+            ),
+        ];
+        assert_eq!(
+            hook_block_reason(&results),
+            Some("contains a secret; too long".to_string())
+        );
+    }
+
+    #[tokio::test]
+    async fn test_rewind_and_fork() {
+        let mut os = Os::new().await.unwrap();
+        let mut tool_manager = ToolManager::default();
+        let tool_config = tool_manager.load_tools(&mut os, &mut vec![]).await.unwrap();
+        let mut conversation =
+            ConversationState::new("fake_conv_id", Agents::default(), tool_config, tool_manager, None).await;
+        conversation.ephemeral = true;
+
+        conversation.set_next_user_message("first".to_string()).await;
+        conversation.push_assistant_message(&mut os, AssistantMessage::new_response(None, "1".to_string()), None);
+        // The second exchange includes a tool use and its result.
+        conversation.set_next_user_message("second".to_string()).await;
+        conversation.push_assistant_message(
+            &mut os,
+            AssistantMessage::new_tool_use(None, "2".to_string(), vec![AssistantToolUse {
+                id: "tool_id".to_string(),
+                name: "fs_read".to_string(),
+                args: serde_json::Value::Null,
+                ..Default::default()
+            }]),
+            None,
+        );
+        conversation.add_tool_results(vec![ToolUseResult {
+            tool_use_id: "tool_id".to_string(),
+            content: vec![],
+            status: ToolResultStatus::Success,
+        }]);
+        conversation.push_assistant_message(&mut os, AssistantMessage::new_response(None, "2".to_string()), None);
+        conversation.set_next_user_message("third".to_string()).await;
+        conversation.push_assistant_message(&mut os, AssistantMessage::new_response(None, "3".to_string()), None);
+
+        let forked = conversation.fork();
+        assert_ne!(forked.conversation_id(), conversation.conversation_id());
+        assert_eq!(forked.history().len(), 4);
+
+        assert_eq!(
+            conversation.exchanges().iter().map(|(_, p)| *p).collect::<Vec<_>>(),
+            vec!["first", "second", "third"]
+        );
+        assert!(conversation.rewind(4).is_none());
+        assert!(conversation.rewind(0).is_none());
+        assert_eq!(conversation.rewind(2), Some("second".to_string()));
+        assert_eq!(conversation.history().len(), 1);
+        assert_eq!(forked.history().len(), 4);
+
+        conversation.set_next_user_message("second, again".to_string()).await;
+        let s = conversation
+            .as_sendable_conversation_state(&os, &mut vec![], true)
+            .await
+            .unwrap();
+        assert_conversation_state_invariants(s, 0);
+    }
+
+    #[tokio::test]
+    async fn test_sessions() {
+        let mut os = Os::new().await.unwrap();
+        let mut conversation = ConversationState::new(
+            "fake_conv_id",
+            Agents::default(),
+            HashMap::new(),
+            ToolManager::default(),
+            Some("claude-sonnet-4".to_string()),
+        )
+        .await;
+        conversation.ephemeral = true;
+        conversation
+            .set_next_user_message("Fix the build\nIt fails on CI".to_string())
+            .await;
+        conversation.push_assistant_message(&mut os, AssistantMessage::new_response(None, "Done".to_string()), None);
+        assert_eq!(conversation.title(), "Fix the build");
+
+        let db = &os.database;
+        db.set_session("/repo", &conversation).unwrap();
+        let sessions = db.list_sessions(Some(Path::new("/repo"))).unwrap();
+        assert_eq!(sessions.len(), 1);
+        assert_eq!(sessions[0].title, "Fix the build");
+        assert_eq!(sessions[0].message_count, 2);
+        assert_eq!(sessions[0].model.as_deref(), Some("claude-sonnet-4"));
+
+        // Saving again keeps a renamed title and the directory.
+        assert!(db.rename_session("fake_conv_id", "Build fixes").unwrap());
+        db.set_session("/elsewhere", &conversation).unwrap();
+        assert!(db.list_sessions(Some(Path::new("/elsewhere"))).unwrap().is_empty());
+        assert_eq!(db.list_sessions(None).unwrap()[0].title, "Build fixes");
+
+        let loaded = db.get_session("fake_conv_id").unwrap().unwrap();
+        let mut restored = ConversationState::new(
+            "other_conv_id",
+            Agents::default(),
+            HashMap::new(),
+            ToolManager::default(),
+            None,
+        )
+        .await;
+        assert!(loaded.restore_into(&mut restored).is_empty());
+        assert_eq!(restored.conversation_id(), "fake_conv_id");
+        assert_eq!(restored.history().len(), 1);
+        assert_eq!(restored.model.as_deref(), Some("claude-sonnet-4"));
+
+        assert!(db.delete_session("fake_conv_id").unwrap());
+        assert!(!db.delete_session("fake_conv_id").unwrap());
+        assert!(db.get_session("fake_conv_id").unwrap().is_none());
+        assert!(!db.rename_session("fake_conv_id", "Gone").unwrap());
+    }
 }
@@ -0,0 +1,900 @@
+//! The file format of conversations saved with `/save` and loaded with `/load`, which is also
+//! the format of the sessions saved as the user chats.
+//!
+//! The format is versioned and independent of [ConversationState], so that changes to the
+//! in-memory types do not make earlier saves unloadable. A file of an earlier version is migrated
+//! to [CURRENT_VERSION] one version at a time before being read, and the parts of a file that
+//! cannot be read are left out and reported rather than failing the whole load.
+//!
+//! To change the format, increment [CURRENT_VERSION], freeze the types of the previous version
+//! in their own module as done in [v1], add a migration from the previous version to
+//! [MIGRATIONS], and add a fixture of the previous version to the tests.
+
+mod v1;
+
+use std::collections::{
+    BTreeMap,
+    VecDeque,
+};
+use std::path::PathBuf;
+
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+use serde::de::DeserializeOwned;
+use serde::{
+    Deserialize,
+    Deserializer,
+    Serialize,
+    Serializer,
+};
+use serde_json::{
+    Map,
+    Value,
+};
+use thiserror::Error;
+
+use super::checkpoint::{
+    self,
+    CheckpointStore,
+};
+use super::conversation::{
+    self,
+    AttachedResource,
+    ConversationState,
+};
+use super::message::{
+    self,
+    AssistantToolUse,
+    ToolUseResultBlock,
+    UserEnvContext,
+    UserMessageContent,
+};
+use super::parser;
+use crate::api_client::model::{
+    self,
+    EnvState,
+};
+
+/// The version of the files written by [to_json].
+pub const CURRENT_VERSION: u64 = 2;
+
+/// Migrations between consecutive versions, the first one migrating version 1 to version 2.
+/// Each migration is best effort: the parts it cannot migrate are left as they are, to be
+/// reported when reading the migrated document.
+const MIGRATIONS: &[fn(Value) -> Value] = &[v1::migrate];
+
+#[derive(Debug, Error)]
+pub enum LoadError {
+    #[error("the file is not valid JSON: {0}")]
+    Json(#[from] serde_json::Error),
+    #[error("the file is not a saved conversation")]
+    NotAConversation,
+    #[error("the file has an invalid version: {0}")]
+    InvalidVersion(Value),
+}
+
+/// A conversation read from a file by [from_json].
+#[derive(Debug)]
+pub struct LoadedConversation {
+    /// The version of the file before migrating it.
+    pub version: u64,
+    conversation_id: String,
+    model: Option<String>,
+    summary: Option<String>,
+    history: VecDeque<conversation::HistoryEntry>,
+    transcript: VecDeque<String>,
+    checkpoints: CheckpointStore,
+    attached_resources: Vec<AttachedResource>,
+    /// The parts of the file that were left out, and why.
+    issues: Vec<String>,
+}
+
+impl LoadedConversation {
+    /// Whether the conversation has no messages.
+    pub fn is_empty(&self) -> bool {
+        self.history.is_empty()
+    }
+
+    /// Replaces the conversation of `conversation` with this one, keeping its tools, context and
+    /// agents. Returns the parts of the file that were left out.
+    pub fn restore_into(self, conversation: &mut ConversationState) -> Vec<String> {
+        conversation.restore(self.conversation_id, self.history, self.summary);
+        conversation.transcript = self.transcript;
+        conversation.checkpoints = self.checkpoints;
+        conversation.attached_resources = self.attached_resources;
+        if self.model.is_some() {
+            conversation.model = self.model;
+        }
+        self.issues
+    }
+}
+
+/// Serializes `conversation` in the format of [CURRENT_VERSION].
+pub fn to_json(conversation: &ConversationState) -> Result<String, serde_json::Error> {
+    let file = ConversationFile {
+        checkpoints: Some(Checkpoints::from(&conversation.checkpoints)),
+        ..ConversationFile::new(conversation)
+    };
+    serde_json::to_string_pretty(&file)
+}
+
+/// Serializes `conversation` in the format of [CURRENT_VERSION] for the sessions saved after
+/// every response, leaving out the checkpoints, which are only saved by `/save`.
+pub fn to_session_json(conversation: &ConversationState) -> Result<String, serde_json::Error> {
+    serde_json::to_string(&ConversationFile::new(conversation))
+}
+
+/// Reads a conversation saved in any version of the format, including the unversioned files
+/// written before the format had a version.
+pub fn from_json(contents: &str) -> Result<LoadedConversation, LoadError> {
+    let mut document: Value = serde_json::from_str(contents)?;
+    if !document.is_object() {
+        return Err(LoadError::NotAConversation);
+    }
+
+    let version = match document.get("version") {
+        None => 1,
+        Some(version) => match version.as_u64() {
+            Some(version) if version > 0 => version,
+            _ => return Err(LoadError::InvalidVersion(version.clone())),
+        },
+    };
+    let mut issues = Vec::new();
+    if version > CURRENT_VERSION {
+        issues.push(format!(
+            "The file was saved in version {version} of the format, newer than the version \
+             {CURRENT_VERSION} supported by this version of q. Fields that were added since were \
+             ignored"
+        ));
+    }
+    for migrate in MIGRATIONS.iter().skip(version as usize - 1) {
+        document = migrate(document);
+    }
+
+    let Value::Object(mut document) = document else {
+        return Err(LoadError::NotAConversation);
+    };
+    if !document.contains_key("history") {
+        return Err(LoadError::NotAConversation);
+    }
+
+    let conversation_id = match section::<Option<String>>(&mut document, "conversationId", &mut issues) {
+        Some(id) => id,
+        None => {
+            issues.push("The conversation id is missing, a new one was generated".to_string());
+            uuid::Uuid::new_v4().to_string()
+        },
+    };
+    let history = section::<Vec<Value>>(&mut document, "history", &mut issues);
+
+    Ok(LoadedConversation {
+        version,
+        conversation_id,
+        model: section(&mut document, "model", &mut issues),
+        summary: section(&mut document, "summary", &mut issues),
+        history: read_history(history, &mut issues),
+        transcript: section(&mut document, "transcript", &mut issues),
+        checkpoints: section::<Option<Checkpoints>>(&mut document, "checkpoints", &mut issues)
+            .map(Into::into)
+            .unwrap_or_default(),
+        attached_resources: section::<Vec<Resource>>(&mut document, "attachedResources", &mut issues)
+            .into_iter()
+            .map(Into::into)
+            .collect(),
+        issues,
+    })
+}
+
+/// Reads the field `key` of `document`, or its default value if it is missing or invalid.
+fn section<T: DeserializeOwned + Default>(document: &mut Map<String, Value>, key: &str, issues: &mut Vec<String>) -> T {
+    match document.remove(key) {
+        None | Some(Value::Null) => T::default(),
+        Some(value) => serde_json::from_value(value).unwrap_or_else(|err| {
+            issues.push(format!("Skipped `{key}`: {err}"));
+            T::default()
+        }),
+    }
+}
+
+/// Reads the entries of the history. An entry that cannot be read is left out together with the
+/// rest of its exchange, the prompt and every tool use made in response, since tool uses and
+/// their results are only valid in pairs.
+fn read_history(entries: Vec<Value>, issues: &mut Vec<String>) -> VecDeque<conversation::HistoryEntry> {
+    // An entry that cannot be read is assumed to belong to the exchange before it.
+    let mut exchanges: Vec<Vec<Result<HistoryEntry, String>>> = Vec::new();
+    for (i, value) in entries.into_iter().enumerate() {
+        let entry = serde_json::from_value::<HistoryEntry>(value).map_err(|err| format!("message {}: {err}", i + 1));
+        let starts_exchange = matches!(&entry, Ok(entry) if entry.user.content.prompt().is_some());
+        match exchanges.last_mut() {
+            Some(exchange) if !starts_exchange => exchange.push(entry),
+            _ => exchanges.push(vec![entry]),
+        }
+    }
+
+    let mut history = VecDeque::new();
+    let mut skipped_previous = false;
+    for exchange in exchanges {
+        let errors = exchange
+            .iter()
+            .filter_map(|entry| entry.as_ref().err())
+            .collect::<Vec<_>>();
+        if !errors.is_empty() {
+            let prompt = exchange
+                .iter()
+                .find_map(|entry| entry.as_ref().ok().and_then(|entry| entry.user.content.prompt()))
+                .map(|prompt| format!(" \"{}\"", prompt.lines().next().unwrap_or_default()))
+                .unwrap_or_default();
+            issues.push(format!(
+                "Skipped the {} messages of the exchange{prompt}, because of {}",
+                exchange.len(),
+                errors.iter().map(|err| err.as_str()).collect::<Vec<_>>().join("; ")
+            ));
+            skipped_previous = true;
+            continue;
+        }
+
+        for (i, mut entry) in exchange.into_iter().flatten().enumerate() {
+            // The results of the tool uses of a skipped exchange would have nothing to refer to.
+            if let UserContent::CancelledToolUses {
+                prompt: Some(prompt), ..
+            } = &entry.user.content
+            {
+                if i == 0 && skipped_previous {
+                    entry.user.content = UserContent::Prompt { prompt: prompt.clone() };
+                }
+            }
+            history.push_back(entry.into());
+        }
+        skipped_previous = false;
+    }
+
+    history
+}
+
+/// The top level of a file, as written by [to_json].
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ConversationFile {
+    version: u64,
+    conversation_id: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    model: Option<String>,
+    /// The summary created by `/compact`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    summary: Option<String>,
+    history: Vec<HistoryEntry>,
+    transcript: Vec<String>,
+    attached_resources: Vec<Resource>,
+    /// The checkpoints of `/checkpoint`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    checkpoints: Option<Checkpoints>,
+}
+
+impl ConversationFile {
+    fn new(conversation: &ConversationState) -> Self {
+        Self {
+            version: CURRENT_VERSION,
+            conversation_id: conversation.conversation_id().to_string(),
+            model: conversation.model.clone(),
+            summary: conversation.latest_summary().map(str::to_string),
+            history: conversation.history().iter().map(HistoryEntry::from).collect(),
+            transcript: conversation.transcript.iter().cloned().collect(),
+            attached_resources: conversation.attached_resources.iter().map(Resource::from).collect(),
+            checkpoints: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct HistoryEntry {
+    user: UserMessage,
+    assistant: AssistantMessage,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    metadata: Option<RequestMetadata>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct UserMessage {
+    content: UserContent,
+    /// Context added by hooks.
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    hook_context: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    environment: Option<Environment>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    images: Vec<Image>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "camelCase", rename_all_fields = "camelCase")]
+enum UserContent {
+    Prompt {
+        prompt: String,
+    },
+    ToolResults {
+        tool_results: Vec<ToolResult>,
+    },
+    /// The results of tool uses that were cancelled, because the user sent a prompt instead.
+    CancelledToolUses {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        prompt: Option<String>,
+        tool_results: Vec<ToolResult>,
+    },
+}
+
+impl UserContent {
+    fn prompt(&self) -> Option<&str> {
+        match self {
+            Self::Prompt { prompt } => Some(prompt),
+            Self::CancelledToolUses { prompt, .. } => prompt.as_deref(),
+            Self::ToolResults { .. } => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ToolResult {
+    tool_use_id: String,
+    status: ToolResultStatus,
+    content: Vec<ContentBlock>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+enum ToolResultStatus {
+    Success,
+    Error,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "camelCase")]
+enum ContentBlock {
+    Text { text: String },
+    Json { json: Value },
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Environment {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    operating_system: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    current_working_directory: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    environment_variables: Vec<EnvironmentVariable>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct EnvironmentVariable {
+    key: String,
+    value: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct Image {
+    format: ImageFormat,
+    /// The bytes of the image, in base64.
+    #[serde(serialize_with = "serialize_base64", deserialize_with = "deserialize_base64")]
+    data: Vec<u8>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+enum ImageFormat {
+    Gif,
+    Jpeg,
+    Png,
+    Webp,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct AssistantMessage {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    message_id: Option<String>,
+    content: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    tool_uses: Vec<ToolUse>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ToolUse {
+    id: String,
+    name: String,
+    args: Value,
+    /// The name of the tool, if the model used a different one.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    original_name: Option<String>,
+    /// The arguments passed by the model, if they were changed before running the tool.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    original_args: Option<Value>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Checkpoints {
+    /// The current turn, incremented on every prompt.
+    turn: usize,
+    /// Preview of the prompt that started the current turn.
+    prompt: String,
+    checkpoints: Vec<Checkpoint>,
+}
+
+/// The files modified during a single turn.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Checkpoint {
+    turn: usize,
+    prompt: String,
+    #[serde(with = "time::serde::rfc3339")]
+    timestamp: time::OffsetDateTime,
+    /// The content of each file before the turn first modified it, null if the file did not
+    /// exist.
+    files: BTreeMap<PathBuf, Option<String>>,
+}
+
+/// The metadata of a request worth keeping, the rest only matters while it is sent.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RequestMetadata {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    request_id: Option<String>,
+    #[serde(default)]
+    message_id: String,
+    /// Unix timestamp in milliseconds of when the request was sent.
+    #[serde(default)]
+    request_start_timestamp_ms: u64,
+    /// Unix timestamp in milliseconds of when the response ended.
+    #[serde(default)]
+    stream_end_timestamp_ms: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    model_id: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Resource {
+    server_name: String,
+    uri: String,
+    content: String,
+}
+
+fn serialize_base64<S: Serializer>(data: &[u8], serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(&STANDARD.encode(data))
+}
+
+fn deserialize_base64<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<u8>, D::Error> {
+    let data = String::deserialize(deserializer)?;
+    STANDARD.decode(data).map_err(serde::de::Error::custom)
+}
+
+impl From<&conversation::HistoryEntry> for HistoryEntry {
+    fn from(entry: &conversation::HistoryEntry) -> Self {
+        Self {
+            user: entry.user().into(),
+            assistant: entry.assistant().into(),
+            metadata: entry.request_metadata().map(|metadata| RequestMetadata {
+                request_id: metadata.request_id.clone(),
+                message_id: metadata.message_id.clone(),
+                request_start_timestamp_ms: metadata.request_start_timestamp_ms,
+                stream_end_timestamp_ms: metadata.stream_end_timestamp_ms,
+                model_id: metadata.model_id.clone(),
+            }),
+        }
+    }
+}
+
+impl From<HistoryEntry> for conversation::HistoryEntry {
+    fn from(entry: HistoryEntry) -> Self {
+        let metadata = entry.metadata.map(|metadata| parser::RequestMetadata {
+            request_id: metadata.request_id,
+            message_id: metadata.message_id,
+            request_start_timestamp_ms: metadata.request_start_timestamp_ms,
+            stream_end_timestamp_ms: metadata.stream_end_timestamp_ms,
+            model_id: metadata.model_id,
+            ..Default::default()
+        });
+        Self::new(entry.user.into(), entry.assistant.into(), metadata)
+    }
+}
+
+impl From<&message::UserMessage> for UserMessage {
+    fn from(message: &message::UserMessage) -> Self {
+        let tool_results = |results: &[message::ToolUseResult]| results.iter().map(ToolResult::from).collect();
+        Self {
+            content: match message.content() {
+                UserMessageContent::Prompt { prompt } => UserContent::Prompt { prompt: prompt.clone() },
+                UserMessageContent::ToolUseResults { tool_use_results } => UserContent::ToolResults {
+                    tool_results: tool_results(tool_use_results),
+                },
+                UserMessageContent::CancelledToolUses {
+                    prompt,
+                    tool_use_results,
+                } => UserContent::CancelledToolUses {
+                    prompt: prompt.clone(),
+                    tool_results: tool_results(tool_use_results),
+                },
+            },
+            hook_context: message.additional_context.clone(),
+            environment: message.env_context.env_state().map(|env| Environment {
+                operating_system: env.operating_system.clone(),
+                current_working_directory: env.current_working_directory.clone(),
+                environment_variables: env
+                    .environment_variables
+                    .iter()
+                    .map(|var| EnvironmentVariable {
+                        key: var.key.clone(),
+                        value: var.value.clone(),
+                    })
+                    .collect(),
+            }),
+            images: message
+                .images
+                .iter()
+                .flatten()
+                .filter_map(|image| match &image.source {
+                    model::ImageSource::Bytes(data) => Some(Image {
+                        format: image.format.clone().into(),
+                        data: data.clone(),
+                    }),
+                    model::ImageSource::Unknown => None,
+                })
+                .collect(),
+        }
+    }
+}
+
+impl From<UserMessage> for message::UserMessage {
+    fn from(message: UserMessage) -> Self {
+        let tool_results = |results: Vec<ToolResult>| results.into_iter().map(Into::into).collect();
+        Self {
+            additional_context: message.hook_context,
+            env_context: UserEnvContext::from_env_state(message.environment.map(|env| {
+                EnvState {
+                    operating_system: env.operating_system,
+                    current_working_directory: env.current_working_directory,
+                    environment_variables: env
+                        .environment_variables
+                        .into_iter()
+                        .map(|var| model::EnvironmentVariable {
+                            key: var.key,
+                            value: var.value,
+                        })
+                        .collect(),
+                }
+            })),
+            content: match message.content {
+                UserContent::Prompt { prompt } => UserMessageContent::Prompt { prompt },
+                UserContent::ToolResults { tool_results: results } => UserMessageContent::ToolUseResults {
+                    tool_use_results: tool_results(results),
+                },
+                UserContent::CancelledToolUses {
+                    prompt,
+                    tool_results: results,
+                } => UserMessageContent::CancelledToolUses {
+                    prompt,
+                    tool_use_results: tool_results(results),
+                },
+            },
+            images: (!message.images.is_empty()).then(|| {
+                message
+                    .images
+                    .into_iter()
+                    .map(|image| model::ImageBlock {
+                        format: image.format.into(),
+                        source: model::ImageSource::Bytes(image.data),
+                    })
+                    .collect()
+            }),
+        }
+    }
+}
+
+impl From<&message::ToolUseResult> for ToolResult {
+    fn from(result: &message::ToolUseResult) -> Self {
+        Self {
+            tool_use_id: result.tool_use_id.clone(),
+            status: match result.status {
+                model::ToolResultStatus::Success => ToolResultStatus::Success,
+                model::ToolResultStatus::Error => ToolResultStatus::Error,
+            },
+            content: result
+                .content
+                .iter()
+                .map(|block| match block {
+                    ToolUseResultBlock::Text(text) => ContentBlock::Text { text: text.clone() },
+                    ToolUseResultBlock::Json(json) => ContentBlock::Json { json: json.clone() },
+                })
+                .collect(),
+        }
+    }
+}
+
+impl From<ToolResult> for message::ToolUseResult {
+    fn from(result: ToolResult) -> Self {
+        Self {
+            tool_use_id: result.tool_use_id,
+            status: match result.status {
+                ToolResultStatus::Success => model::ToolResultStatus::Success,
+                ToolResultStatus::Error => model::ToolResultStatus::Error,
+            },
+            content: result
+                .content
+                .into_iter()
+                .map(|block| match block {
+                    ContentBlock::Text { text } => ToolUseResultBlock::Text(text),
+                    ContentBlock::Json { json } => ToolUseResultBlock::Json(json),
+                })
+                .collect(),
+        }
+    }
+}
+
+impl From<model::ImageFormat> for ImageFormat {
+    fn from(format: model::ImageFormat) -> Self {
+        match format {
+            model::ImageFormat::Gif => Self::Gif,
+            model::ImageFormat::Jpeg => Self::Jpeg,
+            model::ImageFormat::Png => Self::Png,
+            model::ImageFormat::Webp => Self::Webp,
+        }
+    }
+}
+
+impl From<ImageFormat> for model::ImageFormat {
+    fn from(format: ImageFormat) -> Self {
+        match format {
+            ImageFormat::Gif => Self::Gif,
+            ImageFormat::Jpeg => Self::Jpeg,
+            ImageFormat::Png => Self::Png,
+            ImageFormat::Webp => Self::Webp,
+        }
+    }
+}
+
+impl From<&message::AssistantMessage> for AssistantMessage {
+    fn from(message: &message::AssistantMessage) -> Self {
+        let message_id = match message {
+            message::AssistantMessage::Response { message_id, .. }
+            | message::AssistantMessage::ToolUse { message_id, .. } => message_id.clone(),
+        };
+        Self {
+            message_id,
+            content: message.content().to_string(),
+            tool_uses: message
+                .tool_uses()
+                .unwrap_or_default()
+                .iter()
+                .map(|tool_use| ToolUse {
+                    id: tool_use.id.clone(),
+                    name: tool_use.name.clone(),
+                    args: tool_use.args.clone(),
+                    original_name: (tool_use.orig_name != tool_use.name).then(|| tool_use.orig_name.clone()),
+                    original_args: (tool_use.orig_args != tool_use.args).then(|| tool_use.orig_args.clone()),
+                })
+                .collect(),
+        }
+    }
+}
+
+impl From<AssistantMessage> for message::AssistantMessage {
+    fn from(message: AssistantMessage) -> Self {
+        if message.tool_uses.is_empty() {
+            return Self::new_response(message.message_id, message.content);
+        }
+        let tool_uses = message
+            .tool_uses
+            .into_iter()
+            .map(|tool_use| AssistantToolUse {
+                orig_name: tool_use.original_name.unwrap_or_else(|| tool_use.name.clone()),
+                orig_args: tool_use.original_args.unwrap_or_else(|| tool_use.args.clone()),
+                id: tool_use.id,
+                name: tool_use.name,
+                args: tool_use.args,
+            })
+            .collect();
+        Self::new_tool_use(message.message_id, message.content, tool_uses)
+    }
+}
+
+impl From<&AttachedResource> for Resource {
+    fn from(resource: &AttachedResource) -> Self {
+        Self {
+            server_name: resource.server_name.clone(),
+            uri: resource.uri.clone(),
+            content: resource.content.clone(),
+        }
+    }
+}
+
+impl From<Resource> for AttachedResource {
+    fn from(resource: Resource) -> Self {
+        Self {
+            server_name: resource.server_name,
+            uri: resource.uri,
+            content: resource.content,
+        }
+    }
+}
+
+impl From<&CheckpointStore> for Checkpoints {
+    fn from(store: &CheckpointStore) -> Self {
+        Self {
+            turn: store.turn(),
+            prompt: store.prompt().to_string(),
+            checkpoints: store
+                .checkpoints()
+                .iter()
+                .map(|checkpoint| Checkpoint {
+                    turn: checkpoint.turn,
+                    prompt: checkpoint.prompt.clone(),
+                    timestamp: checkpoint.timestamp,
+                    files: checkpoint.files.clone(),
+                })
+                .collect(),
+        }
+    }
+}
+
+impl From<Checkpoints> for CheckpointStore {
+    fn from(checkpoints: Checkpoints) -> Self {
+        CheckpointStore::from_parts(
+            checkpoints.turn,
+            checkpoints.prompt,
+            checkpoints
+                .checkpoints
+                .into_iter()
+                .map(|checkpoint| checkpoint::Checkpoint {
+                    turn: checkpoint.turn,
+                    prompt: checkpoint.prompt,
+                    timestamp: checkpoint.timestamp,
+                    files: checkpoint.files,
+                })
+                .collect(),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+    use crate::cli::agent::Agents;
+    use crate::cli::chat::tool_manager::ToolManager;
+    use crate::cli::chat::tools::ToolSpec;
+
+    const V1: &str = include_str!("fixtures/v1.json");
+    const V2: &str = include_str!("fixtures/v2.json");
+
+    async fn conversation() -> ConversationState {
+        let tool_config = serde_json::from_str::<HashMap<String, ToolSpec>>(include_str!("../tools/tool_index.json"))
+            .expect("Tools failed to load");
+        ConversationState::new(
+            "fake_conv_id",
+            Agents::default(),
+            tool_config,
+            ToolManager::default(),
+            None,
+        )
+        .await
+    }
+
+    /// Loads `contents` into a new conversation, returning it with the parts left out.
+    async fn load(contents: &str) -> (ConversationState, Vec<String>) {
+        let mut conversation = conversation().await;
+        let issues = from_json(contents).unwrap().restore_into(&mut conversation);
+        (conversation, issues)
+    }
+
+    fn parse(contents: &str) -> Value {
+        serde_json::from_str(contents).unwrap()
+    }
+
+    #[test]
+    fn test_migrations() {
+        assert_eq!(MIGRATIONS.len() as u64, CURRENT_VERSION - 1);
+    }
+
+    #[tokio::test]
+    async fn test_round_trip() {
+        let (conversation, issues) = load(V2).await;
+        assert!(issues.is_empty(), "{issues:?}");
+        assert_eq!(parse(&to_json(&conversation).unwrap()), parse(V2));
+    }
+
+    #[tokio::test]
+    async fn test_load_v1() {
+        assert_eq!(from_json(V1).unwrap().version, 1);
+        let (conversation, issues) = load(V1).await;
+        assert!(issues.is_empty(), "{issues:?}");
+
+        assert_eq!(conversation.conversation_id(), "5c0a8f2e-7d3b-4f21-9a6e-2b8d41c7e903");
+        assert_eq!(conversation.model.as_deref(), Some("claude-sonnet-4"));
+        assert_eq!(
+            conversation.latest_summary(),
+            Some("The user is exploring a hello world project.")
+        );
+        assert_eq!(conversation.exchanges(), vec![
+            (0, "What does main.rs do?"),
+            (2, "What is in this screenshot?")
+        ]);
+        let history = conversation.history();
+        assert_eq!(history[0].assistant().tool_uses().unwrap()[0].name, "fs_read");
+        assert_eq!(
+            history[1].user().tool_use_results().unwrap()[0].tool_use_id,
+            "tooluse_1"
+        );
+        assert_eq!(history[2].user().images.as_ref().unwrap().len(), 1);
+        assert_eq!(conversation.attached_resources[0].uri, "docs://style-guide");
+        let checkpoint = conversation.checkpoints.get(1).unwrap();
+        assert_eq!(checkpoint.prompt, "What does main.rs do?");
+        assert_eq!(checkpoint.files[&PathBuf::from("/project/notes.txt")], None);
+        assert_eq!(conversation.checkpoints.turn(), 2);
+
+        // Saving a migrated conversation writes the current version.
+        assert_eq!(parse(&to_json(&conversation).unwrap()), parse(V2));
+    }
+
+    #[tokio::test]
+    async fn test_partial_recovery() {
+        let mut document = parse(V2);
+        document["history"][1]["user"]["content"]["type"] = "somethingNew".into();
+        document["transcript"] = 42.into();
+        document["checkpoints"]["checkpoints"][0]["timestamp"] = "yesterday".into();
+        let (conversation, issues) = load(&document.to_string()).await;
+
+        // The exchange with the unreadable message is left out as a whole.
+        assert_eq!(issues.len(), 3, "{issues:?}");
+        assert!(issues[0].starts_with("Skipped the 2 messages of the exchange \"What does main.rs do?\""));
+        assert!(issues[0].contains("message 2"));
+        assert!(issues[1].starts_with("Skipped `transcript`"));
+        assert!(issues[2].starts_with("Skipped `checkpoints`"));
+        assert!(conversation.checkpoints.checkpoints().is_empty());
+        assert_eq!(conversation.exchanges(), vec![(0, "What is in this screenshot?")]);
+        assert!(conversation.transcript.is_empty());
+        assert_eq!(conversation.model.as_deref(), Some("claude-sonnet-4"));
+    }
+
+    #[tokio::test]
+    async fn test_session_json() {
+        let (conversation, _) = load(V2).await;
+        let mut expected = parse(V2);
+        expected.as_object_mut().unwrap().remove("checkpoints");
+        assert_eq!(parse(&to_session_json(&conversation).unwrap()), expected);
+
+        let (conversation, issues) = load(&to_session_json(&conversation).unwrap()).await;
+        assert!(issues.is_empty(), "{issues:?}");
+        assert!(conversation.checkpoints.checkpoints().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_newer_version() {
+        let mut document = parse(V2);
+        document["version"] = 3.into();
+        document["history"][0]["user"]["mood"] = "curious".into();
+        let (conversation, issues) = load(&document.to_string()).await;
+
+        assert_eq!(issues.len(), 1, "{issues:?}");
+        assert!(issues[0].contains("version 3"));
+        assert_eq!(conversation.history().len(), 3);
+    }
+
+    #[test]
+    fn test_invalid_files() {
+        assert!(matches!(from_json("not json"), Err(LoadError::Json(_))));
+        assert!(matches!(from_json("[]"), Err(LoadError::NotAConversation)));
+        assert!(matches!(
+            from_json(r#"{"name": "q"}"#),
+            Err(LoadError::NotAConversation)
+        ));
+        assert!(matches!(
+            from_json(r#"{"version": "2", "history": []}"#),
+            Err(LoadError::InvalidVersion(_))
+        ));
+    }
+}
@@ -0,0 +1,346 @@
+//! Version 1 of the format, the unversioned files written before the format had a version.
+//!
+//! These files are the conversation state serialized as it was in memory, so the types below
+//! mirror the in-memory types of that time and must not change with them.
+
+use serde::Deserialize;
+use serde_json::{
+    Map,
+    Value,
+};
+
+/// Migrates a document of version 1 to version 2.
+pub fn migrate(document: Value) -> Value {
+    let Value::Object(mut legacy) = document else {
+        return document;
+    };
+
+    let mut document = Map::new();
+    document.insert("version".to_string(), 2.into());
+    for (from, to) in [
+        ("conversation_id", "conversationId"),
+        ("model", "model"),
+        ("transcript", "transcript"),
+        ("checkpoints", "checkpoints"),
+    ] {
+        if let Some(value) = legacy.remove(from) {
+            document.insert(to.to_string(), value);
+        }
+    }
+    // The summary used to be stored along with the metadata of the request that created it.
+    if let Some(summary) = legacy.remove("latest_summary") {
+        let summary = match summary {
+            Value::Array(mut parts) if !parts.is_empty() => parts.swap_remove(0),
+            summary => summary,
+        };
+        document.insert("summary".to_string(), summary);
+    }
+    if let Some(history) = legacy.remove("history") {
+        document.insert(
+            "history".to_string(),
+            migrate_each::<HistoryEntry, super::HistoryEntry>(history),
+        );
+    }
+    if let Some(resources) = legacy.remove("attached_resources") {
+        document.insert(
+            "attachedResources".to_string(),
+            migrate_each::<AttachedResource, super::Resource>(resources),
+        );
+    }
+
+    Value::Object(document)
+}
+
+/// Migrates each item of `array` from `Legacy` to `Current`, leaving the items that cannot be
+/// read as they are.
+fn migrate_each<Legacy, Current>(array: Value) -> Value
+where
+    Legacy: for<'de> Deserialize<'de>,
+    Current: From<Legacy> + serde::Serialize,
+{
+    let Value::Array(items) = array else {
+        return array;
+    };
+    items
+        .into_iter()
+        .map(|item| {
+            Legacy::deserialize(&item)
+                .ok()
+                .and_then(|legacy| serde_json::to_value(Current::from(legacy)).ok())
+                .unwrap_or(item)
+        })
+        .collect()
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum HistoryEntry {
+    Entry {
+        user: UserMessage,
+        assistant: AssistantMessage,
+        #[serde(default)]
+        request_metadata: Option<RequestMetadata>,
+    },
+    /// The history used to be a list of user and assistant message pairs.
+    Pair(UserMessage, AssistantMessage),
+}
+
+#[derive(Debug, Deserialize)]
+struct UserMessage {
+    #[serde(default)]
+    additional_context: String,
+    #[serde(default)]
+    env_context: Option<UserEnvContext>,
+    content: UserMessageContent,
+    #[serde(default)]
+    images: Option<Vec<ImageBlock>>,
+}
+
+#[derive(Debug, Deserialize)]
+struct UserEnvContext {
+    env_state: Option<EnvState>,
+}
+
+#[derive(Debug, Deserialize)]
+struct EnvState {
+    operating_system: Option<String>,
+    current_working_directory: Option<String>,
+    #[serde(default)]
+    environment_variables: Vec<EnvironmentVariable>,
+}
+
+#[derive(Debug, Deserialize)]
+struct EnvironmentVariable {
+    key: String,
+    value: String,
+}
+
+#[derive(Debug, Deserialize)]
+enum UserMessageContent {
+    Prompt {
+        prompt: String,
+    },
+    CancelledToolUses {
+        prompt: Option<String>,
+        tool_use_results: Vec<ToolUseResult>,
+    },
+    ToolUseResults {
+        tool_use_results: Vec<ToolUseResult>,
+    },
+}
+
+#[derive(Debug, Deserialize)]
+struct ToolUseResult {
+    tool_use_id: String,
+    content: Vec<ToolUseResultBlock>,
+    status: ToolResultStatus,
+}
+
+#[derive(Debug, Deserialize)]
+enum ToolUseResultBlock {
+    Json(Value),
+    Text(String),
+}
+
+#[derive(Debug, Deserialize)]
+enum ToolResultStatus {
+    Error,
+    Success,
+}
+
+#[derive(Debug, Deserialize)]
+struct ImageBlock {
+    format: ImageFormat,
+    source: ImageSource,
+}
+
+#[derive(Debug, Deserialize)]
+enum ImageFormat {
+    Gif,
+    Jpeg,
+    Png,
+    Webp,
+}
+
+#[derive(Debug, Deserialize)]
+enum ImageSource {
+    Bytes(Vec<u8>),
+}
+
+#[derive(Debug, Deserialize)]
+enum AssistantMessage {
+    Response {
+        message_id: Option<String>,
+        content: String,
+    },
+    ToolUse {
+        message_id: Option<String>,
+        content: String,
+        tool_uses: Vec<AssistantToolUse>,
+    },
+}
+
+#[derive(Debug, Deserialize)]
+struct AssistantToolUse {
+    id: String,
+    name: String,
+    #[serde(default)]
+    orig_name: Option<String>,
+    args: Value,
+    #[serde(default)]
+    orig_args: Option<Value>,
+}
+
+#[derive(Debug, Deserialize)]
+struct RequestMetadata {
+    request_id: Option<String>,
+    #[serde(default)]
+    message_id: String,
+    #[serde(default)]
+    request_start_timestamp_ms: u64,
+    #[serde(default)]
+    stream_end_timestamp_ms: u64,
+    #[serde(default)]
+    model_id: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct AttachedResource {
+    server_name: String,
+    uri: String,
+    content: String,
+}
+
+impl From<HistoryEntry> for super::HistoryEntry {
+    fn from(entry: HistoryEntry) -> Self {
+        let (user, assistant, metadata) = match entry {
+            HistoryEntry::Entry {
+                user,
+                assistant,
+                request_metadata,
+            } => (user, assistant, request_metadata),
+            HistoryEntry::Pair(user, assistant) => (user, assistant, None),
+        };
+        Self {
+            user: user.into(),
+            assistant: assistant.into(),
+            metadata: metadata.map(|metadata| super::RequestMetadata {
+                request_id: metadata.request_id,
+                message_id: metadata.message_id,
+                request_start_timestamp_ms: metadata.request_start_timestamp_ms,
+                stream_end_timestamp_ms: metadata.stream_end_timestamp_ms,
+                model_id: metadata.model_id,
+            }),
+        }
+    }
+}
+
+impl From<UserMessage> for super::UserMessage {
+    fn from(message: UserMessage) -> Self {
+        let tool_results = |results: Vec<ToolUseResult>| results.into_iter().map(Into::into).collect();
+        Self {
+            content: match message.content {
+                UserMessageContent::Prompt { prompt } => super::UserContent::Prompt { prompt },
+                UserMessageContent::ToolUseResults { tool_use_results } => super::UserContent::ToolResults {
+                    tool_results: tool_results(tool_use_results),
+                },
+                UserMessageContent::CancelledToolUses {
+                    prompt,
+                    tool_use_results,
+                } => super::UserContent::CancelledToolUses {
+                    prompt,
+                    tool_results: tool_results(tool_use_results),
+                },
+            },
+            hook_context: message.additional_context,
+            environment: message
+                .env_context
+                .and_then(|context| context.env_state)
+                .map(|env| super::Environment {
+                    operating_system: env.operating_system,
+                    current_working_directory: env.current_working_directory,
+                    environment_variables: env
+                        .environment_variables
+                        .into_iter()
+                        .map(|var| super::EnvironmentVariable {
+                            key: var.key,
+                            value: var.value,
+                        })
+                        .collect(),
+                }),
+            images: message
+                .images
+                .into_iter()
+                .flatten()
+                .map(|image| super::Image {
+                    format: match image.format {
+                        ImageFormat::Gif => super::ImageFormat::Gif,
+                        ImageFormat::Jpeg => super::ImageFormat::Jpeg,
+                        ImageFormat::Png => super::ImageFormat::Png,
+                        ImageFormat::Webp => super::ImageFormat::Webp,
+                    },
+                    data: match image.source {
+                        ImageSource::Bytes(data) => data,
+                    },
+                })
+                .collect(),
+        }
+    }
+}
+
+impl From<ToolUseResult> for super::ToolResult {
+    fn from(result: ToolUseResult) -> Self {
+        Self {
+            tool_use_id: result.tool_use_id,
+            status: match result.status {
+                ToolResultStatus::Success => super::ToolResultStatus::Success,
+                ToolResultStatus::Error => super::ToolResultStatus::Error,
+            },
+            content: result
+                .content
+                .into_iter()
+                .map(|block| match block {
+                    ToolUseResultBlock::Text(text) => super::ContentBlock::Text { text },
+                    ToolUseResultBlock::Json(json) => super::ContentBlock::Json { json },
+                })
+                .collect(),
+        }
+    }
+}
+
+impl From<AssistantMessage> for super::AssistantMessage {
+    fn from(message: AssistantMessage) -> Self {
+        let (message_id, content, tool_uses) = match message {
+            AssistantMessage::Response { message_id, content } => (message_id, content, Vec::new()),
+            AssistantMessage::ToolUse {
+                message_id,
+                content,
+                tool_uses,
+            } => (message_id, content, tool_uses),
+        };
+        Self {
+            message_id,
+            content,
+            tool_uses: tool_uses
+                .into_iter()
+                .map(|tool_use| super::ToolUse {
+                    original_name: tool_use.orig_name.filter(|name| *name != tool_use.name),
+                    original_args: tool_use.orig_args.filter(|args| *args != tool_use.args),
+                    id: tool_use.id,
+                    name: tool_use.name,
+                    args: tool_use.args,
+                })
+                .collect(),
+        }
+    }
+}
+
+impl From<AttachedResource> for super::Resource {
+    fn from(resource: AttachedResource) -> Self {
+        Self {
+            server_name: resource.server_name,
+            uri: resource.uri,
+            content: resource.content,
+        }
+    }
+}
@@ -0,0 +1,276 @@
+//! Answers requests from MCP servers for information from the user, see
+//! https://modelcontextprotocol.io/specification/2025-06-18/client/elicitation
+
+use std::io::Write;
+
+use crossterm::queue;
+use crossterm::style::{
+    self,
+    Color,
+};
+use dialoguer::console::Term;
+use dialoguer::{
+    Confirm,
+    Input,
+    Select,
+};
+use serde_json::Value;
+
+use super::server_messenger::ElicitationRequest;
+use crate::mcp_client::{
+    ElicitAction,
+    ElicitResult,
+    PrimitiveSchema,
+};
+use crate::util::dialoguer_theme;
+
+/// The answer to a single field of the form.
+enum Answer {
+    Value(Value),
+    /// The field is optional and was left empty.
+    Skipped,
+    /// The user dismissed the form.
+    Cancelled,
+}
+
+/// Shows the information requested by a server as a form and responds with the answers of the
+/// user. Requests are declined in non-interactive sessions.
+pub fn handle_elicitation_request(stderr: &mut impl Write, interactive: bool, request: ElicitationRequest) {
+    if !interactive {
+        request.respond(Ok(ElicitResult::decline()));
+        return;
+    }
+    let result = ask_user(stderr, &request).map_err(|err| format!("Failed to ask the user: {err}"));
+    request.respond(result);
+}
+
+fn ask_user(stderr: &mut impl Write, request: &ElicitationRequest) -> eyre::Result<ElicitResult> {
+    queue!(
+        stderr,
+        style::Print("\n"),
+        style::SetForegroundColor(Color::Magenta),
+        style::Print(&request.server_name),
+        style::SetForegroundColor(Color::Reset),
+        style::Print(" requests information:\n"),
+        style::SetForegroundColor(Color::DarkGrey),
+    )?;
+    for line in request.request.message.lines() {
+        queue!(stderr, style::Print(format!("  {line}\n")))?;
+    }
+    queue!(stderr, style::SetForegroundColor(Color::Reset), style::Print("\n"))?;
+    stderr.flush()?;
+
+    let choice = Select::with_theme(&dialoguer_theme())
+        .with_prompt("Provide this information?")
+        .items(&["Yes", "No, decline"])
+        .default(0)
+        .interact_on_opt(&Term::stdout());
+    match choice {
+        Ok(Some(0)) => {},
+        Ok(Some(_)) => return Ok(ElicitResult::decline()),
+        Ok(None) => return Ok(ElicitResult::cancel()),
+        Err(dialoguer::Error::IO(ref e)) if e.kind() == std::io::ErrorKind::Interrupted => {
+            return Ok(ElicitResult::cancel());
+        },
+        Err(e) => return Err(e.into()),
+    }
+
+    let schema = &request.request.requested_schema;
+    let mut content = serde_json::Map::new();
+    for (name, field) in &schema.properties {
+        let required = schema.required.contains(name);
+        match ask_field(name, field, required) {
+            Ok(Answer::Value(value)) => {
+                content.insert(name.clone(), value);
+            },
+            Ok(Answer::Skipped) => {},
+            Ok(Answer::Cancelled) => return Ok(ElicitResult::cancel()),
+            Err(dialoguer::Error::IO(ref e)) if e.kind() == std::io::ErrorKind::Interrupted => {
+                return Ok(ElicitResult::cancel());
+            },
+            Err(e) => return Err(e.into()),
+        }
+    }
+
+    Ok(ElicitResult {
+        action: ElicitAction::Accept,
+        content: Some(content),
+    })
+}
+
+fn ask_field(name: &str, field: &PrimitiveSchema, required: bool) -> dialoguer::Result<Answer> {
+    let mut prompt = field.title().unwrap_or(name).to_string();
+    if let Some(description) = field.description() {
+        prompt.push_str(&format!(" ({description})"));
+    }
+    if !required {
+        prompt.push_str(" [optional]");
+    }
+    let theme = dialoguer_theme();
+
+    match field {
+        PrimitiveSchema::Boolean { default, .. } => Ok(Confirm::with_theme(&theme)
+            .with_prompt(prompt)
+            .default(default.unwrap_or(false))
+            .interact_opt()?
+            .map_or(Answer::Cancelled, |value| Answer::Value(value.into()))),
+        PrimitiveSchema::String {
+            choices: Some(choices),
+            enum_names,
+            default,
+            ..
+        } => {
+            let mut labels = match enum_names {
+                Some(names) if names.len() == choices.len() => names.clone(),
+                _ => choices.clone(),
+            };
+            if !required {
+                labels.push("(skip)".to_string());
+            }
+            let default = default
+                .as_ref()
+                .and_then(|default| choices.iter().position(|choice| choice == default))
+                .unwrap_or(0);
+            let selection = Select::with_theme(&theme)
+                .with_prompt(prompt)
+                .items(&labels)
+                .default(default)
+                .interact_on_opt(&Term::stdout())?;
+            Ok(match selection {
+                Some(index) if index < choices.len() => Answer::Value(choices[index].clone().into()),
+                Some(_) => Answer::Skipped,
+                None => Answer::Cancelled,
+            })
+        },
+        _ => {
+            let mut input = Input::<String>::with_theme(&theme)
+                .with_prompt(prompt)
+                .allow_empty(!required)
+                .validate_with(|input: &String| parse_value(field, input).map(|_| ()));
+            if let Some(default) = default_text(field) {
+                input = input.default(default);
+            }
+            let text = input.interact_text()?;
+            Ok(match parse_value(field, &text) {
+                Ok(Some(value)) => Answer::Value(value),
+                _ => Answer::Skipped,
+            })
+        },
+    }
+}
+
+fn default_text(field: &PrimitiveSchema) -> Option<String> {
+    match field {
+        PrimitiveSchema::String { default, .. } => default.clone(),
+        PrimitiveSchema::Number { default, .. } => default.map(|d| d.to_string()),
+        PrimitiveSchema::Integer { default, .. } => default.map(|d| d.to_string()),
+        PrimitiveSchema::Boolean { default, .. } => default.map(|d| d.to_string()),
+    }
+}
+
+/// Converts the text entered for a field into its value, [None] if the text is empty.
+fn parse_value(field: &PrimitiveSchema, text: &str) -> Result<Option<Value>, String> {
+    let text = text.trim();
+    if text.is_empty() {
+        return Ok(None);
+    }
+    let value = match field {
+        PrimitiveSchema::String {
+            choices,
+            min_length,
+            max_length,
+            format,
+            ..
+        } => {
+            let length = text.chars().count();
+            if let Some(choices) = choices {
+                if !choices.iter().any(|choice| choice == text) {
+                    return Err(format!("Must be one of: {}", choices.join(", ")));
+                }
+            }
+            if min_length.is_some_and(|min| length < min) {
+                return Err(format!(
+                    "Must be at least {} characters",
+                    min_length.unwrap_or_default()
+                ));
+            }
+            if max_length.is_some_and(|max| length > max) {
+                return Err(format!("Must be at most {} characters", max_length.unwrap_or_default()));
+            }
+            match format.as_deref() {
+                Some("email") if !text.contains('@') => return Err("Must be an email address".to_string()),
+                Some("uri") if url::Url::parse(text).is_err() => return Err("Must be a uri".to_string()),
+                _ => {},
+            }
+            Value::from(text)
+        },
+        PrimitiveSchema::Number { minimum, maximum, .. } => {
+            let number = text.parse::<f64>().map_err(|_invalid| "Must be a number".to_string())?;
+            check_bounds(number, *minimum, *maximum)?;
+            Value::from(number)
+        },
+        PrimitiveSchema::Integer { minimum, maximum, .. } => {
+            let number = text
+                .parse::<i64>()
+                .map_err(|_invalid| "Must be an integer".to_string())?;
+            check_bounds(number, *minimum, *maximum)?;
+            Value::from(number)
+        },
+        PrimitiveSchema::Boolean { .. } => match text.to_lowercase().as_str() {
+            "true" | "yes" | "y" => Value::from(true),
+            "false" | "no" | "n" => Value::from(false),
+            _ => return Err("Must be yes or no".to_string()),
+        },
+    };
+    Ok(Some(value))
+}
+
+fn check_bounds<T: PartialOrd + std::fmt::Display>(
+    value: T,
+    minimum: Option<T>,
+    maximum: Option<T>,
+) -> Result<(), String> {
+    if let Some(minimum) = minimum.filter(|minimum| value < *minimum) {
+        return Err(format!("Must be at least {minimum}"));
+    }
+    if let Some(maximum) = maximum.filter(|maximum| value > *maximum) {
+        return Err(format!("Must be at most {maximum}"));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_value() {
+        let field = |schema: Value| serde_json::from_value::<PrimitiveSchema>(schema).unwrap();
+
+        let name = field(serde_json::json!({ "type": "string", "minLength": 2, "maxLength": 4 }));
+        assert_eq!(parse_value(&name, " abc "), Ok(Some(Value::from("abc"))));
+        assert_eq!(parse_value(&name, ""), Ok(None));
+        assert!(parse_value(&name, "a").is_err());
+        assert!(parse_value(&name, "abcde").is_err());
+
+        let environment = field(serde_json::json!({ "type": "string", "enum": ["dev", "prod"] }));
+        assert_eq!(parse_value(&environment, "prod"), Ok(Some(Value::from("prod"))));
+        assert!(parse_value(&environment, "staging").is_err());
+
+        let email = field(serde_json::json!({ "type": "string", "format": "email" }));
+        assert!(parse_value(&email, "someone").is_err());
+        assert!(parse_value(&email, "someone@example.com").is_ok());
+
+        let replicas = field(serde_json::json!({ "type": "integer", "minimum": 1, "maximum": 5 }));
+        assert_eq!(parse_value(&replicas, "3"), Ok(Some(Value::from(3))));
+        assert!(parse_value(&replicas, "0").is_err());
+        assert!(parse_value(&replicas, "2.5").is_err());
+
+        let ratio = field(serde_json::json!({ "type": "number", "maximum": 1 }));
+        assert_eq!(parse_value(&ratio, "0.5"), Ok(Some(Value::from(0.5))));
+        assert!(parse_value(&ratio, "1.5").is_err());
+
+        let confirm = field(serde_json::json!({ "type": "boolean" }));
+        assert_eq!(parse_value(&confirm, "Yes"), Ok(Some(Value::from(true))));
+    }
+}
@@ -7,7 +7,11 @@ use super::skim_integration::SkimCommandSelector;
 use crate::os::Os;
 
 #[derive(Debug)]
-pub struct InputSource(inner::Inner);
+pub struct InputSource {
+    inner: inner::Inner,
+    /// Text the next line starts with, set by [Self::set_buffer].
+    buffer: Option<String>,
+}
 
 mod inner {
     use rustyline::Editor;
@@ -33,7 +37,10 @@ impl InputSource {
         sender: std::sync::mpsc::Sender<Option<String>>,
         receiver: std::sync::mpsc::Receiver<Vec<String>>,
     ) -> Result<Self> {
-        Ok(Self(inner::Inner::Readline(rl(os, sender, receiver)?)))
+        Ok(Self {
+            inner: inner::Inner::Readline(rl(os, sender, receiver)?),
+            buffer: None,
+        })
     }
 
     #[cfg(unix)]
@@ -50,7 +57,7 @@ impl InputSource {
 
         use crate::database::settings::Setting;
 
-        if let inner::Inner::Readline(rl) = &mut self.0 {
+        if let inner::Inner::Readline(rl) = &mut self.inner {
             let key_char = match os.database.settings.get_string(Setting::SkimCommandKey) {
                 Some(key) if key.len() == 1 => key.chars().next().unwrap_or('s'),
                 _ => 's', // Default to 's' if setting is missing or invalid
@@ -68,14 +75,21 @@ impl InputSource {
 
     #[allow(dead_code)]
     pub fn new_mock(lines: Vec<String>) -> Self {
-        Self(inner::Inner::Mock { index: 0, lines })
+        Self {
+            inner: inner::Inner::Mock { index: 0, lines },
+            buffer: None,
+        }
     }
 
     pub fn read_line(&mut self, prompt: Option<&str>) -> Result<Option<String>, ReadlineError> {
-        match &mut self.0 {
+        let buffer = self.buffer.take();
+        match &mut self.inner {
             inner::Inner::Readline(rl) => {
                 let prompt = prompt.unwrap_or_default();
-                let curr_line = rl.readline(prompt);
+                let curr_line = match buffer {
+                    Some(buffer) => rl.readline_with_initial(prompt, (&buffer, "")),
+                    None => rl.readline(prompt),
+                };
                 match curr_line {
                     Ok(line) => {
                         let _ = rl.add_history_entry(line.as_str());
@@ -97,13 +111,9 @@ impl InputSource {
         }
     }
 
-    // We're keeping this method for potential future use
-    #[allow(dead_code)]
+    /// Fills the next line read with `content`, so that it can be edited before it is sent.
     pub fn set_buffer(&mut self, content: &str) {
-        if let inner::Inner::Readline(rl) = &mut self.0 {
-            // Add to history so user can access it with up arrow
-            let _ = rl.add_history_entry(content);
-        }
+        self.buffer = Some(content.to_string());
     }
 }
 
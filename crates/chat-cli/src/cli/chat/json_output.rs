@@ -0,0 +1,259 @@
+use std::io::Write;
+use std::process::ExitCode;
+
+use serde::Serialize;
+
+use super::ChatError;
+use super::message::ToolUseResult;
+use crate::api_client::ApiClientError;
+use crate::api_client::model::ToolResultStatus;
+use crate::cli::OutputFormat;
+
+/// An event of a non-interactive chat session, reported to scripts with `--output-format`.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "type", rename_all = "camelCase", rename_all_fields = "camelCase")]
+pub enum OutputEvent {
+    /// A chunk of the response text, in the order received from the model.
+    AssistantText { text: String },
+    /// A tool use requested by the model.
+    ToolUse {
+        id: String,
+        name: String,
+        input: serde_json::Value,
+    },
+    /// The result of a tool use, including tool uses the model supplied invalid input for.
+    ToolResult {
+        id: String,
+        name: String,
+        success: bool,
+        output: String,
+    },
+    /// A tool use rejected by a permission rule or hook, or because it required approval.
+    PermissionDenied { id: String, name: String, reason: String },
+    /// Estimated token usage of a single response.
+    Usage {
+        request_id: Option<String>,
+        input_tokens: usize,
+        output_tokens: usize,
+    },
+    /// How the session ended. Always the last event.
+    Result {
+        status: RunStatus,
+        error: Option<String>,
+        /// The text of the last response.
+        response: String,
+        /// Output tokens of every response and input tokens of the last one.
+        input_tokens: usize,
+        output_tokens: usize,
+    },
+}
+
+impl OutputEvent {
+    pub fn tool_result(id: String, name: String, result: &ToolUseResult) -> Self {
+        Self::ToolResult {
+            id,
+            name,
+            success: matches!(result.status, ToolResultStatus::Success),
+            output: result.text(),
+        }
+    }
+}
+
+/// How a non-interactive chat session ended, reported by its exit code.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum RunStatus {
+    Success,
+    /// Any error not covered by the other statuses.
+    Error,
+    /// The model could not be reached or failed to respond.
+    ModelError,
+    /// A tool use required approval, which cannot be given without a user.
+    ToolDenied,
+    /// The request rate or the monthly request limit was exceeded.
+    QuotaExceeded,
+}
+
+impl RunStatus {
+    pub fn from_error(err: &ChatError) -> Self {
+        let is_quota_error = |err: &ApiClientError| {
+            matches!(
+                err,
+                ApiClientError::QuotaBreach { .. } | ApiClientError::MonthlyLimitReached { .. }
+            )
+        };
+        match err {
+            ChatError::Client(err) if is_quota_error(err) => Self::QuotaExceeded,
+            ChatError::SendMessage(err) if is_quota_error(&err.source) => Self::QuotaExceeded,
+            ChatError::Client(_) | ChatError::SendMessage(_) | ChatError::ResponseStream(_) => Self::ModelError,
+            ChatError::NonInteractiveToolApproval => Self::ToolDenied,
+            _ => Self::Error,
+        }
+    }
+
+    pub fn exit_code(self) -> ExitCode {
+        match self {
+            RunStatus::Success => ExitCode::SUCCESS,
+            RunStatus::Error => ExitCode::FAILURE,
+            RunStatus::ModelError => ExitCode::from(3),
+            RunStatus::ToolDenied => ExitCode::from(4),
+            RunStatus::QuotaExceeded => ExitCode::from(5),
+        }
+    }
+}
+
+/// Writes the events of a chat session to stdout as JSON. [OutputFormat::StreamJson] writes each
+/// event on its own line as it happens, the other formats write a single result object listing
+/// every event once the session ends.
+#[derive(Debug)]
+pub struct JsonOutput {
+    format: OutputFormat,
+    /// Events waiting for the end of the session, unless they are streamed.
+    events: Vec<OutputEvent>,
+    response: String,
+    input_tokens: usize,
+    output_tokens: usize,
+}
+
+impl JsonOutput {
+    /// Returns [None] for [OutputFormat::Plain], where the session is only printed for humans.
+    pub fn new(format: OutputFormat) -> Option<Self> {
+        match format {
+            OutputFormat::Plain => None,
+            format => Some(Self {
+                format,
+                events: Vec::new(),
+                response: String::new(),
+                input_tokens: 0,
+                output_tokens: 0,
+            }),
+        }
+    }
+
+    pub fn emit(&mut self, event: OutputEvent) -> Result<(), std::io::Error> {
+        match &event {
+            OutputEvent::AssistantText { text } => self.response.push_str(text),
+            OutputEvent::Usage {
+                input_tokens,
+                output_tokens,
+                ..
+            } => {
+                self.input_tokens = *input_tokens;
+                self.output_tokens += output_tokens;
+            },
+            // Text followed by tool uses is not the final response.
+            _ => self.response.clear(),
+        }
+
+        match self.format {
+            OutputFormat::StreamJson => {
+                let mut stdout = std::io::stdout().lock();
+                serde_json::to_writer(&mut stdout, &event)?;
+                writeln!(stdout)?;
+                stdout.flush()
+            },
+            _ => {
+                self.events.push(event);
+                Ok(())
+            },
+        }
+    }
+
+    /// Writes the [OutputEvent::Result] event of the session.
+    pub fn finish(&mut self, status: RunStatus, error: Option<String>) -> Result<(), std::io::Error> {
+        let result = OutputEvent::Result {
+            status,
+            error,
+            response: std::mem::take(&mut self.response),
+            input_tokens: self.input_tokens,
+            output_tokens: self.output_tokens,
+        };
+        if self.format == OutputFormat::StreamJson {
+            return self.emit(result);
+        }
+
+        let mut result = serde_json::to_value(&result)?;
+        result["events"] = serde_json::to_value(std::mem::take(&mut self.events))?;
+        let mut stdout = std::io::stdout().lock();
+        match self.format {
+            OutputFormat::JsonPretty => serde_json::to_writer_pretty(&mut stdout, &result)?,
+            _ => serde_json::to_writer(&mut stdout, &result)?,
+        }
+        writeln!(stdout)?;
+        stdout.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_output_event_serialization() {
+        let event = OutputEvent::ToolUse {
+            id: "tooluse_1".to_string(),
+            name: "fs_read".to_string(),
+            input: serde_json::json!({ "path": "README.md" }),
+        };
+        assert_eq!(
+            serde_json::to_value(&event).unwrap(),
+            serde_json::json!({
+                "type": "toolUse",
+                "id": "tooluse_1",
+                "name": "fs_read",
+                "input": { "path": "README.md" },
+            })
+        );
+
+        let event = OutputEvent::Usage {
+            request_id: None,
+            input_tokens: 120,
+            output_tokens: 30,
+        };
+        assert_eq!(
+            serde_json::to_value(&event).unwrap(),
+            serde_json::json!({ "type": "usage", "requestId": null, "inputTokens": 120, "outputTokens": 30 })
+        );
+    }
+
+    #[test]
+    fn test_run_status() {
+        assert_eq!(
+            RunStatus::from_error(&ApiClientError::MonthlyLimitReached { status_code: None }.into()),
+            RunStatus::QuotaExceeded
+        );
+        assert_eq!(
+            RunStatus::from_error(&ChatError::NonInteractiveToolApproval),
+            RunStatus::ToolDenied
+        );
+        assert_eq!(
+            RunStatus::from_error(&ChatError::CompactHistoryFailure),
+            RunStatus::Error
+        );
+        assert_eq!(RunStatus::ToolDenied.exit_code(), ExitCode::from(4));
+    }
+
+    #[test]
+    fn test_final_response() {
+        let mut output = JsonOutput::new(OutputFormat::Json).unwrap();
+        output
+            .emit(OutputEvent::AssistantText {
+                text: "Reading the file".to_string(),
+            })
+            .unwrap();
+        output
+            .emit(OutputEvent::ToolUse {
+                id: "tooluse_1".to_string(),
+                name: "fs_read".to_string(),
+                input: serde_json::json!({}),
+            })
+            .unwrap();
+        output
+            .emit(OutputEvent::AssistantText {
+                text: "The file is empty".to_string(),
+            })
+            .unwrap();
+        assert_eq!(output.response, "The file is empty");
+        assert_eq!(output.events.len(), 3);
+    }
+}
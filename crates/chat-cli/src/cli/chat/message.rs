@@ -291,6 +291,20 @@ pub struct ToolUseResult {
     pub status: ToolResultStatus,
 }
 
+impl ToolUseResult {
+    /// Returns the content of the result as text, with JSON blocks serialized.
+    pub fn text(&self) -> String {
+        self.content
+            .iter()
+            .map(|block| match block {
+                ToolUseResultBlock::Text(text) => text.clone(),
+                ToolUseResultBlock::Json(json) => json.to_string(),
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
+
 impl From<ToolResult> for ToolUseResult {
     fn from(value: ToolResult) -> Self {
         Self {
@@ -382,6 +396,14 @@ impl UserEnvContext {
             env_state: Some(build_env_state()),
         }
     }
+
+    pub fn from_env_state(env_state: Option<EnvState>) -> Self {
+        Self { env_state }
+    }
+
+    pub fn env_state(&self) -> Option<&EnvState> {
+        self.env_state.as_ref()
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -1,21 +1,29 @@
+mod checkpoint;
 pub mod cli;
 mod consts;
 pub mod context;
 mod conversation;
+pub(crate) mod conversation_file;
+mod elicitation;
 mod error_formatter;
 mod input_source;
+mod json_output;
 mod message;
 mod parse;
 use std::path::MAIN_SEPARATOR;
 mod parser;
+mod permission_prompt;
 mod prompt;
 mod prompt_parser;
+mod sampling;
 mod server_messenger;
+mod sessions;
 #[cfg(unix)]
 mod skim_integration;
 mod token_counter;
 pub mod tool_manager;
 pub mod tools;
+mod transcript;
 pub mod util;
 
 use std::borrow::Cow;
@@ -40,11 +48,17 @@ use clap::{
     Args,
     CommandFactory,
     Parser,
+    Subcommand,
 };
 use cli::compact::CompactStrategy;
+use cli::hooks::{
+    HookDecision,
+    HookEvent,
+};
 use cli::model::{
     get_model_options,
     select_model,
+    tokenizer_kind,
 };
 pub use conversation::ConversationState;
 use conversation::TokenWarningLevel;
@@ -60,6 +74,7 @@ use crossterm::{
     style,
     terminal,
 };
+use elicitation::handle_elicitation_request;
 use eyre::{
     Report,
     Result,
@@ -67,6 +82,11 @@ use eyre::{
     eyre,
 };
 use input_source::InputSource;
+use json_output::{
+    JsonOutput,
+    OutputEvent,
+    RunStatus,
+};
 use message::{
     AssistantMessage,
     AssistantToolUse,
@@ -82,14 +102,29 @@ use parser::{
     RequestMetadata,
     SendMessageStream,
 };
+use permission_prompt::{
+    PermissionDecision,
+    PermissionRequest,
+    ask_permission,
+};
 use regex::Regex;
+use sampling::handle_sampling_request;
+use server_messenger::InteractiveRequest;
+use sessions::{
+    ExportSessionArgs,
+    SavedSession,
+    SessionsSubcommand,
+};
 use spinners::{
     Spinner,
     Spinners,
 };
 use thiserror::Error;
 use time::OffsetDateTime;
-use token_counter::TokenCounter;
+use token_counter::{
+    CountTokens,
+    TokenCounter,
+};
 use tokio::signal::ctrl_c;
 use tokio::sync::{
     Mutex,
@@ -99,13 +134,17 @@ use tool_manager::{
     ToolManager,
     ToolManagerBuilder,
 };
+use tools::execute::BackgroundProcesses;
 use tools::gh_issue::GhIssueContext;
 use tools::{
+    InvokeOutput,
     NATIVE_TOOLS,
     OutputKind,
     QueuedTool,
     Tool,
     ToolSpec,
+    can_run_in_parallel,
+    sanitize_path_tool_arg,
 };
 use tracing::{
     debug,
@@ -131,7 +170,9 @@ use crate::api_client::{
 };
 use crate::auth::AuthError;
 use crate::auth::builder_id::is_idc_user;
+use crate::cli::OutputFormat;
 use crate::cli::agent::Agents;
+use crate::cli::agent::hook::HookTrigger;
 use crate::cli::chat::cli::SlashCommand;
 use crate::cli::chat::cli::model::default_model_id;
 use crate::cli::chat::cli::prompts::{
@@ -178,9 +219,13 @@ pub const EXTRA_HELP: &str = color_print::cstr! {"
 
 #[derive(Debug, Clone, PartialEq, Eq, Default, Args)]
 pub struct ChatArgs {
-    /// Resumes the previous conversation from this directory.
+    /// Resumes a previous conversation from this directory, picking from a list if there are
+    /// several.
     #[arg(short, long)]
     pub resume: bool,
+    /// Resumes the saved conversation with this id, or a unique prefix of it.
+    #[arg(long, value_name = "ID", conflicts_with = "resume")]
+    pub resume_id: Option<String>,
     /// Context profile to use
     #[arg(long = "agent", alias = "profile")]
     pub agent: Option<String>,
@@ -197,12 +242,37 @@ pub struct ChatArgs {
     /// Whether the command should run without expecting user input
     #[arg(long, alias = "non-interactive")]
     pub no_interactive: bool,
+    /// The format of the output when running without user input. The JSON formats report the
+    /// response, tool uses and their results as events
+    #[arg(long, value_enum, default_value_t, requires = "no_interactive")]
+    pub output_format: OutputFormat,
+    /// Command deciding whether tool uses that require approval may run when running without user
+    /// input. It receives the tool use as JSON on stdin and answers with a JSON decision on stdout
+    #[arg(long, value_name = "COMMAND", requires = "no_interactive")]
+    pub permission_prompt_command: Option<String>,
     /// The first question to ask
     pub input: Option<String>,
+    #[command(subcommand)]
+    pub subcommand: Option<ChatSubcommand>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Subcommand)]
+pub enum ChatSubcommand {
+    /// Manage saved conversations
+    #[command(subcommand)]
+    Sessions(SessionsSubcommand),
+    /// Export a saved conversation as a Markdown, HTML or JSONL transcript
+    Export(ExportSessionArgs),
 }
 
 impl ChatArgs {
     pub async fn execute(mut self, os: &mut Os) -> Result<ExitCode> {
+        match self.subcommand {
+            Some(ChatSubcommand::Sessions(subcommand)) => return subcommand.execute(os).await,
+            Some(ChatSubcommand::Export(args)) => return args.execute(os).await,
+            None => (),
+        }
+
         let mut input = self.input;
 
         if self.no_interactive && input.is_none() {
@@ -225,7 +295,11 @@ impl ChatArgs {
             }
         }
 
-        let stdout = std::io::stdout();
+        // With a JSON output format, stdout is reserved for the events of the session.
+        let stdout: Box<dyn Write + Send + Sync> = match self.output_format {
+            OutputFormat::Plain => Box::new(std::io::stdout()),
+            _ => Box::new(std::io::stderr()),
+        };
         let mut stderr = std::io::stderr();
 
         let args: Vec<String> = std::env::args().collect();
@@ -246,6 +320,14 @@ impl ChatArgs {
             )?;
         }
 
+        let previous_conversation = match self.resume_id {
+            Some(id) => Some(sessions::load_session(&os.database, &id)?),
+            None if self.resume => {
+                sessions::resume_session(&os.database, &os.env.current_dir()?, !self.no_interactive)?
+            },
+            None => None,
+        };
+
         let conversation_id = uuid::Uuid::new_v4().to_string();
         info!(?conversation_id, "Generated new conversation id");
 
@@ -350,17 +432,19 @@ impl ChatArgs {
             agents,
             input,
             InputSource::new(os, prompt_request_sender, prompt_response_receiver)?,
-            self.resume,
+            previous_conversation,
             || terminal::window_size().map(|s| s.columns.into()).ok(),
             tool_manager,
             model_id,
             tool_config,
             !self.no_interactive,
+            self.output_format,
+            self.permission_prompt_command,
         )
         .await?
         .spawn(os)
         .await
-        .map(|_| ExitCode::SUCCESS)
+        .map(RunStatus::exit_code)
     }
 }
 
@@ -467,6 +551,8 @@ pub enum ChatError {
     NonInteractiveToolApproval,
     #[error("The conversation history is too large to compact")]
     CompactHistoryFailure,
+    #[error("The prompt was blocked by a hook: {0}")]
+    PromptBlocked(String),
 }
 
 impl ChatError {
@@ -483,6 +569,7 @@ impl ChatError {
             ChatError::GetPromptError(_) => None,
             ChatError::NonInteractiveToolApproval => None,
             ChatError::CompactHistoryFailure => None,
+            ChatError::PromptBlocked(_) => None,
         }
     }
 }
@@ -501,6 +588,7 @@ impl ReasonCode for ChatError {
             ChatError::Auth(_) => "AuthError".to_string(),
             ChatError::NonInteractiveToolApproval => "NonInteractiveToolApproval".to_string(),
             ChatError::CompactHistoryFailure => "CompactHistoryFailure".to_string(),
+            ChatError::PromptBlocked(_) => "PromptBlocked".to_string(),
         }
     }
 }
@@ -525,7 +613,7 @@ impl From<parser::RecvError> for ChatError {
 
 pub struct ChatSession {
     /// For output read by humans and machine
-    pub stdout: std::io::Stdout,
+    pub stdout: Box<dyn Write + Send + Sync>,
     /// For display output, only read by humans
     pub stderr: std::io::Stderr,
     initial_input: Option<String>,
@@ -554,9 +642,17 @@ pub struct ChatSession {
     tool_use_status: ToolUseStatus,
     /// Any failed requests that could be useful for error report/debugging
     failed_request_ids: Vec<String>,
+    /// Processes started by `execute_bash` in background mode.
+    background_processes: BackgroundProcesses,
     /// Pending prompts to be sent
     pending_prompts: VecDeque<Prompt>,
     interactive: bool,
+    /// Reports the events of the session when running with a JSON `--output-format`.
+    json_output: Option<JsonOutput>,
+    /// The error that ended a non-interactive session, if any.
+    failure: Option<(RunStatus, String)>,
+    /// Command deciding on tool uses that require approval in a non-interactive session.
+    permission_prompt_command: Option<String>,
     inner: Option<ChatState>,
     ctrlc_rx: broadcast::Receiver<()>,
 }
@@ -565,18 +661,20 @@ impl ChatSession {
     #[allow(clippy::too_many_arguments)]
     pub async fn new(
         os: &mut Os,
-        stdout: std::io::Stdout,
+        stdout: impl Write + Send + Sync + 'static,
         mut stderr: std::io::Stderr,
         conversation_id: &str,
         mut agents: Agents,
         mut input: Option<String>,
         input_source: InputSource,
-        resume_conversation: bool,
+        previous_conversation: Option<SavedSession>,
         terminal_width_provider: fn() -> Option<usize>,
         tool_manager: ToolManager,
         model_id: Option<String>,
         tool_config: HashMap<String, ToolSpec>,
         interactive: bool,
+        output_format: OutputFormat,
+        permission_prompt_command: Option<String>,
     ) -> Result<Self> {
         let model_options = get_model_options(os).await?;
         let valid_model_id = match model_id {
@@ -600,26 +698,14 @@ impl ChatSession {
             },
         };
 
-        // Reload prior conversation
-        let mut existing_conversation = false;
-        let previous_conversation = std::env::current_dir()
-            .ok()
-            .and_then(|cwd| os.database.get_conversation_by_path(cwd).ok())
-            .flatten();
-
         // Only restore conversations where there were actual messages.
         // Prevents edge case where user clears conversation then exits without chatting.
-        let conversation = match resume_conversation
-            && previous_conversation
-                .as_ref()
-                .is_some_and(|cs| !cs.history().is_empty())
-        {
-            true => {
-                let mut cs = previous_conversation.unwrap();
+        let mut existing_conversation = false;
+        let conversation = match previous_conversation.filter(|session| !session.conversation.is_empty()) {
+            Some(session) => {
                 existing_conversation = true;
                 input = Some(input.unwrap_or("In a few words, summarize our conversation so far.".to_owned()));
-                cs.tool_manager = tool_manager;
-                if let Some(profile) = cs.current_profile() {
+                if let Some(profile) = &session.summary.agent {
                     if agents.switch(profile).is_err() {
                         execute!(
                             stderr,
@@ -633,12 +719,22 @@ impl ChatSession {
                         let _ = agents.switch("default");
                     }
                 }
-                cs.agents = agents;
-                cs.update_state(true).await;
-                cs.enforce_tool_use_history_invariants();
-                cs
+                let (conversation, issues) = session
+                    .into_conversation(agents, tool_config, tool_manager, Some(valid_model_id))
+                    .await;
+                if !issues.is_empty() {
+                    execute!(
+                        stderr,
+                        style::SetForegroundColor(Color::Yellow),
+                        style::Print("Some parts of the conversation could not be resumed:\n"),
+                        style::Print(issues.iter().map(|issue| format!("  • {issue}\n")).collect::<String>()),
+                        style::SetForegroundColor(Color::Reset),
+                        style::Print("\n")
+                    )?;
+                }
+                conversation
             },
-            false => {
+            None => {
                 ConversationState::new(conversation_id, agents, tool_config, tool_manager, Some(valid_model_id)).await
             },
         };
@@ -661,7 +757,7 @@ impl ChatSession {
         });
 
         Ok(Self {
-            stdout,
+            stdout: Box::new(stdout),
             stderr,
             initial_input: input,
             existing_conversation,
@@ -676,8 +772,12 @@ impl ChatSession {
             tool_use_telemetry_events: HashMap::new(),
             tool_use_status: ToolUseStatus::Idle,
             failed_request_ids: Vec::new(),
+            background_processes: BackgroundProcesses::default(),
             pending_prompts: VecDeque::new(),
             interactive,
+            json_output: JsonOutput::new(output_format),
+            failure: None,
+            permission_prompt_command,
             inner: Some(ChatState::default()),
             ctrlc_rx,
         })
@@ -696,6 +796,15 @@ impl ChatSession {
                         return Ok(());
                     },
                     (false, false) => {
+                        if let Some(output) = self.json_output.as_mut() {
+                            for tool in self.tool_uses.iter().filter(|tool| !tool.accepted) {
+                                output.emit(OutputEvent::PermissionDenied {
+                                    id: tool.id.clone(),
+                                    name: tool.name.clone(),
+                                    reason: "Tool approval required".to_string(),
+                                })?;
+                            }
+                        }
                         return Err(ChatError::NonInteractiveToolApproval);
                     },
                     _ => (),
@@ -770,6 +879,7 @@ impl ChatSession {
         let (reason, reason_desc) = get_error_reason(&err);
         self.send_error_telemetry(os, reason, Some(reason_desc), err.status_code())
             .await;
+        let failure = (RunStatus::from_error(&err), err.to_string());
 
         if self.spinner.is_some() {
             drop(self.spinner.take());
@@ -985,6 +1095,9 @@ impl ChatSession {
                         )?;
                     }
 
+                    if !self.interactive {
+                        self.failure = Some(failure);
+                    }
                     self.inner = Some(ChatState::PromptUser {
                         skip_printing_tools: false,
                     });
@@ -1032,6 +1145,10 @@ impl ChatSession {
         self.tool_turn_start_time = None;
         self.reset_user_turn();
 
+        // The session exits next if it is non-interactive.
+        if !self.interactive {
+            self.failure = Some(failure);
+        }
         self.inner = Some(ChatState::PromptUser {
             skip_printing_tools: false,
         });
@@ -1130,7 +1247,7 @@ impl ChatSession {
         }
     }
 
-    async fn spawn(&mut self, os: &mut Os) -> Result<()> {
+    async fn spawn(&mut self, os: &mut Os) -> Result<RunStatus> {
         let is_small_screen = self.terminal_width() < GREETING_BREAK_POINT;
         if os
             .database
@@ -1212,11 +1329,45 @@ impl ChatSession {
             self.inner = Some(ChatState::HandleInput { input: user_input });
         }
 
+        let mut result = Ok(());
         while !matches!(self.inner, Some(ChatState::Exit)) {
-            self.next(os).await?;
+            if let Err(err) = self.next(os).await {
+                result = Err(err);
+                break;
+            }
         }
 
-        Ok(())
+        if let Err(err) = self.run_event_hooks(HookEvent::new(os, HookTrigger::SessionEnd)).await {
+            error!(?err, "failed to run sessionEnd hooks");
+        }
+
+        // Background processes must not outlive the session.
+        self.background_processes.kill_all().await;
+
+        if let Err(err) = result {
+            if self.interactive {
+                return Err(err.into());
+            }
+            execute!(
+                self.stderr,
+                style::SetAttribute(Attribute::Bold),
+                style::SetForegroundColor(Color::Red),
+                style::Print("error: "),
+                style::SetAttribute(Attribute::Reset),
+                style::SetForegroundColor(Color::Reset),
+                style::Print(format!("{err}\n")),
+            )?;
+            self.failure = Some((RunStatus::from_error(&err), err.to_string()));
+        }
+
+        let (status, error) = match self.failure.take() {
+            Some((status, error)) => (status, Some(error)),
+            None => (RunStatus::Success, None),
+        };
+        if let Some(output) = self.json_output.as_mut() {
+            output.finish(status, error)?;
+        }
+        Ok(status)
     }
 
     /// Compacts the conversation history using the strategy specified by [CompactStrategy],
@@ -1513,6 +1664,20 @@ impl ChatSession {
         }
     }
 
+    /// The rule that would allow the pending tool use when saved to the active agent, along with
+    /// the name of the agent. Agents without a config file, such as the default agent, cannot save
+    /// rules.
+    fn persistable_permission_rule(&self, os: &Os) -> Option<(String, String)> {
+        let agent = self
+            .conversation
+            .agents
+            .get_active()
+            .filter(|agent| agent.path.is_some())?;
+        let tool_use = self.tool_uses.get(self.pending_tool_index?)?;
+        let rule = tool_use.tool.suggested_permission_rule(&os.env.current_dir().ok()?)?;
+        Some((rule, agent.name.clone()))
+    }
+
     /// Read input from the user.
     async fn prompt_user(&mut self, os: &Os, skip_printing_tools: bool) -> Result<ChatState, ChatError> {
         execute!(self.stderr, cursor::Show)?;
@@ -1527,14 +1692,33 @@ impl ChatSession {
 
         let show_tool_use_confirmation_dialog = !skip_printing_tools && self.pending_tool_index.is_some();
         if show_tool_use_confirmation_dialog {
-            execute!(
+            queue!(
                 self.stderr,
                 style::SetForegroundColor(Color::DarkGrey),
                 style::Print("\nAllow this action? Use '"),
                 style::SetForegroundColor(Color::Green),
                 style::Print("t"),
                 style::SetForegroundColor(Color::DarkGrey),
-                style::Print("' to trust (always allow) this tool for the session. ["),
+                style::Print("' to trust (always allow) this tool for the session."),
+            )?;
+            let persistable_rule = self.persistable_permission_rule(os);
+            if let Some((rule, agent_name)) = &persistable_rule {
+                queue!(
+                    self.stderr,
+                    style::Print("\nUse '"),
+                    style::SetForegroundColor(Color::Green),
+                    style::Print("a"),
+                    style::SetForegroundColor(Color::DarkGrey),
+                    style::Print("' to always allow "),
+                    style::SetForegroundColor(Color::Green),
+                    style::Print(rule),
+                    style::SetForegroundColor(Color::DarkGrey),
+                    style::Print(format!(" in the agent {agent_name}.")),
+                )?;
+            }
+            queue!(
+                self.stderr,
+                style::Print(" ["),
                 style::SetForegroundColor(Color::Green),
                 style::Print("y"),
                 style::SetForegroundColor(Color::DarkGrey),
@@ -1546,6 +1730,18 @@ impl ChatSession {
                 style::SetForegroundColor(Color::Green),
                 style::Print("t"),
                 style::SetForegroundColor(Color::DarkGrey),
+            )?;
+            if persistable_rule.is_some() {
+                queue!(
+                    self.stderr,
+                    style::Print("/"),
+                    style::SetForegroundColor(Color::Green),
+                    style::Print("a"),
+                    style::SetForegroundColor(Color::DarkGrey),
+                )?;
+            }
+            execute!(
+                self.stderr,
                 style::Print("]:\n\n"),
                 style::SetForegroundColor(Color::Reset),
             )?;
@@ -1587,6 +1783,37 @@ impl ChatSession {
         Ok(ChatState::HandleInput { input: user_input })
     }
 
+    /// Changes the working directory of the session to `dir`, or the home directory if [None],
+    /// and shares the new directory with MCP servers through their roots.
+    async fn change_directory(&mut self, os: &Os, dir: Option<&str>) -> Result<(), ChatError> {
+        let path = sanitize_path_tool_arg(os, dir.unwrap_or("~"));
+        match os.env.set_current_dir(&path) {
+            Ok(()) => {
+                let cwd = os.env.current_dir()?;
+                execute!(
+                    self.stderr,
+                    style::SetForegroundColor(Color::DarkGrey),
+                    style::Print(format!("Working directory is now {}\n", cwd.display())),
+                    style::SetForegroundColor(Color::Reset)
+                )?;
+            },
+            Err(err) => {
+                execute!(
+                    self.stderr,
+                    style::SetForegroundColor(Color::Red),
+                    style::Print(format!("Failed to change directory to {}: {err}\n", path.display())),
+                    style::SetForegroundColor(Color::Reset)
+                )?;
+                return Ok(());
+            },
+        }
+        self.conversation
+            .tool_manager
+            .update_roots(os, self.conversation.agents.get_active())
+            .await;
+        Ok(())
+    }
+
     async fn handle_input(&mut self, os: &mut Os, mut user_input: String) -> Result<ChatState, ChatError> {
         queue!(self.stderr, style::Print('\n'))?;
         user_input = sanitize_unicode_tags(&user_input);
@@ -1706,6 +1933,15 @@ impl ChatSession {
             };
             return subcommand.execute(self).await;
         } else if let Some(command) = input.strip_prefix("!") {
+            // The shell the command runs in cannot change the directory of the session, so `cd`
+            // is handled here instead.
+            if let Some(args) = shlex::split(command).filter(|args| args.len() <= 2 && args[0] == "cd") {
+                self.change_directory(os, args.get(1).map(String::as_str)).await?;
+                return Ok(ChatState::PromptUser {
+                    skip_printing_tools: false,
+                });
+            }
+
             // Use platform-appropriate shell
             let result = if cfg!(target_os = "windows") {
                 std::process::Command::new("cmd").args(["/C", command]).status()
@@ -1741,6 +1977,38 @@ impl ChatSession {
         } else {
             // Check for a pending tool approval
             if let Some(index) = self.pending_tool_index {
+                if ["a", "A"].contains(&input) {
+                    if let Some((rule, agent_name)) = self.persistable_permission_rule(os) {
+                        let result = match self.conversation.agents.get_active_mut() {
+                            Some(agent) => agent.persist_allow_rule(os, &rule).await,
+                            None => Err(eyre!("No active agent")),
+                        };
+                        match result {
+                            Ok(()) => {
+                                queue!(
+                                    self.stderr,
+                                    style::SetForegroundColor(Color::DarkGrey),
+                                    style::Print(format!("Saved the rule {rule} to the agent {agent_name}\n")),
+                                    style::SetForegroundColor(Color::Reset),
+                                )?;
+                                self.tool_uses[index].accepted = true;
+                                return Ok(ChatState::ExecuteTools);
+                            },
+                            Err(err) => {
+                                execute!(
+                                    self.stderr,
+                                    style::SetForegroundColor(Color::Red),
+                                    style::Print(format!("Failed to save the rule {rule}: {err}\n")),
+                                    style::SetForegroundColor(Color::Reset),
+                                )?;
+                                return Ok(ChatState::PromptUser {
+                                    skip_printing_tools: false,
+                                });
+                            },
+                        }
+                    }
+                }
+
                 let is_trust = ["t", "T"].contains(&input);
                 let tool_use = &mut self.tool_uses[index];
                 if ["y", "Y"].contains(&input) || is_trust {
@@ -1775,6 +2043,8 @@ impl ChatSession {
             // Otherwise continue with normal chat on 'n' or other responses
             self.tool_use_status = ToolUseStatus::Idle;
 
+            self.conversation.checkpoints.begin_turn(&user_input);
+
             if self.pending_tool_index.is_some() {
                 // If the user just enters "n", replace the message we send to the model with
                 // something more substantial.
@@ -1793,10 +2063,25 @@ impl ChatSession {
 
             self.reset_user_turn();
 
-            let conv_state = self
+            let conv_state = match self
                 .conversation
                 .as_sendable_conversation_state(os, &mut self.stderr, true)
-                .await?;
+                .await
+            {
+                Ok(conv_state) => conv_state,
+                Err(err @ ChatError::PromptBlocked(_)) => {
+                    execute!(
+                        self.stderr,
+                        style::SetForegroundColor(Color::Red),
+                        style::Print(format!("\n{err}\n\n")),
+                        style::SetForegroundColor(Color::Reset),
+                    )?;
+                    return Ok(ChatState::PromptUser {
+                        skip_printing_tools: false,
+                    });
+                },
+                Err(err) => return Err(err),
+            };
             self.send_tool_use_telemetry(os).await;
 
             queue!(self.stderr, style::SetForegroundColor(Color::Magenta))?;
@@ -1814,37 +2099,105 @@ impl ChatSession {
     async fn tool_use_execute(&mut self, os: &mut Os) -> Result<ChatState, ChatError> {
         // Verify tools have permissions.
         for i in 0..self.tool_uses.len() {
-            let tool = &mut self.tool_uses[i];
-
             // Manually accepted by the user or otherwise verified already.
-            if tool.accepted {
+            if self.tool_uses[i].accepted {
                 continue;
             }
 
-            let mut denied = false;
-            let allowed =
-                self.conversation
-                    .agents
-                    .get_active()
-                    .is_some_and(|a| match tool.tool.requires_acceptance(a) {
-                        PermissionEvalResult::Allow => true,
-                        PermissionEvalResult::Ask => false,
-                        PermissionEvalResult::Deny => {
-                            denied = true;
-                            false
-                        },
-                    })
-                    || self.conversation.agents.trust_all_tools;
+            let (hook_decision, hook_reason) = self.run_pre_tool_use_hooks(os, i).await?;
+            let tool = &mut self.tool_uses[i];
+            if hook_decision == Some(HookDecision::Deny) {
+                if let Some(output) = self.json_output.as_mut() {
+                    output.emit(OutputEvent::PermissionDenied {
+                        id: tool.id.clone(),
+                        name: tool.name.clone(),
+                        reason: format!(
+                            "Blocked by a hook{}",
+                            hook_reason
+                                .as_ref()
+                                .map(|reason| format!(": {reason}"))
+                                .unwrap_or_default()
+                        ),
+                    })?;
+                }
+                return Ok(ChatState::HandleInput {
+                    input: format!(
+                        "Tool use with {} was blocked by a hook{}",
+                        tool.name,
+                        hook_reason.map(|reason| format!(": {reason}")).unwrap_or_default()
+                    ),
+                });
+            }
 
-            if denied {
+            // The agent is always consulted, so that its deny rules win over hooks allowing the tool
+            // use and apply to any input the hooks rewrote.
+            let permission = self
+                .conversation
+                .agents
+                .get_active()
+                .map(|a| tool.tool.requires_acceptance(a));
+            let denied = match &permission {
+                Some(PermissionEvalResult::Deny) => Some(None),
+                Some(PermissionEvalResult::DeniedByRule(rule)) => Some(Some(rule.clone())),
+                _ => None,
+            };
+            let mut allowed = match hook_decision {
+                Some(HookDecision::Allow) => true,
+                Some(HookDecision::Ask) => false,
+                _ => {
+                    matches!(
+                        permission,
+                        Some(PermissionEvalResult::Allow | PermissionEvalResult::AllowedByRule(_))
+                    ) || self.conversation.agents.trust_all_tools
+                },
+            };
+
+            if let Some(rule) = denied {
+                if let Some(output) = self.json_output.as_mut() {
+                    output.emit(OutputEvent::PermissionDenied {
+                        id: tool.id.clone(),
+                        name: tool.name.clone(),
+                        reason: match &rule {
+                            Some(rule) => format!("Denied by the permission rule {rule}"),
+                            None => "Denied by the agent".to_string(),
+                        },
+                    })?;
+                }
                 return Ok(ChatState::HandleInput {
                     input: format!(
-                        "Tool use with {} was rejected because the arguments supplied were forbidden",
-                        tool.name
+                        "Tool use with {} was rejected because the arguments supplied were forbidden{}",
+                        tool.name,
+                        rule.map(|rule| format!(" by the permission rule {rule}"))
+                            .unwrap_or_default()
                     ),
                 });
             }
 
+            // TODO: Control flow is hacky here because of borrow rules
+            let _ = tool;
+
+            // Without a user, the permission prompt command decides on tool uses requiring approval.
+            if !allowed && !self.interactive {
+                if let Some(command) = self.permission_prompt_command.clone() {
+                    match self.run_permission_prompt_command(os, i, &command).await {
+                        Ok(()) => allowed = true,
+                        Err(reason) => {
+                            let tool = &self.tool_uses[i];
+                            if let Some(output) = self.json_output.as_mut() {
+                                output.emit(OutputEvent::PermissionDenied {
+                                    id: tool.id.clone(),
+                                    name: tool.name.clone(),
+                                    reason: reason.clone(),
+                                })?;
+                            }
+                            return Ok(ChatState::HandleInput {
+                                input: format!("Tool use with {} was denied: {reason}", tool.name),
+                            });
+                        },
+                    }
+                }
+            }
+
             if os
                 .database
                 .settings
@@ -1854,8 +2207,6 @@ impl ChatSession {
                 play_notification_bell(!allowed);
             }
 
-            // TODO: Control flow is hacky here because of borrow rules
-            let _ = tool;
             self.print_tool_description(os, i, allowed).await?;
             let tool = &mut self.tool_uses[i];
 
@@ -1878,8 +2229,48 @@ impl ChatSession {
         let mut tool_results = vec![];
         let mut image_blocks: Vec<RichImageBlock> = Vec::new();
 
-        for tool in &self.tool_uses {
-            let tool_start = std::time::Instant::now();
+        // Independent tools run at the same time, after which the output of each one is printed
+        // in order.
+        let mut parallel_results = if can_run_in_parallel(&self.tool_uses) {
+            Some(self.invoke_tools_in_parallel(os).await?.into_iter())
+        } else {
+            None
+        };
+
+        for i in 0..self.tool_uses.len() {
+            let (invoke_result, tool_time) = match parallel_results.as_mut().and_then(Iterator::next) {
+                Some((invoke_result, output, tool_time)) => {
+                    self.stdout.write_all(&output)?;
+                    (invoke_result, tool_time)
+                },
+                None => {
+                    let tool_start = std::time::Instant::now();
+
+                    // Record the files this tool may modify so that the turn can be rolled back.
+                    let tree_snapshot = self
+                        .conversation
+                        .checkpoints
+                        .snapshot_tool(os, &self.tool_uses[i].tool)
+                        .await;
+
+                    // MCP servers may report progress, request completions from the model and
+                    // ask the user for information while their tools run. Serving these requires
+                    // the whole session, so stdout is moved out of it until the tool finishes.
+                    let tool = self.tool_uses[i].tool.clone();
+                    let mut stdout = std::mem::replace(&mut self.stdout, Box::new(std::io::sink()));
+                    let invoke_result = self.drive_tool_invocation(os, tool.invoke(os, &mut stdout)).await;
+                    self.stdout = stdout;
+                    let invoke_result = invoke_result?;
+
+                    self.conversation
+                        .checkpoints
+                        .record_tool_changes(os, &tool, tree_snapshot)
+                        .await;
+                    (invoke_result, tool_start.elapsed())
+                },
+            };
+
+            let tool = &self.tool_uses[i];
             let mut tool_telemetry = self.tool_use_telemetry_events.entry(tool.id.clone());
             tool_telemetry = tool_telemetry.and_modify(|ev| {
                 ev.is_accepted = true;
@@ -1897,9 +2288,8 @@ impl ChatSession {
                 }
             }
 
-            let invoke_result = tool.tool.invoke(os, &mut self.stdout).await;
-
             if self.spinner.is_some() {
+                drop(self.spinner.take());
                 queue!(
                     self.stderr,
                     terminal::Clear(terminal::ClearType::CurrentLine),
@@ -1910,7 +2300,6 @@ impl ChatSession {
             execute!(self.stdout, style::Print("\n"))?;
 
             let tool_end_time = Instant::now();
-            let tool_time = tool_end_time.duration_since(tool_start);
             tool_telemetry = tool_telemetry.and_modify(|ev| {
                 ev.execution_duration = Some(tool_time);
                 ev.turn_duration = self.tool_turn_start_time.map(|t| tool_end_time.duration_since(t));
@@ -1924,6 +2313,21 @@ impl ChatSession {
                 });
             }
             let tool_time = format!("{}.{}", tool_time.as_secs(), tool_time.subsec_millis());
+            let tool_response = match &invoke_result {
+                Ok(result) => serde_json::json!({ "success": true, "output": result.as_str() }),
+                Err(err) => serde_json::json!({ "success": false, "error": err.to_string() }),
+            };
+            if let Some(output) = self.json_output.as_mut() {
+                output.emit(OutputEvent::ToolResult {
+                    id: tool.id.clone(),
+                    name: tool.name.clone(),
+                    success: invoke_result.is_ok(),
+                    output: match &invoke_result {
+                        Ok(result) => result.as_str().into_owned(),
+                        Err(err) => err.to_string(),
+                    },
+                })?;
+            }
             match invoke_result {
                 Ok(result) => {
                     match result.output {
@@ -2002,6 +2406,13 @@ impl ChatSession {
                     }
                 },
             }
+
+            if let Some(cm) = self.conversation.context_manager.as_mut() {
+                let event = HookEvent::new(os, HookTrigger::PostToolUse)
+                    .with_tool(&tool.qualified_name(), tool.input.clone())
+                    .with_tool_response(tool_response);
+                cm.run_hooks(&event, &mut self.stderr).await?;
+            }
         }
 
         if !image_blocks.is_empty() {
@@ -2033,6 +2444,82 @@ impl ChatSession {
         ));
     }
 
+    /// Waits for `invoke` to complete while serving the requests MCP servers make as their tools
+    /// run: progress notifications, and requests for completions from the model or for information
+    /// from the user.
+    async fn drive_tool_invocation<T>(&mut self, os: &Os, invoke: impl Future<Output = T>) -> Result<T, ChatError> {
+        tokio::pin!(invoke);
+        loop {
+            tokio::select! {
+                result = &mut invoke => return Ok(result),
+                Some(request) = self.conversation.tool_manager.next_interactive_request() => {
+                    if self.spinner.is_some() {
+                        drop(self.spinner.take());
+                        execute!(
+                            self.stderr,
+                            terminal::Clear(terminal::ClearType::CurrentLine),
+                            cursor::MoveToColumn(0),
+                            cursor::Show
+                        )?;
+                    }
+                    match request {
+                        InteractiveRequest::Sampling(request) => {
+                            handle_sampling_request(
+                                os,
+                                &mut self.stderr,
+                                &mut self.input_source,
+                                self.interactive,
+                                self.conversation.agents.get_active(),
+                                self.conversation.model.as_deref(),
+                                request,
+                            )
+                            .await;
+                        },
+                        InteractiveRequest::Elicitation(request) => {
+                            handle_elicitation_request(&mut self.stderr, self.interactive, request);
+                        },
+                    }
+                },
+                Some((server_name, progress)) = self.conversation.tool_manager.next_progress() => {
+                    if self.interactive {
+                        drop(self.spinner.take());
+                        execute!(
+                            self.stderr,
+                            terminal::Clear(terminal::ClearType::CurrentLine),
+                            cursor::MoveToColumn(0),
+                            cursor::Hide
+                        )?;
+                        self.spinner = Some(Spinner::new(Spinners::Dots, format!("{server_name}: {progress}")));
+                    }
+                },
+            }
+        }
+    }
+
+    /// Invokes every queued tool at the same time, returning the result, output and duration of
+    /// each one in order. The output is buffered so that it can be printed in order.
+    async fn invoke_tools_in_parallel(
+        &mut self,
+        os: &Os,
+    ) -> Result<Vec<(Result<InvokeOutput>, Vec<u8>, Duration)>, ChatError> {
+        let tools = self.tool_uses.iter().map(|tool| tool.tool.clone()).collect::<Vec<_>>();
+        if self.interactive {
+            execute!(self.stderr, cursor::Hide)?;
+            self.spinner = Some(Spinner::new(
+                Spinners::Dots,
+                format!("Running {} tools...", tools.len()),
+            ));
+        }
+
+        let invoke_all = futures::future::join_all(tools.iter().map(|tool| async move {
+            let start = Instant::now();
+            let mut output = Vec::new();
+            let result = tool.invoke(os, &mut output).await;
+            (result, output, start.elapsed())
+        }));
+        self.drive_tool_invocation(os, invoke_all).await
+    }
+
     /// Sends a [crate::api_client::ApiClient::send_message] request to the backend and consumes
     /// the response stream.
     ///
@@ -2095,6 +2582,9 @@ impl ChatSession {
                                 )?;
                                 response_prefix_printed = true;
                             }
+                            if let Some(output) = self.json_output.as_mut() {
+                                output.emit(OutputEvent::AssistantText { text: text.clone() })?;
+                            }
                             buf.push_str(&text);
                         },
                         parser::ResponseEvent::ToolUse(tool_use) => {
@@ -2107,6 +2597,13 @@ impl ChatSession {
                                     cursor::Show
                                 )?;
                             }
+                            if let Some(output) = self.json_output.as_mut() {
+                                output.emit(OutputEvent::ToolUse {
+                                    id: tool_use.id.clone(),
+                                    name: tool_use.name.clone(),
+                                    input: tool_use.args.clone(),
+                                })?;
+                            }
                             tool_uses.push(tool_use);
                             tool_name_being_recvd = None;
                         },
@@ -2119,6 +2616,14 @@ impl ChatSession {
                             if message.content() == RESPONSE_TIMEOUT_CONTENT {
                                 error!(?request_id, ?message, "Encountered an unexpected model response");
                             }
+                            if let Some(output) = self.json_output.as_mut() {
+                                output.emit(OutputEvent::Usage {
+                                    request_id: request_id.clone(),
+                                    input_tokens: *self.conversation.estimate_request_token_count(),
+                                    output_tokens: *message
+                                        .count_tokens(tokenizer_kind(self.conversation.model.as_deref()).tokenizer()),
+                                })?;
+                            }
                             self.conversation.push_assistant_message(os, message, Some(rm.clone()));
                             self.user_turn_request_metadata.push(rm);
                             ended = true;
@@ -2320,6 +2825,7 @@ impl ChatSession {
 
             self.send_chat_telemetry(os, TelemetryResult::Succeeded, None, None, None, true)
                 .await;
+            self.run_event_hooks(HookEvent::new(os, HookTrigger::Stop)).await?;
 
             Ok(ChatState::PromptUser {
                 skip_printing_tools: false,
@@ -2336,6 +2842,7 @@ impl ChatSession {
         for tool_use in tool_uses {
             let tool_use_id = tool_use.id.clone();
             let tool_use_name = tool_use.name.clone();
+            let tool_input = tool_use.args.clone();
             let mut tool_telemetry =
                 ToolUseEventBuilder::new(conv_id.clone(), tool_use.id.clone(), self.conversation.model.clone())
                     .set_tool_use_id(tool_use_id.clone())
@@ -2351,9 +2858,10 @@ impl ChatSession {
                             tool_telemetry.is_valid = Some(true);
                             queued_tools.push(QueuedTool {
                                 id: tool_use_id.clone(),
-                                name: tool_use_name,
+                                name: tool_use_name.clone(),
                                 tool,
                                 accepted: false,
+                                input: tool_input,
                             });
                         },
                         Err(err) => {
@@ -2373,6 +2881,11 @@ impl ChatSession {
                     tool_results.push(err.into());
                 },
             }
+            if let Some(output) = self.json_output.as_mut() {
+                if let Some(result) = tool_results.last().filter(|result| result.tool_use_id == tool_use_id) {
+                    output.emit(OutputEvent::tool_result(tool_use_id.clone(), tool_use_name, result))?;
+                }
+            }
             self.tool_use_telemetry_events.insert(tool_use_id, tool_telemetry);
         }
 
@@ -2458,11 +2971,165 @@ impl ChatSession {
         ))
     }
 
+    /// Runs the preToolUse hooks of the tool at `index`, replacing the tool if a hook rewrites its
+    /// input. Returns the strictest decision of the hooks along with their reasons.
+    async fn run_pre_tool_use_hooks(
+        &mut self,
+        os: &Os,
+        index: usize,
+    ) -> Result<(Option<HookDecision>, Option<String>), ChatError> {
+        let Some(cm) = self.conversation.context_manager.as_mut() else {
+            return Ok((None, None));
+        };
+        let tool = &self.tool_uses[index];
+        let event = HookEvent::new(os, HookTrigger::PreToolUse).with_tool(&tool.qualified_name(), tool.input.clone());
+        let responses = cm
+            .run_hooks(&event, &mut self.stderr)
+            .await?
+            .into_iter()
+            .map(|(_, output)| output.response())
+            .collect::<Vec<_>>();
+
+        let mut decision = responses.iter().filter_map(|response| response.decision).max();
+        let mut reasons = responses
+            .iter()
+            .filter(|response| response.decision == decision)
+            .filter_map(|response| response.reason.clone())
+            .collect::<Vec<_>>();
+
+        if decision != Some(HookDecision::Deny) {
+            if let Some(input) = responses.into_iter().find_map(|response| response.tool_input) {
+                match self.rewrite_tool_input(os, index, input).await {
+                    Ok(()) => {},
+                    Err(err) => {
+                        decision = Some(HookDecision::Deny);
+                        reasons = vec![format!("the input it provided is invalid: {err}")];
+                    },
+                }
+            }
+        }
+
+        Ok((decision, (!reasons.is_empty()).then(|| reasons.join("; "))))
+    }
+
+    /// Asks the permission prompt command whether the queued tool at `index` may run, replacing
+    /// its input if the command modifies it. Returns the reason if the tool use is denied.
+    async fn run_permission_prompt_command(&mut self, os: &Os, index: usize, command: &str) -> Result<(), String> {
+        let tool = &self.tool_uses[index];
+        let request = PermissionRequest {
+            tool_use_id: tool.id.clone(),
+            tool_name: tool.qualified_name(),
+            tool_input: tool.input.clone(),
+            cwd: os
+                .env
+                .current_dir()
+                .map(|cwd| cwd.to_string_lossy().to_string())
+                .unwrap_or_default(),
+        };
+        let response = ask_permission(command, &request)
+            .await
+            .map_err(|err| format!("the permission prompt command failed: {err}"))?;
+
+        match (response.decision, response.tool_input) {
+            (PermissionDecision::Allow, _) => Ok(()),
+            (PermissionDecision::Modify, Some(input)) => {
+                self.rewrite_tool_input(os, index, input)
+                    .await
+                    .map_err(|err| format!("the input provided by the permission prompt command is invalid: {err}"))?;
+
+                // The new input must not be forbidden by the agent.
+                let tool = &self.tool_uses[index];
+                match self
+                    .conversation
+                    .agents
+                    .get_active()
+                    .map(|a| tool.tool.requires_acceptance(a))
+                {
+                    Some(PermissionEvalResult::Deny) => Err("the modified arguments are forbidden".to_string()),
+                    Some(PermissionEvalResult::DeniedByRule(rule)) => Err(format!(
+                        "the modified arguments are forbidden by the permission rule {rule}"
+                    )),
+                    _ => Ok(()),
+                }
+            },
+            (PermissionDecision::Deny | PermissionDecision::Modify, _) => Err(response
+                .reason
+                .unwrap_or_else(|| "denied by the permission prompt command".to_string())),
+        }
+    }
+
+    /// Replaces the input of the queued tool at `index`.
+    async fn rewrite_tool_input(&mut self, os: &Os, index: usize, input: serde_json::Value) -> Result<(), String> {
+        let tool = &self.tool_uses[index];
+        let tool_use = AssistantToolUse {
+            id: tool.id.clone(),
+            name: tool.name.clone(),
+            orig_name: tool.name.clone(),
+            args: input.clone(),
+            orig_args: input.clone(),
+        };
+        let mut new_tool = self
+            .conversation
+            .tool_manager
+            .get_tool_from_tool_use(tool_use)
+            .map_err(|err| {
+                ToolUseResult::from(err)
+                    .content
+                    .into_iter()
+                    .map(|block| match block {
+                        ToolUseResultBlock::Text(text) => text,
+                        ToolUseResultBlock::Json(json) => json.to_string(),
+                    })
+                    .collect::<Vec<_>>()
+                    .join("\n")
+            })?;
+        self.contextualize_tool(&mut new_tool);
+        new_tool.validate(os).await.map_err(|err| err.to_string())?;
+
+        let tool = &mut self.tool_uses[index];
+        tool.tool = new_tool;
+        tool.input = input;
+        Ok(())
+    }
+
+    /// Runs the hooks triggered by `event`, ignoring their output.
+    async fn run_event_hooks(&mut self, event: HookEvent) -> Result<(), ChatError> {
+        if let Some(cm) = self.conversation.context_manager.as_mut() {
+            cm.run_hooks(&event, &mut self.stderr).await?;
+        }
+        Ok(())
+    }
+
     /// Apply program context to tools that Q may not have.
     // We cannot attach this any other way because Tools are constructed by deserializing
     // output from Amazon Q.
     // TODO: Is there a better way?
     fn contextualize_tool(&self, tool: &mut Tool) {
+        if let Tool::FsRead(fs_read) = tool {
+            if let Some(agent) = self.conversation.agents.get_active() {
+                fs_read.set_denied_paths(agent);
+            }
+        }
+
+        if let Tool::ExecuteCommand(execute_command) = tool {
+            if let Some(agent) = self.conversation.agents.get_active() {
+                execute_command.set_sandbox(agent);
+            }
+            execute_command.background_processes = Some(self.background_processes.clone());
+        }
+
+        if let Tool::BackgroundProcess(background_process) = tool {
+            background_process.processes = Some(self.background_processes.clone());
+        }
+
+        if let Tool::UseSubagent(use_subagent) = tool {
+            use_subagent.set_context(
+                &self.conversation.agents,
+                self.conversation.model.clone(),
+                &self.conversation.tool_manager,
+            );
+        }
+
         if let Tool::GhIssue(gh_issue) = tool {
             let allowed_tools = self
                 .conversation
@@ -2919,12 +3586,14 @@ mod tests {
                 "y".to_string(),
                 "exit".to_string(),
             ]),
-            false,
+            None,
             || Some(80),
             tool_manager,
             None,
             tool_config,
             true,
+            OutputFormat::Plain,
+            None,
         )
         .await
         .unwrap()
@@ -3060,12 +3729,14 @@ mod tests {
                 "n".to_string(),             // cancel
                 "exit".to_string(),
             ]),
-            false,
+            None,
             || Some(80),
             tool_manager,
             None,
             tool_config,
             true,
+            OutputFormat::Plain,
+            None,
         )
         .await
         .unwrap()
@@ -3156,12 +3827,14 @@ mod tests {
                 "y".to_string(),
                 "exit".to_string(),
             ]),
-            false,
+            None,
             || Some(80),
             tool_manager,
             None,
             tool_config,
             true,
+            OutputFormat::Plain,
+            None,
         )
         .await
         .unwrap()
@@ -3230,12 +3903,14 @@ mod tests {
                 "create a new file".to_string(),
                 "exit".to_string(),
             ]),
-            false,
+            None,
             || Some(80),
             tool_manager,
             None,
             tool_config,
             true,
+            OutputFormat::Plain,
+            None,
         )
         .await
         .unwrap()
@@ -3247,6 +3922,144 @@ mod tests {
         assert!(!os.fs.exists("/file2.txt"));
     }
 
+    #[tokio::test]
+    #[cfg(unix)]
+    async fn test_flow_hook_allow_does_not_override_deny_rules() {
+        let mut os = Os::new().await.unwrap();
+        os.client.set_mock_output(serde_json::json!([
+            [
+                "Sure, I'll create a file for you",
+                {
+                    "tool_use_id": "1",
+                    "name": "fs_write",
+                    "args": {
+                        "command": "create",
+                        "file_text": "Hello, world!",
+                        "path": "/secret.txt",
+                    }
+                }
+            ],
+            [
+                "Ok I won't.",
+            ],
+        ]));
+
+        let mut agents = Agents::default();
+        let agent = serde_json::from_value::<Agent>(serde_json::json!({
+            "name": "TestAgent",
+            "permissions": { "deny": ["fs_write(/secret*)"] },
+            "hooks": {
+                "preToolUse": [{ "command": "echo '{\"decision\": \"allow\"}'" }]
+            }
+        }))
+        .unwrap();
+        agents.agents.insert("TestAgent".to_string(), agent);
+        agents.switch("TestAgent").expect("Failed to switch agent");
+
+        let tool_manager = ToolManager::default();
+        let tool_config = serde_json::from_str::<HashMap<String, ToolSpec>>(include_str!("tools/tool_index.json"))
+            .expect("Tools failed to load");
+        ChatSession::new(
+            &mut os,
+            std::io::stdout(),
+            std::io::stderr(),
+            "fake_conv_id",
+            agents,
+            None,
+            InputSource::new_mock(vec!["create a secret file".to_string(), "exit".to_string()]),
+            None,
+            || Some(80),
+            tool_manager,
+            None,
+            tool_config,
+            true,
+            OutputFormat::Plain,
+            None,
+        )
+        .await
+        .unwrap()
+        .spawn(&mut os)
+        .await
+        .unwrap();
+
+        assert!(!os.fs.exists("/secret.txt"));
+    }
+
+    /// A writer whose output can be read after it is moved into a [ChatSession].
+    #[derive(Clone, Default)]
+    struct SharedBuffer(Arc<std::sync::Mutex<Vec<u8>>>);
+
+    impl Write for SharedBuffer {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    #[cfg(unix)]
+    async fn test_flow_tool_output_goes_to_session_stdout() {
+        let mut os = Os::new().await.unwrap();
+        os.client.set_mock_output(serde_json::json!([
+            [
+                "Running it",
+                {
+                    "tool_use_id": "1",
+                    "name": "execute_bash",
+                    "args": {
+                        "command": "echo tool-output-marker",
+                    }
+                }
+            ],
+            [
+                "Done",
+            ],
+        ]));
+
+        let mut agents = Agents::default();
+        let agent = serde_json::from_value::<Agent>(serde_json::json!({
+            "name": "TestAgent",
+            "allowedTools": ["execute_bash"],
+        }))
+        .unwrap();
+        agents.agents.insert("TestAgent".to_string(), agent);
+        agents.switch("TestAgent").expect("Failed to switch agent");
+
+        let stdout = SharedBuffer::default();
+        let tool_manager = ToolManager::default();
+        let tool_config = serde_json::from_str::<HashMap<String, ToolSpec>>(include_str!("tools/tool_index.json"))
+            .expect("Tools failed to load");
+        ChatSession::new(
+            &mut os,
+            stdout.clone(),
+            std::io::stderr(),
+            "fake_conv_id",
+            agents,
+            None,
+            InputSource::new_mock(vec!["run a command".to_string(), "exit".to_string()]),
+            None,
+            || Some(80),
+            tool_manager,
+            None,
+            tool_config,
+            true,
+            OutputFormat::Plain,
+            None,
+        )
+        .await
+        .unwrap()
+        .spawn(&mut os)
+        .await
+        .unwrap();
+
+        let output = String::from_utf8(stdout.0.lock().unwrap().clone()).unwrap();
+        assert!(output.contains("tool-output-marker"), "{output}");
+    }
+
     #[test]
     fn test_editor_content_processing() {
         // Since we no longer have template replacement, this test is simplified
@@ -3280,12 +4093,14 @@ mod tests {
             agents,
             None,
             InputSource::new_mock(vec!["/subscribe".to_string(), "y".to_string(), "/quit".to_string()]),
-            false,
+            None,
             || Some(80),
             tool_manager,
             None,
             tool_config,
             true,
+            OutputFormat::Plain,
+            None,
         )
         .await
         .unwrap()
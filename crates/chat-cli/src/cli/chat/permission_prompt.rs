@@ -0,0 +1,129 @@
+use std::process::Stdio;
+use std::time::Duration;
+
+use bstr::ByteSlice;
+use eyre::{
+    Result,
+    bail,
+    eyre,
+};
+use serde::{
+    Deserialize,
+    Serialize,
+};
+use serde_json::Value;
+use tokio::io::AsyncWriteExt;
+
+use super::cli::hooks::shell_command;
+
+/// How long the permission prompt command may take to answer before the tool use is denied.
+const PERMISSION_PROMPT_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// A tool use that requires approval, written as JSON to the stdin of the command given with
+/// `--permission-prompt-command`.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PermissionRequest {
+    pub tool_use_id: String,
+    pub tool_name: String,
+    pub tool_input: Value,
+    pub cwd: String,
+}
+
+/// The answer of the permission prompt command, read as JSON from its stdout.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PermissionResponse {
+    pub decision: PermissionDecision,
+    pub reason: Option<String>,
+    /// The input to use the tool with instead, required by [PermissionDecision::Modify].
+    pub tool_input: Option<Value>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PermissionDecision {
+    Allow,
+    Deny,
+    /// Use the tool with the input of the response.
+    Modify,
+}
+
+/// Runs `command` to decide whether the tool use described by `request` may run.
+pub async fn ask_permission(command: &str, request: &PermissionRequest) -> Result<PermissionResponse> {
+    let mut cmd = shell_command(command);
+    cmd.stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true);
+
+    let output = tokio::time::timeout(PERMISSION_PROMPT_TIMEOUT, async {
+        let mut child = cmd.spawn()?;
+        if let Some(mut stdin) = child.stdin.take() {
+            // The command may exit without reading its input.
+            let _ = stdin.write_all(&serde_json::to_vec(request)?).await;
+        }
+        Ok::<_, eyre::Report>(child.wait_with_output().await?)
+    })
+    .await
+    .map_err(|_elapsed| eyre!("timed out after {}s", PERMISSION_PROMPT_TIMEOUT.as_secs()))??;
+
+    if !output.status.success() {
+        bail!("exited with {}: {}", output.status, output.stderr.to_str_lossy().trim());
+    }
+    let response: PermissionResponse =
+        serde_json::from_slice(output.stdout.trim()).map_err(|err| eyre!("invalid response: {err}"))?;
+    if response.decision == PermissionDecision::Modify && response.tool_input.is_none() {
+        bail!("invalid response: toolInput is required to modify the tool use");
+    }
+    Ok(response)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request() -> PermissionRequest {
+        PermissionRequest {
+            tool_use_id: "tooluse_1".to_string(),
+            tool_name: "execute_bash".to_string(),
+            tool_input: serde_json::json!({ "command": "rm -rf target" }),
+            cwd: "/repo".to_string(),
+        }
+    }
+
+    #[tokio::test]
+    #[cfg(unix)]
+    async fn test_ask_permission() {
+        let response = ask_permission(
+            r#"grep -q rm && echo '{"decision": "deny", "reason": "no rm"}'"#,
+            &request(),
+        )
+        .await
+        .unwrap();
+        assert_eq!(response, PermissionResponse {
+            decision: PermissionDecision::Deny,
+            reason: Some("no rm".to_string()),
+            tool_input: None,
+        });
+
+        let response = ask_permission(
+            r#"echo '{"decision": "modify", "toolInput": {"command": "cargo clean"}}'"#,
+            &request(),
+        )
+        .await
+        .unwrap();
+        assert_eq!(
+            response.tool_input,
+            Some(serde_json::json!({ "command": "cargo clean" }))
+        );
+
+        assert!(
+            ask_permission(r#"echo '{"decision": "modify"}'"#, &request())
+                .await
+                .is_err()
+        );
+        assert!(ask_permission("echo allow", &request()).await.is_err());
+        assert!(ask_permission("exit 1", &request()).await.is_err());
+    }
+}
@@ -52,6 +52,8 @@ pub const COMMANDS: &[&str] = &[
     "/tools trust-all",
     "/tools reset",
     "/mcp",
+    "/mcp resources",
+    "/mcp resources remove",
     "/model",
     "/agent",
     "/agent help",
@@ -79,9 +81,17 @@ pub const COMMANDS: &[&str] = &[
     "/hooks disable-all",
     "/compact",
     "/compact help",
+    "/checkpoint list",
+    "/checkpoint diff",
+    "/checkpoint restore",
+    "/rewind",
+    "/fork",
+    "/processes",
+    "/processes kill",
     "/usage",
     "/save",
     "/load",
+    "/export",
     "/subscribe",
 ];
 
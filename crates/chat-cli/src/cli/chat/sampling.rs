@@ -0,0 +1,243 @@
+//! Answers requests from MCP servers to sample the model, see
+//! https://modelcontextprotocol.io/specification/2025-03-26/client/sampling
+
+use std::io::Write;
+
+use crossterm::style::{
+    self,
+    Color,
+};
+use crossterm::{
+    execute,
+    queue,
+};
+
+use super::input_source::InputSource;
+use super::server_messenger::SamplingRequest;
+use super::util::truncate_safe;
+use crate::api_client::model::{
+    AssistantResponseMessage,
+    ChatMessage,
+    ChatResponseStream,
+    ConversationState as ApiConversationState,
+    UserInputMessage,
+};
+use crate::cli::agent::Agent;
+use crate::mcp_client::{
+    CreateMessageRequest,
+    CreateMessageResult,
+    MessageContent,
+    Role,
+};
+use crate::os::Os;
+
+/// Maximum number of bytes of the request shown when asking for approval.
+const MAX_PREVIEW_SIZE: usize = 500;
+
+/// Asks the user to approve a sampling request, unless the server is allowed to sample without
+/// approval, and responds with the completion of the model.
+#[allow(clippy::too_many_arguments)]
+pub async fn handle_sampling_request(
+    os: &Os,
+    stderr: &mut impl Write,
+    input_source: &mut InputSource,
+    interactive: bool,
+    agent: Option<&Agent>,
+    model_id: Option<&str>,
+    request: SamplingRequest,
+) {
+    let allowed = agent
+        .and_then(|agent| agent.mcp_servers.mcp_servers.get(&request.server_name))
+        .is_some_and(|config| config.allow_sampling);
+    if !allowed {
+        if !interactive {
+            request.respond(Err(
+                "Sampling requires approval, which is unavailable in this session".to_string()
+            ));
+            return;
+        }
+        match ask_approval(stderr, input_source, &request) {
+            Ok(true) => {},
+            Ok(false) => {
+                request.respond(Err("The user rejected the sampling request".to_string()));
+                return;
+            },
+            Err(err) => {
+                request.respond(Err(format!("Failed to ask for approval: {err}")));
+                return;
+            },
+        }
+    }
+
+    let result = sample(os, model_id, &request.request).await;
+    if let Err(err) = &result {
+        let _ = execute!(
+            stderr,
+            style::SetForegroundColor(Color::Red),
+            style::Print(format!("Sampling for {} failed: {err}\n", request.server_name)),
+            style::SetForegroundColor(Color::Reset),
+        );
+    }
+    request.respond(result);
+}
+
+fn ask_approval(
+    stderr: &mut impl Write,
+    input_source: &mut InputSource,
+    request: &SamplingRequest,
+) -> eyre::Result<bool> {
+    let preview = request
+        .request
+        .messages
+        .last()
+        .map(|message| message.content.to_string())
+        .unwrap_or_default();
+    queue!(
+        stderr,
+        style::Print("\n"),
+        style::SetForegroundColor(Color::Magenta),
+        style::Print(&request.server_name),
+        style::SetForegroundColor(Color::Reset),
+        style::Print(" requests a completion from the model:\n"),
+        style::SetForegroundColor(Color::DarkGrey),
+    )?;
+    for line in truncate_safe(&preview, MAX_PREVIEW_SIZE).lines() {
+        queue!(stderr, style::Print(format!("  {line}\n")))?;
+    }
+    if preview.len() > MAX_PREVIEW_SIZE {
+        queue!(stderr, style::Print("  ...\n"))?;
+    }
+    execute!(stderr, style::SetForegroundColor(Color::Reset))?;
+
+    loop {
+        let Some(input) = input_source.read_line(Some("\nAllow this request? [y/n]: "))? else {
+            return Ok(false);
+        };
+        match input.trim().to_lowercase().as_str() {
+            "y" | "yes" => return Ok(true),
+            "n" | "no" => return Ok(false),
+            _ => {},
+        }
+    }
+}
+
+/// Sends the messages of the request to the model and returns its response.
+async fn sample(
+    os: &Os,
+    model_id: Option<&str>,
+    request: &CreateMessageRequest,
+) -> Result<CreateMessageResult, String> {
+    let conversation = to_conversation_state(request, model_id)?;
+    let mut output = os
+        .client
+        .send_message(conversation)
+        .await
+        .map_err(|err| err.to_string())?;
+
+    let mut text = String::new();
+    while let Some(event) = output.recv().await.map_err(|err| err.to_string())? {
+        match event {
+            ChatResponseStream::AssistantResponseEvent { content } | ChatResponseStream::CodeEvent { content } => {
+                text.push_str(&content);
+            },
+            ChatResponseStream::InvalidStateEvent { message, .. } => return Err(message),
+            _ => {},
+        }
+    }
+
+    Ok(CreateMessageResult {
+        role: Role::Assistant,
+        content: MessageContent::Text { text },
+        model: model_id.unwrap_or("default").to_string(),
+        stop_reason: Some("endTurn".to_string()),
+    })
+}
+
+/// Converts the messages of a sampling request into the alternating user and assistant messages
+/// expected by the backend, with the system prompt prepended to the first user message.
+fn to_conversation_state(
+    request: &CreateMessageRequest,
+    model_id: Option<&str>,
+) -> Result<ApiConversationState, String> {
+    let mut messages = Vec::<(Role, String)>::new();
+    for message in &request.messages {
+        let content = match &message.content {
+            MessageContent::Image { mime_type, .. } => format!("[image of type {mime_type} omitted]"),
+            content => content.to_string(),
+        };
+        match messages.last_mut() {
+            Some((role, text)) if *role == message.role => {
+                text.push_str("\n\n");
+                text.push_str(&content);
+            },
+            _ => messages.push((message.role.clone(), content)),
+        }
+    }
+
+    if messages.first().is_some_and(|(role, _)| *role == Role::Assistant) {
+        messages.insert(0, (Role::User, String::new()));
+    }
+    if let (Some(system_prompt), Some((_, text))) = (&request.system_prompt, messages.first_mut()) {
+        *text = format!("{system_prompt}\n\n{text}");
+    }
+    let Some((Role::User, content)) = messages.pop() else {
+        return Err("The last message of a sampling request must be from the user".to_string());
+    };
+
+    let user_message = |content: String| UserInputMessage {
+        content,
+        user_input_message_context: None,
+        user_intent: None,
+        images: None,
+        model_id: model_id.map(str::to_string),
+    };
+    let history = messages
+        .into_iter()
+        .map(|(role, content)| match role {
+            Role::User => ChatMessage::UserInputMessage(user_message(content)),
+            Role::Assistant => ChatMessage::AssistantResponseMessage(AssistantResponseMessage {
+                message_id: None,
+                content,
+                tool_uses: None,
+            }),
+        })
+        .collect::<Vec<_>>();
+
+    Ok(ApiConversationState {
+        conversation_id: None,
+        user_input_message: user_message(content),
+        history: (!history.is_empty()).then_some(history),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_to_conversation_state() {
+        let request = serde_json::from_value::<CreateMessageRequest>(serde_json::json!({
+            "messages": [
+                { "role": "assistant", "content": { "type": "text", "text": "Ready" } },
+                { "role": "user", "content": { "type": "text", "text": "Summarise" } },
+                { "role": "user", "content": { "type": "text", "text": "the log" } },
+            ],
+            "systemPrompt": "Be brief",
+            "maxTokens": 100,
+        }))
+        .unwrap();
+        let state = to_conversation_state(&request, Some("model")).unwrap();
+        assert_eq!(state.user_input_message.content, "Summarise\n\nthe log");
+        assert_eq!(state.user_input_message.model_id.as_deref(), Some("model"));
+        let history = state.history.unwrap();
+        assert_eq!(history.len(), 2);
+        assert!(matches!(&history[0], ChatMessage::UserInputMessage(m) if m.content == "Be brief\n\n"));
+        assert!(matches!(&history[1], ChatMessage::AssistantResponseMessage(m) if m.content == "Ready"));
+
+        let request = serde_json::from_value::<CreateMessageRequest>(serde_json::json!({
+            "messages": [{ "role": "assistant", "content": { "type": "text", "text": "Hi" } }],
+        }))
+        .unwrap();
+        assert!(to_conversation_state(&request, None).is_err());
+    }
+}
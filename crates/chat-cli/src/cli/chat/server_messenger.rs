@@ -1,15 +1,27 @@
+use std::sync::{
+    Arc,
+    RwLock,
+};
+
 use tokio::sync::mpsc::{
     Receiver,
     Sender,
     channel,
 };
+use tokio::sync::oneshot;
 
 use crate::mcp_client::{
+    CreateMessageRequest,
+    CreateMessageResult,
+    ElicitRequest,
+    ElicitResult,
     Messenger,
     MessengerError,
+    ProgressNotification,
     PromptsListResult,
     ResourceTemplatesListResult,
     ResourcesListResult,
+    Root,
     ToolsListResult,
 };
 
@@ -32,21 +44,81 @@ pub enum UpdateEventMessage {
         server_name: String,
         result: eyre::Result<ResourceTemplatesListResult>,
     },
+    ResourceUpdated {
+        server_name: String,
+        uri: String,
+    },
+    Progress {
+        server_name: String,
+        progress: ProgressNotification,
+    },
+    InteractiveRequest(InteractiveRequest),
     InitStart {
         server_name: String,
     },
 }
 
+/// A request from a server that the chat session answers, possibly after asking the user.
+#[derive(Debug)]
+pub enum InteractiveRequest {
+    Sampling(SamplingRequest),
+    Elicitation(ElicitationRequest),
+}
+
+impl InteractiveRequest {
+    /// Tells the server that the request cannot be answered.
+    pub fn reject(self, reason: String) {
+        match self {
+            Self::Sampling(request) => request.respond(Err(reason)),
+            Self::Elicitation(request) => request.respond(Err(reason)),
+        }
+    }
+}
+
+/// A request from a server to sample the model.
+pub type SamplingRequest = ServerRequest<CreateMessageRequest, CreateMessageResult>;
+
+/// A request from a server for information from the user.
+pub type ElicitationRequest = ServerRequest<ElicitRequest, ElicitResult>;
+
+/// A request from a server, answered with [ServerRequest::respond].
+#[derive(Debug)]
+pub struct ServerRequest<Req, Res> {
+    pub server_name: String,
+    pub request: Req,
+    responder: oneshot::Sender<Result<Res, String>>,
+}
+
+impl<Req, Res> ServerRequest<Req, Res> {
+    fn new(server_name: String, request: Req) -> (Self, oneshot::Receiver<Result<Res, String>>) {
+        let (responder, response) = oneshot::channel();
+        let request = Self {
+            server_name,
+            request,
+            responder,
+        };
+        (request, response)
+    }
+
+    /// Sends the result to the server, or the reason why the request was not fulfilled.
+    pub fn respond(self, result: Result<Res, String>) {
+        let _ = self.responder.send(result);
+    }
+}
+
 #[derive(Clone, Debug)]
 pub struct ServerMessengerBuilder {
     pub update_event_sender: Sender<UpdateEventMessage>,
+    /// The roots shared with every server, see [Messenger::list_roots].
+    pub roots: Arc<RwLock<Vec<Root>>>,
 }
 
 impl ServerMessengerBuilder {
-    pub fn new(capacity: usize) -> (Receiver<UpdateEventMessage>, Self) {
+    pub fn new(capacity: usize, roots: Arc<RwLock<Vec<Root>>>) -> (Receiver<UpdateEventMessage>, Self) {
         let (tx, rx) = channel::<UpdateEventMessage>(capacity);
         let this = Self {
             update_event_sender: tx,
+            roots,
         };
         (rx, this)
     }
@@ -55,6 +127,7 @@ impl ServerMessengerBuilder {
         ServerMessenger {
             server_name,
             update_event_sender: self.update_event_sender.clone(),
+            roots: self.roots.clone(),
         }
     }
 }
@@ -63,6 +136,7 @@ impl ServerMessengerBuilder {
 pub struct ServerMessenger {
     pub server_name: String,
     pub update_event_sender: Sender<UpdateEventMessage>,
+    pub roots: Arc<RwLock<Vec<Root>>>,
 }
 
 #[async_trait::async_trait]
@@ -117,6 +191,66 @@ impl Messenger for ServerMessenger {
             .map_err(|e| MessengerError::Custom(e.to_string()))?)
     }
 
+    async fn send_resource_updated_notification(&self, uri: String) -> Result<(), MessengerError> {
+        Ok(self
+            .update_event_sender
+            .send(UpdateEventMessage::ResourceUpdated {
+                server_name: self.server_name.clone(),
+                uri,
+            })
+            .await
+            .map_err(|e| MessengerError::Custom(e.to_string()))?)
+    }
+
+    async fn send_progress_notification(&self, progress: ProgressNotification) -> Result<(), MessengerError> {
+        Ok(self
+            .update_event_sender
+            .send(UpdateEventMessage::Progress {
+                server_name: self.server_name.clone(),
+                progress,
+            })
+            .await
+            .map_err(|e| MessengerError::Custom(e.to_string()))?)
+    }
+
+    async fn send_sampling_request(
+        &self,
+        request: CreateMessageRequest,
+    ) -> Result<CreateMessageResult, MessengerError> {
+        let (request, response) = SamplingRequest::new(self.server_name.clone(), request);
+        self.update_event_sender
+            .send(UpdateEventMessage::InteractiveRequest(InteractiveRequest::Sampling(
+                request,
+            )))
+            .await
+            .map_err(|e| MessengerError::Custom(e.to_string()))?;
+        response
+            .await
+            .map_err(|_dropped| MessengerError::Custom("The sampling request was not answered".to_string()))?
+            .map_err(MessengerError::Custom)
+    }
+
+    async fn send_elicitation_request(&self, request: ElicitRequest) -> Result<ElicitResult, MessengerError> {
+        let (request, response) = ElicitationRequest::new(self.server_name.clone(), request);
+        self.update_event_sender
+            .send(UpdateEventMessage::InteractiveRequest(InteractiveRequest::Elicitation(
+                request,
+            )))
+            .await
+            .map_err(|e| MessengerError::Custom(e.to_string()))?;
+        response
+            .await
+            .map_err(|_dropped| MessengerError::Custom("The elicitation request was not answered".to_string()))?
+            .map_err(MessengerError::Custom)
+    }
+
+    async fn list_roots(&self) -> Result<Vec<Root>, MessengerError> {
+        self.roots
+            .read()
+            .map(|roots| roots.clone())
+            .map_err(|e| MessengerError::Custom(e.to_string()))
+    }
+
     async fn send_init_msg(&self) -> Result<(), MessengerError> {
         Ok(self
             .update_event_sender
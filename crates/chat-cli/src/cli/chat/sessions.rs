@@ -0,0 +1,281 @@
+use std::collections::HashMap;
+use std::io::{
+    IsTerminal,
+    Write,
+};
+use std::path::Path;
+use std::process::ExitCode;
+
+use clap::{
+    Args,
+    Subcommand,
+};
+use crossterm::style::{
+    self,
+    Color,
+};
+use crossterm::{
+    execute,
+    queue,
+};
+use eyre::{
+    Result,
+    bail,
+    eyre,
+};
+
+use super::ConversationState;
+use super::conversation_file::LoadedConversation;
+use super::tool_manager::ToolManager;
+use super::tools::ToolSpec;
+use super::transcript::{
+    ExportFormat,
+    ExportOptions,
+    export,
+};
+use super::util::select_item;
+use crate::cli::agent::Agents;
+use crate::database::{
+    Database,
+    SessionSummary,
+};
+use crate::os::Os;
+use crate::util::directories;
+
+/// Number of characters of a session id shown in listings, usually enough to identify it.
+const SHORT_ID_LEN: usize = 8;
+
+#[derive(Debug, Clone, PartialEq, Eq, Subcommand)]
+pub enum SessionsSubcommand {
+    /// List the saved conversations of the current directory, most recent first
+    List {
+        /// List the saved conversations of every directory
+        #[arg(long, short)]
+        all: bool,
+    },
+    /// Delete a saved conversation
+    Delete {
+        /// The id of the conversation, or a unique prefix of it
+        id: String,
+    },
+    /// Rename a saved conversation
+    Rename {
+        /// The id of the conversation, or a unique prefix of it
+        id: String,
+        title: String,
+    },
+}
+
+impl SessionsSubcommand {
+    pub async fn execute(self, os: &mut Os) -> Result<ExitCode> {
+        let mut stderr = std::io::stderr();
+        match self {
+            Self::List { all } => {
+                let cwd = os.env.current_dir()?;
+                let sessions = os.database.list_sessions((!all).then_some(cwd.as_path()))?;
+                if sessions.is_empty() {
+                    execute!(
+                        stderr,
+                        style::SetForegroundColor(Color::DarkGrey),
+                        style::Print("No saved conversations found. Conversations are saved as you chat.\n"),
+                        style::SetForegroundColor(Color::Reset)
+                    )?;
+                }
+                for session in &sessions {
+                    queue!(
+                        stderr,
+                        style::SetForegroundColor(Color::Cyan),
+                        style::Print(short_id(&session.id)),
+                        style::SetForegroundColor(Color::Reset),
+                        style::Print(format!("  {}\n", format_session(session))),
+                    )?;
+                    if all {
+                        queue!(
+                            stderr,
+                            style::SetForegroundColor(Color::DarkGrey),
+                            style::Print(format!("          {}\n", session.path)),
+                            style::SetForegroundColor(Color::Reset),
+                        )?;
+                    }
+                }
+                stderr.flush()?;
+            },
+            Self::Delete { id } => {
+                let session = find_session(&os.database, &id)?;
+                os.database.delete_session(&session.id)?;
+                writeln!(stderr, "Deleted conversation {}", short_id(&session.id))?;
+            },
+            Self::Rename { id, title } => {
+                let title = title.trim();
+                if title.is_empty() {
+                    bail!("The title cannot be empty");
+                }
+                let session = find_session(&os.database, &id)?;
+                os.database.rename_session(&session.id, title)?;
+                writeln!(stderr, "Renamed conversation {} to '{title}'", short_id(&session.id))?;
+            },
+        }
+
+        Ok(ExitCode::SUCCESS)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Args)]
+pub struct ExportSessionArgs {
+    /// The id of the conversation, or a unique prefix of it
+    id: String,
+    /// The format of the transcript, guessed from the extension of the output file if omitted
+    #[arg(long, value_enum)]
+    format: Option<ExportFormat>,
+    /// Leave out the environment of each prompt, and mask secrets and the home directory
+    #[arg(long)]
+    redact: bool,
+    /// Write the transcript to this file instead of stdout
+    #[arg(short, long)]
+    output: Option<String>,
+}
+
+impl ExportSessionArgs {
+    pub async fn execute(self, os: &mut Os) -> Result<ExitCode> {
+        let session = load_session(&os.database, &self.id)?;
+        let (conversation, _) = session
+            .into_conversation(Agents::default(), HashMap::new(), ToolManager::default(), None)
+            .await;
+        let options = ExportOptions {
+            format: match (self.format, &self.output) {
+                (Some(format), _) => format,
+                (None, Some(path)) => ExportFormat::from_path(path),
+                (None, None) => ExportFormat::default(),
+            },
+            redact: self.redact,
+            home_dir: directories::home_dir(os).ok(),
+        };
+        let contents = export(&conversation, &options)?;
+
+        match self.output {
+            Some(path) => {
+                os.fs.write(&path, contents).await?;
+                writeln!(std::io::stderr(), "Exported conversation to {path}")?;
+            },
+            None => {
+                let mut stdout = std::io::stdout().lock();
+                stdout.write_all(contents.as_bytes())?;
+                stdout.flush()?;
+            },
+        }
+
+        Ok(ExitCode::SUCCESS)
+    }
+}
+
+/// A saved conversation, along with the summary it is listed with.
+#[derive(Debug)]
+pub struct SavedSession {
+    pub summary: SessionSummary,
+    pub conversation: LoadedConversation,
+}
+
+impl SavedSession {
+    /// Creates the state of the saved conversation with `agents` and tools. Returns the parts of
+    /// the conversation that could not be read.
+    pub async fn into_conversation(
+        self,
+        agents: Agents,
+        tool_config: HashMap<String, ToolSpec>,
+        tool_manager: ToolManager,
+        model: Option<String>,
+    ) -> (ConversationState, Vec<String>) {
+        let mut conversation = ConversationState::new(&self.summary.id, agents, tool_config, tool_manager, model).await;
+        let issues = self.conversation.restore_into(&mut conversation);
+        conversation.enforce_tool_use_history_invariants();
+        (conversation, issues)
+    }
+}
+
+/// Loads the saved conversation with the id or unique id prefix `id`.
+pub fn load_session(database: &Database, id: &str) -> Result<SavedSession> {
+    let summary = find_session(database, id)?;
+    let conversation = database
+        .get_session(&summary.id)?
+        .ok_or_else(|| eyre!("No saved conversation with id '{id}'"))?;
+    Ok(SavedSession { summary, conversation })
+}
+
+/// Loads the conversation to continue with `--resume`. The user picks one of the saved
+/// conversations of `cwd` if there are several, otherwise the most recent one is used.
+pub fn resume_session(database: &Database, cwd: &Path, interactive: bool) -> Result<Option<SavedSession>> {
+    let sessions = database
+        .list_sessions(Some(cwd))?
+        .into_iter()
+        .filter(|session| session.message_count > 0)
+        .collect::<Vec<_>>();
+
+    let session = match sessions.as_slice() {
+        [] => return Ok(None),
+        [session] => session,
+        [latest, ..] if !interactive || !std::io::stdin().is_terminal() => latest,
+        sessions => match select_session(sessions)? {
+            Some(session) => session,
+            // Cancelling the picker starts a new conversation.
+            None => return Ok(None),
+        },
+    };
+
+    Ok(database.get_session(&session.id)?.map(|conversation| SavedSession {
+        summary: session.clone(),
+        conversation,
+    }))
+}
+
+/// Finds the saved conversation whose id is `id`, or the only one whose id starts with it.
+fn find_session(database: &Database, id: &str) -> Result<SessionSummary> {
+    let mut sessions = database
+        .list_sessions(None)?
+        .into_iter()
+        .filter(|session| session.id.starts_with(id))
+        .collect::<Vec<_>>();
+
+    if let Some(i) = sessions.iter().position(|session| session.id == id) {
+        return Ok(sessions.swap_remove(i));
+    }
+    match sessions.len() {
+        0 => bail!("No saved conversation with id '{id}'. Use `q chat sessions list` to see them"),
+        1 => Ok(sessions.remove(0)),
+        count => bail!("'{id}' matches {count} saved conversations, use a longer id"),
+    }
+}
+
+fn select_session(sessions: &[SessionSummary]) -> Result<Option<&SessionSummary>> {
+    let lines = sessions
+        .iter()
+        .map(|session| format!("{}  {}", short_id(&session.id), format_session(session)))
+        .collect::<Vec<_>>();
+    Ok(select_item("Resume conversation", &lines)?.map(|i| &sessions[i]))
+}
+
+pub fn short_id(id: &str) -> &str {
+    id.get(..SHORT_ID_LEN).unwrap_or(id)
+}
+
+/// Formats the last update, title and details of a session on a single line.
+fn format_session(session: &SessionSummary) -> String {
+    let updated_at = time::OffsetDateTime::from_unix_timestamp(session.updated_at)
+        .ok()
+        .and_then(|time| {
+            time.format(time::macros::format_description!(
+                "[month repr:short] [day] [hour]:[minute]"
+            ))
+            .ok()
+        })
+        .unwrap_or_default();
+    let title = match session.title.as_str() {
+        "" => "(untitled)",
+        title => title,
+    };
+
+    let mut details = vec![format!("{} messages", session.message_count)];
+    details.extend(session.agent.iter().map(|agent| format!("agent: {agent}")));
+    details.extend(session.model.iter().map(|model| format!("model: {model}")));
+
+    format!("{updated_at}  {title}  ({})", details.join(", "))
+}
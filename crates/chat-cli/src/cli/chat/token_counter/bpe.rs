@@ -0,0 +1,215 @@
+//! A byte-level BPE tokenizer that works offline, using merges bundled with the binary.
+//!
+//! The bundled merges are trained by `scripts/train_bpe.py`, which splits text into chunks the
+//! same way as [split_chunks]. Counts are close to, but not exactly, what the models' own
+//! tokenizers produce.
+
+use std::collections::HashMap;
+
+use parking_lot::Mutex;
+
+use super::Tokenizer;
+
+/// Chunks longer than this many bytes are counted in pieces, so that the cost of merging stays
+/// bounded for long runs such as base64 data.
+const MAX_PIECE_LEN: usize = 128;
+
+/// The number of chunks whose counts are remembered before the cache is cleared.
+const MAX_CACHED_CHUNKS: usize = 1 << 16;
+
+pub struct BpeTokenizer {
+    /// The rank of each pair of tokens that can be merged, where a lower rank is merged first.
+    /// Merging the pair of rank `n` creates the token `256 + n`.
+    ranks: HashMap<(u32, u32), u32>,
+    /// Token counts of the chunks seen so far, since the same words come up again and again.
+    cache: Mutex<HashMap<String, usize>>,
+}
+
+impl BpeTokenizer {
+    /// Creates a tokenizer from merges in the order they were learned.
+    pub fn new(merges: impl IntoIterator<Item = (u32, u32)>) -> Self {
+        Self {
+            ranks: merges.into_iter().zip(0..).collect(),
+            cache: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Creates a tokenizer from the merges bundled with the binary.
+    pub fn bundled() -> Self {
+        let merges = include_str!("bpe_merges.txt").lines().map(|line| {
+            let (a, b) = line.split_once(' ').expect("bundled merges must be pairs");
+            (
+                a.parse().expect("bundled merges must be token ids"),
+                b.parse().expect("bundled merges must be token ids"),
+            )
+        });
+        Self::new(merges)
+    }
+
+    fn count_chunk(&self, chunk: &str) -> usize {
+        if let Some(count) = self.cache.lock().get(chunk) {
+            return *count;
+        }
+
+        let count = chunk
+            .as_bytes()
+            .chunks(MAX_PIECE_LEN)
+            .map(|piece| self.count_piece(piece))
+            .sum();
+
+        let mut cache = self.cache.lock();
+        if cache.len() >= MAX_CACHED_CHUNKS {
+            cache.clear();
+        }
+        cache.insert(chunk.to_string(), count);
+        count
+    }
+
+    /// Applies the merges to `piece` in order of rank and returns the number of tokens left.
+    fn count_piece(&self, piece: &[u8]) -> usize {
+        let mut tokens = piece.iter().map(|b| *b as u32).collect::<Vec<_>>();
+        loop {
+            let best = tokens
+                .windows(2)
+                .enumerate()
+                .filter_map(|(i, pair)| self.ranks.get(&(pair[0], pair[1])).map(|rank| (*rank, i)))
+                .min();
+            let Some((rank, i)) = best else {
+                return tokens.len();
+            };
+            tokens[i] = 256 + rank;
+            tokens.remove(i + 1);
+        }
+    }
+}
+
+impl Tokenizer for BpeTokenizer {
+    fn count_tokens(&self, text: &str) -> usize {
+        split_chunks(text).map(|chunk| self.count_chunk(chunk)).sum()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum CharClass {
+    Space,
+    Letter,
+    Digit,
+    Other,
+}
+
+impl CharClass {
+    fn of(c: char) -> Self {
+        if c.is_whitespace() {
+            Self::Space
+        } else if c.is_alphabetic() {
+            Self::Letter
+        } else if c.is_ascii_digit() {
+            Self::Digit
+        } else {
+            Self::Other
+        }
+    }
+
+    /// Whether a single space before a run of this class is kept with the run.
+    fn takes_space(self) -> bool {
+        matches!(self, Self::Letter | Self::Other)
+    }
+}
+
+/// Splits text into words, numbers of up to 3 digits, runs of punctuation and runs of whitespace.
+/// A single space before a word or punctuation is kept with it.
+///
+/// This must stay in sync with `split_chunks` in `scripts/train_bpe.py`.
+fn split_chunks(text: &str) -> impl Iterator<Item = &str> {
+    let mut rest = text;
+    std::iter::from_fn(move || {
+        let mut chars = rest.chars();
+        let first = chars.next()?;
+        let class = CharClass::of(first);
+        let next_class = chars.next().map(CharClass::of);
+
+        let len = match class {
+            CharClass::Space if first == ' ' && next_class.is_some_and(CharClass::takes_space) => {
+                let after_space = &rest[1..];
+                1 + run_len(after_space, usize::MAX, CharClass::of(after_space.chars().next()?))
+            },
+            CharClass::Space => {
+                let len = run_len(rest, usize::MAX, CharClass::Space);
+                // Leave the last space to the word or punctuation that follows.
+                let followed_by_word = rest[len..]
+                    .chars()
+                    .next()
+                    .is_some_and(|c| CharClass::of(c).takes_space());
+                if len > 1 && followed_by_word && rest[..len].ends_with(' ') {
+                    len - 1
+                } else {
+                    len
+                }
+            },
+            CharClass::Digit => run_len(rest, 3, CharClass::Digit),
+            _ => run_len(rest, usize::MAX, class),
+        };
+
+        let (chunk, remaining) = rest.split_at(len);
+        rest = remaining;
+        Some(chunk)
+    })
+}
+
+/// Returns the length in bytes of the run of at most `max_chars` characters of `class` at the
+/// start of `text`.
+fn run_len(text: &str, max_chars: usize, class: CharClass) -> usize {
+    text.chars()
+        .take(max_chars)
+        .take_while(|c| CharClass::of(*c) == class)
+        .map(char::len_utf8)
+        .sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split_chunks() {
+        let chunks = |text| split_chunks(text).collect::<Vec<_>>();
+        assert_eq!(chunks("Hello, world!"), vec!["Hello", ",", " world", "!"]);
+        assert_eq!(chunks("let x  = 12345;"), vec![
+            "let", " x", " ", " =", " ", "123", "45", ";"
+        ]);
+        assert_eq!(chunks("fn main() {\n    x\n}"), vec![
+            "fn", " main", "()", " {", "\n   ", " x", "\n", "}"
+        ]);
+        assert_eq!(chunks("日本語 テキスト"), vec!["日本語", " テキスト"]);
+        assert_eq!(chunks("  "), vec!["  "]);
+        assert!(chunks("").is_empty());
+    }
+
+    #[test]
+    fn test_count_piece() {
+        // 'a' 'b' merges first into 256, then 256 'c' into 257.
+        let tokenizer = BpeTokenizer::new([(97, 98), (256, 99)]);
+        assert_eq!(tokenizer.count_tokens("abc"), 1);
+        assert_eq!(tokenizer.count_tokens("abab"), 2);
+        assert_eq!(tokenizer.count_tokens("cab"), 2);
+        assert_eq!(tokenizer.count_tokens("xyz"), 3);
+    }
+
+    #[test]
+    fn test_bundled() {
+        let tokenizer = BpeTokenizer::bundled();
+        // Counts of the reference implementation in scripts/train_bpe.py.
+        for (text, count) in [
+            ("", 0),
+            ("Hello, world!", 4),
+            ("fn main() {\n    println!(\"Hello, world!\");\n}\n", 15),
+            ("The quick brown fox jumps over the lazy dog.", 16),
+            ("日本語のテキストです。", 14),
+            ("Привет, как дела?", 10),
+        ] {
+            assert_eq!(tokenizer.count_tokens(text), count, "{text:?}");
+            // Counted again from the cache.
+            assert_eq!(tokenizer.count_tokens(text), count, "{text:?}");
+        }
+    }
+}
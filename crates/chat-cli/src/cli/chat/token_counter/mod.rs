@@ -0,0 +1,442 @@
+mod bpe;
+
+use std::hash::{
+    DefaultHasher,
+    Hasher,
+};
+use std::ops::Deref;
+use std::sync::LazyLock;
+
+use bpe::BpeTokenizer;
+use parking_lot::Mutex;
+
+use super::message::{
+    AssistantMessage,
+    ToolUseResult,
+    ToolUseResultBlock,
+    UserMessage,
+    UserMessageContent,
+};
+
+#[derive(Debug, Clone, Copy)]
+pub struct CharCount(usize);
+
+impl CharCount {
+    pub fn value(&self) -> usize {
+        self.0
+    }
+}
+
+impl Deref for CharCount {
+    type Target = usize;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl From<usize> for CharCount {
+    fn from(value: usize) -> Self {
+        Self(value)
+    }
+}
+
+impl std::ops::Add for CharCount {
+    type Output = CharCount;
+
+    fn add(self, rhs: Self) -> Self::Output {
+        Self(self.value() + rhs.value())
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub struct TokenCount(usize);
+
+impl TokenCount {
+    pub fn value(&self) -> usize {
+        self.0
+    }
+}
+
+impl Deref for TokenCount {
+    type Target = usize;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl From<usize> for TokenCount {
+    fn from(value: usize) -> Self {
+        Self(value)
+    }
+}
+
+impl std::ops::Add for TokenCount {
+    type Output = TokenCount;
+
+    fn add(self, rhs: Self) -> Self::Output {
+        Self(self.value() + rhs.value())
+    }
+}
+
+impl std::ops::AddAssign for TokenCount {
+    fn add_assign(&mut self, rhs: Self) {
+        self.0 += rhs.0;
+    }
+}
+
+impl std::iter::Sum for TokenCount {
+    fn sum<I: Iterator<Item = Self>>(iter: I) -> Self {
+        iter.fold(Self::default(), |acc, count| acc + count)
+    }
+}
+
+impl std::fmt::Display for TokenCount {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+/// Counts the tokens a model would see for some text.
+pub trait Tokenizer: Send + Sync {
+    fn count_tokens(&self, text: &str) -> usize;
+}
+
+/// The tokenizers available for counting, chosen per model with
+/// [crate::cli::chat::cli::model::tokenizer_kind].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum TokenizerKind {
+    /// The byte-level BPE tokenizer bundled with the binary.
+    #[default]
+    Bpe,
+    /// Estimates one token for every [TokenCounter::TOKEN_TO_CHAR_RATIO] bytes, for models whose
+    /// tokenizer is not known.
+    CharRatio,
+}
+
+impl TokenizerKind {
+    pub fn tokenizer(self) -> &'static dyn Tokenizer {
+        static BUNDLED_BPE: LazyLock<BpeTokenizer> = LazyLock::new(BpeTokenizer::bundled);
+
+        match self {
+            Self::Bpe => &*BUNDLED_BPE,
+            Self::CharRatio => &CharRatioTokenizer,
+        }
+    }
+}
+
+struct CharRatioTokenizer;
+
+impl Tokenizer for CharRatioTokenizer {
+    fn count_tokens(&self, text: &str) -> usize {
+        text.len().div_ceil(TokenCounter::TOKEN_TO_CHAR_RATIO)
+    }
+}
+
+pub struct TokenCounter;
+
+impl TokenCounter {
+    pub const TOKEN_TO_CHAR_RATIO: usize = 4;
+
+    /// Counts the tokens in the input content with the default tokenizer, for content that is not
+    /// tied to a particular model such as context files.
+    pub fn count_tokens(content: &str) -> usize {
+        TokenizerKind::default().tokenizer().count_tokens(content)
+    }
+}
+
+/// A trait for messages whose tokens count towards the context window.
+pub trait CountTokens {
+    fn count_tokens(&self, tokenizer: &dyn Tokenizer) -> TokenCount;
+}
+
+impl CountTokens for UserMessage {
+    fn count_tokens(&self, tokenizer: &dyn Tokenizer) -> TokenCount {
+        let mut total_tokens = tokenizer.count_tokens(self.additional_context());
+        match self.content() {
+            UserMessageContent::Prompt { prompt } => {
+                total_tokens += tokenizer.count_tokens(prompt);
+            },
+            UserMessageContent::CancelledToolUses {
+                prompt,
+                tool_use_results,
+            } => {
+                total_tokens += prompt.as_deref().map_or(0, |prompt| tokenizer.count_tokens(prompt));
+                total_tokens += *tool_use_results.as_slice().count_tokens(tokenizer);
+            },
+            UserMessageContent::ToolUseResults { tool_use_results } => {
+                total_tokens += *tool_use_results.as_slice().count_tokens(tokenizer);
+            },
+        }
+        total_tokens.into()
+    }
+}
+
+impl CountTokens for AssistantMessage {
+    fn count_tokens(&self, tokenizer: &dyn Tokenizer) -> TokenCount {
+        let mut total_tokens = tokenizer.count_tokens(self.content());
+        for tool_use in self.tool_uses().unwrap_or_default() {
+            total_tokens += tokenizer.count_tokens(&tool_use.name);
+            total_tokens += tokenizer.count_tokens(&tool_use.args.to_string());
+        }
+        total_tokens.into()
+    }
+}
+
+impl CountTokens for &[ToolUseResult] {
+    fn count_tokens(&self, tokenizer: &dyn Tokenizer) -> TokenCount {
+        self.iter()
+            .flat_map(|v| &v.content)
+            .map(|v| match v {
+                ToolUseResultBlock::Json(v) => tokenizer.count_tokens(&v.to_string()),
+                ToolUseResultBlock::Text(s) => tokenizer.count_tokens(s),
+            })
+            .sum::<usize>()
+            .into()
+    }
+}
+
+/// The tokens of a user message and the assistant response to it.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct EntryTokenCount {
+    pub user: TokenCount,
+    pub assistant: TokenCount,
+}
+
+/// Remembers the [EntryTokenCount] of a history entry so that the conversation can be measured
+/// again without counting every message.
+///
+/// The count is recounted when asked for with another tokenizer, or when the content of the
+/// messages has changed since, e.g. after they were truncated.
+#[derive(Debug, Default)]
+pub struct TokenCountCache(Mutex<Option<CachedTokenCount>>);
+
+#[derive(Debug, Clone, Copy)]
+struct CachedTokenCount {
+    tokenizer: TokenizerKind,
+    content_hash: u64,
+    count: EntryTokenCount,
+}
+
+impl TokenCountCache {
+    pub fn get(&self, kind: TokenizerKind, user: &UserMessage, assistant: &AssistantMessage) -> EntryTokenCount {
+        let content_hash = content_hash(user, assistant);
+        let mut cached = self.0.lock();
+        if let Some(cached) = cached.as_ref() {
+            if cached.tokenizer == kind && cached.content_hash == content_hash {
+                return cached.count;
+            }
+        }
+
+        let tokenizer = kind.tokenizer();
+        let count = EntryTokenCount {
+            user: user.count_tokens(tokenizer),
+            assistant: assistant.count_tokens(tokenizer),
+        };
+        *cached = Some(CachedTokenCount {
+            tokenizer: kind,
+            content_hash,
+            count,
+        });
+        count
+    }
+}
+
+/// Hashes the serialized form of the messages, which is much cheaper than counting their tokens.
+fn content_hash(user: &UserMessage, assistant: &AssistantMessage) -> u64 {
+    struct HashWriter(DefaultHasher);
+
+    impl std::io::Write for HashWriter {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.write(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    let mut writer = HashWriter(DefaultHasher::new());
+    // Messages only have string keys, so serializing them cannot fail.
+    let _ = serde_json::to_writer(&mut writer, &(user, assistant));
+    writer.0.finish()
+}
+
+impl Clone for TokenCountCache {
+    fn clone(&self) -> Self {
+        Self(Mutex::new(*self.0.lock()))
+    }
+}
+
+/// A trait for types that represent some number of characters (aka bytes). For use in telling
+/// whether a message has changed since its tokens were counted.
+pub trait CharCounter {
+    /// Returns the number of characters contained within this type.
+    ///
+    /// One "character" is essentially the same as one "byte"
+    fn char_count(&self) -> CharCount;
+}
+
+impl CharCounter for UserMessage {
+    fn char_count(&self) -> CharCount {
+        let mut total_chars = 0;
+        total_chars += self.additional_context().len();
+        match self.content() {
+            UserMessageContent::Prompt { prompt } => {
+                total_chars += prompt.len();
+            },
+            UserMessageContent::CancelledToolUses {
+                prompt,
+                tool_use_results,
+            } => {
+                total_chars += prompt.as_ref().map_or(0, String::len);
+                total_chars += tool_use_results.as_slice().char_count().0;
+            },
+            UserMessageContent::ToolUseResults { tool_use_results } => {
+                total_chars += tool_use_results.as_slice().char_count().0;
+            },
+        }
+        total_chars.into()
+    }
+}
+
+impl CharCounter for AssistantMessage {
+    fn char_count(&self) -> CharCount {
+        let mut total_chars = 0;
+        total_chars += self.content().len();
+        if let Some(tool_uses) = self.tool_uses() {
+            total_chars += tool_uses
+                .iter()
+                .map(|v| calculate_value_char_count(&v.args))
+                .reduce(|acc, e| acc + e)
+                .unwrap_or_default();
+        }
+        total_chars.into()
+    }
+}
+
+impl CharCounter for &[ToolUseResult] {
+    fn char_count(&self) -> CharCount {
+        self.iter()
+            .flat_map(|v| &v.content)
+            .fold(0, |acc, v| {
+                acc + match v {
+                    ToolUseResultBlock::Json(v) => calculate_value_char_count(v),
+                    ToolUseResultBlock::Text(s) => s.len(),
+                }
+            })
+            .into()
+    }
+}
+
+fn calculate_value_char_count(document: &serde_json::Value) -> usize {
+    match document {
+        serde_json::Value::Null => 1,
+        serde_json::Value::Bool(_) => 1,
+        serde_json::Value::Number(_) => 1,
+        serde_json::Value::String(s) => s.len(),
+        serde_json::Value::Array(vec) => vec.iter().fold(0, |acc, v| acc + calculate_value_char_count(v)),
+        serde_json::Value::Object(map) => map.values().fold(0, |acc, v| acc + calculate_value_char_count(v)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn test_token_count() {
+        let text = "This is a test sentence.";
+        assert_eq!(TokenCounter::count_tokens(text), 7);
+        assert_eq!(TokenizerKind::CharRatio.tokenizer().count_tokens(text), 6);
+        assert_eq!(TokenizerKind::CharRatio.tokenizer().count_tokens("abcde"), 2);
+    }
+
+    #[test]
+    fn test_token_count_cache() {
+        let mut user = UserMessage::new_prompt("This is a test sentence.".to_string());
+        let assistant = AssistantMessage::new_response(None, "Hello, world!".to_string());
+        let cache = TokenCountCache::default();
+
+        let count = cache.get(TokenizerKind::Bpe, &user, &assistant);
+        assert_eq!(count, EntryTokenCount {
+            user: 7.into(),
+            assistant: 4.into(),
+        });
+        assert_eq!(cache.clone().get(TokenizerKind::Bpe, &user, &assistant), count);
+
+        // Recounted with another tokenizer.
+        assert_eq!(cache.get(TokenizerKind::CharRatio, &user, &assistant).user, 6.into());
+
+        // Recounted once the message has changed.
+        user = UserMessage::new_prompt("This is a longer test sentence.".to_string());
+        assert_eq!(cache.get(TokenizerKind::CharRatio, &user, &assistant).user, 8.into());
+
+        // Even if its length has not.
+        let count = cache.get(TokenizerKind::Bpe, &user, &assistant);
+        user = UserMessage::new_prompt("a".repeat("This is a longer test sentence.".len()));
+        assert_ne!(cache.get(TokenizerKind::Bpe, &user, &assistant), count);
+    }
+
+    #[test]
+    fn test_calculate_value_char_count() {
+        // Test simple types
+        assert_eq!(
+            calculate_value_char_count(&serde_json::Value::String("hello".to_string())),
+            5
+        );
+        assert_eq!(
+            calculate_value_char_count(&serde_json::Value::Number(serde_json::Number::from(123))),
+            1
+        );
+        assert_eq!(calculate_value_char_count(&serde_json::Value::Bool(true)), 1);
+        assert_eq!(calculate_value_char_count(&serde_json::Value::Null), 1);
+
+        // Test array
+        let array = serde_json::Value::Array(vec![
+            serde_json::Value::String("test".to_string()),
+            serde_json::Value::Number(serde_json::Number::from(42)),
+            serde_json::Value::Bool(false),
+        ]);
+        assert_eq!(calculate_value_char_count(&array), 6); // "test" (4) + Number (1) + Bool (1)
+
+        // Test object
+        let mut obj = serde_json::Map::new();
+        obj.insert("key1".to_string(), serde_json::Value::String("value1".to_string()));
+        obj.insert(
+            "key2".to_string(),
+            serde_json::Value::Number(serde_json::Number::from(99)),
+        );
+        let object = serde_json::Value::Object(obj);
+        assert_eq!(calculate_value_char_count(&object), 7); // "value1" (6) + Number (1)
+
+        // Test nested structure
+        let mut nested_obj = serde_json::Map::new();
+        let mut inner_obj = serde_json::Map::new();
+        inner_obj.insert(
+            "inner_key".to_string(),
+            serde_json::Value::String("inner_value".to_string()),
+        );
+        nested_obj.insert("outer_key".to_string(), serde_json::Value::Object(inner_obj));
+        nested_obj.insert(
+            "array_key".to_string(),
+            serde_json::Value::Array(vec![
+                serde_json::Value::String("item1".to_string()),
+                serde_json::Value::String("item2".to_string()),
+            ]),
+        );
+
+        let complex = serde_json::Value::Object(nested_obj);
+        assert_eq!(calculate_value_char_count(&complex), 21); // "inner_value" (11) + "item1" (5) + "item2" (5)
+
+        // Test empty structures
+        assert_eq!(calculate_value_char_count(&serde_json::Value::Array(vec![])), 0);
+        assert_eq!(
+            calculate_value_char_count(&serde_json::Value::Object(serde_json::Map::new())),
+            0
+        );
+    }
+}
@@ -49,6 +49,7 @@ use tokio::sync::{
 };
 use tokio::task::JoinHandle;
 use tracing::{
+    debug,
     error,
     warn,
 };
@@ -66,9 +67,11 @@ use crate::cli::chat::cli::prompts::GetPromptError;
 use crate::cli::chat::consts::DUMMY_TOOL_NAME;
 use crate::cli::chat::message::AssistantToolUse;
 use crate::cli::chat::server_messenger::{
+    InteractiveRequest,
     ServerMessengerBuilder,
     UpdateEventMessage,
 };
+use crate::cli::chat::tools::background_process::BackgroundProcess;
 use crate::cli::chat::tools::custom_tool::{
     CustomTool,
     CustomToolClient,
@@ -80,6 +83,7 @@ use crate::cli::chat::tools::gh_issue::GhIssue;
 use crate::cli::chat::tools::knowledge::Knowledge;
 use crate::cli::chat::tools::thinking::Thinking;
 use crate::cli::chat::tools::use_aws::UseAws;
+use crate::cli::chat::tools::use_subagent::UseSubagent;
 use crate::cli::chat::tools::{
     Tool,
     ToolOrigin,
@@ -90,7 +94,11 @@ use crate::database::settings::Setting;
 use crate::mcp_client::{
     JsonRpcResponse,
     Messenger,
+    ProgressNotification,
     PromptGet,
+    ResourceInfo,
+    ResourceTemplateInfo,
+    Root,
 };
 use crate::os::Os;
 use crate::telemetry::TelemetryThread;
@@ -366,12 +374,19 @@ impl ToolManagerBuilder {
         let has_new_stuff_clone = has_new_stuff.clone();
         let pending = Arc::new(RwLock::new(HashSet::<String>::new()));
         let pending_clone = pending.clone();
-        let (mut msg_rx, messenger_builder) = ServerMessengerBuilder::new(20);
+        let roots = Arc::new(SyncRwLock::new(mcp_roots(os, self.agent.as_ref())));
+        let (mut msg_rx, messenger_builder) = ServerMessengerBuilder::new(20, roots.clone());
         let telemetry_clone = os.telemetry.clone();
         let notify = Arc::new(Notify::new());
         let notify_weak = Arc::downgrade(&notify);
         let load_record = Arc::new(Mutex::new(HashMap::<String, Vec<LoadingRecord>>::new()));
         let load_record_clone = load_record.clone();
+        let resources = Arc::new(Mutex::new(HashMap::<String, ServerResources>::new()));
+        let resources_clone = resources.clone();
+        let updated_resources = Arc::new(Mutex::new(HashSet::<(String, String)>::new()));
+        let updated_resources_clone = updated_resources.clone();
+        let (interactive_tx, interactive_rx) = tokio::sync::mpsc::channel::<InteractiveRequest>(10);
+        let (progress_tx, progress_rx) = tokio::sync::mpsc::channel::<ServerProgress>(10);
         let agent = Arc::new(Mutex::new(self.agent.unwrap_or_default()));
         let agent_clone = agent.clone();
         let database = os.database.clone();
@@ -584,14 +599,42 @@ impl ToolManagerBuilder {
                         server_name: _,
                         result: _,
                     } => {},
-                    UpdateEventMessage::ResourcesListResult {
-                        server_name: _,
-                        result: _,
-                    } => {},
-                    UpdateEventMessage::ResourceTemplatesListResult {
-                        server_name: _,
-                        result: _,
-                    } => {},
+                    UpdateEventMessage::ResourcesListResult { server_name, result } => match result {
+                        Ok(result) => {
+                            let list = result
+                                .resources
+                                .into_iter()
+                                .filter_map(|v| serde_json::from_value::<ResourceInfo>(v).ok())
+                                .collect::<Vec<_>>();
+                            resources_clone.lock().await.entry(server_name).or_default().resources = list;
+                        },
+                        Err(e) => error!("Error retrieving resources for server {server_name}: {:?}", e),
+                    },
+                    UpdateEventMessage::ResourceTemplatesListResult { server_name, result } => match result {
+                        Ok(result) => {
+                            let list = result
+                                .resource_templates
+                                .into_iter()
+                                .filter_map(|v| serde_json::from_value::<ResourceTemplateInfo>(v).ok())
+                                .collect::<Vec<_>>();
+                            resources_clone.lock().await.entry(server_name).or_default().templates = list;
+                        },
+                        // Servers without templates need not implement the method
+                        Err(e) => debug!("No resource templates retrieved for server {server_name}: {:?}", e),
+                    },
+                    UpdateEventMessage::ResourceUpdated { server_name, uri } => {
+                        updated_resources_clone.lock().await.insert((server_name, uri));
+                    },
+                    UpdateEventMessage::Progress { server_name, progress } => {
+                        // Progress is only informative, it is dropped if the chat session falls
+                        // behind.
+                        let _ = progress_tx.try_send((server_name, progress));
+                    },
+                    UpdateEventMessage::InteractiveRequest(request) => {
+                        if let Err(e) = interactive_tx.send(request).await {
+                            e.0.reject("The chat session is no longer running".to_string());
+                        }
+                    },
                     UpdateEventMessage::InitStart { server_name } => {
                         pending_clone.write().await.insert(server_name.clone());
                         loading_servers.insert(server_name, std::time::Instant::now());
@@ -725,6 +768,11 @@ impl ToolManagerBuilder {
             has_new_stuff,
             is_interactive: interactive,
             mcp_load_record: load_record,
+            resources,
+            updated_resources,
+            interactive_requests: Some(Arc::new(Mutex::new(interactive_rx))),
+            progress: Some(Arc::new(Mutex::new(progress_rx))),
+            roots,
             agent,
             disabled_servers: disabled_servers_display,
             ..Default::default()
@@ -732,6 +780,13 @@ impl ToolManagerBuilder {
     }
 }
 
+/// The resources offered by a server.
+#[derive(Clone, Debug, Default)]
+pub struct ServerResources {
+    pub resources: Vec<ResourceInfo>,
+    pub templates: Vec<ResourceTemplateInfo>,
+}
+
 #[derive(Clone, Debug)]
 /// A collection of information that is used for the following purposes:
 /// - Checking if prompt info cached is out of date
@@ -782,6 +837,9 @@ type HostToolName = String;
 /// MCP server name as they are defined in the config
 type ServerName = String;
 
+/// Progress reported by a server, with the name of the server.
+type ServerProgress = (ServerName, ProgressNotification);
+
 /// A list of new tools to be included in the main chat loop.
 /// The vector of [ToolSpec] is a comprehensive list of all tools exposed by the server.
 /// The hashmap of [ModelToolName]: [HostToolName] are mapping of tool names that have been changed
@@ -858,6 +916,22 @@ pub struct ToolManager {
     /// List of disabled MCP server names for display purposes
     disabled_servers: Vec<String>,
 
+    /// Resources and resource templates offered by each server, keyed by server name.
+    pub resources: Arc<Mutex<HashMap<String, ServerResources>>>,
+
+    /// Resources that servers reported as changed since they were last read, as pairs of server
+    /// name and uri. See [Self::take_updated_resources].
+    updated_resources: Arc<Mutex<HashSet<(String, String)>>>,
+
+    /// Requests from servers that the chat session answers while tools run.
+    interactive_requests: Option<Arc<Mutex<tokio::sync::mpsc::Receiver<InteractiveRequest>>>>,
+
+    /// Progress reported by servers on running tools, as pairs of server name and progress.
+    progress: Option<Arc<Mutex<tokio::sync::mpsc::Receiver<ServerProgress>>>>,
+
+    /// The directories servers may operate in, see [Self::update_roots].
+    roots: Arc<SyncRwLock<Vec<Root>>>,
+
     /// A collection of preferences that pertains to the conversation.
     /// As far as tool manager goes, this is relevant for tool and server filters
     pub agent: Arc<Mutex<Agent>>,
@@ -876,6 +950,11 @@ impl Clone for ToolManager {
             is_interactive: self.is_interactive,
             mcp_load_record: self.mcp_load_record.clone(),
             disabled_servers: self.disabled_servers.clone(),
+            resources: self.resources.clone(),
+            updated_resources: self.updated_resources.clone(),
+            interactive_requests: self.interactive_requests.clone(),
+            progress: self.progress.clone(),
+            roots: self.roots.clone(),
             ..Default::default()
         }
     }
@@ -932,6 +1011,10 @@ impl ToolManager {
                     "summary": {
                         "type": "string",
                         "description": "A brief explanation of what the command does"
+                    },
+                    "background": {
+                        "type": "boolean",
+                        "description": "Start the command and return a process id immediately instead of waiting for it to exit. Use for long running commands such as servers and watchers, then use the background_process tool to manage them."
                     }
                     },
                         "required": ["command"]})),
@@ -1062,10 +1145,14 @@ impl ToolManager {
             "execute_bash" => {
                 Tool::ExecuteCommand(serde_json::from_value::<ExecuteCommand>(value.args).map_err(map_err)?)
             },
+            "background_process" => {
+                Tool::BackgroundProcess(serde_json::from_value::<BackgroundProcess>(value.args).map_err(map_err)?)
+            },
             "use_aws" => Tool::UseAws(serde_json::from_value::<UseAws>(value.args).map_err(map_err)?),
             "report_issue" => Tool::GhIssue(serde_json::from_value::<GhIssue>(value.args).map_err(map_err)?),
             "thinking" => Tool::Thinking(serde_json::from_value::<Thinking>(value.args).map_err(map_err)?),
             "knowledge" => Tool::Knowledge(serde_json::from_value::<Knowledge>(value.args).map_err(map_err)?),
+            "use_subagent" => Tool::UseSubagent(serde_json::from_value::<UseSubagent>(value.args).map_err(map_err)?),
             // Note that this name is namespaced with server_name{DELIMITER}tool_name
             name => {
                 // Note: tn_map also has tools that underwent no transformation. In otherwords, if
@@ -1353,6 +1440,61 @@ impl ToolManager {
     pub async fn pending_clients(&self) -> Vec<String> {
         self.pending_clients.read().await.iter().cloned().collect::<Vec<_>>()
     }
+
+    /// Waits for the next request from a server that the chat session needs to answer.
+    pub async fn next_interactive_request(&self) -> Option<InteractiveRequest> {
+        match &self.interactive_requests {
+            Some(requests) => requests.lock().await.recv().await,
+            None => std::future::pending().await,
+        }
+    }
+
+    /// Waits for the next progress reported by a server, returned with the name of the server.
+    pub async fn next_progress(&self) -> Option<ServerProgress> {
+        match &self.progress {
+            Some(progress) => progress.lock().await.recv().await,
+            None => std::future::pending().await,
+        }
+    }
+
+    /// Recomputes the roots shared with servers, e.g. after the working directory or the agent
+    /// changed, and lets the servers know if they differ from the previous ones.
+    pub async fn update_roots(&self, os: &Os, agent: Option<&Agent>) {
+        let roots = mcp_roots(os, agent);
+        {
+            let Ok(mut current) = self.roots.write() else {
+                error!("Failed to update mcp roots: lock poisoned");
+                return;
+            };
+            if *current == roots {
+                return;
+            }
+            *current = roots;
+        }
+        for (server_name, client) in &self.clients {
+            if let Err(e) = client.notify("roots/list_changed", None).await {
+                warn!("Failed to notify {server_name} of changed roots: {e}");
+            }
+        }
+    }
+
+    /// Returns the resources reported as changed since the last call, as pairs of server name
+    /// and uri.
+    pub async fn take_updated_resources(&self) -> HashSet<(String, String)> {
+        std::mem::take(&mut *self.updated_resources.lock().await)
+    }
+}
+
+/// The roots shared with servers, see [Agent::workspace_roots].
+fn mcp_roots(os: &Os, agent: Option<&Agent>) -> Vec<Root> {
+    let Ok(cwd) = os.env.current_dir() else {
+        return Vec::new();
+    };
+    let paths = match agent {
+        Some(agent) => agent.workspace_roots(&cwd, os.env.home().as_deref()),
+        None => vec![cwd],
+    };
+    paths.iter().map(|path| Root::from_path(path)).collect()
 }
 
 #[allow(clippy::too_many_arguments)]
@@ -0,0 +1,246 @@
+use std::io::Write;
+use std::time::Duration;
+
+use crossterm::queue;
+use crossterm::style::{
+    self,
+    Color,
+};
+use eyre::{
+    Result,
+    bail,
+};
+use serde::Deserialize;
+
+use super::execute::{
+    BackgroundProcess as Process,
+    BackgroundProcesses,
+};
+use super::{
+    InvokeOutput,
+    OutputKind,
+};
+use crate::cli::agent::permission::RuleEffect;
+use crate::cli::agent::{
+    Agent,
+    PermissionEvalResult,
+};
+use crate::cli::chat::sanitize_unicode_tags;
+use crate::os::Os;
+
+/// Maximum number of seconds a read waits for new output.
+const MAX_WAIT_SECS: u64 = 30;
+
+/// Manages the processes started by `execute_bash` with `background` set.
+#[derive(Debug, Clone, Deserialize)]
+pub struct BackgroundProcess {
+    #[serde(flatten)]
+    pub operation: ProcessOperation,
+    /// Set from the chat session.
+    #[serde(skip)]
+    pub processes: Option<BackgroundProcesses>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "operation", rename_all = "snake_case")]
+pub enum ProcessOperation {
+    /// List every background process.
+    List,
+    /// Read the output written since the previous read.
+    Read {
+        process_id: usize,
+        /// Wait up to this many seconds for output if there is none yet.
+        #[serde(default)]
+        wait_secs: u64,
+    },
+    /// Write to the stdin of a process.
+    Write {
+        process_id: usize,
+        input: String,
+    },
+    Status {
+        process_id: usize,
+    },
+    Kill {
+        process_id: usize,
+    },
+}
+
+impl BackgroundProcess {
+    pub async fn invoke(&self, _updates: impl Write) -> Result<InvokeOutput> {
+        let processes = self.processes()?;
+        let result = match &self.operation {
+            ProcessOperation::List => serde_json::Value::Array(processes.list().iter().map(|p| status(p)).collect()),
+            ProcessOperation::Read { process_id, wait_secs } => {
+                let process = get(processes, *process_id)?;
+                let output = process
+                    .read_output(Duration::from_secs((*wait_secs).min(MAX_WAIT_SECS)))
+                    .await;
+                let mut result = status(&process);
+                result["output"] = sanitize_unicode_tags(&output.output).into();
+                if output.truncated {
+                    result["truncated"] = true.into();
+                }
+                result
+            },
+            ProcessOperation::Write { process_id, input } => {
+                let process = get(processes, *process_id)?;
+                process.write_input(input).await?;
+                status(&process)
+            },
+            ProcessOperation::Status { process_id } => {
+                let process = get(processes, *process_id)?;
+                status(&process)
+            },
+            ProcessOperation::Kill { process_id } => {
+                let process = get(processes, *process_id)?;
+                process.kill().await?;
+                status(&process)
+            },
+        };
+
+        Ok(InvokeOutput {
+            output: OutputKind::Json(result),
+        })
+    }
+
+    pub fn queue_description(&self, output: &mut impl Write) -> Result<()> {
+        let (action, process_id) = match &self.operation {
+            ProcessOperation::List => {
+                queue!(output, style::Print("Listing background processes\n"))?;
+                return Ok(());
+            },
+            ProcessOperation::Read { process_id, .. } => ("Reading the output of", process_id),
+            ProcessOperation::Write { process_id, .. } => ("Writing to the input of", process_id),
+            ProcessOperation::Status { process_id } => ("Checking the status of", process_id),
+            ProcessOperation::Kill { process_id } => ("Killing", process_id),
+        };
+        let command = self
+            .processes
+            .as_ref()
+            .and_then(|processes| processes.get(*process_id))
+            .map(|process| format!(": {}", process.command))
+            .unwrap_or_default();
+        queue!(
+            output,
+            style::Print(format!("{action} background process ")),
+            style::SetForegroundColor(Color::Green),
+            style::Print(format!("{process_id}{command}\n")),
+            style::ResetColor,
+        )?;
+        if let ProcessOperation::Write { input, .. } = &self.operation {
+            queue!(
+                output,
+                style::SetForegroundColor(Color::DarkGrey),
+                style::Print(format!("{}\n", input.trim_end())),
+                style::ResetColor,
+            )?;
+        }
+        Ok(())
+    }
+
+    pub async fn validate(&mut self, _os: &Os) -> Result<()> {
+        let processes = self.processes()?;
+        match &self.operation {
+            ProcessOperation::List => Ok(()),
+            ProcessOperation::Read { process_id, .. }
+            | ProcessOperation::Write { process_id, .. }
+            | ProcessOperation::Status { process_id }
+            | ProcessOperation::Kill { process_id } => get(processes, *process_id).map(|_| ()),
+        }
+    }
+
+    /// Whether the operation matches `pattern` of a permission rule like
+    /// `background_process(read)`.
+    pub fn operation_name(&self) -> &'static str {
+        match self.operation {
+            ProcessOperation::List => "list",
+            ProcessOperation::Read { .. } => "read",
+            ProcessOperation::Write { .. } => "write",
+            ProcessOperation::Status { .. } => "status",
+            ProcessOperation::Kill { .. } => "kill",
+        }
+    }
+
+    pub fn matches_permission_pattern(&self, pattern: &str, effect: RuleEffect) -> bool {
+        effect.matches_glob(pattern, [self.operation_name()])
+    }
+
+    /// Writing to a process may run arbitrary commands, e.g. in an interactive shell, and so
+    /// requires approval. Other operations only affect processes started in this session.
+    pub fn eval_perm(&self, agent: &Agent) -> PermissionEvalResult {
+        if agent.allowed_tools.contains("background_process") {
+            return PermissionEvalResult::Allow;
+        }
+        match self.operation {
+            ProcessOperation::Write { .. } => PermissionEvalResult::Ask,
+            _ => PermissionEvalResult::Allow,
+        }
+    }
+
+    fn processes(&self) -> Result<&BackgroundProcesses> {
+        match &self.processes {
+            Some(processes) => Ok(processes),
+            None => bail!("Background processes are not supported in this session"),
+        }
+    }
+}
+
+fn get(processes: &BackgroundProcesses, process_id: usize) -> Result<std::sync::Arc<Process>> {
+    match processes.get(process_id) {
+        Some(process) => Ok(process),
+        None => bail!("There is no background process with id {process_id}"),
+    }
+}
+
+fn status(process: &Process) -> serde_json::Value {
+    serde_json::json!({
+        "process_id": process.id,
+        "command": process.command,
+        "status": process.status().to_string(),
+        "running_secs": process.started.elapsed().as_secs(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_background_process_deserialize() {
+        let tool = serde_json::from_value::<BackgroundProcess>(serde_json::json!({
+            "operation": "read",
+            "process_id": 2,
+        }))
+        .unwrap();
+        assert!(matches!(tool.operation, ProcessOperation::Read {
+            process_id: 2,
+            wait_secs: 0
+        }));
+
+        let tool = serde_json::from_value::<BackgroundProcess>(serde_json::json!({"operation": "list"})).unwrap();
+        assert!(matches!(tool.operation, ProcessOperation::List));
+        assert!(serde_json::from_value::<BackgroundProcess>(serde_json::json!({"operation": "write"})).is_err());
+    }
+
+    #[test]
+    fn test_background_process_eval_perm() {
+        let agent = Agent::default();
+        let tool = |operation| BackgroundProcess {
+            operation,
+            processes: None,
+        };
+        assert!(matches!(
+            tool(ProcessOperation::Kill { process_id: 1 }).eval_perm(&agent),
+            PermissionEvalResult::Allow
+        ));
+        assert!(matches!(
+            tool(ProcessOperation::Write {
+                process_id: 1,
+                input: "y\n".to_string()
+            })
+            .eval_perm(&agent),
+            PermissionEvalResult::Ask
+        ));
+    }
+}
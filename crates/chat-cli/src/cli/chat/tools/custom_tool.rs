@@ -18,6 +18,7 @@ use tokio::sync::RwLock;
 use tracing::warn;
 
 use super::InvokeOutput;
+use crate::cli::agent::permission::RuleEffect;
 use crate::cli::agent::{
     Agent,
     PermissionEvalResult,
@@ -27,22 +28,33 @@ use crate::cli::chat::token_counter::TokenCounter;
 use crate::mcp_client::{
     Client as McpClient,
     ClientConfig as McpClientConfig,
+    HttpClientConfig as McpHttpClientConfig,
+    HttpTransport,
     JsonRpcResponse,
     JsonRpcStdioTransport,
     MessageContent,
     Messenger,
     PromptGet,
+    ResourceReadResult,
     ServerCapabilities,
     StdioTransport,
     ToolCallResult,
 };
 use crate::os::Os;
 
-// TODO: support http transport type
 #[derive(Clone, Serialize, Deserialize, Debug, Eq, PartialEq, JsonSchema)]
 pub struct CustomToolConfig {
     /// The command string used to initialize the mcp server
+    #[serde(default, skip_serializing_if = "String::is_empty")]
     pub command: String,
+    /// The url of a remote mcp server, used instead of the command. Servers are reached with
+    /// the Streamable HTTP transport, falling back to the legacy HTTP+SSE transport
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
+    /// HTTP headers sent with every request to the url. Values may reference environment
+    /// variables with ${env:VAR_NAME}
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub headers: Option<HashMap<String, String>>,
     /// A list of arguments to be used to run the command with
     #[serde(default)]
     pub args: Vec<String>,
@@ -55,11 +67,22 @@ pub struct CustomToolConfig {
     /// A boolean flag to denote whether or not to load this mcp server
     #[serde(default)]
     pub disabled: bool,
+    /// A boolean flag to allow the server to request completions from the model (sampling)
+    /// without asking for approval
+    #[serde(default, rename = "allowSampling")]
+    pub allow_sampling: bool,
     /// A flag to denote whether this is a server from the legacy mcp.json
     #[serde(skip)]
     pub is_from_legacy_mcp_json: bool,
 }
 
+impl CustomToolConfig {
+    /// A description of how the server is reached, i.e. its url or command.
+    pub fn target(&self) -> &str {
+        self.url.as_deref().unwrap_or(&self.command)
+    }
+}
+
 pub fn default_timeout() -> u64 {
     120 * 1000
 }
@@ -84,6 +107,17 @@ fn process_env_vars(env_vars: &mut HashMap<String, String>, env: &crate::os::Env
     }
 }
 
+/// Evaluates `$body` with `$client` bound to the [McpClient] of a [CustomToolClient], whatever
+/// its transport.
+macro_rules! with_client {
+    ($self:expr, $client:ident => $body:expr) => {
+        match $self {
+            CustomToolClient::Stdio { client: $client, .. } => $body,
+            CustomToolClient::Http { client: $client, .. } => $body,
+        }
+    };
+}
+
 #[derive(Debug)]
 pub enum CustomToolClient {
     Stdio {
@@ -92,13 +126,20 @@ pub enum CustomToolClient {
         client: McpClient<StdioTransport>,
         server_capabilities: RwLock<Option<ServerCapabilities>>,
     },
+    Http {
+        /// This is the server name as recognized by the model (post sanitized)
+        server_name: String,
+        client: McpClient<HttpTransport>,
+        server_capabilities: RwLock<Option<ServerCapabilities>>,
+    },
 }
 
 impl CustomToolClient {
-    // TODO: add support for http transport
     pub fn from_config(server_name: String, config: CustomToolConfig, os: &crate::os::Os) -> Result<Self> {
         let CustomToolConfig {
             command,
+            url,
+            headers,
             args,
             env,
             timeout,
@@ -106,6 +147,33 @@ impl CustomToolClient {
             ..
         } = config;
 
+        if let Some(url) = url {
+            if !command.is_empty() {
+                eyre::bail!("MCP server {server_name} must not configure both a command and a url");
+            }
+            let mut headers = headers.unwrap_or_default();
+            process_env_vars(&mut headers, &os.env);
+            let mcp_client_config = McpHttpClientConfig {
+                server_name: server_name.clone(),
+                url: substitute_env_vars(&url, &os.env),
+                headers,
+                timeout,
+                client_info: serde_json::json!({
+                   "name": "Q CLI Chat",
+                   "version": "1.0.0"
+                }),
+            };
+            let client = McpClient::<HttpTransport>::from_config(mcp_client_config)?;
+            return Ok(CustomToolClient::Http {
+                server_name,
+                client,
+                server_capabilities: RwLock::new(None),
+            });
+        }
+        if command.is_empty() {
+            eyre::bail!("MCP server {server_name} must configure either a command or a url");
+        }
+
         // Process environment variables if present
         let processed_env = env.map(|mut env_vars| {
             process_env_vars(&mut env_vars, &os.env);
@@ -132,69 +200,93 @@ impl CustomToolClient {
     }
 
     pub async fn init(&self) -> Result<()> {
-        match self {
-            CustomToolClient::Stdio {
-                client,
-                server_capabilities,
-                ..
-            } => {
-                if let Some(messenger) = &client.messenger {
-                    let _ = messenger.send_init_msg().await;
-                }
-                // We'll need to first initialize. This is the handshake every client and server
-                // needs to do before proceeding to anything else
-                let cap = client.init().await?;
-                // We'll be scrapping this for background server load: https://github.com/aws/amazon-q-developer-cli/issues/1466
-                // So don't worry about the tidiness for now
-                server_capabilities.write().await.replace(cap);
-                Ok(())
-            },
+        let (CustomToolClient::Stdio {
+            server_capabilities, ..
         }
+        | CustomToolClient::Http {
+            server_capabilities, ..
+        }) = self;
+        with_client!(self, client => {
+            if let Some(messenger) = &client.messenger {
+                let _ = messenger.send_init_msg().await;
+            }
+            // We'll need to first initialize. This is the handshake every client and server
+            // needs to do before proceeding to anything else
+            let cap = client.init().await?;
+            // We'll be scrapping this for background server load: https://github.com/aws/amazon-q-developer-cli/issues/1466
+            // So don't worry about the tidiness for now
+            server_capabilities.write().await.replace(cap);
+            Ok(())
+        })
     }
 
     pub fn assign_messenger(&mut self, messenger: Box<dyn Messenger>) {
-        match self {
-            CustomToolClient::Stdio { client, .. } => {
-                client.messenger = Some(messenger);
-            },
-        }
+        with_client!(self, client => client.messenger = Some(messenger));
     }
 
     pub fn get_server_name(&self) -> &str {
         match self {
-            CustomToolClient::Stdio { server_name, .. } => server_name.as_str(),
+            CustomToolClient::Stdio { server_name, .. } | CustomToolClient::Http { server_name, .. } => {
+                server_name.as_str()
+            },
         }
     }
 
     pub async fn request(&self, method: &str, params: Option<serde_json::Value>) -> Result<JsonRpcResponse> {
-        match self {
-            CustomToolClient::Stdio { client, .. } => Ok(client.request(method, params).await?),
+        with_client!(self, client => Ok(client.request(method, params).await?))
+    }
+
+    pub async fn request_with_progress(
+        &self,
+        method: &str,
+        params: Option<serde_json::Value>,
+    ) -> Result<JsonRpcResponse> {
+        with_client!(self, client => Ok(client.request_with_progress(method, params).await?))
+    }
+
+    pub async fn read_resource(&self, uri: &str) -> Result<ResourceReadResult> {
+        with_client!(self, client => Ok(client.read_resource(uri).await?))
+    }
+
+    pub async fn subscribe_resource(&self, uri: &str) -> Result<()> {
+        with_client!(self, client => Ok(client.subscribe_resource(uri).await?))
+    }
+
+    pub async fn unsubscribe_resource(&self, uri: &str) -> Result<()> {
+        with_client!(self, client => Ok(client.unsubscribe_resource(uri).await?))
+    }
+
+    /// Whether the server sends updates for resources once subscribed to.
+    pub async fn supports_resource_subscriptions(&self) -> bool {
+        let (CustomToolClient::Stdio {
+            server_capabilities, ..
         }
+        | CustomToolClient::Http {
+            server_capabilities, ..
+        }) = self;
+        server_capabilities.read().await.as_ref().is_some_and(|cap| {
+            cap.resources
+                .as_ref()
+                .and_then(|r| r.get("subscribe"))
+                .and_then(|v| v.as_bool())
+                .unwrap_or(false)
+        })
     }
 
     pub fn list_prompt_gets(&self) -> Arc<std::sync::RwLock<HashMap<String, PromptGet>>> {
-        match self {
-            CustomToolClient::Stdio { client, .. } => client.prompt_gets.clone(),
-        }
+        with_client!(self, client => client.prompt_gets.clone())
     }
 
-    #[allow(dead_code)]
     pub async fn notify(&self, method: &str, params: Option<serde_json::Value>) -> Result<()> {
-        match self {
-            CustomToolClient::Stdio { client, .. } => Ok(client.notify(method, params).await?),
-        }
+        with_client!(self, client => Ok(client.notify(method, params).await?))
     }
 
     pub fn is_prompts_out_of_date(&self) -> bool {
-        match self {
-            CustomToolClient::Stdio { client, .. } => client.is_prompts_out_of_date.load(Ordering::Relaxed),
-        }
+        with_client!(self, client => client.is_prompts_out_of_date.load(Ordering::Relaxed))
     }
 
     pub fn prompts_updated(&self) {
-        match self {
-            CustomToolClient::Stdio { client, .. } => client.is_prompts_out_of_date.store(false, Ordering::Relaxed),
-        }
+        with_client!(self, client => client.is_prompts_out_of_date.store(false, Ordering::Relaxed));
     }
 }
 
@@ -217,7 +309,10 @@ pub struct CustomTool {
 impl CustomTool {
     pub async fn invoke(&self, _os: &Os, _updates: impl Write) -> Result<InvokeOutput> {
         // Assuming a response shape as per https://spec.modelcontextprotocol.io/specification/2024-11-05/server/tools/#calling-tools
-        let resp = self.client.request(self.method.as_str(), self.params.clone()).await?;
+        let resp = self
+            .client
+            .request_with_progress(self.method.as_str(), self.params.clone())
+            .await?;
         let result = match resp.result {
             Some(result) => result,
             None => {
@@ -287,6 +382,25 @@ impl CustomTool {
             + TokenCounter::count_tokens(self.params.as_ref().map_or("", |p| p.as_str().unwrap_or_default()))
     }
 
+    /// Whether the arguments match `pattern` of a permission rule like
+    /// `@github/create_issue(repo=aws/*)`, which matches the argument named `repo` against the
+    /// glob `aws/*`. Arguments that are not strings are matched in their JSON form.
+    pub fn matches_permission_pattern(&self, pattern: &str, effect: RuleEffect) -> bool {
+        let Some((name, value_pattern)) = pattern.split_once('=') else {
+            return false;
+        };
+        let Some(value) = self
+            .params
+            .as_ref()
+            .and_then(|params| params.get("arguments"))
+            .and_then(|args| args.get(name.trim()))
+        else {
+            return false;
+        };
+        let value = value.as_str().map_or_else(|| value.to_string(), str::to_string);
+        effect.matches_glob(value_pattern.trim(), [value.as_str()])
+    }
+
     pub fn eval_perm(&self, agent: &Agent) -> PermissionEvalResult {
         use crate::util::MCP_SERVER_TOOL_DELIMITER;
         let Self {
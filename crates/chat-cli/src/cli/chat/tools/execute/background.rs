@@ -0,0 +1,431 @@
+use std::collections::BTreeMap;
+use std::sync::{
+    Arc,
+    Mutex,
+    MutexGuard,
+};
+use std::time::{
+    Duration,
+    Instant,
+};
+
+use eyre::{
+    Result,
+    bail,
+};
+use tokio::io::{
+    AsyncRead,
+    AsyncReadExt,
+    AsyncWriteExt,
+};
+use tokio::process::{
+    Child,
+    ChildStdin,
+};
+use tracing::error;
+
+use super::{
+    SandboxSettings,
+    kill_process_group,
+    spawn_background_command,
+    terminate_process_group,
+};
+
+/// Maximum number of live background processes per session.
+const MAX_RUNNING_PROCESSES: usize = 10;
+/// Maximum number of bytes of output kept for each background process. Older output is dropped
+/// once the limit is reached.
+const MAX_OUTPUT_BUFFER_SIZE: usize = 256 * 1024;
+/// How long a process is given to exit after being asked to terminate before it is killed.
+const TERMINATE_GRACE_PERIOD: Duration = Duration::from_secs(2);
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// The processes started by `execute_bash` in background mode during a chat session.
+///
+/// Cloning is cheap, all clones refer to the same processes. Every process that is still running
+/// is killed once the last clone is dropped. Processes that have exited are forgotten once all of
+/// their output has been read.
+#[derive(Debug, Clone, Default)]
+pub struct BackgroundProcesses {
+    inner: Arc<Mutex<Registry>>,
+}
+
+#[derive(Debug, Default)]
+struct Registry {
+    next_id: usize,
+    processes: BTreeMap<usize, Arc<BackgroundProcess>>,
+}
+
+impl Drop for Registry {
+    fn drop(&mut self) {
+        for process in self.processes.values() {
+            process.kill_now();
+        }
+    }
+}
+
+/// A process started in the background, see [BackgroundProcesses::spawn].
+#[derive(Debug)]
+pub struct BackgroundProcess {
+    pub id: usize,
+    pub command: String,
+    pub pid: Option<u32>,
+    pub started: Instant,
+    child: Mutex<Child>,
+    stdin: tokio::sync::Mutex<Option<ChildStdin>>,
+    output: Arc<Mutex<OutputBuffer>>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProcessStatus {
+    Running,
+    /// The process exited with the given exit code, [None] if it was killed by a signal.
+    Exited(Option<i32>),
+}
+
+impl std::fmt::Display for ProcessStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Running => write!(f, "running"),
+            Self::Exited(Some(code)) => write!(f, "exited ({code})"),
+            Self::Exited(None) => write!(f, "killed"),
+        }
+    }
+}
+
+/// Output read with [BackgroundProcess::read_output].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProcessOutput {
+    /// Combined stdout and stderr written since the previous read.
+    pub output: String,
+    /// Whether some output was dropped because it was not read before the buffer filled up.
+    pub truncated: bool,
+}
+
+/// The combined output of a process, keeping track of how much of it has been read.
+#[derive(Debug, Default)]
+struct OutputBuffer {
+    data: String,
+    /// Number of bytes dropped from the front of `data`.
+    dropped: usize,
+    /// Number of bytes read so far, including dropped bytes.
+    read: usize,
+    /// Number of stdout and stderr streams that are still being collected.
+    open_streams: usize,
+}
+
+impl OutputBuffer {
+    fn push(&mut self, output: &str) {
+        self.data.push_str(output);
+        if self.data.len() > MAX_OUTPUT_BUFFER_SIZE {
+            let mut start = self.data.len() - MAX_OUTPUT_BUFFER_SIZE;
+            while !self.data.is_char_boundary(start) {
+                start += 1;
+            }
+            self.data.drain(..start);
+            self.dropped += start;
+        }
+    }
+
+    fn has_unread(&self) -> bool {
+        self.read < self.dropped + self.data.len()
+    }
+
+    fn take_unread(&mut self) -> ProcessOutput {
+        let truncated = self.read < self.dropped;
+        let start = self.read.saturating_sub(self.dropped);
+        let output = self.data[start..].to_string();
+        self.read = self.dropped + self.data.len();
+        ProcessOutput { output, truncated }
+    }
+}
+
+impl BackgroundProcesses {
+    /// Starts `command` in the background, inside `sandbox` if given.
+    pub fn spawn(&self, command: &str, sandbox: Option<&SandboxSettings>) -> Result<Arc<BackgroundProcess>> {
+        let running = self
+            .list()
+            .iter()
+            .filter(|p| p.status() == ProcessStatus::Running)
+            .count();
+        if running >= MAX_RUNNING_PROCESSES {
+            bail!("There are already {running} background processes running, kill one of them before starting another");
+        }
+
+        let mut child = spawn_background_command(command, sandbox)?;
+        let output = Arc::new(Mutex::new(OutputBuffer::default()));
+        if let Some(stdout) = child.stdout.take() {
+            output.lock().expect("background process output poisoned").open_streams += 1;
+            tokio::spawn(collect_output(stdout, Arc::clone(&output)));
+        }
+        if let Some(stderr) = child.stderr.take() {
+            output.lock().expect("background process output poisoned").open_streams += 1;
+            tokio::spawn(collect_output(stderr, Arc::clone(&output)));
+        }
+
+        let mut registry = self.registry();
+        registry.next_id += 1;
+        let process = Arc::new(BackgroundProcess {
+            id: registry.next_id,
+            command: command.to_string(),
+            pid: child.id(),
+            started: Instant::now(),
+            stdin: tokio::sync::Mutex::new(child.stdin.take()),
+            child: Mutex::new(child),
+            output,
+        });
+        registry.processes.insert(process.id, Arc::clone(&process));
+        Ok(process)
+    }
+
+    pub fn get(&self, id: usize) -> Option<Arc<BackgroundProcess>> {
+        self.registry().processes.get(&id).cloned()
+    }
+
+    /// Returns every process started in this session, including those that have exited but still
+    /// have unread output.
+    pub fn list(&self) -> Vec<Arc<BackgroundProcess>> {
+        self.registry().processes.values().cloned().collect()
+    }
+
+    /// Kills every process that is still running.
+    pub async fn kill_all(&self) {
+        for process in self.list() {
+            if let Err(err) = process.kill().await {
+                error!(?err, id = process.id, "failed to kill background process");
+            }
+        }
+    }
+
+    /// Locks the registry, dropping the processes whose final output has been read.
+    fn registry(&self) -> MutexGuard<'_, Registry> {
+        let mut registry = self.inner.lock().expect("background process registry poisoned");
+        registry.processes.retain(|_, process| !process.is_finished());
+        registry
+    }
+}
+
+impl BackgroundProcess {
+    pub fn status(&self) -> ProcessStatus {
+        let mut child = self.child.lock().expect("background process poisoned");
+        match child.try_wait() {
+            Ok(Some(status)) => ProcessStatus::Exited(status.code()),
+            Ok(None) => ProcessStatus::Running,
+            Err(err) => {
+                error!(?err, id = self.id, "failed to get the status of a background process");
+                ProcessStatus::Exited(None)
+            },
+        }
+    }
+
+    /// Returns the output written since the previous read.
+    ///
+    /// If there is no unread output yet, waits up to `wait` for the process to write something
+    /// or exit.
+    pub async fn read_output(&self, wait: Duration) -> ProcessOutput {
+        let deadline = Instant::now() + wait;
+        while Instant::now() < deadline && !self.has_unread() && !self.is_finished() {
+            tokio::time::sleep(POLL_INTERVAL).await;
+        }
+        self.output
+            .lock()
+            .expect("background process output poisoned")
+            .take_unread()
+    }
+
+    /// Writes `input` to the stdin of the process.
+    pub async fn write_input(&self, input: &str) -> Result<()> {
+        if self.status() != ProcessStatus::Running {
+            bail!("Process {} is no longer running", self.id);
+        }
+        let mut stdin = self.stdin.lock().await;
+        let Some(stdin) = stdin.as_mut() else {
+            bail!("The stdin of process {} is closed", self.id);
+        };
+        stdin.write_all(input.as_bytes()).await?;
+        stdin.flush().await?;
+        Ok(())
+    }
+
+    /// Asks the process and its children to terminate, killing them if they are still running
+    /// after a grace period. Returns the final status.
+    pub async fn kill(&self) -> Result<ProcessStatus> {
+        if self.status() != ProcessStatus::Running {
+            return Ok(self.status());
+        }
+        if let Some(pid) = self.pid {
+            terminate_process_group(pid)?;
+        }
+        let deadline = Instant::now() + TERMINATE_GRACE_PERIOD;
+        while Instant::now() < deadline {
+            if self.status() != ProcessStatus::Running {
+                return Ok(self.status());
+            }
+            tokio::time::sleep(POLL_INTERVAL).await;
+        }
+        self.kill_now();
+        Ok(self.status())
+    }
+
+    /// Kills the process and its children without waiting for them to exit.
+    fn kill_now(&self) {
+        if self.status() != ProcessStatus::Running {
+            return;
+        }
+        if let Some(pid) = self.pid {
+            if let Err(err) = kill_process_group(pid) {
+                error!(?err, id = self.id, "failed to kill background process");
+            }
+        }
+        let mut child = self.child.lock().expect("background process poisoned");
+        let _ = child.start_kill();
+    }
+
+    fn has_unread(&self) -> bool {
+        self.output
+            .lock()
+            .expect("background process output poisoned")
+            .has_unread()
+    }
+
+    /// Whether the process has exited and all of its output has been read.
+    fn is_finished(&self) -> bool {
+        if self.status() == ProcessStatus::Running {
+            return false;
+        }
+        let output = self.output.lock().expect("background process output poisoned");
+        output.open_streams == 0 && !output.has_unread()
+    }
+}
+
+async fn collect_output(mut stream: impl AsyncRead + Unpin, output: Arc<Mutex<OutputBuffer>>) {
+    let mut buf = vec![0; 8 * 1024];
+    // Bytes of a character that was split across reads.
+    let mut pending = Vec::new();
+    loop {
+        match stream.read(&mut buf).await {
+            Ok(0) => break,
+            Ok(n) => {
+                pending.extend_from_slice(&buf[..n]);
+                let text = decode_complete_chars(&mut pending);
+                output
+                    .lock()
+                    .expect("background process output poisoned")
+                    .push(&text);
+            },
+            Err(err) => {
+                error!(?err, "failed to read the output of a background process");
+                break;
+            },
+        }
+    }
+    let mut output = output.lock().expect("background process output poisoned");
+    output.push(&String::from_utf8_lossy(&pending));
+    output.open_streams -= 1;
+}
+
+/// Decodes and removes the bytes of `pending`, except for an incomplete character at the end.
+/// Invalid bytes are replaced with U+FFFD.
+fn decode_complete_chars(pending: &mut Vec<u8>) -> String {
+    let mut incomplete = 0;
+    // A character is at most 4 bytes, so its first byte is within the last 4.
+    for (i, byte) in pending.iter().rev().take(4).enumerate() {
+        if byte & 0b1100_0000 == 0b1000_0000 {
+            continue;
+        }
+        let len = match byte {
+            0b1100_0000..=0b1101_1111 => 2,
+            0b1110_0000..=0b1110_1111 => 3,
+            0b1111_0000..=0b1111_0111 => 4,
+            _ => 1,
+        };
+        if len > i + 1 {
+            incomplete = i + 1;
+        }
+        break;
+    }
+    let complete = pending.len() - incomplete;
+    let text = String::from_utf8_lossy(&pending[..complete]).into_owned();
+    pending.drain(..complete);
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_output_buffer() {
+        let mut buffer = OutputBuffer::default();
+        assert!(!buffer.has_unread());
+        buffer.push("hello ");
+        buffer.push("world");
+        assert_eq!(buffer.take_unread(), ProcessOutput {
+            output: "hello world".to_string(),
+            truncated: false,
+        });
+        assert_eq!(buffer.take_unread().output, "");
+
+        buffer.push("new");
+        assert_eq!(buffer.take_unread().output, "new");
+
+        // Unread output that no longer fits is dropped from the front.
+        buffer.push(&"a".repeat(MAX_OUTPUT_BUFFER_SIZE));
+        buffer.push("tail");
+        let output = buffer.take_unread();
+        assert!(output.truncated);
+        assert_eq!(output.output.len(), MAX_OUTPUT_BUFFER_SIZE);
+        assert!(output.output.ends_with("aatail"));
+        assert!(!buffer.has_unread());
+    }
+
+    #[test]
+    fn test_decode_complete_chars() {
+        let bytes = "héllo €".as_bytes();
+        let mut pending = bytes[..2].to_vec();
+        assert_eq!(decode_complete_chars(&mut pending), "h");
+        assert_eq!(pending, bytes[1..2]);
+        pending.extend_from_slice(&bytes[2..bytes.len() - 1]);
+        assert_eq!(decode_complete_chars(&mut pending), "éllo ");
+        pending.extend_from_slice(&bytes[bytes.len() - 1..]);
+        assert_eq!(decode_complete_chars(&mut pending), "€");
+        assert!(pending.is_empty());
+
+        let mut pending = vec![b'a', 0xff, b'b'];
+        assert_eq!(decode_complete_chars(&mut pending), "a\u{fffd}b");
+        assert!(pending.is_empty());
+    }
+
+    #[cfg(not(windows))]
+    #[tokio::test]
+    async fn test_background_process() {
+        let processes = BackgroundProcesses::default();
+        let process = processes
+            .spawn("echo started; read line; echo \"got $line\"; sleep 30", None)
+            .unwrap();
+        assert_eq!(process.id, 1);
+
+        let output = process.read_output(Duration::from_secs(5)).await;
+        assert_eq!(output.output, "started\n");
+        assert_eq!(process.status(), ProcessStatus::Running);
+
+        process.write_input("hello\n").await.unwrap();
+        let output = process.read_output(Duration::from_secs(5)).await;
+        assert_eq!(output.output, "got hello\n");
+
+        assert_eq!(process.kill().await.unwrap(), ProcessStatus::Exited(None));
+        assert!(process.write_input("ignored\n").await.is_err());
+
+        let process = processes.spawn("echo done; exit 3", None).unwrap();
+        assert_eq!(process.id, 2);
+        while process.status() == ProcessStatus::Running {
+            tokio::time::sleep(POLL_INTERVAL).await;
+        }
+        assert!(processes.get(2).is_some());
+
+        // Exited processes are dropped once their final output is read.
+        assert_eq!(process.read_output(Duration::from_secs(5)).await.output, "done\n");
+        assert!(process.read_output(Duration::from_secs(5)).await.output.is_empty());
+        assert!(processes.get(2).is_none());
+    }
+}
@@ -5,11 +5,15 @@ use crossterm::style::{
     self,
     Color,
 };
-use eyre::Result;
+use eyre::{
+    Result,
+    bail,
+};
 use regex::Regex;
 use serde::Deserialize;
 use tracing::error;
 
+use crate::cli::agent::permission::RuleEffect;
 use crate::cli::agent::{
     Agent,
     PermissionEvalResult,
@@ -23,6 +27,16 @@ use crate::cli::chat::tools::{
 use crate::cli::chat::util::truncate_safe;
 use crate::os::Os;
 
+mod background;
+mod sandbox;
+mod shell;
+pub use background::{
+    BackgroundProcess,
+    BackgroundProcesses,
+    ProcessStatus,
+};
+pub use sandbox::SandboxSettings;
+
 // Platform-specific modules
 #[cfg(windows)]
 mod windows;
@@ -43,9 +57,22 @@ pub const READONLY_COMMANDS: &[&str] = &[
 pub struct ExecuteCommand {
     pub command: String,
     pub summary: Option<String>,
+    /// Start the command and return immediately, see [BackgroundProcesses].
+    #[serde(default)]
+    pub background: bool,
+    /// Set from the active agent, see [Self::set_sandbox].
+    #[serde(skip)]
+    pub sandbox: Option<SandboxSettings>,
+    /// Set from the chat session, required to run in the background.
+    #[serde(skip)]
+    pub background_processes: Option<BackgroundProcesses>,
 }
 
 impl ExecuteCommand {
+    /// Whether the command must be approved by the user before running.
+    ///
+    /// `allowed_commands` are checked against the whole command as anchored regular expressions,
+    /// and against each simple command as argument patterns, see [matches_command_pattern].
     pub fn requires_acceptance(&self, allowed_commands: Option<&Vec<String>>, allow_read_only: bool) -> bool {
         let default_arr = vec![];
         let allowed_commands = allowed_commands.unwrap_or(&default_arr);
@@ -60,92 +87,146 @@ impl ExecuteCommand {
             return false;
         }
 
-        let Some(args) = shlex::split(&self.command) else {
+        let Ok(list) = shell::parse(&self.command) else {
             return true;
         };
-        const DANGEROUS_PATTERNS: &[&str] = &["<(", "$(", "`", ">", "&&", "||", "&", ";"];
+        list.simple_commands()
+            .into_iter()
+            .any(|command| simple_command_requires_acceptance(command, allowed_commands, allow_read_only))
+    }
 
-        if args
-            .iter()
-            .any(|arg| DANGEROUS_PATTERNS.iter().any(|p| arg.contains(p)))
-        {
-            return true;
+    /// Whether any simple command within the command matches one of `denied_commands`, see
+    /// [matches_command_pattern]. Commands that cannot be parsed are not denied, they always
+    /// require acceptance instead, see [Self::requires_acceptance].
+    pub fn is_denied(&self, denied_commands: &[String]) -> bool {
+        if denied_commands.is_empty() {
+            return false;
         }
+        shell::parse(&self.command).is_ok_and(|list| list_is_denied(&list, denied_commands, 0))
+    }
 
-        // Split commands by pipe and check each one
-        let mut current_cmd = Vec::new();
-        let mut all_commands = Vec::new();
+    /// Whether the command matches `pattern` of a permission rule, such as
+    /// `execute_bash(cargo test *)`. Deny rules match if any command run matches, like
+    /// `deniedCommands`. Allow rules match if every command run matches and none of them writes
+    /// to a file. Commands that cannot be parsed match neither, so they always require acceptance.
+    pub fn matches_permission_pattern(&self, pattern: &str, effect: RuleEffect) -> bool {
+        let patterns = [pattern.to_string()];
+        let Ok(list) = shell::parse(&self.command) else {
+            return false;
+        };
+        match effect {
+            RuleEffect::Deny => list_is_denied(&list, &patterns, 0),
+            RuleEffect::Allow => !list
+                .simple_commands()
+                .into_iter()
+                .any(|command| simple_command_requires_acceptance(command, &patterns, false)),
+        }
+    }
 
-        for arg in args {
-            if arg == "|" {
-                if !current_cmd.is_empty() {
-                    all_commands.push(current_cmd);
-                }
-                current_cmd = Vec::new();
-            } else if arg.contains("|") {
-                // if pipe appears without spacing e.g. `echo myimportantfile|args rm` it won't get
-                // parsed out, in this case - we want to verify before running
-                return true;
-            } else {
-                current_cmd.push(arg);
-            }
+    /// Suggests a permission rule pattern allowing commands like this one. Read-only commands and
+    /// the subcommands of [GENERALIZABLE_SUBCOMMANDS] are generalized, such as `cargo test *` for
+    /// `cargo test -p chat_cli`, and other commands are only allowed as they are. Only single
+    /// commands that do not write to files are suggested.
+    pub fn suggested_permission_pattern(&self) -> Option<String> {
+        let list = shell::parse(&self.command).ok()?;
+        let commands = list.simple_commands();
+        let [command] = commands.as_slice() else {
+            return None;
+        };
+        if !command.assignments.is_empty() || command.redirects.iter().any(shell::Redirect::writes_file) {
+            return None;
         }
-        if !current_cmd.is_empty() {
-            all_commands.push(current_cmd);
+        let name = command.words.first()?;
+        if WRAPPER_COMMANDS.contains(&name.text.as_str())
+            || SHELL_COMMANDS.contains(&name.text.as_str())
+            || name.text == "eval"
+        {
+            return None;
         }
 
-        // Check if each command in the pipe chain starts with a safe command
-        for cmd_args in all_commands {
-            match cmd_args.first() {
-                // Special casing for `find` so that we support most cases while safeguarding
-                // against unwanted mutations
-                Some(cmd)
-                    if cmd == "find"
-                        && cmd_args.iter().any(|arg| {
-                            arg.contains("-exec") // includes -execdir
-                                || arg.contains("-delete")
-                                || arg.contains("-ok") // includes -okdir
-                        }) =>
-                {
-                    return true;
-                },
-                Some(cmd) => {
-                    // Special casing for `grep`. -P flag for perl regexp has RCE issues, apparently
-                    // should not be supported within grep but is flagged as a possibility since this is perl
-                    // regexp.
-                    if cmd == "grep" && cmd_args.iter().any(|arg| arg.contains("-P")) {
-                        return true;
-                    }
-                    let is_cmd_read_only = READONLY_COMMANDS.contains(&cmd.as_str());
-                    if !allow_read_only || !is_cmd_read_only {
-                        return true;
-                    }
-                },
-                None => return true,
-            }
+        // Words are matched as globs, so only words without glob characters are suggested as is.
+        let is_literal = |word: &shell::Word| {
+            !word.dynamic
+                && !word.glob
+                && !word.text.is_empty()
+                && !word.text.contains(['*', '?', '[', ']', '{', '}', '\\'])
+        };
+        if !command.words.iter().all(is_literal) {
+            return None;
         }
+        let words = command.words.iter().map(|word| word.text.as_str()).collect::<Vec<_>>();
 
-        false
+        if READONLY_COMMANDS.contains(&words[0]) {
+            return Some(format!("{} *", words[0]));
+        }
+        if let [name, subcommand, ..] = words.as_slice() {
+            if GENERALIZABLE_SUBCOMMANDS.contains(&(*name, *subcommand)) {
+                return Some(format!("{name} {subcommand} *"));
+            }
+        }
+        shlex::try_join(words).ok()
     }
 
     pub async fn invoke(&self, output: &mut impl Write) -> Result<InvokeOutput> {
-        let output = run_command(&self.command, MAX_TOOL_RESPONSE_SIZE / 3, Some(output)).await?;
-        let clean_stdout = sanitize_unicode_tags(&output.stdout);
-        let clean_stderr = sanitize_unicode_tags(&output.stderr);
+        if self.background {
+            return self.invoke_background();
+        }
 
-        let result = serde_json::json!({
-            "exit_status": output.exit_status.unwrap_or(0).to_string(),
-            "stdout": clean_stdout,
-            "stderr": clean_stderr,
-        });
+        if let Some(sandbox) = &self.sandbox {
+            let output =
+                run_sandboxed_command(&self.command, sandbox, MAX_TOOL_RESPONSE_SIZE / 3, Some(output)).await?;
+            let mut result = Self::format_result(&output);
+            result["sandbox_violations"] = sandbox.violations(&output).into();
+            return Ok(InvokeOutput {
+                output: OutputKind::Json(result),
+            });
+        }
+
+        let output = run_command(&self.command, MAX_TOOL_RESPONSE_SIZE / 3, Some(output)).await?;
+        let result = Self::format_result(&output);
 
         Ok(InvokeOutput {
             output: OutputKind::Json(result),
         })
     }
 
+    fn invoke_background(&self) -> Result<InvokeOutput> {
+        let Some(processes) = &self.background_processes else {
+            bail!("Background commands are not supported in this session");
+        };
+        let process = processes.spawn(&self.command, self.sandbox.as_ref())?;
+        Ok(InvokeOutput {
+            output: OutputKind::Json(serde_json::json!({
+                "process_id": process.id,
+                "pid": process.pid,
+                "status": ProcessStatus::Running.to_string(),
+                "message": "The command is running in the background. Use the background_process tool to read its output, write to its stdin, check its status, or kill it.",
+            })),
+        })
+    }
+
+    fn format_result(output: &CommandResult) -> serde_json::Value {
+        serde_json::json!({
+            "exit_status": output.exit_status.unwrap_or(0).to_string(),
+            "stdout": sanitize_unicode_tags(&output.stdout),
+            "stderr": sanitize_unicode_tags(&output.stderr),
+        })
+    }
+
+    /// Enables the sandbox if the agent's `toolsSettings` configure one.
+    pub fn set_sandbox(&mut self, agent: &Agent) {
+        self.sandbox = sandbox_settings(agent).filter(|sandbox| sandbox.enabled);
+    }
+
     pub fn queue_description(&self, output: &mut impl Write) -> Result<()> {
-        queue!(output, style::Print("I will run the following shell command: "),)?;
+        if self.background {
+            queue!(
+                output,
+                style::Print("I will run the following shell command in the background: "),
+            )?;
+        } else {
+            queue!(output, style::Print("I will run the following shell command: "),)?;
+        }
 
         // TODO: Could use graphemes for a better heuristic
         if self.command.len() > 20 {
@@ -160,6 +241,15 @@ impl ExecuteCommand {
             style::ResetColor
         )?;
 
+        if self.sandbox.is_some() {
+            queue!(
+                output,
+                style::SetForegroundColor(Color::DarkGrey),
+                style::Print("(sandboxed)\n"),
+                style::ResetColor
+            )?;
+        }
+
         // Add the summary if available
         if let Some(ref summary) = self.summary {
             super::display_purpose(Some(summary), output)?;
@@ -172,6 +262,11 @@ impl ExecuteCommand {
 
     pub async fn validate(&mut self, _os: &Os) -> Result<()> {
         // TODO: probably some small amount of PATH checking
+        if self.sandbox.is_some() && !SandboxSettings::is_available() {
+            bail!(
+                "The sandbox is enabled for this agent but is unavailable, it requires Linux and bubblewrap (bwrap) to be installed"
+            );
+        }
         Ok(())
     }
 
@@ -191,7 +286,6 @@ impl ExecuteCommand {
             true
         }
 
-        let Self { command, .. } = self;
         let tool_name = if cfg!(windows) { "execute_cmd" } else { "execute_bash" };
         let is_in_allowlist = agent.allowed_tools.contains("execute_bash");
         match agent.tools_settings.get(tool_name) {
@@ -208,10 +302,18 @@ impl ExecuteCommand {
                     },
                 };
 
-                if denied_commands.iter().any(|dc| command.contains(dc)) {
+                if self.is_denied(&denied_commands) {
                     return PermissionEvalResult::Deny;
                 }
 
+                // Only commands that are understood are auto-allowed, and only when they will run in
+                // the sandbox set by [Self::set_sandbox], which is also what [Self::invoke] uses.
+                if let Some(sandbox) = &self.sandbox {
+                    if sandbox.auto_allow && SandboxSettings::is_available() && shell::parse(&self.command).is_ok() {
+                        return PermissionEvalResult::Allow;
+                    }
+                }
+
                 if self.requires_acceptance(Some(&allowed_commands), allow_read_only) {
                     PermissionEvalResult::Ask
                 } else {
@@ -230,6 +332,180 @@ impl ExecuteCommand {
     }
 }
 
+/// Commands that run their arguments as another command, e.g. `sudo rm -rf /`.
+const WRAPPER_COMMANDS: &[&str] = &[
+    "builtin", "command", "doas", "env", "exec", "flock", "ionice", "nice", "nohup", "setsid", "stdbuf", "strace",
+    "sudo", "time", "timeout", "watch", "xargs",
+];
+
+/// Subcommands that only read, build or test, whose arguments are generalized in suggested
+/// permission rules.
+const GENERALIZABLE_SUBCOMMANDS: &[(&str, &str)] = &[
+    ("cargo", "build"),
+    ("cargo", "check"),
+    ("cargo", "clippy"),
+    ("cargo", "test"),
+    ("git", "diff"),
+    ("git", "log"),
+    ("git", "show"),
+    ("git", "status"),
+    ("go", "build"),
+    ("go", "test"),
+    ("go", "vet"),
+    ("npm", "test"),
+];
+
+/// Shells whose `-c` argument is itself a command.
+const SHELL_COMMANDS: &[&str] = &["bash", "dash", "ksh", "sh", "zsh"];
+
+/// Arguments that make `find` run commands or write files.
+const FIND_UNSAFE_ARGS: &[&str] = &["-exec", "-delete", "-ok", "-fprint", "-fls"];
+
+/// Limits how deeply `sh -c` and `eval` arguments are parsed when checking denied commands.
+const MAX_NESTED_SHELL_DEPTH: usize = 4;
+
+fn simple_command_requires_acceptance(
+    command: &shell::SimpleCommand,
+    allowed_commands: &[String],
+    allow_read_only: bool,
+) -> bool {
+    if command.redirects.iter().any(shell::Redirect::writes_file) {
+        return true;
+    }
+    // Assignments and redirections alone only affect the shell itself.
+    let Some(name) = command.words.first() else {
+        return false;
+    };
+    // Assignments like `PAGER=...` or `LD_PRELOAD=...` can change what the command does.
+    if !command.assignments.is_empty() || name.dynamic {
+        return true;
+    }
+    if allowed_commands
+        .iter()
+        .any(|pattern| matches_command_pattern(pattern, &command.words, false))
+    {
+        return false;
+    }
+    if !allow_read_only || !READONLY_COMMANDS.contains(&name.text.as_str()) {
+        return true;
+    }
+
+    let args = &command.words[1..];
+    match name.text.as_str() {
+        // Globs and expansions could expand to one of the unsafe arguments.
+        "find" => args.iter().any(|arg| {
+            arg.glob || arg.dynamic || FIND_UNSAFE_ARGS.iter().any(|unsafe_arg| arg.text.contains(unsafe_arg))
+        }),
+        // The -P flag for perl regexp has RCE issues, apparently should not be supported within
+        // grep but is flagged as a possibility since this is perl regexp.
+        "grep" => args.iter().any(|arg| {
+            let is_short_flags = arg.text.starts_with('-') && !arg.text.starts_with("--");
+            arg.dynamic || (is_short_flags && arg.text.contains('P')) || arg.text == "--perl-regexp"
+        }),
+        _ => false,
+    }
+}
+
+fn list_is_denied(list: &shell::List, denied_commands: &[String], depth: usize) -> bool {
+    list.simple_commands().into_iter().any(|command| {
+        let words = &command.words;
+        // Commands run through a wrapper like `sudo` are checked at every possible position.
+        let starts = match words.first() {
+            Some(name) if WRAPPER_COMMANDS.contains(&basename(&name.text)) => 0..words.len(),
+            Some(_) => 0..1,
+            None => 0..0,
+        };
+        starts.into_iter().any(|start| {
+            let words = &words[start..];
+            if denied_commands
+                .iter()
+                .any(|pattern| matches_command_pattern(pattern, words, true))
+            {
+                return true;
+            }
+            match nested_shell_command(words) {
+                Some(_) if depth >= MAX_NESTED_SHELL_DEPTH => true,
+                Some(nested) => match shell::parse(&nested) {
+                    Ok(list) => list_is_denied(&list, denied_commands, depth + 1),
+                    Err(_) => denied_commands.iter().any(|dc| nested.contains(dc)),
+                },
+                None => false,
+            }
+        })
+    })
+}
+
+/// Returns the command run by `sh -c <command>` or `eval <args>`.
+fn nested_shell_command(words: &[shell::Word]) -> Option<String> {
+    let name = basename(&words.first()?.text);
+    if name == "eval" {
+        return Some(words[1..].iter().map(|w| w.text.as_str()).collect::<Vec<_>>().join(" "));
+    }
+    if !SHELL_COMMANDS.contains(&name) {
+        return None;
+    }
+    let flag = words[1..]
+        .iter()
+        .position(|w| w.text.starts_with('-') && !w.text.starts_with("--") && w.text.contains('c'))?;
+    words.get(flag + 2).map(|w| w.text.clone())
+}
+
+fn basename(path: &str) -> &str {
+    path.rsplit('/').next().unwrap_or(path)
+}
+
+/// Matches a simple command against an argument pattern such as `git log *`.
+///
+/// The pattern is split like a shell command and each token is matched against the word at the
+/// same position as a glob. A trailing `*` token matches any remaining arguments, including
+/// none. Words whose value is only known at runtime only match a `*` token.
+///
+/// With `prefix`, the pattern also matches commands with additional arguments, and the command
+/// name is also matched by its basename, e.g. `rm` matches `/bin/rm -rf`.
+fn matches_command_pattern(pattern: &str, words: &[shell::Word], prefix: bool) -> bool {
+    let Some(tokens) = shlex::split(pattern) else {
+        return false;
+    };
+    if tokens.is_empty() || words.is_empty() {
+        return false;
+    }
+
+    for (i, token) in tokens.iter().enumerate() {
+        if token == "*" && i == tokens.len() - 1 {
+            return true;
+        }
+        let Some(word) = words.get(i) else {
+            return false;
+        };
+        if token == "*" {
+            continue;
+        }
+        if word.dynamic {
+            return false;
+        }
+        let matches = |text: &str| match globset::Glob::new(token) {
+            Ok(glob) => glob.compile_matcher().is_match(text),
+            Err(_) => token == text,
+        };
+        if !(matches(&word.text) || (prefix && i == 0 && matches(basename(&word.text)))) {
+            return false;
+        }
+    }
+    prefix || words.len() == tokens.len()
+}
+
+fn sandbox_settings(agent: &Agent) -> Option<SandboxSettings> {
+    let tool_name = if cfg!(windows) { "execute_cmd" } else { "execute_bash" };
+    let settings = agent.tools_settings.get(tool_name)?.get("sandbox")?;
+    match serde_json::from_value::<SandboxSettings>(settings.clone()) {
+        Ok(settings) => Some(settings),
+        Err(e) => {
+            error!("Failed to deserialize sandbox settings for execute_bash: {:?}", e);
+            None
+        },
+    }
+}
+
 pub struct CommandResult {
     pub exit_status: Option<i32>,
     /// Truncated stdout
@@ -384,4 +660,240 @@ mod tests {
             );
         }
     }
+
+    #[test]
+    fn test_requires_acceptance_bypasses() {
+        let cmds = &[
+            // Separators and substitutions hidden from a naive split
+            ("ls\nrm -rf ~", true),
+            ("ls ; rm -rf ~", true),
+            ("(rm -rf ~)", true),
+            ("{ ls; rm -rf ~; }", true),
+            ("ls ${HOME:-$(rm -rf ~)}", true),
+            ("ls \"$(rm -rf ~)\"", true),
+            ("cat <<EOF\n$(rm -rf ~)\nEOF", true),
+            ("$CMD -rf ~", true),
+            ("LD_PRELOAD=/tmp/evil.so ls", true),
+            ("for f in *; do rm $f; done", true),
+            ("ls 'unterminated", true),
+            // `find` arguments hidden in globs or expansions
+            ("find . -name *.rs", true),
+            ("find . $(echo -delete)", true),
+            ("find . -fprint /etc/passwd", true),
+            ("grep -rP pattern .", true),
+            // Harmless commands that the substring heuristic used to block
+            ("echo 'a && b; c > d'", false),
+            ("grep -r '>' src", false),
+            ("ls 2>/dev/null", false),
+            ("ls -la > /dev/null 2>&1", false),
+            ("cat <<'EOF'\n$(rm -rf ~)\nEOF", false),
+            ("echo $HOME", false),
+            ("ls && pwd", false),
+        ];
+        for (cmd, expected) in cmds {
+            let tool = serde_json::from_value::<ExecuteCommand>(serde_json::json!({
+                "command": cmd,
+            }))
+            .unwrap();
+            assert_eq!(
+                tool.requires_acceptance(None, true),
+                *expected,
+                "expected command: `{}` to have requires_acceptance: `{}`",
+                cmd,
+                expected
+            );
+        }
+    }
+
+    #[test]
+    fn test_requires_acceptance_argument_patterns() {
+        let allowed_cmds: &[String] = &[
+            String::from("git log *"),
+            String::from("git status"),
+            String::from("npm run *"),
+            String::from("cargo * --release"),
+        ];
+        let cmds = &[
+            ("git log", false),
+            ("git log --oneline -n 5", false),
+            ("git push origin main", true),
+            ("git status", false),
+            ("git status --short", true),
+            ("git log && git push", true),
+            ("git log | head -n 5", false),
+            ("npm run build && git log -1", false),
+            ("git log $(git push)", true),
+            ("GIT_PAGER=evil git log", true),
+            ("git log > out.txt", true),
+            ("cargo build --release", false),
+            ("cargo build", true),
+        ];
+        for (cmd, expected) in cmds {
+            let tool = serde_json::from_value::<ExecuteCommand>(serde_json::json!({
+                "command": cmd,
+            }))
+            .unwrap();
+            assert_eq!(
+                tool.requires_acceptance(Option::from(&allowed_cmds.to_vec()), true),
+                *expected,
+                "expected command: `{}` to have requires_acceptance: `{}`",
+                cmd,
+                expected
+            );
+        }
+    }
+
+    #[test]
+    fn test_is_denied() {
+        let denied_cmds = &["rm".to_string(), "git push".to_string()];
+        let cmds = &[
+            ("rm -rf x", true),
+            ("/bin/rm x", true),
+            ("r''m x", true),
+            ("\\rm x", true),
+            ("sudo rm x", true),
+            ("echo x | xargs rm", true),
+            ("bash -c 'rm x'", true),
+            ("sh -ec \"ls; rm x\"", true),
+            ("eval 'rm x'", true),
+            ("ls $(rm x)", true),
+            // Commands that cannot be parsed are left to the user.
+            ("if true; then rm x; fi", false),
+            ("for f in *; do ls $f; done", false),
+            ("git push origin main", true),
+            ("git status", false),
+            ("npm run format", false),
+            ("echo 'rm'", false),
+        ];
+        for (cmd, expected) in cmds {
+            let tool = serde_json::from_value::<ExecuteCommand>(serde_json::json!({
+                "command": cmd,
+            }))
+            .unwrap();
+            assert_eq!(
+                tool.is_denied(denied_cmds),
+                *expected,
+                "expected command: `{}` to have is_denied: `{}`",
+                cmd,
+                expected
+            );
+        }
+    }
+
+    #[test]
+    fn test_eval_perm_denied_commands() {
+        let agent = serde_json::from_value::<Agent>(serde_json::json!({
+            "name": "test",
+            "allowedTools": ["execute_bash"],
+            "toolsSettings": {
+                "execute_bash": { "allowedCommands": ["git log *"], "deniedCommands": ["git push"] }
+            }
+        }))
+        .unwrap();
+        let eval = |cmd: &str| {
+            serde_json::from_value::<ExecuteCommand>(serde_json::json!({ "command": cmd }))
+                .unwrap()
+                .eval_perm(&agent)
+        };
+        assert!(matches!(eval("git log -1"), PermissionEvalResult::Allow));
+        assert!(matches!(eval("git log -1; git push -f"), PermissionEvalResult::Deny));
+        assert!(matches!(eval("git commit"), PermissionEvalResult::Ask));
+        assert!(matches!(
+            eval("for f in *; do git push $f; done"),
+            PermissionEvalResult::Ask
+        ));
+    }
+
+    #[test]
+    fn test_eval_perm_sandbox_auto_allow() {
+        let agent = serde_json::from_value::<Agent>(serde_json::json!({
+            "name": "test",
+            "allowedTools": ["execute_bash"],
+            "toolsSettings": {
+                "execute_bash": { "sandbox": { "enabled": true, "autoAllow": true } }
+            }
+        }))
+        .unwrap();
+        let eval = |cmd: &str, sandboxed: bool| {
+            let mut command = serde_json::from_value::<ExecuteCommand>(serde_json::json!({ "command": cmd })).unwrap();
+            if sandboxed {
+                command.set_sandbox(&agent);
+            }
+            command.eval_perm(&agent)
+        };
+
+        // Commands are not auto-allowed unless they run in the sandbox.
+        assert!(matches!(eval("rm -rf build", false), PermissionEvalResult::Ask));
+        if SandboxSettings::is_available() {
+            assert!(matches!(eval("rm -rf build", true), PermissionEvalResult::Allow));
+        } else {
+            assert!(matches!(eval("rm -rf build", true), PermissionEvalResult::Ask));
+        }
+        // Commands that cannot be parsed are never auto-allowed.
+        assert!(matches!(
+            eval("if true; then rm -rf build; fi", true),
+            PermissionEvalResult::Ask
+        ));
+    }
+
+    #[test]
+    fn test_permission_rules() {
+        let agent = serde_json::from_value::<Agent>(serde_json::json!({
+            "name": "test",
+            "permissions": {
+                "allow": ["execute_bash(cargo test *)"],
+                "deny": ["execute_bash(rm *)"]
+            }
+        }))
+        .unwrap();
+        let eval = |cmd: &str| {
+            super::super::Tool::ExecuteCommand(
+                serde_json::from_value::<ExecuteCommand>(serde_json::json!({ "command": cmd })).unwrap(),
+            )
+            .requires_acceptance(&agent)
+        };
+        assert_eq!(
+            eval("cargo test -p chat_cli && cargo test --doc"),
+            PermissionEvalResult::AllowedByRule("execute_bash(cargo test *)".to_string())
+        );
+        assert_eq!(eval("cargo test > out.txt"), PermissionEvalResult::Ask);
+        assert_eq!(
+            eval("cargo test; sudo rm -rf target"),
+            PermissionEvalResult::DeniedByRule("execute_bash(rm *)".to_string())
+        );
+        assert_eq!(eval("ls -la"), PermissionEvalResult::Allow);
+        assert_eq!(eval("for f in *; do cargo test $f; done"), PermissionEvalResult::Ask);
+    }
+
+    #[test]
+    fn test_suggested_permission_pattern() {
+        let suggest = |cmd: &str| {
+            serde_json::from_value::<ExecuteCommand>(serde_json::json!({ "command": cmd }))
+                .unwrap()
+                .suggested_permission_pattern()
+        };
+        assert_eq!(suggest("cargo test -p chat_cli"), Some("cargo test *".to_string()));
+        assert_eq!(suggest("git log --oneline"), Some("git log *".to_string()));
+        assert_eq!(suggest("ls -la"), Some("ls *".to_string()));
+        // Other commands are only allowed as they are.
+        assert_eq!(suggest("npm run lint"), Some("npm run lint".to_string()));
+        assert_eq!(suggest("rm -rf build"), Some("rm -rf build".to_string()));
+        assert_eq!(suggest("git push --force"), Some("git push --force".to_string()));
+        assert_eq!(
+            suggest("git commit -m 'a message'"),
+            Some("git commit -m 'a message'".to_string())
+        );
+        let rule = suggest("rm -rf build").unwrap();
+        let command =
+            |cmd: &str| serde_json::from_value::<ExecuteCommand>(serde_json::json!({ "command": cmd })).unwrap();
+        assert!(command("rm -rf build").matches_permission_pattern(&rule, RuleEffect::Allow));
+        assert!(!command("rm -rf build /").matches_permission_pattern(&rule, RuleEffect::Allow));
+        assert_eq!(suggest("rm *.log"), None);
+        assert_eq!(suggest("rm 'x?'"), None);
+        assert_eq!(suggest("cargo test > out.txt"), None);
+        assert_eq!(suggest("cargo build && cargo test"), None);
+        assert_eq!(suggest("sudo cargo test"), None);
+        assert_eq!(suggest("bash -c 'cargo test'"), None);
+        assert_eq!(suggest("$CARGO test"), None);
+    }
 }
@@ -0,0 +1,278 @@
+use std::ffi::OsString;
+use std::path::{
+    Path,
+    PathBuf,
+};
+
+use serde::Deserialize;
+
+use super::CommandResult;
+
+/// The sandbox wrapper executable, see <https://github.com/containers/bubblewrap>.
+const BWRAP: &str = "bwrap";
+/// Exit status reported by the shell when a process is killed by `SIGXCPU`.
+const SIGXCPU_EXIT_STATUS: i32 = 128 + 24;
+/// Exit status reported by the shell when a process is killed by `SIGKILL`, which is sent once
+/// the hard CPU limit is reached.
+const SIGKILL_EXIT_STATUS: i32 = 128 + 9;
+
+const WRITE_VIOLATION_PATTERNS: &[&str] = &["Read-only file system"];
+const NETWORK_VIOLATION_PATTERNS: &[&str] = &[
+    "Network is unreachable",
+    "Temporary failure in name resolution",
+    "Could not resolve host",
+    "Name or service not known",
+    "getaddrinfo",
+];
+const MEMORY_VIOLATION_PATTERNS: &[&str] = &[
+    "Cannot allocate memory",
+    "out of memory",
+    "MemoryError",
+    "std::bad_alloc",
+    "memory allocation of",
+];
+
+/// Sandbox configuration for `execute_bash`, read from `toolsSettings.execute_bash.sandbox`.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SandboxSettings {
+    #[serde(default)]
+    pub enabled: bool,
+    /// Allow network access. Only loopback is available otherwise.
+    #[serde(default)]
+    pub allow_network: bool,
+    /// Paths that are writable in addition to the current working directory and a private `/tmp`.
+    #[serde(default)]
+    pub writable_paths: Vec<String>,
+    #[serde(default = "default_cpu_time_secs")]
+    pub cpu_time_secs: u64,
+    #[serde(default = "default_memory_mb")]
+    pub memory_mb: u64,
+    /// Run commands without prompting, other than those matching `deniedCommands`.
+    #[serde(default)]
+    pub auto_allow: bool,
+}
+
+fn default_cpu_time_secs() -> u64 {
+    600
+}
+
+fn default_memory_mb() -> u64 {
+    4096
+}
+
+impl Default for SandboxSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            allow_network: false,
+            writable_paths: Vec::new(),
+            cpu_time_secs: default_cpu_time_secs(),
+            memory_mb: default_memory_mb(),
+            auto_allow: false,
+        }
+    }
+}
+
+impl SandboxSettings {
+    /// Whether the sandbox can be used on this system.
+    pub fn is_available() -> bool {
+        cfg!(target_os = "linux")
+            && std::env::var_os("PATH")
+                .is_some_and(|paths| std::env::split_paths(&paths).any(|dir| dir.join(BWRAP).is_file()))
+    }
+
+    /// Returns the program and arguments that run `command` with `shell` inside the sandbox.
+    ///
+    /// The whole file system is mounted read-only, except for `workspace`, the configured
+    /// writable paths, and a private `/tmp`.
+    pub fn wrap(&self, shell: &str, command: &str, workspace: &Path, home: Option<&Path>) -> (String, Vec<OsString>) {
+        let mut args: Vec<OsString> = vec![
+            "--ro-bind".into(),
+            "/".into(),
+            "/".into(),
+            "--dev".into(),
+            "/dev".into(),
+            "--proc".into(),
+            "/proc".into(),
+            // Mounted before the writable paths so that a workspace under /tmp is not hidden.
+            "--tmpfs".into(),
+            "/tmp".into(),
+        ];
+        for path in std::iter::once(workspace.to_path_buf()).chain(self.writable_paths(workspace, home)) {
+            args.extend(["--bind-try".into(), path.clone().into(), path.into()]);
+        }
+        if !self.allow_network {
+            args.push("--unshare-net".into());
+        }
+        args.extend([
+            "--unshare-pid".into(),
+            "--unshare-ipc".into(),
+            "--die-with-parent".into(),
+            "--new-session".into(),
+            "--chdir".into(),
+            workspace.into(),
+            "--".into(),
+            shell.into(),
+            "-c".into(),
+        ]);
+        // ulimit takes the virtual memory limit in KiB.
+        args.push(
+            format!(
+                "ulimit -t {} -v {} || exit 1\n{command}",
+                self.cpu_time_secs,
+                self.memory_mb * 1024
+            )
+            .into(),
+        );
+        (BWRAP.to_string(), args)
+    }
+
+    /// Returns a description of each sandbox restriction that `result` appears to have hit.
+    pub fn violations(&self, result: &CommandResult) -> Vec<String> {
+        let mut violations = Vec::new();
+        let output = || result.stderr.lines().chain(result.stdout.lines());
+
+        for line in output().filter(|line| WRITE_VIOLATION_PATTERNS.iter().any(|p| line.contains(p))) {
+            violations.push(format!(
+                "write outside of the writable paths was blocked: {}",
+                line.trim()
+            ));
+        }
+        if !self.allow_network {
+            if let Some(line) = output().find(|line| NETWORK_VIOLATION_PATTERNS.iter().any(|p| line.contains(p))) {
+                violations.push(format!("network access is disabled: {}", line.trim()));
+            }
+        }
+        if matches!(result.exit_status, Some(SIGXCPU_EXIT_STATUS | SIGKILL_EXIT_STATUS)) {
+            violations.push(format!(
+                "the command was killed, it may have exceeded the CPU time limit of {}s",
+                self.cpu_time_secs
+            ));
+        }
+        if let Some(line) = output().find(|line| MEMORY_VIOLATION_PATTERNS.iter().any(|p| line.contains(p))) {
+            violations.push(format!(
+                "the memory limit of {} MB may have been exceeded: {}",
+                self.memory_mb,
+                line.trim()
+            ));
+        }
+        violations
+    }
+
+    fn writable_paths(&self, workspace: &Path, home: Option<&Path>) -> Vec<PathBuf> {
+        self.writable_paths
+            .iter()
+            .filter_map(|path| match (path.strip_prefix("~"), home) {
+                (Some(rest), Some(home)) => Some(home.join(rest.trim_start_matches('/'))),
+                (Some(_), None) => None,
+                (None, _) => Some(workspace.join(path)),
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args_to_strings(args: &[OsString]) -> Vec<String> {
+        args.iter().map(|a| a.to_string_lossy().to_string()).collect()
+    }
+
+    #[test]
+    fn test_sandbox_settings_deserialize() {
+        let settings = serde_json::from_value::<SandboxSettings>(serde_json::json!({
+            "enabled": true,
+            "writablePaths": ["~/.cargo"],
+            "memoryMb": 512
+        }))
+        .unwrap();
+        assert_eq!(settings, SandboxSettings {
+            enabled: true,
+            writable_paths: vec!["~/.cargo".to_string()],
+            memory_mb: 512,
+            ..Default::default()
+        });
+    }
+
+    #[test]
+    fn test_sandbox_wrap() {
+        let settings = SandboxSettings {
+            enabled: true,
+            writable_paths: vec!["~/.cache".to_string(), "target".to_string(), "/var/tmp".to_string()],
+            cpu_time_secs: 10,
+            memory_mb: 1,
+            ..Default::default()
+        };
+        let (program, args) = settings.wrap("bash", "echo hi", Path::new("/work"), Some(Path::new("/home/user")));
+        let args = args_to_strings(&args);
+        assert_eq!(program, "bwrap");
+
+        let binds = args
+            .windows(2)
+            .filter(|w| w[0] == "--bind-try")
+            .map(|w| w[1].as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(binds, vec!["/work", "/home/user/.cache", "/work/target", "/var/tmp"]);
+        // Writable paths must be mounted after /tmp to not be hidden by it.
+        let tmpfs = args.iter().position(|a| a == "--tmpfs").unwrap();
+        let first_bind = args.iter().position(|a| a == "--bind-try").unwrap();
+        assert!(tmpfs < first_bind);
+        assert!(args.contains(&"--unshare-net".to_string()));
+
+        let separator = args.iter().position(|a| a == "--").unwrap();
+        assert_eq!(&args[separator + 1..], &[
+            "bash".to_string(),
+            "-c".to_string(),
+            "ulimit -t 10 -v 1024 || exit 1\necho hi".to_string()
+        ]);
+
+        let settings = SandboxSettings {
+            allow_network: true,
+            ..settings
+        };
+        let (_, args) = settings.wrap("bash", "echo hi", Path::new("/work"), None);
+        let args = args_to_strings(&args);
+        assert!(!args.contains(&"--unshare-net".to_string()));
+        assert!(!args.iter().any(|a| a.contains(".cache")));
+    }
+
+    #[test]
+    fn test_sandbox_violations() {
+        let settings = SandboxSettings::default();
+        let result = |exit_status, stderr: &str| CommandResult {
+            exit_status: Some(exit_status),
+            stdout: String::new(),
+            stderr: stderr.to_string(),
+        };
+
+        assert!(settings.violations(&result(0, "")).is_empty());
+        assert!(settings.violations(&result(1, "ls: cannot access 'x'")).is_empty());
+
+        let violations = settings.violations(&result(1, "touch: cannot touch '/etc/x': Read-only file system"));
+        assert_eq!(violations.len(), 1);
+        assert!(violations[0].contains("/etc/x"), "{violations:?}");
+
+        let violations = settings.violations(&result(6, "curl: (6) Could not resolve host: example.com"));
+        assert_eq!(violations.len(), 1);
+        assert!(violations[0].starts_with("network access is disabled"));
+        let allow_network = SandboxSettings {
+            allow_network: true,
+            ..Default::default()
+        };
+        assert!(
+            allow_network
+                .violations(&result(6, "curl: (6) Could not resolve host: example.com"))
+                .is_empty()
+        );
+
+        let violations = settings.violations(&result(152, ""));
+        assert_eq!(violations.len(), 1);
+        assert!(violations[0].contains("CPU time"));
+
+        let violations = settings.violations(&result(1, "python: MemoryError"));
+        assert_eq!(violations.len(), 1);
+        assert!(violations[0].contains("memory limit"));
+    }
+}
@@ -0,0 +1,1022 @@
+//! A parser for the subset of the POSIX shell command language needed to reason about what a
+//! command will run: lists, pipelines, subshells, brace groups, redirections (including here
+//! documents), variable assignments, and command and process substitutions.
+//!
+//! Compound commands such as `if`, `for`, `while`, and `case`, as well as function definitions,
+//! are rejected with [ParseError::Unsupported] so that callers can fail closed.
+
+use thiserror::Error;
+
+/// Words that start a compound command or a function definition when in command position.
+const RESERVED_WORDS: &[&str] = &[
+    "if", "then", "else", "elif", "fi", "do", "done", "case", "esac", "while", "until", "for", "select", "function",
+    "in", "[[", "]]", "coproc",
+];
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum ParseError {
+    #[error("unexpected end of input, expected {0}")]
+    UnexpectedEof(&'static str),
+    #[error("unexpected '{found}' at position {pos}")]
+    Unexpected { found: char, pos: usize },
+    #[error("unsupported shell syntax at position {pos}: {syntax}")]
+    Unsupported { syntax: String, pos: usize },
+}
+
+/// A sequence of pipelines separated by `;`, `&`, `&&`, `||`, or newlines.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct List {
+    pub items: Vec<ListItem>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ListItem {
+    pub pipeline: Pipeline,
+    /// The operator following the pipeline.
+    pub op: ListOp,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ListOp {
+    /// `;`, a newline, or the end of the list.
+    Sequence,
+    /// `&`
+    Background,
+    /// `&&`
+    And,
+    /// `||`
+    Or,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Pipeline {
+    /// Whether the pipeline is prefixed with `!`.
+    pub negated: bool,
+    pub commands: Vec<Command>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Command {
+    Simple(SimpleCommand),
+    /// `( list )`
+    Subshell {
+        list: List,
+        redirects: Vec<Redirect>,
+    },
+    /// `{ list; }`
+    Group {
+        list: List,
+        redirects: Vec<Redirect>,
+    },
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SimpleCommand {
+    /// Assignments preceding the command name, e.g. `FOO=bar cmd`.
+    pub assignments: Vec<Assignment>,
+    /// The command name followed by its arguments. Empty for a command consisting only of
+    /// assignments or redirections.
+    pub words: Vec<Word>,
+    pub redirects: Vec<Redirect>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Assignment {
+    pub name: String,
+    pub value: Word,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Redirect {
+    /// The explicit file descriptor, e.g. `2` in `2>file`.
+    pub fd: Option<u32>,
+    pub op: RedirectOp,
+    /// The target of the redirection. For here documents, this is the body.
+    pub target: Word,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RedirectOp {
+    /// `<`
+    Input,
+    /// `>`
+    Output,
+    /// `>>`
+    Append,
+    /// `>|`
+    Clobber,
+    /// `<>`
+    ReadWrite,
+    /// `<&`
+    DupInput,
+    /// `>&`
+    DupOutput,
+    /// `&>`
+    OutputAll,
+    /// `&>>`
+    AppendAll,
+    /// `<<` and `<<-`
+    HereDoc,
+    /// `<<<`
+    HereString,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Word {
+    /// The word with quotes removed. Expansions are kept as written.
+    pub text: String,
+    /// Whether the value is only known when the command runs, because of parameter, command,
+    /// arithmetic, or brace expansion.
+    pub dynamic: bool,
+    /// Whether the word contains unquoted pattern matching characters.
+    pub glob: bool,
+    /// The commands run by command and process substitutions within the word.
+    pub substitutions: Vec<List>,
+}
+
+impl Redirect {
+    /// Whether the redirection may create or modify a file.
+    pub fn writes_file(&self) -> bool {
+        const HARMLESS_TARGETS: &[&str] = &["/dev/null", "/dev/stdout", "/dev/stderr"];
+        let is_harmless_target = !self.target.dynamic && HARMLESS_TARGETS.contains(&self.target.text.as_str());
+        match self.op {
+            RedirectOp::Input | RedirectOp::DupInput | RedirectOp::HereDoc | RedirectOp::HereString => false,
+            // `>&2` and `>&-` duplicate or close descriptors, anything else is a file name.
+            RedirectOp::DupOutput => {
+                let is_descriptor = self.target.text == "-" || self.target.text.chars().all(|c| c.is_ascii_digit());
+                (self.target.dynamic || !is_descriptor) && !is_harmless_target
+            },
+            RedirectOp::Output
+            | RedirectOp::Append
+            | RedirectOp::Clobber
+            | RedirectOp::ReadWrite
+            | RedirectOp::OutputAll
+            | RedirectOp::AppendAll => !is_harmless_target,
+        }
+    }
+}
+
+impl List {
+    /// Returns every simple command in the list, including those nested in subshells, groups,
+    /// and substitutions.
+    pub fn simple_commands(&self) -> Vec<&SimpleCommand> {
+        let mut commands = Vec::new();
+        self.collect_simple_commands(&mut commands);
+        commands
+    }
+
+    fn collect_simple_commands<'a>(&'a self, commands: &mut Vec<&'a SimpleCommand>) {
+        fn collect_redirects<'a>(redirects: &'a [Redirect], commands: &mut Vec<&'a SimpleCommand>) {
+            for redirect in redirects {
+                redirect.target.collect_simple_commands(commands);
+            }
+        }
+
+        for item in &self.items {
+            for command in &item.pipeline.commands {
+                match command {
+                    Command::Simple(simple) => {
+                        commands.push(simple);
+                        for assignment in &simple.assignments {
+                            assignment.value.collect_simple_commands(commands);
+                        }
+                        for word in &simple.words {
+                            word.collect_simple_commands(commands);
+                        }
+                        collect_redirects(&simple.redirects, commands);
+                    },
+                    Command::Subshell { list, redirects } | Command::Group { list, redirects } => {
+                        list.collect_simple_commands(commands);
+                        collect_redirects(redirects, commands);
+                    },
+                }
+            }
+        }
+    }
+}
+
+impl Word {
+    fn collect_simple_commands<'a>(&'a self, commands: &mut Vec<&'a SimpleCommand>) {
+        for list in &self.substitutions {
+            list.collect_simple_commands(commands);
+        }
+    }
+}
+
+/// Parses `input` as a shell command list.
+pub fn parse(input: &str) -> Result<List, ParseError> {
+    let mut parser = Parser::new(input);
+    let list = parser.parse_list(None)?;
+    match parser.peek() {
+        None => Ok(list),
+        Some(c) => Err(parser.unexpected(c)),
+    }
+}
+
+struct Parser {
+    chars: Vec<char>,
+    pos: usize,
+    /// The end of the here document bodies read for the current line. The parser skips to it
+    /// at the next newline.
+    heredoc_end: Option<usize>,
+}
+
+impl Parser {
+    fn new(input: &str) -> Self {
+        Self {
+            chars: input.chars().collect(),
+            pos: 0,
+            heredoc_end: None,
+        }
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
+    fn peek_at(&self, offset: usize) -> Option<char> {
+        self.chars.get(self.pos + offset).copied()
+    }
+
+    fn starts_with(&self, s: &str) -> bool {
+        s.chars().enumerate().all(|(i, c)| self.peek_at(i) == Some(c))
+    }
+
+    fn unexpected(&self, found: char) -> ParseError {
+        ParseError::Unexpected { found, pos: self.pos }
+    }
+
+    fn unsupported(&self, syntax: impl Into<String>) -> ParseError {
+        ParseError::Unsupported {
+            syntax: syntax.into(),
+            pos: self.pos,
+        }
+    }
+
+    /// Skips spaces, tabs, line continuations, and comments.
+    fn skip_blanks(&mut self) {
+        loop {
+            match self.peek() {
+                Some(' ' | '\t') => self.pos += 1,
+                Some('\\') if self.peek_at(1) == Some('\n') => self.pos += 2,
+                Some('#') => {
+                    while self.peek().is_some_and(|c| c != '\n') {
+                        self.pos += 1;
+                    }
+                },
+                _ => return,
+            }
+        }
+    }
+
+    /// Consumes a newline, skipping over any here document bodies that follow it.
+    fn consume_newline(&mut self) {
+        self.pos += 1;
+        if let Some(end) = self.heredoc_end.take() {
+            self.pos = self.pos.max(end);
+        }
+    }
+
+    /// Skips blanks and newlines.
+    fn skip_linebreaks(&mut self) {
+        loop {
+            self.skip_blanks();
+            if self.peek() == Some('\n') {
+                self.consume_newline();
+            } else {
+                return;
+            }
+        }
+    }
+
+    /// Whether the next token is the reserved word `word`, i.e. it is followed by a delimiter.
+    fn at_reserved_word(&self, word: &str) -> bool {
+        self.starts_with(word)
+            && self
+                .peek_at(word.chars().count())
+                .is_none_or(|c| matches!(c, ' ' | '\t' | '\n' | ';' | '&' | '|' | '(' | ')' | '<' | '>'))
+    }
+
+    /// Parses a list until the end of input or `terminator`, which is not consumed.
+    fn parse_list(&mut self, terminator: Option<char>) -> Result<List, ParseError> {
+        let mut list = List::default();
+        loop {
+            self.skip_linebreaks();
+            match self.peek() {
+                None => break,
+                Some(')') if terminator == Some(')') => break,
+                Some('}') if terminator == Some('}') && self.at_reserved_word("}") => break,
+                _ => (),
+            }
+
+            let pipeline = self.parse_pipeline()?;
+            self.skip_blanks();
+            let op = match self.peek() {
+                Some('&') if self.peek_at(1) == Some('&') => {
+                    self.pos += 2;
+                    ListOp::And
+                },
+                Some('|') if self.peek_at(1) == Some('|') => {
+                    self.pos += 2;
+                    ListOp::Or
+                },
+                Some('&') => {
+                    self.pos += 1;
+                    ListOp::Background
+                },
+                Some(';') if self.peek_at(1) == Some(';') => return Err(self.unsupported("case clause terminator")),
+                Some(';') => {
+                    self.pos += 1;
+                    ListOp::Sequence
+                },
+                Some('\n') | None => ListOp::Sequence,
+                Some(')') if terminator == Some(')') => ListOp::Sequence,
+                Some(c) => return Err(self.unexpected(c)),
+            };
+            list.items.push(ListItem { pipeline, op });
+
+            if matches!(op, ListOp::And | ListOp::Or) {
+                self.skip_linebreaks();
+                if self.peek().is_none() {
+                    return Err(ParseError::UnexpectedEof("a command after '&&' or '||'"));
+                }
+            }
+        }
+
+        if let Some(last) = list.items.last() {
+            if matches!(last.op, ListOp::And | ListOp::Or) {
+                return Err(ParseError::UnexpectedEof("a command after '&&' or '||'"));
+            }
+        }
+        Ok(list)
+    }
+
+    fn parse_pipeline(&mut self) -> Result<Pipeline, ParseError> {
+        self.skip_blanks();
+        let negated = self.at_reserved_word("!");
+        if negated {
+            self.pos += 1;
+        }
+
+        let mut commands = vec![self.parse_command()?];
+        loop {
+            self.skip_blanks();
+            if self.peek() == Some('|') && self.peek_at(1) != Some('|') {
+                self.pos += if self.peek_at(1) == Some('&') { 2 } else { 1 };
+                self.skip_linebreaks();
+                commands.push(self.parse_command()?);
+            } else {
+                break;
+            }
+        }
+        Ok(Pipeline { negated, commands })
+    }
+
+    fn parse_command(&mut self) -> Result<Command, ParseError> {
+        self.skip_blanks();
+        match self.peek() {
+            None => Err(ParseError::UnexpectedEof("a command")),
+            Some('(') if self.peek_at(1) == Some('(') => Err(self.unsupported("arithmetic command")),
+            Some('(') => {
+                self.pos += 1;
+                let list = self.parse_list(Some(')'))?;
+                self.expect(')', "')' to close the subshell")?;
+                let redirects = self.parse_trailing_redirects()?;
+                Ok(Command::Subshell { list, redirects })
+            },
+            Some('{') if self.at_reserved_word("{") => {
+                self.pos += 1;
+                let list = self.parse_list(Some('}'))?;
+                self.expect('}', "'}' to close the group")?;
+                let redirects = self.parse_trailing_redirects()?;
+                Ok(Command::Group { list, redirects })
+            },
+            Some(c @ (')' | ';' | '&' | '|')) => Err(self.unexpected(c)),
+            Some(_) => {
+                if let Some(word) = RESERVED_WORDS.iter().find(|w| self.at_reserved_word(w)) {
+                    return Err(self.unsupported(format!("'{word}'")));
+                }
+                self.parse_simple_command().map(Command::Simple)
+            },
+        }
+    }
+
+    fn expect(&mut self, c: char, expected: &'static str) -> Result<(), ParseError> {
+        match self.peek() {
+            Some(found) if found == c => {
+                self.pos += 1;
+                Ok(())
+            },
+            Some(found) => Err(self.unexpected(found)),
+            None => Err(ParseError::UnexpectedEof(expected)),
+        }
+    }
+
+    fn parse_trailing_redirects(&mut self) -> Result<Vec<Redirect>, ParseError> {
+        let mut redirects = Vec::new();
+        loop {
+            self.skip_blanks();
+            match self.try_parse_redirect()? {
+                Some(redirect) => redirects.push(redirect),
+                None => return Ok(redirects),
+            }
+        }
+    }
+
+    fn parse_simple_command(&mut self) -> Result<SimpleCommand, ParseError> {
+        let mut command = SimpleCommand::default();
+        loop {
+            self.skip_blanks();
+            match self.peek() {
+                None | Some('\n' | ';' | '|' | ')') => break,
+                Some('&') if self.peek_at(1) != Some('>') => break,
+                Some('(') => return Err(self.unsupported("function definition")),
+                _ => (),
+            }
+
+            if let Some(redirect) = self.try_parse_redirect()? {
+                command.redirects.push(redirect);
+                continue;
+            }
+
+            if command.words.is_empty() {
+                if let Some(name) = self.assignment_name() {
+                    self.pos += name.chars().count() + 1;
+                    if self.peek() == Some('(') {
+                        return Err(self.unsupported("array assignment"));
+                    }
+                    let value = self.parse_word()?.unwrap_or_default();
+                    command.assignments.push(Assignment { name, value });
+                    continue;
+                }
+            }
+
+            match self.parse_word()? {
+                Some(word) => command.words.push(word),
+                None => return Err(self.unexpected(self.peek().unwrap_or(' '))),
+            }
+        }
+        Ok(command)
+    }
+
+    /// Returns the variable name if the next word is an assignment, without consuming it. The
+    /// `+=` form is returned as `name+`.
+    fn assignment_name(&self) -> Option<String> {
+        let mut name = String::new();
+        let mut i = 0;
+        while let Some(c) = self.peek_at(i) {
+            match c {
+                '=' if !name.is_empty() => return Some(name),
+                '+' if !name.is_empty() && self.peek_at(i + 1) == Some('=') => {
+                    name.push('+');
+                    return Some(name);
+                },
+                'a'..='z' | 'A'..='Z' | '_' => name.push(c),
+                '0'..='9' if !name.is_empty() => name.push(c),
+                _ => return None,
+            }
+            i += 1;
+        }
+        None
+    }
+
+    fn try_parse_redirect(&mut self) -> Result<Option<Redirect>, ParseError> {
+        let start = self.pos;
+        let mut digits = String::new();
+        while let Some(c) = self.peek().filter(char::is_ascii_digit) {
+            digits.push(c);
+            self.pos += 1;
+        }
+
+        let op = if self.starts_with("&>>") && digits.is_empty() {
+            Some((RedirectOp::AppendAll, 3))
+        } else if self.starts_with("&>") && digits.is_empty() {
+            Some((RedirectOp::OutputAll, 2))
+        } else if self.starts_with("<(") || self.starts_with(">(") {
+            // Process substitution, parsed as a word.
+            None
+        } else if self.starts_with("<<<") {
+            Some((RedirectOp::HereString, 3))
+        } else if self.starts_with("<<") {
+            Some((RedirectOp::HereDoc, 2))
+        } else if self.starts_with("<>") {
+            Some((RedirectOp::ReadWrite, 2))
+        } else if self.starts_with("<&") {
+            Some((RedirectOp::DupInput, 2))
+        } else if self.starts_with("<") {
+            Some((RedirectOp::Input, 1))
+        } else if self.starts_with(">>") {
+            Some((RedirectOp::Append, 2))
+        } else if self.starts_with(">|") {
+            Some((RedirectOp::Clobber, 2))
+        } else if self.starts_with(">&") {
+            Some((RedirectOp::DupOutput, 2))
+        } else if self.starts_with(">") {
+            Some((RedirectOp::Output, 1))
+        } else {
+            None
+        };
+
+        let Some((op, len)) = op else {
+            self.pos = start;
+            return Ok(None);
+        };
+        self.pos += len;
+        let fd = match digits.parse() {
+            Ok(fd) => Some(fd),
+            Err(_) if digits.is_empty() => None,
+            Err(_) => return Err(self.unsupported("file descriptor out of range")),
+        };
+
+        let strip_tabs = op == RedirectOp::HereDoc && self.peek() == Some('-');
+        if strip_tabs {
+            self.pos += 1;
+        }
+        self.skip_blanks();
+        let target_start = self.pos;
+        let Some(target) = self.parse_word()? else {
+            return match self.peek() {
+                Some(c) => Err(self.unexpected(c)),
+                None => Err(ParseError::UnexpectedEof("a redirection target")),
+            };
+        };
+
+        let target = if op == RedirectOp::HereDoc {
+            let quoted = self.chars[target_start..self.pos]
+                .iter()
+                .any(|c| matches!(c, '\'' | '"' | '\\'));
+            self.read_heredoc(&target.text, strip_tabs, quoted)?
+        } else {
+            target
+        };
+
+        Ok(Some(Redirect { fd, op, target }))
+    }
+
+    /// Reads the body of a here document, which starts on the line after the current one.
+    fn read_heredoc(&mut self, delimiter: &str, strip_tabs: bool, quoted: bool) -> Result<Word, ParseError> {
+        let start = match self.heredoc_end {
+            Some(end) => end,
+            None => match self.chars[self.pos..].iter().position(|c| *c == '\n') {
+                Some(i) => self.pos + i + 1,
+                // A here document without a body.
+                None => return Ok(Word::default()),
+            },
+        };
+
+        let mut body = String::new();
+        let mut i = start;
+        while i < self.chars.len() {
+            let line_end = self.chars[i..]
+                .iter()
+                .position(|c| *c == '\n')
+                .map_or(self.chars.len(), |n| i + n);
+            let line = self.chars[i..line_end].iter().collect::<String>();
+            let line = if strip_tabs {
+                line.trim_start_matches('\t')
+            } else {
+                &line
+            };
+            i = (line_end + 1).min(self.chars.len());
+            if line == delimiter {
+                break;
+            }
+            body.push_str(line);
+            body.push('\n');
+        }
+        self.heredoc_end = Some(i);
+
+        if quoted {
+            return Ok(Word {
+                text: body,
+                ..Default::default()
+            });
+        }
+
+        // Unquoted bodies are subject to parameter expansion and command substitution.
+        let mut parser = Parser::new(&body);
+        let mut word = Word::default();
+        while parser.peek().is_some() {
+            parser.parse_double_quoted_char(&mut word, None)?;
+        }
+        Ok(word)
+    }
+
+    /// Parses a word, returning [None] if the next character is a delimiter.
+    fn parse_word(&mut self) -> Result<Option<Word>, ParseError> {
+        let mut word = Word::default();
+        let mut unquoted = String::new();
+        let mut empty = true;
+        loop {
+            let Some(c) = self.peek() else { break };
+            match c {
+                ' ' | '\t' | '\n' | ';' | '&' | '|' | ')' => break,
+                '(' => return Err(self.unsupported("'(' within a word")),
+                '<' | '>' if self.peek_at(1) == Some('(') => {
+                    self.pos += 2;
+                    let list = self.parse_list(Some(')'))?;
+                    self.expect(')', "')' to close the process substitution")?;
+                    word.text.push(c);
+                    word.text.push_str("(...)");
+                    word.dynamic = true;
+                    word.substitutions.push(list);
+                },
+                '<' | '>' => break,
+                '\\' => {
+                    self.pos += 1;
+                    match self.peek() {
+                        Some('\n') => self.pos += 1,
+                        Some(c) => {
+                            word.text.push(c);
+                            self.pos += 1;
+                        },
+                        None => (),
+                    }
+                },
+                '\'' => {
+                    self.pos += 1;
+                    loop {
+                        match self.peek() {
+                            Some('\'') => break,
+                            Some(c) => word.text.push(c),
+                            None => return Err(ParseError::UnexpectedEof("a closing single quote")),
+                        }
+                        self.pos += 1;
+                    }
+                    self.pos += 1;
+                },
+                '"' => {
+                    self.pos += 1;
+                    loop {
+                        match self.peek() {
+                            Some('"') => break,
+                            Some(_) => self.parse_double_quoted_char(&mut word, Some('"'))?,
+                            None => return Err(ParseError::UnexpectedEof("a closing double quote")),
+                        }
+                    }
+                    self.pos += 1;
+                },
+                '$' | '`' => self.parse_expansion(&mut word)?,
+                '*' | '?' | '[' => {
+                    word.glob = true;
+                    word.text.push(c);
+                    unquoted.push(c);
+                    self.pos += 1;
+                },
+                _ => {
+                    word.text.push(c);
+                    unquoted.push(c);
+                    self.pos += 1;
+                },
+            }
+            empty = false;
+        }
+
+        if empty {
+            return Ok(None);
+        }
+        if has_brace_expansion(&unquoted) {
+            word.dynamic = true;
+        }
+        Ok(Some(word))
+    }
+
+    /// Parses a single character or expansion within double quotes or an unquoted here
+    /// document body.
+    fn parse_double_quoted_char(&mut self, word: &mut Word, quote: Option<char>) -> Result<(), ParseError> {
+        match self.peek() {
+            Some('\\') => {
+                self.pos += 1;
+                match self.peek() {
+                    Some('\n') => self.pos += 1,
+                    Some(c @ ('$' | '`' | '\\')) => {
+                        word.text.push(c);
+                        self.pos += 1;
+                    },
+                    Some(c) if Some(c) == quote => {
+                        word.text.push(c);
+                        self.pos += 1;
+                    },
+                    _ => word.text.push('\\'),
+                }
+            },
+            Some('$' | '`') => self.parse_expansion(word)?,
+            Some(c) => {
+                word.text.push(c);
+                self.pos += 1;
+            },
+            None => (),
+        }
+        Ok(())
+    }
+
+    /// Parses a `$` or backquote expansion.
+    fn parse_expansion(&mut self, word: &mut Word) -> Result<(), ParseError> {
+        let start = self.pos;
+        match (self.peek(), self.peek_at(1)) {
+            (Some('`'), _) => {
+                self.pos += 1;
+                let mut inner = String::new();
+                loop {
+                    match self.peek() {
+                        Some('`') => break,
+                        Some('\\') if matches!(self.peek_at(1), Some('`' | '\\' | '$')) => {
+                            inner.push(self.peek_at(1).unwrap_or_default());
+                            self.pos += 2;
+                            continue;
+                        },
+                        Some(c) => inner.push(c),
+                        None => return Err(ParseError::UnexpectedEof("a closing backquote")),
+                    }
+                    self.pos += 1;
+                }
+                self.pos += 1;
+                word.substitutions.push(parse(&inner)?);
+                word.dynamic = true;
+            },
+            (Some('$'), Some('(')) if self.peek_at(2) == Some('(') => {
+                // Arithmetic expansion. Nested expansions are rejected rather than parsed.
+                self.pos += 3;
+                let mut depth = 0;
+                loop {
+                    match self.peek() {
+                        Some('$' | '`') => return Err(self.unsupported("expansion within arithmetic expansion")),
+                        Some('(') => depth += 1,
+                        Some(')') if depth == 0 && self.peek_at(1) == Some(')') => break,
+                        Some(')') => depth -= 1,
+                        Some(_) => (),
+                        None => return Err(ParseError::UnexpectedEof("'))' to close the arithmetic expansion")),
+                    }
+                    self.pos += 1;
+                }
+                self.pos += 2;
+                word.dynamic = true;
+            },
+            (Some('$'), Some('(')) => {
+                self.pos += 2;
+                let list = self.parse_list(Some(')'))?;
+                self.expect(')', "')' to close the command substitution")?;
+                word.substitutions.push(list);
+                word.dynamic = true;
+            },
+            (Some('$'), Some('{')) => {
+                self.pos += 2;
+                let mut inner = Word::default();
+                loop {
+                    match self.peek() {
+                        Some('}') => break,
+                        Some('\'') => {
+                            self.pos += 1;
+                            while self.peek().is_some_and(|c| c != '\'') {
+                                self.pos += 1;
+                            }
+                            self.pos += 1;
+                        },
+                        Some('"') => {
+                            self.pos += 1;
+                            while self.peek().is_some_and(|c| c != '"') {
+                                self.parse_double_quoted_char(&mut inner, Some('"'))?;
+                            }
+                            self.pos += 1;
+                        },
+                        Some(_) => self.parse_double_quoted_char(&mut inner, Some('}'))?,
+                        None => return Err(ParseError::UnexpectedEof("'}' to close the parameter expansion")),
+                    }
+                }
+                self.pos += 1;
+                word.substitutions.extend(inner.substitutions);
+                word.dynamic = true;
+            },
+            (Some('$'), Some('\'')) => {
+                // ANSI-C quoting, whose escapes can spell out anything.
+                self.pos += 2;
+                loop {
+                    match self.peek() {
+                        Some('\'') => break,
+                        Some('\\') => self.pos += 2,
+                        Some(_) => self.pos += 1,
+                        None => return Err(ParseError::UnexpectedEof("a closing single quote")),
+                    }
+                }
+                self.pos += 1;
+                word.dynamic = true;
+            },
+            (Some('$'), Some(c)) if c.is_ascii_alphanumeric() || "_@*#?-$!".contains(c) => {
+                self.pos += 2;
+                if c.is_ascii_alphabetic() || c == '_' {
+                    while self.peek().is_some_and(|c| c.is_ascii_alphanumeric() || c == '_') {
+                        self.pos += 1;
+                    }
+                }
+                word.dynamic = true;
+            },
+            (Some('$'), Some('"')) => {
+                // Locale translation, equivalent to a double quoted string.
+                self.pos += 1;
+                return Ok(());
+            },
+            _ => {
+                word.text.push('$');
+                self.pos += 1;
+                return Ok(());
+            },
+        }
+        word.text.extend(&self.chars[start..self.pos]);
+        Ok(())
+    }
+}
+
+/// Whether `unquoted` contains a brace expansion such as `{a,b}` or `{1..3}`.
+fn has_brace_expansion(unquoted: &str) -> bool {
+    unquoted.match_indices('{').any(|(i, _)| {
+        unquoted[i + 1..].find('}').is_some_and(|end| {
+            unquoted[i + 1..i + 1 + end].contains(',') || unquoted[i + 1..i + 1 + end].contains("..")
+        })
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn words(command: &SimpleCommand) -> Vec<&str> {
+        command.words.iter().map(|w| w.text.as_str()).collect()
+    }
+
+    fn all_words(input: &str) -> Vec<Vec<String>> {
+        parse(input)
+            .unwrap_or_else(|err| panic!("failed to parse `{input}`: {err}"))
+            .simple_commands()
+            .into_iter()
+            .map(|c| words(c).into_iter().map(str::to_string).collect())
+            .collect()
+    }
+
+    #[test]
+    fn test_parse_lists_and_pipelines() {
+        let list = parse("a && b || c; d & e | f |& g\nh").unwrap();
+        let ops = list.items.iter().map(|i| i.op).collect::<Vec<_>>();
+        assert_eq!(ops, vec![
+            ListOp::And,
+            ListOp::Or,
+            ListOp::Sequence,
+            ListOp::Background,
+            ListOp::Sequence,
+            ListOp::Sequence
+        ]);
+        assert_eq!(list.items[4].pipeline.commands.len(), 3);
+        assert_eq!(all_words("a && b || c; d & e | f |& g\nh").len(), 8);
+
+        assert!(parse("! grep x file").unwrap().items[0].pipeline.negated);
+        assert_eq!(all_words("a &&\n  b"), vec![vec!["a"], vec!["b"]]);
+        assert_eq!(all_words("echo a\\\nb"), vec![vec!["echo", "ab"]]);
+        assert_eq!(all_words("echo hi # rm -rf /"), vec![vec!["echo", "hi"]]);
+        assert_eq!(all_words("echo a#b"), vec![vec!["echo", "a#b"]]);
+    }
+
+    #[test]
+    fn test_parse_quoting() {
+        assert_eq!(all_words(r#"echo 'a && b' "c > d" e\;f"#), vec![vec![
+            "echo", "a && b", "c > d", "e;f"
+        ]]);
+        assert_eq!(all_words(r#"r'm' "-rf" \/"#), vec![vec!["rm", "-rf", "/"]]);
+        assert_eq!(all_words(r#"echo "a \"b\" \$c""#), vec![vec!["echo", r#"a "b" $c"#]]);
+
+        let list = parse("echo $HOME ${X:-y} $'\\x72m' {a,b} '{a,b}' *.rs '*.rs'").unwrap();
+        let command = list.simple_commands()[0];
+        let flags = command.words[1..]
+            .iter()
+            .map(|w| (w.dynamic, w.glob))
+            .collect::<Vec<_>>();
+        assert_eq!(flags, vec![
+            (true, false),
+            (true, false),
+            (true, false),
+            (true, false),
+            (false, false),
+            (false, true),
+            (false, false)
+        ]);
+    }
+
+    #[test]
+    fn test_parse_substitutions() {
+        assert_eq!(all_words("echo $(rm -rf x)"), vec![vec!["echo", "$(rm -rf x)"], vec![
+            "rm", "-rf", "x"
+        ]]);
+        assert_eq!(all_words("echo `rm x`"), vec![vec!["echo", "`rm x`"], vec!["rm", "x"]]);
+        assert_eq!(all_words("echo \"$(rm x)\""), vec![vec!["echo", "$(rm x)"], vec![
+            "rm", "x"
+        ]]);
+        assert_eq!(all_words("echo ${X:-$(rm x)}"), vec![
+            vec!["echo", "${X:-$(rm x)}"],
+            vec!["rm", "x"]
+        ]);
+        assert_eq!(all_words("diff <(ls a) >(rm b)"), vec![
+            vec!["diff", "<(...)", ">(...)"],
+            vec!["ls", "a"],
+            vec!["rm", "b"]
+        ]);
+        assert_eq!(all_words("X=$(rm x) ls"), vec![vec!["ls"], vec!["rm", "x"]]);
+        assert_eq!(all_words("echo $(echo $(rm x))"), vec![
+            vec!["echo", "$(echo $(rm x))"],
+            vec!["echo", "$(rm x)"],
+            vec!["rm", "x"]
+        ]);
+        assert!(matches!(
+            parse("echo $((1 + $(rm x)))"),
+            Err(ParseError::Unsupported { .. })
+        ));
+        assert!(parse("echo $((1 + 2))").unwrap().simple_commands()[0].words[1].dynamic);
+    }
+
+    #[test]
+    fn test_parse_subshells_and_groups() {
+        assert_eq!(all_words("(cd x && rm y) | { cat; ls; }"), vec![
+            vec!["cd", "x"],
+            vec!["rm", "y"],
+            vec!["cat"],
+            vec!["ls"]
+        ]);
+        assert_eq!(all_words("echo }"), vec![vec!["echo", "}"]]);
+        assert!(parse("(echo").is_err());
+        assert!(parse("{ echo; ").is_err());
+    }
+
+    #[test]
+    fn test_parse_assignments_and_redirects() {
+        let list = parse("A=1 B+=2 cmd x=y 2>&1 >out <in &>/dev/null").unwrap();
+        let command = list.simple_commands()[0];
+        assert_eq!(
+            command.assignments.iter().map(|a| a.name.as_str()).collect::<Vec<_>>(),
+            vec!["A", "B+"]
+        );
+        assert_eq!(words(command), vec!["cmd", "x=y"]);
+        let redirects = command
+            .redirects
+            .iter()
+            .map(|r| (r.fd, r.op, r.target.text.as_str(), r.writes_file()))
+            .collect::<Vec<_>>();
+        assert_eq!(redirects, vec![
+            (Some(2), RedirectOp::DupOutput, "1", false),
+            (None, RedirectOp::Output, "out", true),
+            (None, RedirectOp::Input, "in", false),
+            (None, RedirectOp::OutputAll, "/dev/null", false),
+        ]);
+        assert_eq!(all_words("echo 2>x 2 >> y"), vec![vec!["echo", "2"]]);
+        assert!(parse("echo >").is_err());
+    }
+
+    #[test]
+    fn test_parse_heredocs() {
+        let list = parse("cat <<EOF > out\n$(rm x) && ls\nEOF\necho done").unwrap();
+        let commands = list
+            .simple_commands()
+            .into_iter()
+            .map(|c| words(c).join(" "))
+            .collect::<Vec<_>>();
+        assert_eq!(commands, vec!["cat", "rm x", "echo done"]);
+
+        let list = parse("cat <<'EOF'\n$(rm x)\nEOF").unwrap();
+        assert_eq!(list.simple_commands().len(), 1);
+        assert_eq!(list.simple_commands()[0].redirects[0].target.text, "$(rm x)\n");
+
+        let list = parse("cat <<-A <<B\n\tone\n\tA\ntwo\nB\nls").unwrap();
+        assert_eq!(
+            list.simple_commands()
+                .iter()
+                .map(|c| words(c).join(" "))
+                .collect::<Vec<_>>(),
+            vec!["cat", "ls"]
+        );
+        let bodies = list.simple_commands()[0]
+            .redirects
+            .iter()
+            .map(|r| r.target.text.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(bodies, vec!["one\n", "two\n"]);
+    }
+
+    #[test]
+    fn test_parse_unsupported() {
+        for input in [
+            "if true; then rm x; fi",
+            "for f in *; do rm $f; done",
+            "while true; do :; done",
+            "case x in x) rm y;; esac",
+            "f() { rm x; }",
+            "function f { rm x; }",
+            "a=(1 2)",
+            "((x++))",
+        ] {
+            assert!(
+                matches!(parse(input), Err(ParseError::Unsupported { .. })),
+                "expected `{input}` to be unsupported, got {:?}",
+                parse(input)
+            );
+        }
+        for input in ["echo 'a", "echo \"a", "echo $(a", "echo `a", "a &&", "| a", "a ;; b"] {
+            assert!(parse(input).is_err(), "expected `{input}` to fail");
+        }
+    }
+}
@@ -1,17 +1,22 @@
 use std::collections::VecDeque;
 use std::io::Write;
+use std::path::PathBuf;
 use std::process::Stdio;
 
 use eyre::{
     Context as EyreContext,
     Result,
 };
+use nix::sys::signal::Signal;
+use nix::unistd::Pid;
 use tokio::io::AsyncBufReadExt;
+use tokio::process::Child;
 use tokio::select;
 use tracing::error;
 
 use super::{
     CommandResult,
+    SandboxSettings,
     format_output,
 };
 
@@ -22,15 +27,11 @@ use super::{
 /// * `updates` - output stream to push informational messages about the progress
 /// # Returns
 /// A [`CommandResult`]
-pub async fn run_command<W: Write>(
-    command: &str,
-    max_result_size: usize,
-    mut updates: Option<W>,
-) -> Result<CommandResult> {
+pub async fn run_command<W: Write>(command: &str, max_result_size: usize, updates: Option<W>) -> Result<CommandResult> {
     let shell = std::env::var("AMAZON_Q_CHAT_SHELL").unwrap_or("bash".to_string());
 
     // We need to maintain a handle on stderr and stdout, but pipe it to the terminal as well
-    let mut child = tokio::process::Command::new(shell)
+    let child = tokio::process::Command::new(shell)
         .arg("-c")
         .arg(command)
         .stdin(Stdio::inherit())
@@ -39,6 +40,81 @@ pub async fn run_command<W: Write>(
         .spawn()
         .wrap_err_with(|| format!("Unable to spawn command '{}'", command))?;
 
+    wait_for_command(command, child, max_result_size, updates).await
+}
+
+/// Run a bash command inside the sandbox described by `sandbox`, see [run_command].
+pub async fn run_sandboxed_command<W: Write>(
+    command: &str,
+    sandbox: &SandboxSettings,
+    max_result_size: usize,
+    updates: Option<W>,
+) -> Result<CommandResult> {
+    let shell = std::env::var("AMAZON_Q_CHAT_SHELL").unwrap_or("bash".to_string());
+    let workspace = std::env::current_dir()?;
+    let home = std::env::var_os("HOME").map(PathBuf::from);
+    let (program, args) = sandbox.wrap(&shell, command, &workspace, home.as_deref());
+
+    let child = tokio::process::Command::new(program)
+        .args(args)
+        .stdin(Stdio::inherit())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .wrap_err_with(|| format!("Unable to spawn sandboxed command '{}'", command))?;
+
+    wait_for_command(command, child, max_result_size, updates).await
+}
+
+/// Spawn a bash command whose output is read later, inside `sandbox` if given.
+///
+/// The command runs in its own process group so that it is not interrupted by Ctrl+C, and so
+/// that any processes it starts can be terminated along with it.
+pub fn spawn_background_command(command: &str, sandbox: Option<&SandboxSettings>) -> Result<Child> {
+    let shell = std::env::var("AMAZON_Q_CHAT_SHELL").unwrap_or("bash".to_string());
+    let mut cmd = match sandbox {
+        Some(sandbox) => {
+            let workspace = std::env::current_dir()?;
+            let home = std::env::var_os("HOME").map(PathBuf::from);
+            let (program, args) = sandbox.wrap(&shell, command, &workspace, home.as_deref());
+            let mut cmd = tokio::process::Command::new(program);
+            cmd.args(args);
+            cmd
+        },
+        None => {
+            let mut cmd = tokio::process::Command::new(shell);
+            cmd.arg("-c").arg(command);
+            cmd
+        },
+    };
+
+    cmd.stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .process_group(0)
+        .kill_on_drop(true)
+        .spawn()
+        .wrap_err_with(|| format!("Unable to spawn background command '{}'", command))
+}
+
+/// Sends `SIGTERM` to the process group led by `pid`.
+pub fn terminate_process_group(pid: u32) -> Result<()> {
+    nix::sys::signal::killpg(Pid::from_raw(pid as i32), Signal::SIGTERM)
+        .wrap_err_with(|| format!("Unable to terminate process {pid}"))
+}
+
+/// Sends `SIGKILL` to the process group led by `pid`.
+pub fn kill_process_group(pid: u32) -> Result<()> {
+    nix::sys::signal::killpg(Pid::from_raw(pid as i32), Signal::SIGKILL)
+        .wrap_err_with(|| format!("Unable to kill process {pid}"))
+}
+
+async fn wait_for_command<W: Write>(
+    command: &str,
+    mut child: Child,
+    max_result_size: usize,
+    mut updates: Option<W>,
+) -> Result<CommandResult> {
     let stdout_final: String;
     let stderr_final: String;
     let exit_status;
@@ -5,6 +5,7 @@ use std::process::Stdio;
 use eyre::{
     Context as EyreContext,
     Result,
+    bail,
 };
 use tokio::io::AsyncBufReadExt;
 use tokio::select;
@@ -12,6 +13,7 @@ use tracing::error;
 
 use super::{
     CommandResult,
+    SandboxSettings,
     format_output,
 };
 
@@ -112,6 +114,60 @@ pub async fn run_command<W: Write>(
     })
 }
 
+/// Sandboxing is not supported on Windows, see [SandboxSettings::is_available].
+pub async fn run_sandboxed_command<W: Write>(
+    _command: &str,
+    _sandbox: &SandboxSettings,
+    _max_result_size: usize,
+    _updates: Option<W>,
+) -> Result<CommandResult> {
+    bail!("The execute_cmd sandbox is not supported on Windows")
+}
+
+/// Spawn a command using cmd.exe whose output is read later.
+pub fn spawn_background_command(command: &str, sandbox: Option<&SandboxSettings>) -> Result<tokio::process::Child> {
+    if sandbox.is_some() {
+        bail!("The execute_cmd sandbox is not supported on Windows");
+    }
+    tokio::process::Command::new("cmd")
+        .arg("/C")
+        .arg(command)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true)
+        .spawn()
+        .wrap_err_with(|| format!("Unable to spawn background command '{}'", command))
+}
+
+/// Asks the process tree rooted at `pid` to terminate.
+pub fn terminate_process_group(pid: u32) -> Result<()> {
+    taskkill(pid, false)
+}
+
+/// Forcefully terminates the process tree rooted at `pid`.
+pub fn kill_process_group(pid: u32) -> Result<()> {
+    taskkill(pid, true)
+}
+
+fn taskkill(pid: u32, force: bool) -> Result<()> {
+    let mut cmd = std::process::Command::new("taskkill");
+    cmd.args(["/PID", &pid.to_string(), "/T"]);
+    if force {
+        cmd.arg("/F");
+    }
+    let output = cmd
+        .output()
+        .wrap_err_with(|| format!("Unable to terminate process {pid}"))?;
+    if !output.status.success() {
+        bail!(
+            "Unable to terminate process {pid}: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(())
+}
+
 #[cfg(test)]
 mod tests {
     use crate::cli::chat::tools::OutputKind;
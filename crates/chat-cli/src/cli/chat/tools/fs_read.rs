@@ -1,6 +1,11 @@
+use std::borrow::Cow;
 use std::collections::VecDeque;
 use std::fs::Metadata;
 use std::io::Write;
+use std::path::{
+    Path,
+    PathBuf,
+};
 
 use crossterm::queue;
 use crossterm::style::{
@@ -13,8 +18,14 @@ use eyre::{
 };
 use globset::{
     Glob,
+    GlobSet,
     GlobSetBuilder,
 };
+use ignore::WalkBuilder;
+use regex::{
+    Regex,
+    RegexBuilder,
+};
 use serde::{
     Deserialize,
     Serialize,
@@ -33,6 +44,7 @@ use super::{
     format_path,
     sanitize_path_tool_arg,
 };
+use crate::cli::agent::permission::RuleEffect;
 use crate::cli::agent::{
     Agent,
     PermissionEvalResult,
@@ -102,6 +114,25 @@ impl FsRead {
         }
     }
 
+    /// Returns every path read, as given to the tool.
+    pub fn permission_paths(&self) -> Vec<&str> {
+        self.operations
+            .iter()
+            .flat_map(|op| match op {
+                FsReadOperation::Line(FsLine { path, .. })
+                | FsReadOperation::Directory(FsDirectory { path, .. })
+                | FsReadOperation::Search(FsSearch { path, .. }) => vec![path.as_str()],
+                FsReadOperation::Image(fs_image) => fs_image.image_paths.iter().map(String::as_str).collect(),
+            })
+            .collect()
+    }
+
+    /// Whether the paths read match `pattern` of a permission rule like `fs_read(src/**)`.
+    pub fn matches_permission_pattern(&self, pattern: &str, effect: RuleEffect) -> bool {
+        let cwd = std::env::current_dir().unwrap_or_default();
+        effect.matches_path_glob(pattern, self.permission_paths(), &cwd, dirs::home_dir().as_deref())
+    }
+
     pub fn eval_perm(&self, agent: &Agent) -> PermissionEvalResult {
         #[derive(Debug, Deserialize)]
         #[serde(rename_all = "camelCase")]
@@ -132,29 +163,8 @@ impl FsRead {
                         return PermissionEvalResult::Ask;
                     },
                 };
-                let allow_set = {
-                    let mut builder = GlobSetBuilder::new();
-                    for path in &allowed_paths {
-                        if let Ok(glob) = Glob::new(path) {
-                            builder.add(glob);
-                        } else {
-                            warn!("Failed to create glob from path given: {path}. Ignoring.");
-                        }
-                    }
-                    builder.build()
-                };
-
-                let deny_set = {
-                    let mut builder = GlobSetBuilder::new();
-                    for path in &denied_paths {
-                        if let Ok(glob) = Glob::new(path) {
-                            builder.add(glob);
-                        } else {
-                            warn!("Failed to create glob from path given: {path}. Ignoring.");
-                        }
-                    }
-                    builder.build()
-                };
+                let allow_set = build_path_glob_set(&allowed_paths);
+                let deny_set = build_path_glob_set(&denied_paths);
 
                 match (allow_set, deny_set) {
                     (Ok(allow_set), Ok(deny_set)) => {
@@ -217,6 +227,33 @@ impl FsRead {
         }
     }
 
+    /// Propagates the agent's `deniedPaths` to directory searches, which otherwise only have
+    /// their root path checked by [Self::eval_perm].
+    pub fn set_denied_paths(&mut self, agent: &Agent) {
+        #[derive(Debug, Deserialize)]
+        #[serde(rename_all = "camelCase")]
+        struct Settings {
+            #[serde(default)]
+            denied_paths: Vec<String>,
+        }
+
+        let Some(settings) = agent.tools_settings.get("fs_read") else {
+            return;
+        };
+        let denied_paths = match serde_json::from_value::<Settings>(settings.clone()) {
+            Ok(settings) => settings.denied_paths,
+            Err(e) => {
+                error!("Failed to deserialize tool settings for fs_read: {:?}", e);
+                return;
+            },
+        };
+        for op in &mut self.operations {
+            if let FsReadOperation::Search(fs_search) = op {
+                fs_search.denied_paths = denied_paths.clone();
+            }
+        }
+    }
+
     pub async fn invoke(&self, os: &Os, updates: &mut impl Write) -> Result<InvokeOutput> {
         if self.operations.len() == 1 {
             // Single operation - return result directly
@@ -514,17 +551,34 @@ time. You tried to read {byte_count} bytes. Try executing with fewer lines speci
     }
 }
 
-/// Search in a file.
+/// Search in a file, or in every file under a directory.
 #[derive(Debug, Clone, Deserialize)]
 pub struct FsSearch {
     pub path: String,
     pub pattern: String,
     pub context_lines: Option<usize>,
+    /// Globs a file must match to be searched. Only applies when `path` is a directory.
+    #[serde(default)]
+    pub include: Vec<String>,
+    /// Globs of files to skip. Only applies when `path` is a directory.
+    #[serde(default)]
+    pub exclude: Vec<String>,
+    /// Whether `pattern` is a regular expression rather than a literal string.
+    pub regex: Option<bool>,
+    pub case_sensitive: Option<bool>,
+    pub max_results: Option<usize>,
+    /// Whether `.gitignore` (and `.ignore`) files are honored when walking a directory.
+    pub respect_gitignore: Option<bool>,
+    /// The `deniedPaths` of the active agent, filled in before the tool is executed so that files
+    /// discovered while walking a directory are subject to the same restrictions as `path`.
+    #[serde(skip)]
+    pub denied_paths: Vec<String>,
 }
 
 impl FsSearch {
     const CONTEXT_LINE_PREFIX: &str = "  ";
     const DEFAULT_CONTEXT_LINES: usize = 2;
+    const DEFAULT_MAX_RESULTS: usize = 100;
     const MATCHING_LINE_PREFIX: &str = "→ ";
 
     pub async fn validate(&mut self, os: &Os) -> Result<()> {
@@ -533,12 +587,19 @@ impl FsSearch {
         if !path.exists() {
             bail!("File not found: {}", relative_path);
         }
-        if !os.fs.symlink_metadata(path).await?.is_file() {
-            bail!("Path is not a file: {}", relative_path);
+        let md = os.fs.symlink_metadata(path).await?;
+        if !md.is_file() && !md.is_dir() {
+            bail!("Path is not a file or directory: {}", relative_path);
         }
         if self.pattern.is_empty() {
             bail!("Search pattern cannot be empty");
         }
+        if self.max_results == Some(0) {
+            bail!("max_results must be greater than 0");
+        }
+        self.matcher()?;
+        build_glob_set(&self.include)?;
+        build_glob_set(&self.exclude)?;
         Ok(())
     }
 
@@ -549,57 +610,122 @@ impl FsSearch {
             style::SetForegroundColor(Color::Green),
             style::Print(&self.path),
             style::ResetColor,
-            style::Print(" for pattern: "),
+            style::Print(" for "),
+            style::Print(if self.is_regex() { "regex: " } else { "pattern: " }),
             style::SetForegroundColor(Color::Green),
-            style::Print(&self.pattern.to_lowercase()),
+            style::Print(if self.is_case_sensitive() {
+                self.pattern.clone()
+            } else {
+                self.pattern.to_lowercase()
+            }),
             style::ResetColor,
         )?;
+        if !self.include.is_empty() {
+            queue!(
+                updates,
+                style::Print(" including: "),
+                style::SetForegroundColor(Color::Green),
+                style::Print(self.include.join(", ")),
+                style::ResetColor,
+            )?;
+        }
+        if !self.exclude.is_empty() {
+            queue!(
+                updates,
+                style::Print(" excluding: "),
+                style::SetForegroundColor(Color::Green),
+                style::Print(self.exclude.join(", ")),
+                style::ResetColor,
+            )?;
+        }
         Ok(())
     }
 
     pub async fn invoke(&self, os: &Os, updates: &mut impl Write) -> Result<InvokeOutput> {
-        let file_path = sanitize_path_tool_arg(os, &self.path);
-        let pattern = &self.pattern;
+        let path = sanitize_path_tool_arg(os, &self.path);
+        let matcher = self.matcher()?;
 
-        let file_bytes = os.fs.read(&file_path).await?;
-        let file_content = String::from_utf8_lossy(&file_bytes);
-        let file_content = sanitize_unicode_tags(&file_content);
-        let lines: Vec<&str> = LinesWithEndings::from(&file_content).collect();
+        if !os.fs.symlink_metadata(&path).await?.is_dir() {
+            let file_bytes = os.fs.read(&path).await?;
+            let (results, truncated) = self.search_content(&file_bytes, &matcher, self.max_results());
 
-        let mut results = Vec::new();
+            super::queue_function_result(
+                &format!(
+                    "Found {}{} matches for pattern '{}' in {}",
+                    results.len(),
+                    if truncated { "+" } else { "" },
+                    self.pattern,
+                    &path.display()
+                ),
+                updates,
+                false,
+                false,
+            )?;
+
+            // Complete results are the plain list of matches.
+            let result = if truncated {
+                serde_json::to_string(&FileSearchResult {
+                    matches: results,
+                    truncated,
+                })?
+            } else {
+                serde_json::to_string(&results)?
+            };
+            return Ok(InvokeOutput {
+                output: OutputKind::Text(result),
+            });
+        }
+
+        let mut files = Vec::new();
         let mut total_matches = 0;
+        let mut truncated = false;
+        for file in self.candidate_files(&path)? {
+            let file_bytes = match os.fs.read(&file).await {
+                Ok(bytes) => bytes,
+                Err(err) => {
+                    debug!(?file, ?err, "Skipping unreadable file");
+                    continue;
+                },
+            };
+            if is_binary(&file_bytes) {
+                continue;
+            }
 
-        // Case insensitive search
-        let pattern_lower = pattern.to_lowercase();
-        for (line_num, line) in lines.iter().enumerate() {
-            if line.to_lowercase().contains(&pattern_lower) {
-                total_matches += 1;
-                let start = line_num.saturating_sub(self.context_lines());
-                let end = lines.len().min(line_num + self.context_lines() + 1);
-                let mut context_text = Vec::new();
-                (start..end).for_each(|i| {
-                    let prefix = if i == line_num {
-                        Self::MATCHING_LINE_PREFIX
-                    } else {
-                        Self::CONTEXT_LINE_PREFIX
-                    };
-                    let line_text = lines[i].to_string();
-                    context_text.push(format!("{}{}: {}", prefix, i + 1, line_text));
-                });
-                let match_text = context_text.join("");
-                results.push(SearchMatch {
-                    line_number: line_num + 1,
-                    context: match_text,
+            let (matches, hit_limit) = self.search_content(&file_bytes, &matcher, self.max_results() - total_matches);
+            if !matches.is_empty() {
+                total_matches += matches.len();
+                files.push(FileSearchMatches {
+                    path: self.display_path(&path, &file),
+                    matches,
                 });
             }
+            if hit_limit {
+                truncated = true;
+                break;
+            }
+        }
+
+        let file_count = files.len();
+        let result = serde_json::to_string(&DirectorySearchResult {
+            files,
+            total_matches,
+            truncated,
+        })?;
+        let byte_count = result.len();
+        if byte_count > MAX_TOOL_RESPONSE_SIZE {
+            bail!(
+                "This tool only supports reading up to {MAX_TOOL_RESPONSE_SIZE} bytes at a time. The search produced {byte_count} bytes ({total_matches} matches). Try narrowing the search with include globs, fewer context lines, or a lower max_results."
+            );
         }
 
         super::queue_function_result(
             &format!(
-                "Found {} matches for pattern '{}' in {}",
+                "Found {}{} matches for pattern '{}' in {} files under {}",
                 total_matches,
-                pattern,
-                &file_path.display()
+                if truncated { "+" } else { "" },
+                self.pattern,
+                file_count,
+                &path.display()
             ),
             updates,
             false,
@@ -607,10 +733,121 @@ impl FsSearch {
         )?;
 
         Ok(InvokeOutput {
-            output: OutputKind::Text(serde_json::to_string(&results)?),
+            output: OutputKind::Text(result),
         })
     }
 
+    /// Returns the matches within `bytes`, up to `limit`, and whether more matches remained.
+    fn search_content(&self, bytes: &[u8], matcher: &Regex, limit: usize) -> (Vec<SearchMatch>, bool) {
+        let file_content = String::from_utf8_lossy(bytes);
+        let file_content = sanitize_unicode_tags(&file_content);
+        let lines: Vec<&str> = LinesWithEndings::from(&file_content).collect();
+
+        let mut results = Vec::new();
+        for (line_num, line) in lines.iter().enumerate() {
+            if !matcher.is_match(line) {
+                continue;
+            }
+            if results.len() == limit {
+                return (results, true);
+            }
+            let start = line_num.saturating_sub(self.context_lines());
+            let end = lines.len().min(line_num + self.context_lines() + 1);
+            let mut context_text = Vec::new();
+            (start..end).for_each(|i| {
+                let prefix = if i == line_num {
+                    Self::MATCHING_LINE_PREFIX
+                } else {
+                    Self::CONTEXT_LINE_PREFIX
+                };
+                let line_text = lines[i].to_string();
+                context_text.push(format!("{}{}: {}", prefix, i + 1, line_text));
+            });
+            results.push(SearchMatch {
+                line_number: line_num + 1,
+                context: context_text.join(""),
+            });
+        }
+        (results, false)
+    }
+
+    /// Walks `root` and returns the files to search, in a stable order.
+    fn candidate_files(&self, root: &Path) -> Result<Vec<PathBuf>> {
+        let include = build_glob_set(&self.include)?;
+        let exclude = build_glob_set(&self.exclude)?;
+        let denied = build_path_glob_set(&self.denied_paths)?;
+        let respect_gitignore = self.respect_gitignore.unwrap_or(true);
+
+        let walker = WalkBuilder::new(root)
+            .standard_filters(respect_gitignore)
+            .require_git(false)
+            .sort_by_file_name(|a, b| a.cmp(b))
+            .build();
+
+        let mut files = Vec::new();
+        for entry in walker {
+            let entry = match entry {
+                Ok(entry) => entry,
+                Err(err) => {
+                    warn!(?err, "Error while walking directory for search");
+                    continue;
+                },
+            };
+            if !entry.file_type().is_some_and(|ft| ft.is_file()) {
+                continue;
+            }
+            let relative = entry.path().strip_prefix(root).unwrap_or(entry.path());
+            if relative.components().any(|c| c.as_os_str() == ".git") {
+                continue;
+            }
+            if !self.include.is_empty() && !include.is_match(relative) {
+                continue;
+            }
+            if exclude.is_match(relative) {
+                continue;
+            }
+            if denied.is_match(Path::new(&self.path).join(relative)) {
+                debug!(path = ?entry.path(), "Skipping file matched by deniedPaths");
+                continue;
+            }
+            files.push(entry.into_path());
+        }
+        Ok(files)
+    }
+
+    /// The path of `file` as the model would refer to it, i.e. joined onto the given search path.
+    fn display_path(&self, root: &Path, file: &Path) -> String {
+        let relative = file.strip_prefix(root).unwrap_or(file);
+        Path::new(&self.path).join(relative).to_string_lossy().to_string()
+    }
+
+    fn matcher(&self) -> Result<Regex> {
+        let pattern = if self.is_regex() {
+            Cow::Borrowed(self.pattern.as_str())
+        } else {
+            Cow::Owned(regex::escape(&self.pattern))
+        };
+        match RegexBuilder::new(&pattern)
+            .case_insensitive(!self.is_case_sensitive())
+            .build()
+        {
+            Ok(regex) => Ok(regex),
+            Err(err) => bail!("Invalid search pattern '{}': {}", self.pattern, err),
+        }
+    }
+
+    fn is_regex(&self) -> bool {
+        self.regex.unwrap_or(false)
+    }
+
+    fn is_case_sensitive(&self) -> bool {
+        self.case_sensitive.unwrap_or(false)
+    }
+
+    fn max_results(&self) -> usize {
+        self.max_results.unwrap_or(Self::DEFAULT_MAX_RESULTS)
+    }
+
     fn context_lines(&self) -> usize {
         self.context_lines.unwrap_or(Self::DEFAULT_CONTEXT_LINES)
     }
@@ -774,6 +1011,58 @@ struct SearchMatch {
     context: String,
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct FileSearchMatches {
+    path: String,
+    matches: Vec<SearchMatch>,
+}
+
+/// The result of searching a single file that has more matches than `max_results`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct FileSearchResult {
+    matches: Vec<SearchMatch>,
+    truncated: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct DirectorySearchResult {
+    files: Vec<FileSearchMatches>,
+    total_matches: usize,
+    truncated: bool,
+}
+
+fn build_glob_set(globs: &[String]) -> Result<GlobSet> {
+    let mut builder = GlobSetBuilder::new();
+    for glob in globs {
+        match Glob::new(glob) {
+            Ok(glob) => {
+                builder.add(glob);
+            },
+            Err(err) => bail!("Invalid glob '{}': {}", glob, err),
+        }
+    }
+    Ok(builder.build()?)
+}
+
+/// Builds a glob set from the paths configured in the agent's tool settings, ignoring the invalid
+/// ones so that one bad pattern does not stop the others from applying.
+fn build_path_glob_set(paths: &[String]) -> Result<GlobSet, globset::Error> {
+    let mut builder = GlobSetBuilder::new();
+    for path in paths {
+        if let Ok(glob) = Glob::new(path) {
+            builder.add(glob);
+        } else {
+            warn!("Failed to create glob from path given: {path}. Ignoring.");
+        }
+    }
+    builder.build()
+}
+
+/// Heuristic used by most search tools: a NUL byte near the start of a file means it is binary.
+fn is_binary(bytes: &[u8]) -> bool {
+    bytes.iter().take(8192).any(|b| *b == 0)
+}
+
 fn format_ftype(md: &Metadata) -> char {
     if md.is_symlink() {
         'l'
@@ -1031,6 +1320,158 @@ mod tests {
                 FsSearch::CONTEXT_LINE_PREFIX
             )
         );
+
+        let output = serde_json::from_value::<FsRead>(serde_json::json!({
+            "operations": [{ "mode": "Search", "path": TEST_FILE_PATH, "pattern": "hello", "max_results": 1 }]
+        }))
+        .unwrap()
+        .invoke(&os, &mut stdout)
+        .await
+        .unwrap();
+        let OutputKind::Text(value) = output.output else {
+            panic!("expected Text output")
+        };
+        let result = serde_json::from_str::<FileSearchResult>(&value).unwrap();
+        assert_eq!(result.matches.len(), 1);
+        assert!(result.truncated);
+    }
+
+    #[tokio::test]
+    async fn test_fs_search_directory_invoke() {
+        let os = Os::new().await.unwrap();
+        let mut stdout = std::io::stdout();
+        os.fs.create_dir_all("/project/src/nested").await.unwrap();
+        os.fs.create_dir_all("/project/target").await.unwrap();
+        os.fs.write("/project/.gitignore", "target/\n").await.unwrap();
+        os.fs
+            .write("/project/src/main.rs", "fn main() {\n    todo!(\"Hello\");\n}\n")
+            .await
+            .unwrap();
+        os.fs
+            .write("/project/src/nested/lib.rs", "// hello from lib\npub fn hello() {}\n")
+            .await
+            .unwrap();
+        os.fs.write("/project/src/notes.md", "hello notes\n").await.unwrap();
+        os.fs
+            .write("/project/target/out.rs", "hello build output\n")
+            .await
+            .unwrap();
+        os.fs.write("/project/src/data.bin", b"hello\0binary").await.unwrap();
+
+        macro_rules! invoke_search {
+            ($value:tt) => {{
+                let v = serde_json::json!({ "operations": [$value] });
+                let output = serde_json::from_value::<FsRead>(v)
+                    .unwrap()
+                    .invoke(&os, &mut stdout)
+                    .await
+                    .unwrap();
+
+                if let OutputKind::Text(value) = output.output {
+                    serde_json::from_str::<DirectorySearchResult>(&value).unwrap()
+                } else {
+                    panic!("expected Text output")
+                }
+            }};
+        }
+
+        // gitignored and binary files are skipped, results are grouped per file.
+        let result = invoke_search!({ "mode": "Search", "path": "/project", "pattern": "hello" });
+        let paths = result.files.iter().map(|f| f.path.as_str()).collect::<Vec<_>>();
+        assert_eq!(paths, vec![
+            "/project/src/main.rs",
+            "/project/src/nested/lib.rs",
+            "/project/src/notes.md"
+        ]);
+        assert_eq!(result.total_matches, 4);
+        assert!(!result.truncated);
+        assert_eq!(result.files[0].matches[0].line_number, 2);
+
+        // include globs, gitignore disabled
+        let result = invoke_search!({
+            "mode": "Search",
+            "path": "/project",
+            "pattern": "hello",
+            "include": ["**/*.rs"],
+            "respect_gitignore": false,
+        });
+        let paths = result.files.iter().map(|f| f.path.as_str()).collect::<Vec<_>>();
+        assert_eq!(paths, vec![
+            "/project/src/main.rs",
+            "/project/src/nested/lib.rs",
+            "/project/target/out.rs"
+        ]);
+
+        // exclude globs and case sensitivity
+        let result = invoke_search!({
+            "mode": "Search",
+            "path": "/project",
+            "pattern": "Hello",
+            "case_sensitive": true,
+            "exclude": ["*.md"],
+        });
+        assert_eq!(result.total_matches, 1);
+        assert_eq!(result.files[0].path, "/project/src/main.rs");
+
+        // regex and max_results
+        let result = invoke_search!({
+            "mode": "Search",
+            "path": "/project",
+            "pattern": r"^(pub )?fn \w+",
+            "regex": true,
+            "max_results": 1,
+        });
+        assert_eq!(result.total_matches, 1);
+        assert!(result.truncated);
+    }
+
+    #[tokio::test]
+    async fn test_fs_search_directory_denied_paths() {
+        let os = Os::new().await.unwrap();
+        let mut stdout = std::io::stdout();
+        os.fs.create_dir_all("/project/secrets").await.unwrap();
+        os.fs.write("/project/config.txt", "token=abc\n").await.unwrap();
+        os.fs.write("/project/secrets/prod.txt", "token=xyz\n").await.unwrap();
+
+        let agent = serde_json::from_value::<Agent>(serde_json::json!({
+            "name": "test",
+            "allowedTools": ["fs_read"],
+            "toolsSettings": { "fs_read": { "deniedPaths": ["/project/secrets/**", "/project/[invalid"] } }
+        }))
+        .unwrap();
+
+        let mut fs_read = serde_json::from_value::<FsRead>(serde_json::json!({
+            "operations": [{ "mode": "Search", "path": "/project", "pattern": "token" }]
+        }))
+        .unwrap();
+        assert_eq!(fs_read.eval_perm(&agent), PermissionEvalResult::Allow);
+        fs_read.set_denied_paths(&agent);
+
+        let output = fs_read.invoke(&os, &mut stdout).await.unwrap();
+        if let OutputKind::Text(value) = output.output {
+            let result = serde_json::from_str::<DirectorySearchResult>(&value).unwrap();
+            assert_eq!(result.files.len(), 1);
+            assert_eq!(result.files[0].path, "/project/config.txt");
+        } else {
+            panic!("expected Text output");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_fs_search_validate() {
+        let os = setup_test_directory().await;
+
+        let mut fs_read = serde_json::from_value::<FsRead>(serde_json::json!({
+            "operations": [{ "mode": "Search", "path": "/", "pattern": "(unclosed", "regex": true }]
+        }))
+        .unwrap();
+        assert!(fs_read.validate(&os).await.is_err());
+
+        let mut fs_read = serde_json::from_value::<FsRead>(serde_json::json!({
+            "operations": [{ "mode": "Search", "path": "/", "pattern": "(unclosed" }]
+        }))
+        .unwrap();
+        assert!(fs_read.validate(&os).await.is_ok());
     }
 
     #[tokio::test]
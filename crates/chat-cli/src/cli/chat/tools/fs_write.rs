@@ -1,5 +1,8 @@
 use std::io::Write;
-use std::path::Path;
+use std::path::{
+    Path,
+    PathBuf,
+};
 use std::sync::LazyLock;
 
 use crossterm::queue;
@@ -37,10 +40,16 @@ use super::{
     sanitize_path_tool_arg,
     supports_truecolor,
 };
+use crate::cli::agent::permission::RuleEffect;
 use crate::cli::agent::{
     Agent,
     PermissionEvalResult,
 };
+use crate::cli::chat::util::patch::{
+    AppliedPatch,
+    FilePatch,
+    parse_unified_diff,
+};
 use crate::os::Os;
 
 static SYNTAX_SET: LazyLock<SyntaxSet> = LazyLock::new(SyntaxSet::load_defaults_newlines);
@@ -78,6 +87,14 @@ pub enum FsWrite {
         new_str: String,
         summary: Option<String>,
     },
+    /// Applies a unified diff that may cover several files. Relative paths in the diff are
+    /// resolved against `path`, which must be a directory.
+    #[serde(rename = "patch")]
+    Patch {
+        path: String,
+        patch: String,
+        summary: Option<String>,
+    },
 }
 
 impl FsWrite {
@@ -181,6 +198,30 @@ impl FsWrite {
                 write_to_file(os, path, file).await?;
                 Ok(Default::default())
             },
+            FsWrite::Patch { path, patch, .. } => {
+                // Re-plan rather than trusting validation, the files may have changed since.
+                let planned = plan_patch(os, path, patch)?;
+                let mut writes = Vec::new();
+                for resolved in &planned {
+                    let (description, target) = match (&resolved.file.old_path, &resolved.applied) {
+                        (None, _) => ("Creating: ", &resolved.path),
+                        (Some(_), None) => ("Deleting: ", &resolved.source),
+                        (Some(_), Some(_)) if resolved.source != resolved.path => ("Renaming: ", &resolved.path),
+                        (Some(_), Some(_)) => ("Updating: ", &resolved.path),
+                    };
+                    queue!(
+                        output,
+                        style::Print(description),
+                        style::SetForegroundColor(Color::Green),
+                        style::Print(format_path(&cwd, target)),
+                        style::ResetColor,
+                        style::Print("\n"),
+                    )?;
+                    writes.extend(resolved.writes());
+                }
+                apply_writes(os, writes).await?;
+                Ok(Default::default())
+            },
         }
     }
 
@@ -264,6 +305,45 @@ impl FsWrite {
                 // Display summary as purpose if available after the diff
                 super::display_purpose(self.get_summary(), output)?;
 
+                Ok(())
+            },
+            FsWrite::Patch { path, patch, .. } => {
+                for resolved in plan_patch(os, path, patch)? {
+                    let relative_path = format_path(&cwd, &resolved.path);
+                    queue!(
+                        output,
+                        style::SetForegroundColor(Color::Green),
+                        style::Print(&relative_path),
+                        style::ResetColor,
+                    )?;
+                    match (&resolved.file.old_path, &resolved.applied) {
+                        (None, _) => queue!(output, style::Print(" (new file)"))?,
+                        (Some(_), None) => queue!(output, style::Print(" (deleted)"))?,
+                        (Some(_), Some(_)) if resolved.source != resolved.path => queue!(
+                            output,
+                            style::Print(" (renamed from "),
+                            style::Print(format_path(&cwd, &resolved.source)),
+                            style::Print(")"),
+                        )?,
+                        _ => (),
+                    }
+                    queue!(output, style::Print("\n"))?;
+
+                    let hunk_starts = resolved.applied.as_ref().map_or_else(
+                        || vec![1; resolved.file.hunks.len()],
+                        |applied| applied.hunk_starts.clone(),
+                    );
+                    for (hunk, start_line) in resolved.file.hunks.iter().zip(hunk_starts) {
+                        let old = stylize_output_if_able(os, &relative_path, &join_lines(&hunk.old_lines()));
+                        let new = stylize_output_if_able(os, &relative_path, &join_lines(&hunk.new_lines()));
+                        print_diff(output, &old, &new, start_line)?;
+                    }
+                    queue!(output, style::Print("\n"))?;
+                }
+
+                // Display summary as purpose if available after the diff
+                super::display_purpose(self.get_summary(), output)?;
+
                 Ok(())
             },
         }
@@ -290,6 +370,17 @@ impl FsWrite {
                     bail!("Content to append must not be empty")
                 };
             },
+            FsWrite::Patch { path, patch, .. } => {
+                let base = sanitize_path_tool_arg(os, &*path);
+                if !base.is_dir() {
+                    bail!("The provided path must be the directory that paths in the patch are relative to")
+                }
+                if patch.trim().is_empty() {
+                    bail!("Patch must not be empty")
+                }
+                // Check that every hunk applies before anything is shown to the user.
+                plan_patch(os, path, patch)?;
+            },
         }
 
         Ok(())
@@ -302,6 +393,7 @@ impl FsWrite {
             FsWrite::StrReplace { path, .. } => path,
             FsWrite::Insert { path, .. } => path,
             FsWrite::Append { path, .. } => path,
+            FsWrite::Patch { path, .. } => path,
         };
         // Sanitize the path to handle tilde expansion
         let path = sanitize_path_tool_arg(os, path);
@@ -343,9 +435,56 @@ impl FsWrite {
             FsWrite::StrReplace { summary, .. } => summary.as_ref(),
             FsWrite::Insert { summary, .. } => summary.as_ref(),
             FsWrite::Append { summary, .. } => summary.as_ref(),
+            FsWrite::Patch { summary, .. } => summary.as_ref(),
+        }
+    }
+
+    /// Returns the sanitized paths of every file that [Self::invoke] may create, modify, or
+    /// delete.
+    pub fn paths(&self, os: &Os) -> Vec<PathBuf> {
+        match self {
+            FsWrite::Create { path, .. }
+            | FsWrite::StrReplace { path, .. }
+            | FsWrite::Insert { path, .. }
+            | FsWrite::Append { path, .. } => vec![sanitize_path_tool_arg(os, path)],
+            // A patch that fails to plan is not applied, so there is nothing to report.
+            FsWrite::Patch { path, patch, .. } => plan_patch(os, path, patch)
+                .unwrap_or_default()
+                .iter()
+                .flat_map(|resolved| resolved.writes().into_iter().map(|(path, ..)| path))
+                .collect(),
+        }
+    }
+
+    /// Returns every path written, as given to the tool. Unlike [Self::paths], the paths of a
+    /// patch are known even if it does not apply.
+    pub fn permission_paths(&self) -> Vec<String> {
+        match self {
+            Self::Create { path, .. }
+            | Self::Insert { path, .. }
+            | Self::Append { path, .. }
+            | Self::StrReplace { path, .. } => vec![path.clone()],
+            Self::Patch { path, patch, .. } => parse_unified_diff(patch)
+                .unwrap_or_default()
+                .iter()
+                .flat_map(|f| [f.old_path.as_deref(), f.new_path.as_deref()])
+                .flatten()
+                .map(|p| resolve_patch_path(path, p))
+                .collect(),
         }
     }
 
+    /// Whether the paths written match `pattern` of a permission rule like `fs_write(src/**)`.
+    pub fn matches_permission_pattern(&self, pattern: &str, effect: RuleEffect) -> bool {
+        let cwd = std::env::current_dir().unwrap_or_default();
+        effect.matches_path_glob(
+            pattern,
+            self.permission_paths().iter().map(String::as_str),
+            &cwd,
+            dirs::home_dir().as_deref(),
+        )
+    }
+
     pub fn eval_perm(&self, agent: &Agent) -> PermissionEvalResult {
         #[derive(Debug, Deserialize)]
         #[serde(rename_all = "camelCase")]
@@ -407,6 +546,23 @@ impl FsWrite {
                                     return PermissionEvalResult::Allow;
                                 }
                             },
+                            Self::Patch { path, patch, .. } => {
+                                let Ok(files) = parse_unified_diff(patch) else {
+                                    return PermissionEvalResult::Ask;
+                                };
+                                let targets = files
+                                    .iter()
+                                    .flat_map(|f| [f.old_path.as_deref(), f.new_path.as_deref()])
+                                    .flatten()
+                                    .map(|p| resolve_patch_path(path, p))
+                                    .collect::<Vec<_>>();
+                                if targets.iter().any(|p| deny_set.is_match(p)) {
+                                    return PermissionEvalResult::Deny;
+                                }
+                                if !targets.is_empty() && targets.iter().all(|p| allow_set.is_match(p)) {
+                                    return PermissionEvalResult::Allow;
+                                }
+                            },
                         }
                         PermissionEvalResult::Ask
                     },
@@ -442,6 +598,138 @@ async fn write_to_file(os: &Os, path: impl AsRef<Path>, mut content: String) ->
     Ok(())
 }
 
+/// A [FilePatch] resolved against the file system, with every hunk already applied in memory.
+struct ResolvedPatch {
+    file: FilePatch,
+    /// The file the hunks were applied to. Differs from [Self::path] for renames.
+    source: PathBuf,
+    /// The file that is written to.
+    path: PathBuf,
+    original: Option<String>,
+    /// [None] if the file is deleted.
+    applied: Option<AppliedPatch>,
+}
+
+impl ResolvedPatch {
+    /// The file system changes required to apply this patch, as `(path, original, updated)`.
+    fn writes(&self) -> Vec<(PathBuf, Option<String>, Option<String>)> {
+        let updated = self.applied.as_ref().map(|applied| applied.content.clone());
+        if self.source != self.path && self.original.is_some() {
+            vec![
+                (self.source.clone(), self.original.clone(), None),
+                (self.path.clone(), None, updated),
+            ]
+        } else {
+            vec![(self.path.clone(), self.original.clone(), updated)]
+        }
+    }
+}
+
+/// Resolves a path from a diff header against the `path` argument of the patch command, in the
+/// form the model would refer to it.
+fn resolve_patch_path(base: &str, path: &str) -> String {
+    if Path::new(path).is_absolute() {
+        path.to_string()
+    } else {
+        Path::new(base).join(path).to_string_lossy().to_string()
+    }
+}
+
+/// Parses `patch` and applies every hunk in memory, without writing anything. Fails with the
+/// exact file and hunk if any of them does not apply.
+fn plan_patch(os: &Os, base: &str, patch: &str) -> Result<Vec<ResolvedPatch>> {
+    let files = parse_unified_diff(patch)?;
+    let mut planned: Vec<ResolvedPatch> = Vec::with_capacity(files.len());
+    for file in files {
+        let target = resolve_patch_path(base, file.target_path());
+        let path = sanitize_path_tool_arg(os, &target);
+        let source = match &file.old_path {
+            Some(old_path) => sanitize_path_tool_arg(os, resolve_patch_path(base, old_path)),
+            None => path.clone(),
+        };
+        if planned.iter().any(|p| p.path == path || p.source == source) {
+            bail!("The patch contains more than one section for {target}, combine them into one");
+        }
+
+        let original = match &file.old_path {
+            Some(_) => {
+                if !source.is_file() {
+                    bail!("Failed to apply patch to {target}: {} does not exist", source.display());
+                }
+                Some(os.fs.read_to_string_sync(&source)?)
+            },
+            None => {
+                if path.exists() {
+                    bail!("Failed to apply patch to {target}: the patch creates the file, but it already exists");
+                }
+                None
+            },
+        };
+        if file.new_path.is_some() && source != path && path.exists() {
+            bail!("Failed to apply patch to {target}: the patch renames a file to it, but it already exists");
+        }
+
+        let applied = file
+            .apply(original.as_deref().unwrap_or_default())
+            .map_err(|err| eyre!("Failed to apply patch to {target}: {err}"))?;
+        let applied = match file.new_path {
+            Some(_) => Some(applied),
+            None if applied.content.is_empty() => None,
+            None => bail!(
+                "Failed to apply patch to {target}: the file is deleted, but its hunks do not remove all of its content"
+            ),
+        };
+
+        planned.push(ResolvedPatch {
+            file,
+            source,
+            path,
+            original,
+            applied,
+        });
+    }
+    Ok(planned)
+}
+
+/// Performs every write, or none of them: if one fails, the files already written are restored.
+async fn apply_writes(os: &Os, writes: Vec<(PathBuf, Option<String>, Option<String>)>) -> Result<()> {
+    async fn apply(os: &Os, path: &Path, content: Option<&String>) -> std::io::Result<()> {
+        match content {
+            Some(content) => {
+                if let Some(parent) = path.parent() {
+                    os.fs.create_dir_all(parent).await?;
+                }
+                os.fs.write(path, content).await
+            },
+            None => os.fs.remove_file(path).await,
+        }
+    }
+
+    for (i, (path, _, updated)) in writes.iter().enumerate() {
+        if let Err(err) = apply(os, path, updated.as_ref()).await {
+            for (path, original, _) in writes[..i].iter().rev() {
+                if let Err(err) = apply(os, path, original.as_ref()).await {
+                    error!(?err, ?path, "failed to restore file after a failed patch");
+                }
+            }
+            bail!(
+                "Failed to write {}: {}. No changes from the patch were kept.",
+                path.display(),
+                err
+            );
+        }
+    }
+    Ok(())
+}
+
+fn join_lines(lines: &[&str]) -> String {
+    let mut s = lines.join("\n");
+    if !lines.is_empty() {
+        s.push('\n');
+    }
+    s
+}
+
 /// Returns a prefix/suffix pair before and after the content dictated by `[start_line, end_line]`
 /// within `content`. The updated start and end lines containing the original context along with
 /// the suffix and prefix are returned.
@@ -929,6 +1217,138 @@ mod tests {
         );
     }
 
+    #[tokio::test]
+    async fn test_fs_write_tool_patch() {
+        let os = setup_test_directory().await;
+        let mut stdout = std::io::stdout();
+        os.fs.create_dir_all("/repo/src").await.unwrap();
+        os.fs
+            .write("/repo/src/lib.rs", "fn a() {}\n\nfn b() {}\n\nfn c() {}\n")
+            .await
+            .unwrap();
+        os.fs.write("/repo/README.md", "# Title\nold\n").await.unwrap();
+        os.fs.write("/repo/obsolete.txt", "bye\n").await.unwrap();
+
+        let patch = "\
+diff --git a/src/lib.rs b/src/lib.rs
+--- a/src/lib.rs
++++ b/src/lib.rs
+@@ -1,3 +1,3 @@
+-fn a() {}
++fn a() -> u8 { 1 }
+ 
+ fn b() {}
+@@ -5 +5,2 @@
+ fn c() {}
++fn d() {}
+--- a/README.md
++++ b/README.md
+@@ -1,2 +1,2 @@
+ # Title
+-old
++new
+--- /dev/null
++++ b/src/new.rs
+@@ -0,0 +1 @@
++pub struct New;
+--- a/obsolete.txt
++++ /dev/null
+@@ -1 +0,0 @@
+-bye
+";
+        let mut fw = serde_json::from_value::<FsWrite>(serde_json::json!({
+            "path": "/repo",
+            "command": "patch",
+            "patch": patch,
+        }))
+        .unwrap();
+        fw.validate(&os).await.unwrap();
+        fw.queue_description(&os, &mut Vec::new()).unwrap();
+        fw.invoke(&os, &mut stdout).await.unwrap();
+
+        assert_eq!(
+            os.fs.read_to_string("/repo/src/lib.rs").await.unwrap(),
+            "fn a() -> u8 { 1 }\n\nfn b() {}\n\nfn c() {}\nfn d() {}\n"
+        );
+        assert_eq!(os.fs.read_to_string("/repo/README.md").await.unwrap(), "# Title\nnew\n");
+        assert_eq!(
+            os.fs.read_to_string("/repo/src/new.rs").await.unwrap(),
+            "pub struct New;\n"
+        );
+        assert!(!os.fs.exists("/repo/obsolete.txt"));
+    }
+
+    #[tokio::test]
+    async fn test_fs_write_tool_patch_failing_hunk_writes_nothing() {
+        let os = setup_test_directory().await;
+        let mut stdout = std::io::stdout();
+
+        let patch = "\
+--- a/test_file.txt
++++ b/test_file.txt
+@@ -1 +1 @@
+-1: Hello world!
++1: Goodbye world!
+@@ -3 +3 @@
+-3: qwerty
++3: zxcv
+--- /dev/null
++++ b/created.txt
+@@ -0,0 +1 @@
++should not exist
+";
+        let mut fw = serde_json::from_value::<FsWrite>(serde_json::json!({
+            "path": "/",
+            "command": "patch",
+            "patch": patch,
+        }))
+        .unwrap();
+        let err = fw.validate(&os).await.unwrap_err().to_string();
+        assert!(err.contains("/test_file.txt"), "{err}");
+        assert!(err.contains("hunk #2 (@@ -3,1 +3,1 @@)"), "{err}");
+        assert!(err.contains("3: qwerty"), "{err}");
+
+        assert!(fw.invoke(&os, &mut stdout).await.is_err());
+        assert_eq!(os.fs.read_to_string(TEST_FILE_PATH).await.unwrap(), TEST_FILE_CONTENTS);
+        assert!(!os.fs.exists("/created.txt"));
+    }
+
+    #[test]
+    fn test_fs_write_patch_eval_perm() {
+        let agent = serde_json::from_value::<Agent>(serde_json::json!({
+            "name": "test",
+            "allowedTools": ["fs_write"],
+            "toolsSettings": {
+                "fs_write": { "allowedPaths": ["/repo/src/**"], "deniedPaths": ["/repo/.env"] }
+            }
+        }))
+        .unwrap();
+        let patch_tool = |patch: &str| {
+            serde_json::from_value::<FsWrite>(serde_json::json!({
+                "path": "/repo",
+                "command": "patch",
+                "patch": patch,
+            }))
+            .unwrap()
+        };
+
+        let allowed = patch_tool("--- a/src/a.rs\n+++ b/src/a.rs\n@@ -1 +1 @@\n-a\n+b\n");
+        assert_eq!(allowed.eval_perm(&agent), PermissionEvalResult::Allow);
+
+        let partially_allowed = patch_tool(
+            "--- a/src/a.rs\n+++ b/src/a.rs\n@@ -1 +1 @@\n-a\n+b\n--- a/Cargo.toml\n+++ b/Cargo.toml\n@@ -1 +1 @@\n-a\n+b\n",
+        );
+        assert_eq!(partially_allowed.eval_perm(&agent), PermissionEvalResult::Ask);
+
+        let denied = patch_tool(
+            "--- a/src/a.rs\n+++ b/src/a.rs\n@@ -1 +1 @@\n-a\n+b\n--- /dev/null\n+++ b/.env\n@@ -0,0 +1 @@\n+SECRET=1\n",
+        );
+        assert_eq!(denied.eval_perm(&agent), PermissionEvalResult::Deny);
+
+        let escaping = patch_tool("--- a/src/../../.bashrc\n+++ b/src/../../.bashrc\n@@ -1 +1 @@\n-a\n+b\n");
+        assert_eq!(escaping.eval_perm(&agent), PermissionEvalResult::Ask);
+    }
+
     #[tokio::test]
     async fn test_fs_write_tool_insert_at_beginning() {
         let os = setup_test_directory().await;
@@ -1,3 +1,4 @@
+pub mod background_process;
 pub mod custom_tool;
 pub mod execute;
 pub mod fs_read;
@@ -6,17 +7,23 @@ pub mod gh_issue;
 pub mod knowledge;
 pub mod thinking;
 pub mod use_aws;
+pub mod use_subagent;
 
 use std::borrow::{
     Borrow,
     Cow,
 };
+use std::collections::HashSet;
 use std::io::Write;
 use std::path::{
     Path,
     PathBuf,
 };
 
+use background_process::{
+    BackgroundProcess,
+    ProcessOperation,
+};
 use crossterm::queue;
 use crossterm::style::{
     self,
@@ -36,9 +43,14 @@ use serde::{
 use thinking::Thinking;
 use tracing::error;
 use use_aws::UseAws;
+use use_subagent::UseSubagent;
 
 use super::consts::MAX_TOOL_RESPONSE_SIZE;
 use super::util::images::RichImageBlocks;
+use crate::cli::agent::permission::{
+    RuleEffect,
+    directory_rule,
+};
 use crate::cli::agent::{
     Agent,
     PermissionEvalResult,
@@ -46,17 +58,19 @@ use crate::cli::agent::{
 use crate::os::Os;
 
 pub const DEFAULT_APPROVE: [&str; 1] = ["fs_read"];
-pub const NATIVE_TOOLS: [&str; 7] = [
+pub const NATIVE_TOOLS: [&str; 9] = [
     "fs_read",
     "fs_write",
     #[cfg(windows)]
     "execute_cmd",
     #[cfg(not(windows))]
     "execute_bash",
+    "background_process",
     "use_aws",
     "gh_issue",
     "knowledge",
     "thinking",
+    "use_subagent",
 ];
 
 /// Represents an executable tool use.
@@ -66,11 +80,13 @@ pub enum Tool {
     FsRead(FsRead),
     FsWrite(FsWrite),
     ExecuteCommand(ExecuteCommand),
+    BackgroundProcess(BackgroundProcess),
     UseAws(UseAws),
     Custom(CustomTool),
     GhIssue(GhIssue),
     Knowledge(Knowledge),
     Thinking(Thinking),
+    UseSubagent(UseSubagent),
 }
 
 impl Tool {
@@ -83,40 +99,124 @@ impl Tool {
             Tool::ExecuteCommand(_) => "execute_cmd",
             #[cfg(not(windows))]
             Tool::ExecuteCommand(_) => "execute_bash",
+            Tool::BackgroundProcess(_) => "background_process",
             Tool::UseAws(_) => "use_aws",
             Tool::Custom(custom_tool) => &custom_tool.name,
             Tool::GhIssue(_) => "gh_issue",
             Tool::Knowledge(_) => "knowledge",
             Tool::Thinking(_) => "thinking (prerelease)",
+            Tool::UseSubagent(_) => "use_subagent",
         }
         .to_owned()
     }
 
+    /// The name of the tool as written in agent configs, such as `fs_read` or `@git/git_status`.
+    pub fn qualified_name(&self) -> String {
+        match self {
+            Tool::Custom(custom_tool) => format!(
+                "@{}{}{}",
+                custom_tool.client.get_server_name(),
+                crate::util::MCP_SERVER_TOOL_DELIMITER,
+                custom_tool.name
+            ),
+            Tool::Thinking(_) => "thinking".to_string(),
+            _ => self.display_name(),
+        }
+    }
+
     /// Whether or not the tool should prompt the user to accept before [Self::invoke] is called.
+    ///
+    /// The [Agent::permissions] rules are evaluated first, falling back to the allowed tools and
+    /// tool settings of the agent.
     pub fn requires_acceptance(&self, agent: &Agent) -> PermissionEvalResult {
+        let cwd = std::env::current_dir().unwrap_or_default();
+        if let Some(result) = agent.permissions.eval(&self.qualified_name(), &cwd, |pattern, effect| {
+            self.matches_permission_pattern(pattern, effect)
+        }) {
+            return result;
+        }
+
         match self {
             Tool::FsRead(fs_read) => fs_read.eval_perm(agent),
             Tool::FsWrite(fs_write) => fs_write.eval_perm(agent),
             Tool::ExecuteCommand(execute_command) => execute_command.eval_perm(agent),
+            Tool::BackgroundProcess(background_process) => background_process.eval_perm(agent),
             Tool::UseAws(use_aws) => use_aws.eval_perm(agent),
             Tool::Custom(custom_tool) => custom_tool.eval_perm(agent),
             Tool::GhIssue(_) => PermissionEvalResult::Allow,
             Tool::Thinking(_) => PermissionEvalResult::Allow,
+            Tool::UseSubagent(_) => UseSubagent::eval_perm(agent),
             Tool::Knowledge(knowledge) => knowledge.eval_perm(agent),
         }
     }
 
+    /// Whether the arguments of the tool match `pattern` of a permission rule, such as the paths
+    /// read by `fs_read`. Tools without arguments worth matching never match a pattern.
+    fn matches_permission_pattern(&self, pattern: &str, effect: RuleEffect) -> bool {
+        match self {
+            Tool::FsRead(fs_read) => fs_read.matches_permission_pattern(pattern, effect),
+            Tool::FsWrite(fs_write) => fs_write.matches_permission_pattern(pattern, effect),
+            Tool::ExecuteCommand(execute_command) => execute_command.matches_permission_pattern(pattern, effect),
+            Tool::BackgroundProcess(background_process) => {
+                background_process.matches_permission_pattern(pattern, effect)
+            },
+            Tool::UseAws(use_aws) => use_aws.matches_permission_pattern(pattern, effect),
+            Tool::Custom(custom_tool) => custom_tool.matches_permission_pattern(pattern, effect),
+            Tool::GhIssue(_) | Tool::Knowledge(_) | Tool::Thinking(_) | Tool::UseSubagent(_) => false,
+        }
+    }
+
+    /// Suggests a narrowly scoped permission rule that would allow this tool use in the current
+    /// directory `cwd`, such as `fs_write(src/**) in /repo` or `execute_bash(cargo test *) in
+    /// /repo`.
+    pub fn suggested_permission_rule(&self, cwd: &Path) -> Option<String> {
+        let name = self.qualified_name();
+        let rule = match self {
+            Tool::FsRead(fs_read) => directory_rule(&name, fs_read.permission_paths()),
+            Tool::FsWrite(fs_write) => directory_rule(&name, fs_write.permission_paths().iter().map(String::as_str)),
+            Tool::ExecuteCommand(execute_command) => execute_command
+                .suggested_permission_pattern()
+                .map(|pattern| format!("{name}({pattern})")),
+            Tool::UseAws(use_aws) => Some(format!("{name}({} {})", use_aws.service_name, use_aws.operation_name)),
+            Tool::BackgroundProcess(background_process) => {
+                Some(format!("{name}({})", background_process.operation_name()))
+            },
+            Tool::Custom(_) | Tool::GhIssue(_) | Tool::Knowledge(_) | Tool::Thinking(_) | Tool::UseSubagent(_) => {
+                Some(name)
+            },
+        }?;
+        Some(format!("{rule} in {}", cwd.display()))
+    }
+
+    /// Whether the tool only reads, and so can run at the same time as other tool uses.
+    pub fn is_read_only(&self) -> bool {
+        match self {
+            Tool::FsRead(_) | Tool::Thinking(_) => true,
+            Tool::ExecuteCommand(execute_command) => {
+                !execute_command.background && !execute_command.requires_acceptance(None, true)
+            },
+            Tool::BackgroundProcess(background_process) => matches!(
+                background_process.operation,
+                ProcessOperation::List | ProcessOperation::Read { .. } | ProcessOperation::Status { .. }
+            ),
+            Tool::UseAws(use_aws) => !use_aws.requires_acceptance(),
+            Tool::FsWrite(_) | Tool::Custom(_) | Tool::GhIssue(_) | Tool::Knowledge(_) | Tool::UseSubagent(_) => false,
+        }
+    }
+
     /// Invokes the tool asynchronously
     pub async fn invoke(&self, os: &Os, stdout: &mut impl Write) -> Result<InvokeOutput> {
         match self {
             Tool::FsRead(fs_read) => fs_read.invoke(os, stdout).await,
             Tool::FsWrite(fs_write) => fs_write.invoke(os, stdout).await,
             Tool::ExecuteCommand(execute_command) => execute_command.invoke(stdout).await,
+            Tool::BackgroundProcess(background_process) => background_process.invoke(stdout).await,
             Tool::UseAws(use_aws) => use_aws.invoke(os, stdout).await,
             Tool::Custom(custom_tool) => custom_tool.invoke(os, stdout).await,
             Tool::GhIssue(gh_issue) => gh_issue.invoke(os, stdout).await,
             Tool::Knowledge(knowledge) => knowledge.invoke(os, stdout).await,
             Tool::Thinking(think) => think.invoke(stdout).await,
+            Tool::UseSubagent(use_subagent) => use_subagent.invoke(os, stdout).await,
         }
     }
 
@@ -126,11 +226,13 @@ impl Tool {
             Tool::FsRead(fs_read) => fs_read.queue_description(os, output).await,
             Tool::FsWrite(fs_write) => fs_write.queue_description(os, output),
             Tool::ExecuteCommand(execute_command) => execute_command.queue_description(output),
+            Tool::BackgroundProcess(background_process) => background_process.queue_description(output),
             Tool::UseAws(use_aws) => use_aws.queue_description(output),
             Tool::Custom(custom_tool) => custom_tool.queue_description(output),
             Tool::GhIssue(gh_issue) => gh_issue.queue_description(output),
             Tool::Knowledge(knowledge) => knowledge.queue_description(os, output).await,
             Tool::Thinking(thinking) => thinking.queue_description(output),
+            Tool::UseSubagent(use_subagent) => use_subagent.queue_description(output),
         }
     }
 
@@ -140,11 +242,13 @@ impl Tool {
             Tool::FsRead(fs_read) => fs_read.validate(os).await,
             Tool::FsWrite(fs_write) => fs_write.validate(os).await,
             Tool::ExecuteCommand(execute_command) => execute_command.validate(os).await,
+            Tool::BackgroundProcess(background_process) => background_process.validate(os).await,
             Tool::UseAws(use_aws) => use_aws.validate(os).await,
             Tool::Custom(custom_tool) => custom_tool.validate(os).await,
             Tool::GhIssue(gh_issue) => gh_issue.validate(os).await,
             Tool::Knowledge(knowledge) => knowledge.validate(os).await,
             Tool::Thinking(think) => think.validate(os).await,
+            Tool::UseSubagent(use_subagent) => use_subagent.validate(os).await,
         }
     }
 
@@ -240,6 +344,27 @@ pub struct QueuedTool {
     pub name: String,
     pub accepted: bool,
     pub tool: Tool,
+    /// The input of the tool, as requested by the model or rewritten by a preToolUse hook.
+    pub input: serde_json::Value,
+}
+
+impl QueuedTool {
+    /// The name of the tool as written in agent configs, such as `fs_read` or `@git/git_status`.
+    pub fn qualified_name(&self) -> String {
+        self.tool.qualified_name()
+    }
+}
+
+/// Whether the tool uses of a response are independent of each other and so can run at the same
+/// time: each one is read-only or the only tool use of its MCP server. Subagents may write files
+/// and run commands, and so never run alongside other tool uses.
+pub fn can_run_in_parallel(tools: &[QueuedTool]) -> bool {
+    let mut servers = HashSet::new();
+    tools.len() > 1
+        && tools.iter().all(|queued| match &queued.tool {
+            Tool::Custom(custom_tool) => servers.insert(custom_tool.client.get_server_name()),
+            tool => tool.is_read_only(),
+        })
 }
 
 /// The schema specification describing a tool's fields.
@@ -335,7 +460,7 @@ fn absolute_to_relative(cwd: impl AsRef<Path>, path: impl AsRef<Path>) -> Result
 }
 
 /// Small helper for formatting the path as a relative path, if able.
-fn format_path(cwd: impl AsRef<Path>, path: impl AsRef<Path>) -> String {
+pub fn format_path(cwd: impl AsRef<Path>, path: impl AsRef<Path>) -> String {
     absolute_to_relative(cwd, path.as_ref())
         .map(|p| p.to_string_lossy().to_string())
         // If we have three consecutive ".." then it should probably just stay as an absolute path.
@@ -425,6 +550,49 @@ mod tests {
     use super::*;
     use crate::os::ACTIVE_USER_HOME;
 
+    #[test]
+    fn test_can_run_in_parallel() {
+        let queued = |name: &str, input: serde_json::Value| QueuedTool {
+            id: name.to_string(),
+            name: name.to_string(),
+            accepted: true,
+            tool: match name {
+                "fs_read" => Tool::FsRead(serde_json::from_value(input.clone()).unwrap()),
+                "fs_write" => Tool::FsWrite(serde_json::from_value(input.clone()).unwrap()),
+                "use_subagent" => Tool::UseSubagent(serde_json::from_value(input.clone()).unwrap()),
+                _ => Tool::ExecuteCommand(serde_json::from_value(input.clone()).unwrap()),
+            },
+            input,
+        };
+        let read = |path: &str| {
+            queued(
+                "fs_read",
+                serde_json::json!({ "operations": [{ "mode": "Line", "path": path }] }),
+            )
+        };
+
+        assert!(can_run_in_parallel(&[read("/a.txt"), read("/b.txt")]));
+        assert!(can_run_in_parallel(&[
+            read("/a.txt"),
+            queued("execute_bash", serde_json::json!({ "command": "ls -la" }))
+        ]));
+        assert!(!can_run_in_parallel(&[read("/a.txt")]));
+        assert!(!can_run_in_parallel(&[
+            read("/a.txt"),
+            queued("execute_bash", serde_json::json!({ "command": "rm -rf target" }))
+        ]));
+        assert!(!can_run_in_parallel(&[
+            read("/a.txt"),
+            queued(
+                "fs_write",
+                serde_json::json!({ "command": "create", "path": "/b.txt", "file_text": "b" })
+            )
+        ]));
+        let subagent = || queued("use_subagent", serde_json::json!({ "task": "Survey src/" }));
+        assert!(!can_run_in_parallel(&[subagent(), subagent()]));
+        assert!(!can_run_in_parallel(&[read("/a.txt"), subagent()]));
+    }
+
     #[tokio::test]
     async fn test_tilde_path_expansion() {
         let os = Os::new().await.unwrap();
@@ -23,6 +23,7 @@ use super::{
     MAX_TOOL_RESPONSE_SIZE,
     OutputKind,
 };
+use crate::cli::agent::permission::RuleEffect;
 use crate::cli::agent::{
     Agent,
     PermissionEvalResult,
@@ -201,6 +202,14 @@ impl UseAws {
         }
     }
 
+    /// Whether the call matches `pattern` of a permission rule like `use_aws(s3 list-*)`, which
+    /// is matched against the service and operation names.
+    pub fn matches_permission_pattern(&self, pattern: &str, effect: RuleEffect) -> bool {
+        effect.matches_glob(pattern, [
+            format!("{} {}", self.service_name, self.operation_name).as_str()
+        ])
+    }
+
     pub fn eval_perm(&self, agent: &Agent) -> PermissionEvalResult {
         #[derive(Debug, Deserialize)]
         #[serde(rename_all = "camelCase")]
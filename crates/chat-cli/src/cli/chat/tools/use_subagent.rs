@@ -0,0 +1,691 @@
+use std::collections::{
+    BTreeMap,
+    HashMap,
+};
+use std::io::Write;
+use std::sync::Arc;
+
+use crossterm::queue;
+use crossterm::style::{
+    self,
+    Color,
+};
+use eyre::{
+    Result,
+    bail,
+    eyre,
+};
+use serde::{
+    Deserialize,
+    Serialize,
+};
+use tokio::sync::Mutex;
+
+use super::{
+    InvokeOutput,
+    OutputKind,
+    Tool,
+};
+use crate::api_client::model::ToolResultStatus;
+use crate::cli::agent::hook::HookTrigger;
+use crate::cli::agent::{
+    Agent,
+    Agents,
+    PermissionEvalResult,
+};
+use crate::cli::chat::cli::hooks::{
+    HookDecision,
+    HookEvent,
+};
+use crate::cli::chat::checkpoint::CheckpointStore;
+use crate::cli::chat::conversation::ConversationState;
+use crate::cli::chat::message::{
+    AssistantToolUse,
+    ToolUseResult,
+    ToolUseResultBlock,
+};
+use crate::cli::chat::parser::{
+    ResponseEvent,
+    SendMessageStream,
+};
+use crate::cli::chat::tool_manager::ToolManager;
+use crate::cli::chat::tools::ToolOrigin;
+use crate::os::Os;
+use crate::util::MCP_SERVER_TOOL_DELIMITER;
+
+/// The name of the tool as exposed to the model.
+pub const USE_SUBAGENT_TOOL_NAME: &str = "use_subagent";
+
+/// Maximum number of requests a subagent may send before it is stopped.
+const MAX_REQUESTS: usize = 50;
+
+/// Runs a self-contained task in a separate conversation with its own context window, returning
+/// only the final answer of the subagent.
+///
+/// The subagent runs headlessly with the tools and permissions of its agent config. Tool uses
+/// that would require asking the user are rejected instead. It shares the MCP servers of the
+/// parent session, so servers of its agent that the session is not running are not available.
+#[derive(Debug, Clone, Deserialize)]
+pub struct UseSubagent {
+    pub task: String,
+    /// Defaults to the active agent.
+    pub agent_name: Option<String>,
+    /// Set from the chat session.
+    #[serde(skip)]
+    pub context: Option<SubagentContext>,
+}
+
+#[derive(Debug, Clone)]
+pub struct SubagentContext {
+    /// The agent config the subagent runs with, if one with the requested name exists.
+    pub agent: Option<Agent>,
+    /// The names of every agent, for reporting an unknown agent.
+    pub agent_names: Vec<String>,
+    pub model: Option<String>,
+    /// The tool manager of the parent session, whose MCP clients the subagent reuses.
+    pub tool_manager: ToolManager,
+    /// The files modified by the tools of the subagent, which the parent session records in the
+    /// checkpoint of its current turn.
+    pub checkpoints: Arc<Mutex<CheckpointStore>>,
+}
+
+/// How many times a subagent used a tool, by outcome.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ToolUsage {
+    pub succeeded: usize,
+    pub failed: usize,
+    /// Tool uses the permissions of the agent did not allow.
+    pub rejected: usize,
+}
+
+impl UseSubagent {
+    pub fn set_context(&mut self, agents: &Agents, model: Option<String>, tool_manager: &ToolManager) {
+        let name = self.agent_name.as_deref().unwrap_or(agents.active_idx.as_str());
+        let mut agent_names = agents.agents.keys().cloned().collect::<Vec<_>>();
+        agent_names.sort();
+        self.context = Some(SubagentContext {
+            agent: agents.agents.get(name).cloned(),
+            agent_names,
+            model,
+            tool_manager: tool_manager.clone(),
+            checkpoints: Arc::default(),
+        });
+    }
+
+    fn agent_name(&self) -> &str {
+        self.context
+            .as_ref()
+            .and_then(|context| context.agent.as_ref())
+            .map_or(self.agent_name.as_deref().unwrap_or("the current agent"), |agent| {
+                agent.name.as_str()
+            })
+    }
+
+    pub fn queue_description(&self, output: &mut impl Write) -> Result<()> {
+        queue!(
+            output,
+            style::Print("Delegating a task to a subagent running "),
+            style::SetForegroundColor(Color::Green),
+            style::Print(self.agent_name()),
+            style::SetForegroundColor(Color::Reset),
+            style::Print(":\n"),
+            style::SetForegroundColor(Color::DarkGrey),
+            style::Print(&self.task),
+            style::SetForegroundColor(Color::Reset),
+            style::Print("\n"),
+        )?;
+        Ok(())
+    }
+
+    pub async fn validate(&mut self, _os: &Os) -> Result<()> {
+        if self.task.trim().is_empty() {
+            bail!("The task must not be empty");
+        }
+        let Some(context) = &self.context else {
+            bail!("Subagents are not supported in this session");
+        };
+        if context.agent.is_none() {
+            bail!(
+                "No agent named {} exists. The available agents are: {}",
+                self.agent_name.as_deref().unwrap_or_default(),
+                context.agent_names.join(", ")
+            );
+        }
+        Ok(())
+    }
+
+    pub fn eval_perm(agent: &Agent) -> PermissionEvalResult {
+        if agent.allowed_tools.contains(USE_SUBAGENT_TOOL_NAME) {
+            PermissionEvalResult::Allow
+        } else {
+            PermissionEvalResult::Ask
+        }
+    }
+
+    pub async fn invoke(&self, os: &Os, updates: &mut impl Write) -> Result<InvokeOutput> {
+        let Some(SubagentContext {
+            agent: Some(agent),
+            model,
+            tool_manager,
+            checkpoints,
+            ..
+        }) = &self.context
+        else {
+            bail!("Subagents are not supported in this session");
+        };
+
+        let mut checkpoints = checkpoints.lock().await;
+        let (response, tool_usage) = run_subagent(
+            os,
+            agent,
+            model.clone(),
+            tool_manager,
+            &self.task,
+            &mut checkpoints,
+            updates,
+        )
+        .await?;
+        Ok(InvokeOutput {
+            output: OutputKind::Json(serde_json::json!({
+                "agent": agent.name,
+                "response": response,
+                "toolUsage": tool_usage,
+            })),
+        })
+    }
+}
+
+/// Runs `task` to completion in a new conversation with `agent`, returning the final response
+/// of the model and how the tools were used. The files modified by its tools are recorded in
+/// `checkpoints`.
+async fn run_subagent(
+    os: &Os,
+    agent: &Agent,
+    model: Option<String>,
+    parent_tool_manager: &ToolManager,
+    task: &str,
+    checkpoints: &mut CheckpointStore,
+    updates: &mut impl Write,
+) -> Result<(String, BTreeMap<String, ToolUsage>)> {
+    // Pushing messages requires a mutable os, and nothing it changes is relevant to the parent
+    // conversation.
+    let mut os = os.clone();
+    let conversation_id = uuid::Uuid::new_v4().to_string();
+    let tool_manager = subagent_tool_manager(parent_tool_manager, agent, &conversation_id);
+    let tool_config = tool_manager.schema.clone();
+
+    let agents = Agents {
+        agents: HashMap::from([(agent.name.clone(), agent.clone())]),
+        active_idx: agent.name.clone(),
+        trust_all_tools: false,
+    };
+    let mut conversation = ConversationState::new(&conversation_id, agents, tool_config, tool_manager, model).await;
+    conversation.ephemeral = true;
+    conversation.set_next_user_message(task.to_string()).await;
+
+    let mut tool_usage = BTreeMap::<String, ToolUsage>::new();
+    for request in 0..MAX_REQUESTS {
+        let state = conversation
+            .as_sendable_conversation_state(&os, &mut std::io::sink(), request == 0)
+            .await
+            .map_err(|err| eyre!("{err}"))?;
+        let mut stream = SendMessageStream::send_message(&os.client, state, Arc::new(Mutex::new(None)), None)
+            .await
+            .map_err(|err| eyre!("{err}"))?;
+
+        let (message, request_metadata) = loop {
+            match stream.recv().await {
+                Some(Ok(ResponseEvent::EndStream {
+                    message,
+                    request_metadata,
+                })) => break (message, request_metadata),
+                Some(Ok(_)) => (),
+                Some(Err(err)) => bail!("{err}"),
+                None => bail!("The response stream ended unexpectedly"),
+            }
+        };
+
+        let tool_uses = message.tool_uses().map(<[_]>::to_vec).unwrap_or_default();
+        let response = message.content().to_string();
+        conversation.push_assistant_message(&mut os, message, Some(request_metadata));
+        if tool_uses.is_empty() {
+            return Ok((response, tool_usage));
+        }
+
+        let mut tool_results = Vec::new();
+        for tool_use in tool_uses {
+            let name = tool_use.name.clone();
+            let (result, outcome) = run_tool(&os, &mut conversation, checkpoints, agent, tool_use).await;
+            let usage = tool_usage.entry(name.clone()).or_default();
+            match outcome {
+                ToolOutcome::Succeeded => usage.succeeded += 1,
+                ToolOutcome::Failed => usage.failed += 1,
+                ToolOutcome::Rejected => usage.rejected += 1,
+            }
+            queue!(
+                updates,
+                style::SetForegroundColor(Color::DarkGrey),
+                style::Print(format!(" ↳ {name}{}\n", match outcome {
+                    ToolOutcome::Succeeded => "",
+                    ToolOutcome::Failed => " (failed)",
+                    ToolOutcome::Rejected => " (rejected)",
+                })),
+                style::SetForegroundColor(Color::Reset),
+            )?;
+            updates.flush()?;
+            tool_results.push(result);
+        }
+        conversation.add_tool_results(tool_results);
+    }
+
+    bail!("The subagent did not finish the task within {MAX_REQUESTS} requests")
+}
+
+/// Narrows the tools of the parent session down to those `agent` allows, sharing the clients of
+/// the MCP servers rather than spawning them again.
+fn subagent_tool_manager(parent: &ToolManager, agent: &Agent, conversation_id: &str) -> ToolManager {
+    let is_allow_all = agent.tools.iter().any(|tool| tool == "*");
+    let is_allow_native = agent.tools.iter().any(|tool| tool == "@builtin");
+    let includes_native = |name: &str| {
+        is_allow_all
+            || is_allow_native
+            || agent
+                .tools
+                .iter()
+                .any(|tool| tool == name || *tool == format!("@builtin/{name}"))
+    };
+    let includes_mcp = |server_name: &str, tool_name: &str| {
+        is_allow_all
+            || agent.tools.iter().any(|tool| {
+                *tool == format!("@{server_name}")
+                    || *tool == format!("@{server_name}{MCP_SERVER_TOOL_DELIMITER}{tool_name}")
+            })
+    };
+
+    let mut tool_manager = parent.clone();
+    tool_manager.conversation_id = conversation_id.to_string();
+    tool_manager.agent = Arc::new(Mutex::new(agent.clone()));
+    tool_manager
+        .tn_map
+        .retain(|_, info| includes_mcp(&info.server_name, &info.host_tool_name));
+    let tn_map = &tool_manager.tn_map;
+    tool_manager.schema.retain(|name, spec| match &spec.tool_origin {
+        // Subagents cannot delegate any further.
+        ToolOrigin::Native => name != USE_SUBAGENT_TOOL_NAME && includes_native(name),
+        ToolOrigin::McpServer(_) => tn_map.contains_key(name),
+    });
+    tool_manager
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ToolOutcome {
+    Succeeded,
+    Failed,
+    Rejected,
+}
+
+/// Runs a tool use of a subagent, provided the permissions and hooks of `agent` allow it without
+/// asking the user.
+async fn run_tool(
+    os: &Os,
+    conversation: &mut ConversationState,
+    checkpoints: &mut CheckpointStore,
+    agent: &Agent,
+    tool_use: AssistantToolUse,
+) -> (ToolUseResult, ToolOutcome) {
+    let tool_use_id = tool_use.id.clone();
+    let input = tool_use.args.clone();
+    let result = |text: String, outcome: ToolOutcome| {
+        (
+            ToolUseResult {
+                tool_use_id: tool_use_id.clone(),
+                content: vec![ToolUseResultBlock::Text(text)],
+                status: match outcome {
+                    ToolOutcome::Succeeded => ToolResultStatus::Success,
+                    _ => ToolResultStatus::Error,
+                },
+            },
+            outcome,
+        )
+    };
+
+    let mut tool = match conversation.tool_manager.get_tool_from_tool_use(tool_use) {
+        Ok(tool) => tool,
+        Err(err) => return (err.into(), ToolOutcome::Failed),
+    };
+    if matches!(tool, Tool::GhIssue(_) | Tool::UseSubagent(_)) {
+        return result(
+            format!("{} is not available to subagents", tool.display_name()),
+            ToolOutcome::Rejected,
+        );
+    }
+    match &mut tool {
+        Tool::FsRead(fs_read) => fs_read.set_denied_paths(agent),
+        Tool::ExecuteCommand(execute_command) => execute_command.set_sandbox(agent),
+        _ => (),
+    }
+    if let Err(err) = tool.validate(os).await {
+        return result(
+            format!("Failed to validate tool parameters: {err}"),
+            ToolOutcome::Failed,
+        );
+    }
+
+    let qualified_name = tool.qualified_name();
+    let mut hook_decision = None;
+    if let Some(cm) = conversation.context_manager.as_mut() {
+        let event = HookEvent::new(os, HookTrigger::PreToolUse).with_tool(&qualified_name, input.clone());
+        if let Ok(outputs) = cm.run_hooks(&event, &mut std::io::sink()).await {
+            hook_decision = outputs
+                .iter()
+                .filter_map(|(_, output)| output.response().decision)
+                .max();
+        }
+    }
+    // The deny rules of the agent apply even if a hook allows the tool use.
+    let rejection = match (hook_decision, tool.requires_acceptance(agent)) {
+        (Some(HookDecision::Deny), _) => Some("it was blocked by a hook".to_string()),
+        (_, PermissionEvalResult::Deny) => Some("the arguments supplied were forbidden".to_string()),
+        (_, PermissionEvalResult::DeniedByRule(rule)) => Some(format!("it was denied by the permission rule {rule}")),
+        (Some(HookDecision::Allow), _) => None,
+        (Some(HookDecision::Ask), _) => Some("a hook asked for approval, which a subagent cannot ask for".to_string()),
+        (None, PermissionEvalResult::Allow | PermissionEvalResult::AllowedByRule(_)) => None,
+        (None, PermissionEvalResult::Ask) => Some(format!(
+            "it requires approval, which a subagent cannot ask for. It can be allowed in the agent {}",
+            agent.name
+        )),
+    };
+    if let Some(reason) = rejection {
+        return result(
+            format!("Tool use with {qualified_name} was rejected because {reason}"),
+            ToolOutcome::Rejected,
+        );
+    }
+
+    // Tools invoked by subagents may themselves be subagents, which requires boxing the future.
+    let tree_snapshot = checkpoints.snapshot_tool(os, &tool).await;
+    let invoke_result = Box::pin(tool.invoke(os, &mut std::io::sink())).await;
+    checkpoints.record_tool_changes(os, &tool, tree_snapshot).await;
+    let tool_response = match &invoke_result {
+        Ok(output) => serde_json::json!({ "success": true, "output": output.as_str() }),
+        Err(err) => serde_json::json!({ "success": false, "error": err.to_string() }),
+    };
+    if let Some(cm) = conversation.context_manager.as_mut() {
+        let event = HookEvent::new(os, HookTrigger::PostToolUse)
+            .with_tool(&qualified_name, input)
+            .with_tool_response(tool_response);
+        let _ = cm.run_hooks(&event, &mut std::io::sink()).await;
+    }
+
+    match invoke_result {
+        Ok(output) => (
+            ToolUseResult {
+                tool_use_id: tool_use_id.clone(),
+                content: vec![output.into()],
+                status: ToolResultStatus::Success,
+            },
+            ToolOutcome::Succeeded,
+        ),
+        Err(err) => result(
+            format!("An error occurred processing the tool: \n{err}"),
+            ToolOutcome::Failed,
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cli::chat::tool_manager::ToolInfo;
+    use crate::cli::chat::tools::{
+        InputSchema,
+        ToolSpec,
+    };
+
+    async fn test_conversation(agent: &Agent) -> ConversationState {
+        let agents = Agents {
+            agents: HashMap::from([(agent.name.clone(), agent.clone())]),
+            active_idx: agent.name.clone(),
+            trust_all_tools: false,
+        };
+        ConversationState::new("test_conv_id", agents, HashMap::new(), ToolManager::default(), None).await
+    }
+
+    fn tool_use(name: &str, args: serde_json::Value) -> AssistantToolUse {
+        AssistantToolUse {
+            id: "1".to_string(),
+            name: name.to_string(),
+            orig_name: name.to_string(),
+            args: args.clone(),
+            orig_args: args,
+        }
+    }
+
+    #[test]
+    fn test_set_context() {
+        let mut agents = Agents::default();
+        for name in ["default", "researcher"] {
+            agents.agents.insert(name.to_string(), Agent {
+                name: name.to_string(),
+                ..Default::default()
+            });
+        }
+        agents.active_idx = "default".to_string();
+
+        let mut tool = serde_json::from_value::<UseSubagent>(serde_json::json!({
+            "task": "Survey the files under src/ and report",
+            "agent_name": "researcher",
+        }))
+        .unwrap();
+        tool.set_context(&agents, None, &ToolManager::default());
+        assert_eq!(tool.agent_name(), "researcher");
+
+        let mut tool = serde_json::from_value::<UseSubagent>(serde_json::json!({ "task": "Survey" })).unwrap();
+        tool.set_context(&agents, None, &ToolManager::default());
+        assert_eq!(tool.agent_name(), "default");
+
+        let mut tool = serde_json::from_value::<UseSubagent>(serde_json::json!({
+            "task": "Survey",
+            "agent_name": "missing",
+        }))
+        .unwrap();
+        tool.set_context(&agents, None, &ToolManager::default());
+        assert!(tool.context.as_ref().unwrap().agent.is_none());
+        assert_eq!(tool.context.as_ref().unwrap().agent_names, vec![
+            "default".to_string(),
+            "researcher".to_string()
+        ]);
+    }
+
+    #[test]
+    fn test_subagent_tool_manager() {
+        let mut parent = ToolManager::default();
+        parent.schema = serde_json::from_str(include_str!("tool_index.json")).unwrap();
+        for (server_name, tool_name) in [("git", "git_status"), ("github", "list_issues")] {
+            let model_tool_name = format!("{server_name}___{tool_name}");
+            parent.tn_map.insert(model_tool_name.clone(), ToolInfo {
+                server_name: server_name.to_string(),
+                host_tool_name: tool_name.to_string(),
+            });
+            parent.schema.insert(model_tool_name.clone(), ToolSpec {
+                name: model_tool_name,
+                description: String::new(),
+                input_schema: InputSchema(serde_json::json!({})),
+                tool_origin: ToolOrigin::McpServer(server_name.to_string()),
+            });
+        }
+
+        let agent = serde_json::from_value::<Agent>(serde_json::json!({
+            "name": "researcher",
+            "tools": ["fs_read", "@builtin/execute_bash", "use_subagent", "@git"],
+        }))
+        .unwrap();
+        let tool_manager = subagent_tool_manager(&parent, &agent, "subagent_conv_id");
+        let mut names = tool_manager.schema.keys().cloned().collect::<Vec<_>>();
+        names.sort();
+        assert_eq!(names, vec!["execute_bash", "fs_read", "git___git_status"]);
+        assert_eq!(tool_manager.tn_map.len(), 1);
+        assert_eq!(tool_manager.conversation_id, "subagent_conv_id");
+
+        let agent = serde_json::from_value::<Agent>(serde_json::json!({ "name": "all", "tools": ["*"] })).unwrap();
+        let tool_manager = subagent_tool_manager(&parent, &agent, "subagent_conv_id");
+        assert!(!tool_manager.schema.contains_key(USE_SUBAGENT_TOOL_NAME));
+        assert_eq!(tool_manager.schema.len(), parent.schema.len() - 1);
+    }
+
+    #[tokio::test]
+    async fn test_run_tool_denied_paths() {
+        let os = Os::new().await.unwrap();
+        os.fs.create_dir_all("/project/secrets").await.unwrap();
+        os.fs.write("/project/config.txt", "token=abc\n").await.unwrap();
+        os.fs.write("/project/secrets/prod.txt", "token=xyz\n").await.unwrap();
+        let agent = serde_json::from_value::<Agent>(serde_json::json!({
+            "name": "researcher",
+            "allowedTools": ["fs_read"],
+            "toolsSettings": { "fs_read": { "deniedPaths": ["/project/secrets/**"] } }
+        }))
+        .unwrap();
+        let mut conversation = test_conversation(&agent).await;
+
+        let (result, outcome) = run_tool(
+            &os,
+            &mut conversation,
+            &mut CheckpointStore::default(),
+            &agent,
+            tool_use(
+                "fs_read",
+                serde_json::json!({ "operations": [{ "mode": "Search", "path": "/project", "pattern": "token" }] }),
+            ),
+        )
+        .await;
+        assert_eq!(outcome, ToolOutcome::Succeeded);
+        let output = format!("{:?}", result.content);
+        assert!(output.contains("config.txt"), "{output}");
+        assert!(!output.contains("prod.txt"), "{output}");
+
+        let (_, outcome) = run_tool(
+            &os,
+            &mut conversation,
+            &mut CheckpointStore::default(),
+            &agent,
+            tool_use(
+                "fs_read",
+                serde_json::json!({ "operations": [{ "mode": "Line", "path": "/project/secrets/prod.txt" }] }),
+            ),
+        )
+        .await;
+        assert_eq!(outcome, ToolOutcome::Rejected);
+    }
+
+    #[tokio::test]
+    async fn test_run_tool_rejects_ask() {
+        let os = Os::new().await.unwrap();
+        os.fs.create_dir_all("/etc").await.unwrap();
+        os.fs.write("/etc/hosts", "127.0.0.1 localhost\n").await.unwrap();
+        let agent = serde_json::from_value::<Agent>(serde_json::json!({
+            "name": "researcher",
+            "allowedTools": ["fs_read"],
+            "permissions": { "deny": ["fs_read(/etc/**)"] }
+        }))
+        .unwrap();
+        let mut conversation = test_conversation(&agent).await;
+
+        let (result, outcome) = run_tool(
+            &os,
+            &mut conversation,
+            &mut CheckpointStore::default(),
+            &agent,
+            tool_use(
+                "fs_write",
+                serde_json::json!({ "command": "create", "path": "/file.txt", "file_text": "Hello" }),
+            ),
+        )
+        .await;
+        assert_eq!(outcome, ToolOutcome::Rejected);
+        assert!(matches!(result.status, ToolResultStatus::Error));
+        assert!(!os.fs.exists("/file.txt"));
+
+        let (result, outcome) = run_tool(
+            &os,
+            &mut conversation,
+            &mut CheckpointStore::default(),
+            &agent,
+            tool_use(
+                "fs_read",
+                serde_json::json!({ "operations": [{ "mode": "Line", "path": "/etc/hosts" }] }),
+            ),
+        )
+        .await;
+        assert_eq!(outcome, ToolOutcome::Rejected);
+        assert!(format!("{:?}", result.content).contains("fs_read(/etc/**)"));
+    }
+
+    #[tokio::test]
+    async fn test_run_tool_records_checkpoints() {
+        let os = Os::new().await.unwrap();
+        os.fs.write("/existing.txt", "before").await.unwrap();
+        let agent = serde_json::from_value::<Agent>(serde_json::json!({
+            "name": "writer",
+            "allowedTools": ["fs_write"],
+        }))
+        .unwrap();
+        let mut conversation = test_conversation(&agent).await;
+        let mut checkpoints = CheckpointStore::default();
+
+        for args in [
+            serde_json::json!({ "command": "create", "path": "/file.txt", "file_text": "Hello" }),
+            serde_json::json!({ "command": "create", "path": "/existing.txt", "file_text": "after" }),
+        ] {
+            let (_, outcome) = run_tool(
+                &os,
+                &mut conversation,
+                &mut checkpoints,
+                &agent,
+                tool_use("fs_write", args),
+            )
+            .await;
+            assert_eq!(outcome, ToolOutcome::Succeeded);
+        }
+        let plan = checkpoints.restore_plan(0);
+        assert_eq!(plan[&os.fs.chroot_path("/file.txt")], None);
+        assert_eq!(plan[&os.fs.chroot_path("/existing.txt")], Some("before".to_string()));
+    }
+
+    #[tokio::test]
+    async fn test_run_subagent_request_limit() {
+        let mut os = Os::new().await.unwrap();
+        let tool_use_response = serde_json::json!([
+            "Writing the file",
+            {
+                "tool_use_id": "1",
+                "name": "fs_write",
+                "args": { "command": "create", "path": "/file.txt", "file_text": "Hello" }
+            }
+        ]);
+        let mut responses = vec![tool_use_response; MAX_REQUESTS];
+        responses.push(serde_json::json!(["Done"]));
+        os.client.set_mock_output(serde_json::Value::Array(responses));
+
+        let agent = serde_json::from_value::<Agent>(serde_json::json!({ "name": "researcher" })).unwrap();
+        let mut updates = Vec::new();
+        let err = run_subagent(
+            &os,
+            &agent,
+            None,
+            &ToolManager::default(),
+            "Write a file",
+            &mut CheckpointStore::default(),
+            &mut updates,
+        )
+            .await
+            .unwrap_err();
+        assert!(
+            err.to_string().contains(&format!("within {MAX_REQUESTS} requests")),
+            "{err}"
+        );
+        let updates = String::from_utf8(updates).unwrap();
+        assert_eq!(updates.matches("fs_write (rejected)").count(), MAX_REQUESTS);
+        assert!(!os.fs.exists("/file.txt"));
+    }
+}
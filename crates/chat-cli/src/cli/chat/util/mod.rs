@@ -1,9 +1,11 @@
 pub mod images;
 pub mod issue;
+pub mod patch;
 #[cfg(test)]
 pub mod test;
 pub mod ui;
 
+use std::cmp::Reverse;
 use std::io::Write;
 use std::time::Duration;
 
@@ -155,6 +157,19 @@ fn should_play_bell() -> bool {
     false
 }
 
+/// Lets the user pick one of `items` with the fuzzy finder, or from a plain list where it is not
+/// available. Returns the index of the selected item, or [None] if the user cancelled.
+pub fn select_item(prompt: &str, items: &[String]) -> Result<Option<usize>> {
+    #[cfg(unix)]
+    let selected = super::skim_integration::launch_skim_selector(items, &format!("{prompt}: "), false)?
+        .and_then(|selections| selections.into_iter().next())
+        .and_then(|selection| items.iter().position(|item| *item == selection));
+    #[cfg(not(unix))]
+    let selected = crate::util::choose(prompt, items)?;
+
+    Ok(selected)
+}
+
 /// This is a simple greedy algorithm that drops the largest files first
 /// until the total size is below the limit
 ///
@@ -164,7 +179,7 @@ fn should_play_bell() -> bool {
 ///
 /// Returns the dropped files
 pub fn drop_matched_context_files(files: &mut [(String, String)], limit: usize) -> Result<Vec<(String, String)>> {
-    files.sort_by(|a, b| TokenCounter::count_tokens(&b.1).cmp(&TokenCounter::count_tokens(&a.1)));
+    files.sort_by_cached_key(|(_, content)| Reverse(TokenCounter::count_tokens(content)));
     let mut total_size = 0;
     let mut dropped_files = Vec::new();
 
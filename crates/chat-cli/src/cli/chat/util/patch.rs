@@ -0,0 +1,470 @@
+//! Parsing and application of unified diffs, as used by the `patch` command of `fs_write`.
+//!
+//! Application is lenient in the same ways as `patch(1)`: hunk line counts are only used as a
+//! hint for where a hunk ends, and a hunk whose context is not found at the line given in its
+//! header is applied at the nearest location where it does match.
+
+use thiserror::Error;
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum PatchError {
+    #[error("invalid patch at line {line}: {reason}")]
+    Parse { line: usize, reason: String },
+    #[error("hunk #{hunk} ({header}) failed to apply: {reason}")]
+    HunkFailed {
+        hunk: usize,
+        header: String,
+        reason: String,
+    },
+}
+
+/// The changes to a single file within a unified diff.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FilePatch {
+    /// Path from the `---` header, or [None] if it was `/dev/null`, i.e. the file is created.
+    pub old_path: Option<String>,
+    /// Path from the `+++` header, or [None] if it was `/dev/null`, i.e. the file is deleted.
+    pub new_path: Option<String>,
+    pub hunks: Vec<Hunk>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Hunk {
+    /// 1-indexed line number of the hunk in the original file, as given by its header.
+    pub old_start: usize,
+    pub old_len: usize,
+    pub new_start: usize,
+    pub new_len: usize,
+    pub lines: Vec<HunkLine>,
+    /// Set by a `\ No newline at end of file` marker following a line of the new file.
+    pub new_missing_newline: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum HunkLine {
+    Context(String),
+    Remove(String),
+    Add(String),
+}
+
+/// The result of successfully applying a [FilePatch] to a file's content.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AppliedPatch {
+    pub content: String,
+    /// 1-indexed line number at which each hunk was applied in the updated file.
+    pub hunk_starts: Vec<usize>,
+}
+
+impl FilePatch {
+    /// The path of the file this patch writes to, or deletes if the file is being removed.
+    pub fn target_path(&self) -> &str {
+        self.new_path
+            .as_deref()
+            .or(self.old_path.as_deref())
+            .unwrap_or_default()
+    }
+
+    /// Applies every hunk to `original`, returning the updated content. Nothing is partially
+    /// applied: either all hunks apply, or the first failing hunk is reported.
+    pub fn apply(&self, original: &str) -> Result<AppliedPatch, PatchError> {
+        let (mut lines, mut trailing_newline) = split_lines(original);
+        let crlf = lines.first().is_some_and(|l| l.ends_with('\r'));
+        let mut hunk_starts = Vec::with_capacity(self.hunks.len());
+        // Difference between where hunks were expected to apply and where they actually did,
+        // carried forward so later hunks are searched for at the right place.
+        let mut offset: isize = 0;
+        let mut min_pos = 0;
+
+        for (i, hunk) in self.hunks.iter().enumerate() {
+            let old = hunk.old_lines();
+            let new = hunk.new_lines();
+            let base = if old.is_empty() {
+                hunk.old_start
+            } else {
+                hunk.old_start.saturating_sub(1)
+            };
+            let expected = (base as isize + offset).max(min_pos as isize) as usize;
+
+            let Some(pos) = find_block(&lines, &old, expected, min_pos) else {
+                return Err(PatchError::HunkFailed {
+                    hunk: i + 1,
+                    header: hunk.header(),
+                    reason: mismatch_reason(&lines, &old, expected),
+                });
+            };
+
+            let replacement = new
+                .iter()
+                .map(|l| if crlf { format!("{l}\r") } else { (*l).to_string() })
+                .collect::<Vec<_>>();
+            lines.splice(pos..pos + old.len(), replacement);
+
+            if pos + new.len() == lines.len() && !new.is_empty() {
+                trailing_newline = !hunk.new_missing_newline;
+            }
+            offset = pos as isize - base as isize + new.len() as isize - old.len() as isize;
+            min_pos = pos + new.len();
+            hunk_starts.push(pos + 1);
+        }
+
+        let mut content = lines.join("\n");
+        if trailing_newline && !lines.is_empty() {
+            content.push('\n');
+        }
+        Ok(AppliedPatch { content, hunk_starts })
+    }
+}
+
+impl Hunk {
+    pub fn header(&self) -> String {
+        format!(
+            "@@ -{},{} +{},{} @@",
+            self.old_start, self.old_len, self.new_start, self.new_len
+        )
+    }
+
+    /// The lines of the original file covered by this hunk.
+    pub fn old_lines(&self) -> Vec<&str> {
+        self.lines
+            .iter()
+            .filter_map(|l| match l {
+                HunkLine::Context(s) | HunkLine::Remove(s) => Some(s.as_str()),
+                HunkLine::Add(_) => None,
+            })
+            .collect()
+    }
+
+    /// The lines that replace [Self::old_lines] in the updated file.
+    pub fn new_lines(&self) -> Vec<&str> {
+        self.lines
+            .iter()
+            .filter_map(|l| match l {
+                HunkLine::Context(s) | HunkLine::Add(s) => Some(s.as_str()),
+                HunkLine::Remove(_) => None,
+            })
+            .collect()
+    }
+}
+
+/// Parses a unified diff, possibly covering several files, as produced by `diff -u` or `git diff`.
+pub fn parse_unified_diff(diff: &str) -> Result<Vec<FilePatch>, PatchError> {
+    let lines = diff.lines().collect::<Vec<_>>();
+    let is_file_header =
+        |i: usize| lines[i].starts_with("--- ") && lines.get(i + 1).is_some_and(|next| next.starts_with("+++ "));
+
+    let mut files: Vec<FilePatch> = Vec::new();
+    let mut i = 0;
+    while i < lines.len() {
+        if is_file_header(i) {
+            let file = FilePatch {
+                old_path: parse_header_path(&lines[i][4..]),
+                new_path: parse_header_path(&lines[i + 1][4..]),
+                hunks: Vec::new(),
+            };
+            // Permissions are checked against the paths as written, which `..` would escape.
+            let is_parent = |path: &&String| path.split(['/', '\\']).any(|component| component == "..");
+            if let Some(path) = [&file.old_path, &file.new_path].into_iter().flatten().find(is_parent) {
+                return Err(PatchError::Parse {
+                    line: i + 1,
+                    reason: format!("path `{path}` must not contain `..`"),
+                });
+            }
+            files.push(file);
+            i += 2;
+            continue;
+        }
+
+        if !lines[i].starts_with("@@") {
+            // `diff --git`, `index`, mode lines and any other commentary.
+            i += 1;
+            continue;
+        }
+
+        let header_line = i + 1;
+        let Some(file) = files.last_mut() else {
+            return Err(PatchError::Parse {
+                line: header_line,
+                reason: "hunk found before any `---`/`+++` file header".to_string(),
+            });
+        };
+        let (old_start, old_len, new_start, new_len) =
+            parse_hunk_header(lines[i]).ok_or_else(|| PatchError::Parse {
+                line: header_line,
+                reason: format!("malformed hunk header `{}`", lines[i]),
+            })?;
+        let mut hunk = Hunk {
+            old_start,
+            old_len,
+            new_start,
+            new_len,
+            lines: Vec::new(),
+            new_missing_newline: false,
+        };
+        i += 1;
+
+        let (mut old_seen, mut new_seen) = (0, 0);
+        while i < lines.len() {
+            let line = lines[i];
+            let counts_satisfied = old_seen >= old_len && new_seen >= new_len;
+            if line.starts_with("@@") || line.starts_with("diff ") || is_file_header(i) {
+                break;
+            }
+            match line.chars().next() {
+                Some(' ') => {
+                    hunk.lines.push(HunkLine::Context(line[1..].to_string()));
+                    old_seen += 1;
+                    new_seen += 1;
+                },
+                Some('-') => {
+                    hunk.lines.push(HunkLine::Remove(line[1..].to_string()));
+                    old_seen += 1;
+                },
+                Some('+') => {
+                    hunk.lines.push(HunkLine::Add(line[1..].to_string()));
+                    new_seen += 1;
+                },
+                Some('\\') => {
+                    if matches!(hunk.lines.last(), Some(HunkLine::Add(_) | HunkLine::Context(_))) {
+                        hunk.new_missing_newline = true;
+                    }
+                },
+                // Blank context lines commonly lose their leading space.
+                None if !counts_satisfied => {
+                    hunk.lines.push(HunkLine::Context(String::new()));
+                    old_seen += 1;
+                    new_seen += 1;
+                },
+                _ if counts_satisfied => break,
+                _ => {
+                    return Err(PatchError::Parse {
+                        line: i + 1,
+                        reason: format!("unexpected line in hunk starting at line {header_line}: `{line}`"),
+                    });
+                },
+            }
+            i += 1;
+        }
+
+        if hunk.lines.is_empty() {
+            return Err(PatchError::Parse {
+                line: header_line,
+                reason: "hunk has no content".to_string(),
+            });
+        }
+        file.hunks.push(hunk);
+    }
+
+    if files.is_empty() {
+        return Err(PatchError::Parse {
+            line: 1,
+            reason: "no `---`/`+++` file headers found".to_string(),
+        });
+    }
+    if let Some(file) = files.iter().find(|f| f.hunks.is_empty()) {
+        return Err(PatchError::Parse {
+            line: 1,
+            reason: format!("no hunks found for {}", file.target_path()),
+        });
+    }
+    Ok(files)
+}
+
+/// Parses the path from a `---` or `+++` header, dropping any timestamp and `a/` or `b/` prefix.
+fn parse_header_path(s: &str) -> Option<String> {
+    let path = s.split('\t').next().unwrap_or_default().trim();
+    if path == "/dev/null" {
+        return None;
+    }
+    let path = path
+        .strip_prefix("a/")
+        .or_else(|| path.strip_prefix("b/"))
+        .unwrap_or(path);
+    Some(path.to_string())
+}
+
+/// Parses `@@ -a,b +c,d @@`, where the lengths are optional and default to 1.
+fn parse_hunk_header(line: &str) -> Option<(usize, usize, usize, usize)> {
+    let rest = line.strip_prefix("@@ -")?;
+    let (ranges, _) = rest.split_once(" @@")?;
+    let (old, new) = ranges.split_once(" +")?;
+    fn range(s: &str) -> Option<(usize, usize)> {
+        match s.split_once(',') {
+            Some((start, len)) => Some((start.parse().ok()?, len.parse().ok()?)),
+            None => Some((s.parse().ok()?, 1)),
+        }
+    }
+    let (old_start, old_len) = range(old)?;
+    let (new_start, new_len) = range(new)?;
+    Some((old_start, old_len, new_start, new_len))
+}
+
+/// Splits `content` into lines, returning whether the final line was newline terminated.
+fn split_lines(content: &str) -> (Vec<String>, bool) {
+    if content.is_empty() {
+        return (Vec::new(), true);
+    }
+    let trailing_newline = content.ends_with('\n');
+    let content = content.strip_suffix('\n').unwrap_or(content);
+    (content.split('\n').map(str::to_string).collect(), trailing_newline)
+}
+
+fn block_matches_at(lines: &[String], block: &[&str], pos: usize) -> bool {
+    pos + block.len() <= lines.len()
+        && block
+            .iter()
+            .zip(&lines[pos..])
+            .all(|(expected, actual)| actual.trim_end_matches('\r') == expected.trim_end_matches('\r'))
+}
+
+/// Returns the position closest to `expected`, and not before `min_pos`, at which `block` matches.
+fn find_block(lines: &[String], block: &[&str], expected: usize, min_pos: usize) -> Option<usize> {
+    if block.is_empty() {
+        return Some(expected.max(min_pos).min(lines.len()));
+    }
+    let last = lines.len().checked_sub(block.len())?;
+    if min_pos > last {
+        return None;
+    }
+    let expected = expected.clamp(min_pos, last);
+    (0..=last).find_map(|distance| {
+        let after = expected + distance;
+        let before = expected.checked_sub(distance).filter(|p| *p >= min_pos);
+        [before, Some(after)]
+            .into_iter()
+            .flatten()
+            .find(|p| *p <= last && block_matches_at(lines, block, *p))
+    })
+}
+
+/// Describes why `block` does not match at `expected`, for reporting back to the model.
+fn mismatch_reason(lines: &[String], block: &[&str], expected: usize) -> String {
+    for (i, want) in block.iter().enumerate() {
+        match lines.get(expected + i) {
+            Some(got) if got.trim_end_matches('\r') == want.trim_end_matches('\r') => (),
+            Some(got) => {
+                return format!(
+                    "expected line {} to be {:?} but found {:?}, and the hunk's lines were not found anywhere else in the file",
+                    expected + i + 1,
+                    want,
+                    got.trim_end_matches('\r')
+                );
+            },
+            None => break,
+        }
+    }
+    format!(
+        "the hunk's {} context and removed lines were not found in the file ({} lines)",
+        block.len(),
+        lines.len()
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ORIGINAL: &str = "one\ntwo\nthree\nfour\nfive\nsix\nseven\neight\nnine\nten\n";
+
+    #[test]
+    fn test_parse_multi_file_diff() {
+        let diff = "diff --git a/src/a.rs b/src/a.rs\nindex 123..456 100644\n--- a/src/a.rs\n+++ b/src/a.rs\n@@ -1,2 +1,2 @@\n-one\n+uno\n two\n--- /dev/null\n+++ b/new.txt\t2024-01-01 00:00:00\n@@ -0,0 +1 @@\n+hello\n";
+        let files = parse_unified_diff(diff).unwrap();
+        assert_eq!(files.len(), 2);
+        assert_eq!(files[0].old_path.as_deref(), Some("src/a.rs"));
+        assert_eq!(files[0].hunks[0].lines, vec![
+            HunkLine::Remove("one".to_string()),
+            HunkLine::Add("uno".to_string()),
+            HunkLine::Context("two".to_string()),
+        ]);
+        assert_eq!(files[1].old_path, None);
+        assert_eq!(files[1].target_path(), "new.txt");
+        assert_eq!(files[1].hunks[0].header(), "@@ -0,0 +1,1 @@");
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert!(matches!(
+            parse_unified_diff("@@ -1 +1 @@\n-a\n+b\n"),
+            Err(PatchError::Parse { line: 1, .. })
+        ));
+        assert!(matches!(
+            parse_unified_diff("--- a/x\n+++ b/x\n@@ -1,2 +1,2 @@\n-a\n?b\n"),
+            Err(PatchError::Parse { line: 5, .. })
+        ));
+        assert!(parse_unified_diff("not a diff").is_err());
+        assert!(matches!(
+            parse_unified_diff("--- a/x\n+++ b/src/../../.bashrc\n@@ -1 +1 @@\n-a\n+b\n"),
+            Err(PatchError::Parse { line: 1, .. })
+        ));
+    }
+
+    #[test]
+    fn test_apply_multiple_hunks() {
+        let diff = "--- a/f\n+++ b/f\n@@ -2,2 +2,2 @@\n-two\n+TWO\n three\n@@ -8,3 +8,4 @@\n eight\n nine\n+nine and a half\n ten\n";
+        let files = parse_unified_diff(diff).unwrap();
+        let applied = files[0].apply(ORIGINAL).unwrap();
+        assert_eq!(
+            applied.content,
+            "one\nTWO\nthree\nfour\nfive\nsix\nseven\neight\nnine\nnine and a half\nten\n"
+        );
+        assert_eq!(applied.hunk_starts, vec![2, 8]);
+    }
+
+    #[test]
+    fn test_apply_with_drifted_line_numbers() {
+        // Header claims line 1, but the content is at line 4.
+        let diff = "--- a/f\n+++ b/f\n@@ -1,3 +1,3 @@\n four\n-five\n+FIVE\n six\n";
+        let files = parse_unified_diff(diff).unwrap();
+        let applied = files[0].apply(ORIGINAL).unwrap();
+        assert!(applied.content.contains("four\nFIVE\nsix\n"));
+        assert_eq!(applied.hunk_starts, vec![4]);
+    }
+
+    #[test]
+    fn test_apply_does_not_overlap_hunks() {
+        // The second hunk repeats the context of the first, which must not match the lines the
+        // first hunk just added.
+        let diff = "--- a/f\n+++ b/f\n@@ -2,1 +2,3 @@\n }\n+fn b() {\n+}\n@@ -2,1 +4,1 @@\n-}\n+} // a\n";
+        let files = parse_unified_diff(diff).unwrap();
+        let err = files[0].apply("fn a() {\n}\n").unwrap_err();
+        assert!(matches!(err, PatchError::HunkFailed { hunk: 2, .. }), "{err:?}");
+
+        // Repeated context further down is still found.
+        let diff = "--- a/f\n+++ b/f\n@@ -2,1 +2,1 @@\n-}\n+} // a\n@@ -2,1 +2,1 @@\n-}\n+} // b\n";
+        let files = parse_unified_diff(diff).unwrap();
+        let applied = files[0].apply("fn a() {\n}\nfn b() {\n}\n").unwrap();
+        assert_eq!(applied.content, "fn a() {\n} // a\nfn b() {\n} // b\n");
+        assert_eq!(applied.hunk_starts, vec![2, 4]);
+    }
+
+    #[test]
+    fn test_apply_reports_failing_hunk() {
+        let diff = "--- a/f\n+++ b/f\n@@ -1,1 +1,1 @@\n-one\n+1\n@@ -3,1 +3,1 @@\n-drei\n+3\n";
+        let files = parse_unified_diff(diff).unwrap();
+        let err = files[0].apply(ORIGINAL).unwrap_err();
+        match err {
+            PatchError::HunkFailed { hunk, header, reason } => {
+                assert_eq!(hunk, 2);
+                assert_eq!(header, "@@ -3,1 +3,1 @@");
+                assert!(reason.contains("\"drei\""), "{reason}");
+                assert!(reason.contains("\"three\""), "{reason}");
+            },
+            other @ PatchError::Parse { .. } => panic!("unexpected error: {other:?}"),
+        }
+    }
+
+    #[test]
+    fn test_apply_newline_handling() {
+        let diff = "--- a/f\n+++ b/f\n@@ -1,2 +1,2 @@\n a\n-b\n+c\n\\ No newline at end of file\n";
+        let files = parse_unified_diff(diff).unwrap();
+        assert_eq!(files[0].apply("a\nb\n").unwrap().content, "a\nc");
+
+        let diff = "--- /dev/null\n+++ b/f\n@@ -0,0 +1,2 @@\n+a\n+b\n";
+        let files = parse_unified_diff(diff).unwrap();
+        assert_eq!(files[0].apply("").unwrap().content, "a\nb\n");
+
+        let diff = "--- a/f\n+++ b/f\n@@ -1,2 +1,2 @@\n a\n-b\n+c\n";
+        let files = parse_unified_diff(diff).unwrap();
+        assert_eq!(files[0].apply("a\r\nb\r\n").unwrap().content, "a\r\nc\r\n");
+    }
+}
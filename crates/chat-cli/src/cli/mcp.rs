@@ -94,8 +94,14 @@ pub struct AddArgs {
     #[arg(long)]
     pub scope: Option<Scope>,
     /// The command used to launch the server
-    #[arg(long)]
-    pub command: String,
+    #[arg(long, required_unless_present = "url")]
+    pub command: Option<String>,
+    /// The url of a remote server, used instead of a command
+    #[arg(long, conflicts_with_all = ["command", "args", "env"])]
+    pub url: Option<String>,
+    /// HTTP headers to send to a remote server, e.g. 'Authorization: Bearer ${env:TOKEN}'
+    #[arg(long = "header", requires = "url", value_parser = parse_header)]
+    pub headers: Vec<(String, String)>,
     /// Arguments to pass to the command
     #[arg(long, action = ArgAction::Append, allow_hyphen_values = true, value_delimiter = ',')]
     pub args: Vec<String>,
@@ -118,6 +124,24 @@ pub struct AddArgs {
 }
 
 impl AddArgs {
+    fn server_config(&self) -> Result<CustomToolConfig> {
+        let merged_env = self.env.iter().flatten().collect::<HashMap<_, _>>();
+        let mut config = serde_json::json!({
+            "command": self.command.clone().unwrap_or_default(),
+            "args": self.args,
+            "env": merged_env,
+            "timeout": self.timeout.unwrap_or(default_timeout()),
+            "disabled": self.disabled,
+        });
+        if let Some(url) = &self.url {
+            config["url"] = url.clone().into();
+            if !self.headers.is_empty() {
+                config["headers"] = serde_json::json!(self.headers.iter().cloned().collect::<HashMap<_, _>>());
+            }
+        }
+        Ok(serde_json::from_value(config)?)
+    }
+
     pub async fn execute(self, os: &Os, output: &mut impl Write) -> Result<()> {
         match self.agent.as_deref() {
             Some(agent_name) => {
@@ -133,14 +157,7 @@ impl AddArgs {
                     );
                 }
 
-                let merged_env = self.env.into_iter().flatten().collect::<HashMap<_, _>>();
-                let tool: CustomToolConfig = serde_json::from_value(serde_json::json!({
-                    "command": self.command,
-                    "args": self.args,
-                    "env": merged_env,
-                    "timeout": self.timeout.unwrap_or(default_timeout()),
-                    "disabled": self.disabled,
-                }))?;
+                let tool = self.server_config()?;
 
                 mcp_servers.insert(self.name.clone(), tool);
                 let json = agent.to_str_pretty()?;
@@ -162,14 +179,7 @@ impl AddArgs {
                     );
                 }
 
-                let merged_env = self.env.into_iter().flatten().collect::<HashMap<_, _>>();
-                let tool: CustomToolConfig = serde_json::from_value(serde_json::json!({
-                    "command": self.command,
-                    "args": self.args,
-                    "env": merged_env,
-                    "timeout": self.timeout.unwrap_or(default_timeout()),
-                    "disabled": self.disabled,
-                }))?;
+                let tool = self.server_config()?;
 
                 mcp_servers.mcp_servers.insert(self.name.clone(), tool);
                 mcp_servers.save_to_file(os, &legacy_mcp_config_path).await?;
@@ -296,7 +306,7 @@ impl ListArgs {
                         servers.sort_by(|a, b| a.0.cmp(&b.0));
                         for (name, tool_cfg) in &servers {
                             let status = if tool_cfg.disabled { " (disabled)" } else { "" };
-                            writeln!(output, "    • {name:<12} {}{}", tool_cfg.command, status)?;
+                            writeln!(output, "    • {name:<12} {}{}", tool_cfg.target(), status)?;
                         }
                     },
                     _ => {
@@ -380,7 +390,10 @@ impl StatusArgs {
                         style::Print("\n─────────────\n"),
                         style::Print(format!("Scope   : {}\n", scope_display(&sc))),
                         style::Print(format!("Agent   : {}\n", name)),
-                        style::Print(format!("Command : {}\n", cfg.command)),
+                        style::Print(match &cfg.url {
+                            Some(url) => format!("Url     : {}\n", url),
+                            None => format!("Command : {}\n", cfg.command),
+                        }),
                         style::Print(format!("Timeout : {} ms\n", cfg.timeout)),
                         style::Print(format!("Disabled: {}\n", cfg.disabled)),
                         style::Print(format!(
@@ -496,6 +509,13 @@ fn parse_env_vars(arg: &str) -> Result<HashMap<String, String>> {
     Ok(vars)
 }
 
+fn parse_header(arg: &str) -> Result<(String, String)> {
+    match arg.split_once(':') {
+        Some((name, value)) if !name.trim().is_empty() => Ok((name.trim().to_string(), value.trim().to_string())),
+        _ => bail!("Failed to parse header '{}'. Expected 'name: value'", arg),
+    }
+}
+
 async fn load_cfg(os: &Os, p: &PathBuf) -> Result<McpServerConfig> {
     Ok(if os.fs.exists(p) {
         McpServerConfig::load_from_file(os, p).await?
@@ -553,7 +573,9 @@ mod tests {
         AddArgs {
             name: "local".into(),
             scope: None,
-            command: "echo hi".into(),
+            command: Some("echo hi".into()),
+            url: None,
+            headers: vec![],
             args: vec![
                 "awslabs.eks-mcp-server".to_string(),
                 "--allow-write".to_string(),
@@ -606,7 +628,9 @@ mod tests {
             RootSubcommand::Mcp(McpSubcommand::Add(AddArgs {
                 name: "test_server".to_string(),
                 scope: None,
-                command: "test_command".to_string(),
+                command: Some("test_command".to_string()),
+                url: None,
+                headers: vec![],
                 args: vec![
                     "awslabs.eks-mcp-server".to_string(),
                     "--allow-write".to_string(),
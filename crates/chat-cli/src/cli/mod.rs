@@ -18,6 +18,7 @@ use std::process::ExitCode;
 use agent::AgentArgs;
 use anstream::println;
 pub use chat::ConversationState;
+pub(crate) use chat::conversation_file;
 use clap::{
     ArgAction,
     CommandFactory,
@@ -63,6 +64,9 @@ pub enum OutputFormat {
     Json,
     /// Outputs the results as pretty print JSON
     JsonPretty,
+    /// Outputs the results as newline delimited JSON, streaming events as they happen where
+    /// supported
+    StreamJson,
 }
 
 impl OutputFormat {
@@ -75,7 +79,9 @@ impl OutputFormat {
     {
         match self {
             OutputFormat::Plain => println!("{}", text_fn()),
-            OutputFormat::Json => println!("{}", serde_json::to_string(&json_fn()).unwrap()),
+            OutputFormat::Json | OutputFormat::StreamJson => {
+                println!("{}", serde_json::to_string(&json_fn()).unwrap());
+            },
             OutputFormat::JsonPretty => println!("{}", serde_json::to_string_pretty(&json_fn()).unwrap()),
         }
     }
@@ -359,12 +365,16 @@ mod test {
         assert_eq!(Cli::parse_from([CHAT_BINARY_NAME, "chat", "-vv"]), Cli {
             subcommand: Some(RootSubcommand::Chat(ChatArgs {
                 resume: false,
+                resume_id: None,
                 input: None,
                 agent: None,
                 model: None,
                 trust_all_tools: false,
                 trust_tools: None,
                 no_interactive: false,
+                output_format: OutputFormat::Plain,
+                permission_prompt_command: None,
+                subcommand: None,
             })),
             verbose: 2,
             help_all: false,
@@ -398,12 +408,16 @@ mod test {
             ["chat", "--profile", "my-profile"],
             RootSubcommand::Chat(ChatArgs {
                 resume: false,
+                resume_id: None,
                 input: None,
                 agent: Some("my-profile".to_string()),
                 model: None,
                 trust_all_tools: false,
                 trust_tools: None,
                 no_interactive: false,
+                output_format: OutputFormat::Plain,
+                permission_prompt_command: None,
+                subcommand: None,
             })
         );
     }
@@ -414,12 +428,16 @@ mod test {
             ["chat", "--profile", "my-profile", "Hello"],
             RootSubcommand::Chat(ChatArgs {
                 resume: false,
+                resume_id: None,
                 input: Some("Hello".to_string()),
                 agent: Some("my-profile".to_string()),
                 model: None,
                 trust_all_tools: false,
                 trust_tools: None,
                 no_interactive: false,
+                output_format: OutputFormat::Plain,
+                permission_prompt_command: None,
+                subcommand: None,
             })
         );
     }
@@ -430,12 +448,16 @@ mod test {
             ["chat", "--profile", "my-profile", "--trust-all-tools"],
             RootSubcommand::Chat(ChatArgs {
                 resume: false,
+                resume_id: None,
                 input: None,
                 agent: Some("my-profile".to_string()),
                 model: None,
                 trust_all_tools: true,
                 trust_tools: None,
                 no_interactive: false,
+                output_format: OutputFormat::Plain,
+                permission_prompt_command: None,
+                subcommand: None,
             })
         );
     }
@@ -446,24 +468,32 @@ mod test {
             ["chat", "--no-interactive", "--resume"],
             RootSubcommand::Chat(ChatArgs {
                 resume: true,
+                resume_id: None,
                 input: None,
                 agent: None,
                 model: None,
                 trust_all_tools: false,
                 trust_tools: None,
                 no_interactive: true,
+                output_format: OutputFormat::Plain,
+                permission_prompt_command: None,
+                subcommand: None,
             })
         );
         assert_parse!(
             ["chat", "--non-interactive", "-r"],
             RootSubcommand::Chat(ChatArgs {
                 resume: true,
+                resume_id: None,
                 input: None,
                 agent: None,
                 model: None,
                 trust_all_tools: false,
                 trust_tools: None,
                 no_interactive: true,
+                output_format: OutputFormat::Plain,
+                permission_prompt_command: None,
+                subcommand: None,
             })
         );
     }
@@ -474,12 +504,16 @@ mod test {
             ["chat", "--trust-all-tools"],
             RootSubcommand::Chat(ChatArgs {
                 resume: false,
+                resume_id: None,
                 input: None,
                 agent: None,
                 model: None,
                 trust_all_tools: true,
                 trust_tools: None,
                 no_interactive: false,
+                output_format: OutputFormat::Plain,
+                permission_prompt_command: None,
+                subcommand: None,
             })
         );
     }
@@ -490,12 +524,16 @@ mod test {
             ["chat", "--trust-tools="],
             RootSubcommand::Chat(ChatArgs {
                 resume: false,
+                resume_id: None,
                 input: None,
                 agent: None,
                 model: None,
                 trust_all_tools: false,
                 trust_tools: Some(vec!["".to_string()]),
                 no_interactive: false,
+                output_format: OutputFormat::Plain,
+                permission_prompt_command: None,
+                subcommand: None,
             })
         );
     }
@@ -506,13 +544,61 @@ mod test {
             ["chat", "--trust-tools=fs_read,fs_write"],
             RootSubcommand::Chat(ChatArgs {
                 resume: false,
+                resume_id: None,
                 input: None,
                 agent: None,
                 model: None,
                 trust_all_tools: false,
                 trust_tools: Some(vec!["fs_read".to_string(), "fs_write".to_string()]),
                 no_interactive: false,
+                output_format: OutputFormat::Plain,
+                permission_prompt_command: None,
+                subcommand: None,
+            })
+        );
+    }
+    #[test]
+    fn test_chat_with_output_format() {
+        assert_parse!(
+            ["chat", "--no-interactive", "--output-format", "stream-json", "Hello"],
+            RootSubcommand::Chat(ChatArgs {
+                resume: false,
+                resume_id: None,
+                input: Some("Hello".to_string()),
+                agent: None,
+                model: None,
+                trust_all_tools: false,
+                trust_tools: None,
+                no_interactive: true,
+                output_format: OutputFormat::StreamJson,
+                permission_prompt_command: None,
+                subcommand: None,
+            })
+        );
+        assert!(Cli::try_parse_from([CHAT_BINARY_NAME, "chat", "--output-format", "json"]).is_err());
+    }
+
+    #[test]
+    fn test_chat_with_sessions() {
+        assert_parse!(
+            ["chat", "--resume-id", "3f2a9c1e"],
+            RootSubcommand::Chat(ChatArgs {
+                resume: false,
+                resume_id: Some("3f2a9c1e".to_string()),
+                input: None,
+                agent: None,
+                model: None,
+                trust_all_tools: false,
+                trust_tools: None,
+                no_interactive: false,
+                output_format: OutputFormat::Plain,
+                permission_prompt_command: None,
+                subcommand: None,
             })
         );
+        assert!(Cli::try_parse_from([CHAT_BINARY_NAME, "chat", "--resume", "--resume-id", "3f2a9c1e"]).is_err());
+        assert!(Cli::try_parse_from([CHAT_BINARY_NAME, "chat", "sessions", "list", "--all"]).is_ok());
+        assert!(Cli::try_parse_from([CHAT_BINARY_NAME, "chat", "sessions", "rename", "3f2a9c1e", "Fix CI"]).is_ok());
+        assert!(Cli::try_parse_from([CHAT_BINARY_NAME, "chat", "export", "3f2a9c1e", "--format", "html"]).is_ok());
     }
 }
@@ -77,7 +77,9 @@ impl SettingsArgs {
                             println!("{key} = {value}");
                         }
                     },
-                    OutputFormat::Json => println!("{}", serde_json::to_string(&settings)?),
+                    OutputFormat::Json | OutputFormat::StreamJson => {
+                        println!("{}", serde_json::to_string(&settings)?);
+                    },
                     OutputFormat::JsonPretty => {
                         println!("{}", serde_json::to_string_pretty(&settings)?);
                     },
@@ -99,14 +101,14 @@ impl SettingsArgs {
                                     Some(value) => println!("{value}"),
                                     None => println!("{value:#}"),
                                 },
-                                OutputFormat::Json => println!("{value}"),
+                                OutputFormat::Json | OutputFormat::StreamJson => println!("{value}"),
                                 OutputFormat::JsonPretty => println!("{value:#}"),
                             }
                             Ok(ExitCode::SUCCESS)
                         },
                         None => match self.format {
                             OutputFormat::Plain => Err(eyre::eyre!("No value associated with {key}")),
-                            OutputFormat::Json | OutputFormat::JsonPretty => {
+                            OutputFormat::Json | OutputFormat::JsonPretty | OutputFormat::StreamJson => {
                                 println!("null");
                                 Ok(ExitCode::SUCCESS)
                             },
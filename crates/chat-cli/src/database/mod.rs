@@ -35,6 +35,11 @@ use tracing::{
 use uuid::Uuid;
 
 use crate::cli::ConversationState;
+use crate::cli::conversation_file::{
+    self,
+    LoadError,
+    LoadedConversation,
+};
 use crate::util::directories::{
     DirectoryError,
     database_path,
@@ -70,7 +75,8 @@ const MIGRATIONS: &[Migration] = migrations![
     "004_state_table",
     "005_auth_table",
     "006_make_state_blob",
-    "007_conversations_table"
+    "007_conversations_table",
+    "008_sessions_table"
 ];
 
 #[derive(Debug, serde::Deserialize, serde::Serialize)]
@@ -144,6 +150,8 @@ pub enum DatabaseError {
     StrFromUtf8(#[from] std::str::Utf8Error),
     #[error("`{}` is not a valid setting", .0)]
     InvalidSetting(String),
+    #[error("the saved conversation cannot be read: {0}")]
+    InvalidSession(#[from] LoadError),
 }
 
 impl<T> From<PoisonError<T>> for DatabaseError {
@@ -156,8 +164,6 @@ impl<T> From<PoisonError<T>> for DatabaseError {
 pub enum Table {
     /// The state table contains persistent application state.
     State,
-    /// The conversations tables contains user chat conversations.
-    Conversations,
     /// The auth table contains SSO and Builder ID credentials.
     Auth,
 }
@@ -166,12 +172,27 @@ impl std::fmt::Display for Table {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             Table::State => write!(f, "state"),
-            Table::Conversations => write!(f, "conversations"),
             Table::Auth => write!(f, "auth_kv"),
         }
     }
 }
 
+/// A saved chat conversation, without its history.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SessionSummary {
+    pub id: String,
+    /// The directory the conversation was started in.
+    pub path: String,
+    pub title: String,
+    /// Unix timestamp in seconds.
+    pub created_at: i64,
+    /// Unix timestamp in seconds.
+    pub updated_at: i64,
+    pub model: Option<String>,
+    pub agent: Option<String>,
+    pub message_count: usize,
+}
+
 #[derive(Debug)]
 struct Migration {
     name: &'static str,
@@ -326,33 +347,95 @@ impl Database {
     //     self.delete_entry(Table::State, LAST_USED_MODEL_ID)
     // }
 
-    /// Get a chat conversation given a path to the conversation.
-    pub fn get_conversation_by_path(
-        &mut self,
-        path: impl AsRef<Path>,
-    ) -> Result<Option<ConversationState>, DatabaseError> {
+    /// Get a saved chat conversation given its id.
+    ///
+    /// Conversations are saved in the format of `/save`, so that those saved by earlier versions
+    /// are migrated, and parts that cannot be read are left out rather than failing the load.
+    pub fn get_session(&self, id: &str) -> Result<Option<LoadedConversation>, DatabaseError> {
+        let conn = self.pool.get()?;
+        let mut stmt = conn.prepare("SELECT value FROM sessions WHERE id = ?1")?;
+        match stmt.query_row([id], |row| row.get::<_, String>(0)) {
+            Ok(value) => Ok(Some(conversation_file::from_json(&value)?)),
+            Err(Error::QueryReturnedNoRows) => Ok(None),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    /// Save a chat conversation started in the directory `path`. A conversation that was saved
+    /// before keeps its directory, title and creation time.
+    pub fn set_session(&self, path: impl AsRef<Path>, state: &ConversationState) -> Result<usize, DatabaseError> {
         // We would need to encode this to support non utf8 paths.
         let path = match path.as_ref().to_str() {
             Some(path) => path,
-            None => return Ok(None),
+            None => return Ok(0),
         };
 
-        self.get_json_entry(Table::Conversations, path)
+        Ok(self.pool.get()?.execute(
+            "INSERT INTO sessions (id, path, title, created_at, updated_at, model, agent, message_count, value)
+            VALUES (?1, ?2, ?3, strftime('%s', 'now'), strftime('%s', 'now'), ?4, ?5, ?6, ?7)
+            ON CONFLICT (id) DO UPDATE SET
+                title = CASE WHEN title = '' THEN excluded.title ELSE title END,
+                updated_at = excluded.updated_at,
+                model = excluded.model,
+                agent = excluded.agent,
+                message_count = excluded.message_count,
+                value = excluded.value",
+            params![
+                state.conversation_id(),
+                path,
+                state.title(),
+                state.model,
+                state.current_profile(),
+                state.history().len() * 2,
+                conversation_file::to_session_json(state)?
+            ],
+        )?)
     }
 
-    /// Set a chat conversation given a path to the conversation.
-    pub fn set_conversation_by_path(
-        &mut self,
-        path: impl AsRef<Path>,
-        state: &ConversationState,
-    ) -> Result<usize, DatabaseError> {
-        // We would need to encode this to support non utf8 paths.
-        let path = match path.as_ref().to_str() {
-            Some(path) => path,
-            None => return Ok(0),
+    /// List the saved chat conversations started in `path`, or all of them, most recently updated
+    /// first.
+    pub fn list_sessions(&self, path: Option<&Path>) -> Result<Vec<SessionSummary>, DatabaseError> {
+        let path = match path.map(|path| path.to_str()) {
+            Some(Some(path)) => Some(path),
+            Some(None) => return Ok(Vec::new()),
+            None => None,
         };
 
-        self.set_json_entry(Table::Conversations, path, state)
+        let conn = self.pool.get()?;
+        let mut stmt = conn.prepare(
+            "SELECT id, path, title, created_at, updated_at, model, agent, message_count FROM sessions
+            WHERE ?1 IS NULL OR path = ?1
+            ORDER BY updated_at DESC, created_at DESC",
+        )?;
+        let rows = stmt.query_map([path], |row| {
+            Ok(SessionSummary {
+                id: row.get(0)?,
+                path: row.get(1)?,
+                title: row.get(2)?,
+                created_at: row.get(3)?,
+                updated_at: row.get(4)?,
+                model: row.get(5)?,
+                agent: row.get(6)?,
+                message_count: row.get(7)?,
+            })
+        })?;
+
+        Ok(rows.collect::<Result<_, _>>()?)
+    }
+
+    /// Rename a saved chat conversation, returning whether it exists.
+    pub fn rename_session(&self, id: &str, title: &str) -> Result<bool, DatabaseError> {
+        let updated = self
+            .pool
+            .get()?
+            .execute("UPDATE sessions SET title = ?2 WHERE id = ?1", params![id, title])?;
+        Ok(updated > 0)
+    }
+
+    /// Delete a saved chat conversation, returning whether it existed.
+    pub fn delete_session(&self, id: &str) -> Result<bool, DatabaseError> {
+        let deleted = self.pool.get()?.execute("DELETE FROM sessions WHERE id = ?1", [id])?;
+        Ok(deleted > 0)
     }
 
     pub async fn get_secret(&self, key: &str) -> Result<Option<Secret>, DatabaseError> {
@@ -534,6 +617,33 @@ mod tests {
         }
     }
 
+    #[tokio::test]
+    async fn test_get_session_of_earlier_version() {
+        let db = Database::new().await.unwrap();
+        let insert = |id: &str, value: &str| {
+            db.pool
+                .get()
+                .unwrap()
+                .execute(
+                    "INSERT INTO sessions (id, path, created_at, updated_at, value) VALUES (?1, '/repo', 0, 0, ?2)",
+                    params![id, value],
+                )
+                .unwrap();
+        };
+
+        // Sessions saved before sessions used the format of `/save` are migrated.
+        insert("legacy", include_str!("../cli/chat/conversation_file/fixtures/v1.json"));
+        let loaded = db.get_session("legacy").unwrap().unwrap();
+        assert_eq!(loaded.version, 1);
+        assert!(!loaded.is_empty());
+
+        insert("broken", "[]");
+        assert!(matches!(
+            db.get_session("broken"),
+            Err(DatabaseError::InvalidSession(_))
+        ));
+    }
+
     #[tokio::test]
     async fn test_migrate() {
         let db = Database::new().await.unwrap();
@@ -0,0 +1,27 @@
+CREATE TABLE sessions (
+    id TEXT PRIMARY KEY,
+    path TEXT NOT NULL,
+    title TEXT NOT NULL DEFAULT '',
+    created_at INTEGER NOT NULL,
+    updated_at INTEGER NOT NULL,
+    model TEXT,
+    agent TEXT,
+    message_count INTEGER NOT NULL DEFAULT 0,
+    value TEXT NOT NULL
+);
+
+CREATE INDEX sessions_path_updated_at ON sessions (path, updated_at);
+
+INSERT OR IGNORE INTO sessions (id, path, title, created_at, updated_at, model, agent, message_count, value)
+SELECT
+    json_extract(value, '$.conversation_id'),
+    key,
+    COALESCE(substr(replace(json_extract(value, '$.history[0].user.content.Prompt.prompt'), char(10), ' '), 1, 80), ''),
+    strftime('%s', 'now'),
+    strftime('%s', 'now'),
+    json_extract(value, '$.model'),
+    json_extract(value, '$.context_manager.current_profile'),
+    json_array_length(value, '$.history') * 2,
+    value
+FROM conversations
+WHERE json_valid(value) AND json_extract(value, '$.conversation_id') IS NOT NULL;
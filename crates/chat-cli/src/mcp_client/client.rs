@@ -20,11 +20,13 @@ use tokio::time;
 use tokio::time::error::Elapsed;
 
 use super::transport::base_protocol::{
+    JsonRpcError,
     JsonRpcMessage,
     JsonRpcNotification,
     JsonRpcRequest,
     JsonRpcVersion,
 };
+use super::transport::http::JsonRpcHttpTransport;
 use super::transport::stdio::JsonRpcStdioTransport;
 use super::transport::{
     self,
@@ -32,15 +34,21 @@ use super::transport::{
     TransportError,
 };
 use super::{
+    CreateMessageRequest,
+    ElicitRequest,
     JsonRpcResponse,
     Listener as _,
     LogListener,
     Messenger,
+    MessengerError,
     PaginationSupportedOps,
+    ProgressNotification,
     PromptGet,
     PromptsListResult,
+    ResourceReadResult,
     ResourceTemplatesListResult,
     ResourcesListResult,
+    RootsListResult,
     ServerCapabilities,
     ToolsListResult,
 };
@@ -50,7 +58,14 @@ use crate::util::process::{
 };
 
 pub type ClientInfo = serde_json::Value;
+
+/// JSON-RPC error codes used in responses to requests from servers
+const METHOD_NOT_FOUND: i32 = -32601;
+const INVALID_PARAMS: i32 = -32602;
+const INTERNAL_ERROR: i32 = -32603;
+const USER_REJECTED: i32 = -1;
 pub type StdioTransport = JsonRpcStdioTransport;
+pub type HttpTransport = JsonRpcHttpTransport;
 
 /// Represents the capabilities of a client in the Model Context Protocol.
 /// This structure is sent to the server during initialization to communicate
@@ -69,6 +84,11 @@ impl From<ClientInfo> for ClientCapabilities {
     fn from(client_info: ClientInfo) -> Self {
         ClientCapabilities {
             client_info,
+            capabilities: HashMap::from([
+                ("sampling".to_string(), serde_json::json!({})),
+                ("roots".to_string(), serde_json::json!({ "listChanged": true })),
+                ("elicitation".to_string(), serde_json::json!({})),
+            ]),
             ..Default::default()
         }
     }
@@ -84,6 +104,16 @@ pub struct ClientConfig {
     pub env: Option<HashMap<String, String>>,
 }
 
+/// Configuration for a server reached over HTTP, see [JsonRpcHttpTransport].
+#[derive(Debug, Deserialize)]
+pub struct HttpClientConfig {
+    pub server_name: String,
+    pub url: String,
+    pub headers: HashMap<String, String>,
+    pub timeout: u64,
+    pub client_info: serde_json::Value,
+}
+
 #[allow(dead_code)]
 #[derive(Debug, Error)]
 pub enum ClientError {
@@ -111,6 +141,10 @@ pub enum ClientError {
     ProcessKillError(String),
     #[error("{0}")]
     PoisonError(String),
+    #[error("Server responded with error {code}: {message}")]
+    ServerError { code: i32, message: String },
+    #[error("Response to {0} is missing result")]
+    MissingResult(String),
 }
 
 impl From<(tokio::time::error::Elapsed, String)> for ClientError {
@@ -266,6 +300,30 @@ impl Client<StdioTransport> {
     }
 }
 
+impl Client<HttpTransport> {
+    pub fn from_config(config: HttpClientConfig) -> Result<Self, ClientError> {
+        let HttpClientConfig {
+            server_name,
+            url,
+            headers,
+            timeout,
+            client_info,
+        } = config;
+        let transport = Arc::new(JsonRpcHttpTransport::client(&url, &headers)?);
+        Ok(Self {
+            server_name,
+            transport,
+            timeout,
+            server_process_id: None,
+            client_info,
+            current_id: Arc::new(AtomicU64::new(0)),
+            messenger: None,
+            prompt_gets: Arc::new(SyncRwLock::new(HashMap::new())),
+            is_prompts_out_of_date: Arc::new(AtomicBool::new(false)),
+        })
+    }
+}
+
 impl<T> Drop for Client<T>
 where
     T: Transport,
@@ -357,6 +415,13 @@ where
                 fetch_tools_and_notify_with_messenger(&client_ref, messenger_ref.as_ref()).await;
             });
         }
+        if cap.resources.is_some() {
+            let client_ref = (*self).clone();
+            let messenger_ref = self.messenger.as_ref().map(|m| m.duplicate());
+            tokio::spawn(async move {
+                fetch_resources_and_notify_with_messenger(&client_ref, messenger_ref.as_ref()).await;
+            });
+        }
 
         let transport_ref = self.transport.clone();
         let server_name = self.server_name.clone();
@@ -365,13 +430,24 @@ where
 
         let prompts_list_changed_supported = cap.prompts.as_ref().is_some_and(|p| p.get("listChanged").is_some());
         let tools_list_changed_supported = cap.tools.as_ref().is_some_and(|t| t.get("listChanged").is_some());
+        let resources_list_changed_supported = cap.resources.as_ref().is_some_and(|r| r.get("listChanged").is_some());
         tokio::spawn(async move {
             let mut listener = transport_ref.get_listener();
             loop {
                 match listener.recv().await {
                     Ok(msg) => {
                         match msg {
-                            JsonRpcMessage::Request(_req) => {},
+                            JsonRpcMessage::Request(req) => {
+                                let transport_ref = transport_ref.clone();
+                                let messenger_ref = messenger_ref.as_ref().map(|m| m.duplicate());
+                                let server_name = server_name.clone();
+                                tokio::spawn(async move {
+                                    let resp = handle_server_request(req, messenger_ref.as_ref()).await;
+                                    if let Err(e) = transport_ref.send(&JsonRpcMessage::Response(resp)).await {
+                                        tracing::error!("Failed to respond to request from {server_name}: {:?}", e);
+                                    }
+                                });
+                            },
                             JsonRpcMessage::Notification(notif) => {
                                 let JsonRpcNotification { method, params, .. } = notif;
                                 match method.as_str() {
@@ -421,6 +497,33 @@ where
                                         fetch_tools_and_notify_with_messenger(&client_ref, messenger_ref.as_ref())
                                             .await;
                                     },
+                                    "notifications/resources/list_changed" if resources_list_changed_supported => {
+                                        fetch_resources_and_notify_with_messenger(&client_ref, messenger_ref.as_ref())
+                                            .await;
+                                    },
+                                    "notifications/progress" => {
+                                        let progress =
+                                            params.and_then(|p| serde_json::from_value::<ProgressNotification>(p).ok());
+                                        if let (Some(progress), Some(messenger)) = (progress, messenger_ref.as_ref()) {
+                                            let _ = messenger.send_progress_notification(progress).await.map_err(|e| {
+                                                tracing::error!("Failed to send progress through messenger {:?}", e);
+                                            });
+                                        }
+                                    },
+                                    "notifications/resources/updated" => {
+                                        let uri = params.as_ref().and_then(|p| p.get("uri")).and_then(|v| v.as_str());
+                                        if let (Some(uri), Some(messenger)) = (uri, messenger_ref.as_ref()) {
+                                            let _ = messenger
+                                                .send_resource_updated_notification(uri.to_string())
+                                                .await
+                                                .map_err(|e| {
+                                                    tracing::error!(
+                                                        "Failed to send resource update through messenger {:?}",
+                                                        e
+                                                    );
+                                                });
+                                        }
+                                    },
                                     _ => {},
                                 }
                             },
@@ -466,10 +569,11 @@ where
         };
         tracing::trace!(target: "mcp", "To {}:\n{:#?}", self.server_name, request);
         let msg = JsonRpcMessage::Request(request);
+        // Subscribe before sending since some transports receive the response while sending.
+        let mut listener = self.transport.get_listener();
         time::timeout(Duration::from_millis(self.timeout), self.transport.send(&msg))
             .await
             .map_err(send_map_err)??;
-        let mut listener = self.transport.get_listener();
         let mut resp = time::timeout(Duration::from_millis(self.timeout), async {
             // we want to ignore all other messages sent by the server at this point and let the
             // background loop handle them
@@ -567,6 +671,127 @@ where
         Ok(resp)
     }
 
+    /// Sends a request the server may report progress on, see
+    /// https://modelcontextprotocol.io/specification/2025-03-26/basic/utilities/progress
+    ///
+    /// Progress is delivered through the messenger. Unlike [Self::request], the timeout starts
+    /// over whenever progress is reported, and the server is asked to cancel the request if it
+    /// times out or the returned future is dropped before the response arrives, e.g. because the
+    /// user interrupted it.
+    pub async fn request_with_progress(
+        &self,
+        method: &str,
+        params: Option<serde_json::Value>,
+    ) -> Result<JsonRpcResponse, ClientError> {
+        let send_map_err = |e: Elapsed| (e, method.to_string());
+        let recv_map_err = |e: Elapsed| (e, format!("recv for {method}"));
+        let id = self.get_id();
+        let mut params = params.unwrap_or_else(|| serde_json::json!({}));
+        if let Some(params) = params.as_object_mut() {
+            let meta = params.entry("_meta").or_insert_with(|| serde_json::json!({}));
+            if let Some(meta) = meta.as_object_mut() {
+                meta.insert("progressToken".to_string(), id.into());
+            }
+        }
+        let request = JsonRpcRequest {
+            jsonrpc: JsonRpcVersion::default(),
+            id,
+            method: method.to_owned(),
+            params: Some(params),
+        };
+        tracing::trace!(target: "mcp", "To {}:\n{:#?}", self.server_name, request);
+        let msg = JsonRpcMessage::Request(request);
+        let mut listener = self.transport.get_listener();
+        time::timeout(Duration::from_millis(self.timeout), self.transport.send(&msg))
+            .await
+            .map_err(send_map_err)??;
+
+        let mut cancellation = Cancellation {
+            transport: Some(self.transport.clone()),
+            request_id: id,
+            reason: "The user interrupted the request",
+        };
+        let timeout = Duration::from_millis(self.timeout);
+        let mut deadline = time::Instant::now() + timeout;
+        let resp = loop {
+            let msg = match time::timeout_at(deadline, listener.recv()).await {
+                Ok(msg) => msg,
+                Err(e) => {
+                    cancellation.reason = "The request timed out";
+                    return Err(recv_map_err(e).into());
+                },
+            };
+            match msg {
+                Ok(JsonRpcMessage::Response(resp)) if resp.id == id => break resp,
+                Ok(JsonRpcMessage::Notification(notif))
+                    if notif.method == "notifications/progress"
+                        && notif
+                            .params
+                            .as_ref()
+                            .and_then(|p| p.get("progressToken"))
+                            .is_some_and(|token| token.as_u64() == Some(id)) =>
+                {
+                    deadline = time::Instant::now() + timeout;
+                },
+                Err(TransportError::RecvError(tokio::sync::broadcast::error::RecvError::Closed)) => {
+                    cancellation.transport = None;
+                    return Err(ClientError::TransportError(TransportError::RecvError(
+                        tokio::sync::broadcast::error::RecvError::Closed,
+                    )));
+                },
+                _ => {},
+            }
+        };
+        cancellation.transport = None;
+        tracing::trace!(target: "mcp", "From {}:\n{:#?}", self.server_name, resp);
+        Ok(resp)
+    }
+
+    /// Lists the resources offered by the server, across all pages.
+    pub async fn list_resources(&self) -> Result<ResourcesListResult, ClientError> {
+        self.request_result("resources/list", None).await
+    }
+
+    /// Lists the resource templates offered by the server, across all pages.
+    pub async fn list_resource_templates(&self) -> Result<ResourceTemplatesListResult, ClientError> {
+        self.request_result("resources/templates/list", None).await
+    }
+
+    pub async fn read_resource(&self, uri: &str) -> Result<ResourceReadResult, ClientError> {
+        self.request_result("resources/read", Some(serde_json::json!({ "uri": uri })))
+            .await
+    }
+
+    /// Asks the server to send `notifications/resources/updated` whenever the resource changes.
+    pub async fn subscribe_resource(&self, uri: &str) -> Result<(), ClientError> {
+        self.request_result::<serde_json::Value>("resources/subscribe", Some(serde_json::json!({ "uri": uri })))
+            .await?;
+        Ok(())
+    }
+
+    pub async fn unsubscribe_resource(&self, uri: &str) -> Result<(), ClientError> {
+        self.request_result::<serde_json::Value>("resources/unsubscribe", Some(serde_json::json!({ "uri": uri })))
+            .await?;
+        Ok(())
+    }
+
+    /// Sends a request and deserializes the result of its response.
+    async fn request_result<R: serde::de::DeserializeOwned>(
+        &self,
+        method: &str,
+        params: Option<serde_json::Value>,
+    ) -> Result<R, ClientError> {
+        let resp = self.request(method, params).await?;
+        if let Some(error) = resp.error {
+            return Err(ClientError::ServerError {
+                code: error.code,
+                message: error.message,
+            });
+        }
+        let result = resp.result.ok_or(ClientError::MissingResult(method.to_string()))?;
+        Ok(serde_json::from_value(result)?)
+    }
+
     /// Sends a notification to the server associated.
     /// Notifications are requests that expect no responses.
     pub async fn notify(&self, method: &str, params: Option<serde_json::Value>) -> Result<(), ClientError> {
@@ -589,6 +814,35 @@ where
     }
 }
 
+/// Sends `notifications/cancelled` for a request when dropped, unless the transport has been
+/// taken because the request completed.
+struct Cancellation<T: Transport> {
+    transport: Option<Arc<T>>,
+    request_id: u64,
+    reason: &'static str,
+}
+
+impl<T: Transport> Drop for Cancellation<T> {
+    fn drop(&mut self) {
+        let (Some(transport), Ok(runtime)) = (self.transport.take(), tokio::runtime::Handle::try_current()) else {
+            return;
+        };
+        let notification = JsonRpcMessage::Notification(JsonRpcNotification {
+            jsonrpc: JsonRpcVersion::default(),
+            method: "notifications/cancelled".to_string(),
+            params: Some(serde_json::json!({
+                "requestId": self.request_id,
+                "reason": self.reason,
+            })),
+        });
+        runtime.spawn(async move {
+            if let Err(e) = transport.send(&notification).await {
+                tracing::error!("Failed to send cancellation: {:?}", e);
+            }
+        });
+    }
+}
+
 fn examine_server_capabilities(ser_cap: &JsonRpcResponse) -> Result<(), ClientError> {
     // Check the jrpc version.
     // Currently we are only proceeding if the versions are EXACTLY the same.
@@ -682,6 +936,118 @@ where
     }
 }
 
+/// Responds to a request sent by the server.
+#[allow(clippy::borrowed_box)]
+async fn handle_server_request(req: JsonRpcRequest, messenger: Option<&Box<dyn Messenger>>) -> JsonRpcResponse {
+    let JsonRpcRequest { id, method, params, .. } = req;
+    let result = match method.as_str() {
+        "ping" => Ok(serde_json::json!({})),
+        "roots/list" => {
+            let roots = match messenger {
+                Some(messenger) => messenger.list_roots().await,
+                None => Ok(Vec::new()),
+            };
+            roots
+                .map(|roots| serde_json::to_value(RootsListResult { roots }).unwrap_or_default())
+                .map_err(|e| JsonRpcError {
+                    code: INTERNAL_ERROR,
+                    message: e.to_string(),
+                    data: None,
+                })
+        },
+        "sampling/createMessage" => 'sampling: {
+            let request = match serde_json::from_value::<CreateMessageRequest>(params.unwrap_or_default()) {
+                Ok(request) => request,
+                Err(e) => {
+                    break 'sampling Err(JsonRpcError {
+                        code: INVALID_PARAMS,
+                        message: format!("Invalid sampling request: {e}"),
+                        data: None,
+                    });
+                },
+            };
+            let result = match messenger {
+                Some(messenger) => messenger.send_sampling_request(request).await,
+                None => Err(MessengerError::Custom("Sampling is not supported".to_string())),
+            };
+            result
+                .map(|result| serde_json::to_value(result).unwrap_or_default())
+                .map_err(|e| JsonRpcError {
+                    code: USER_REJECTED,
+                    message: e.to_string(),
+                    data: None,
+                })
+        },
+        "elicitation/create" => 'elicitation: {
+            let request = match serde_json::from_value::<ElicitRequest>(params.unwrap_or_default()) {
+                Ok(request) => request,
+                Err(e) => {
+                    break 'elicitation Err(JsonRpcError {
+                        code: INVALID_PARAMS,
+                        message: format!("Invalid elicitation request: {e}"),
+                        data: None,
+                    });
+                },
+            };
+            let result = match messenger {
+                Some(messenger) => messenger.send_elicitation_request(request).await,
+                None => Err(MessengerError::Custom("Elicitation is not supported".to_string())),
+            };
+            result
+                .map(|result| serde_json::to_value(result).unwrap_or_default())
+                .map_err(|e| JsonRpcError {
+                    code: INTERNAL_ERROR,
+                    message: e.to_string(),
+                    data: None,
+                })
+        },
+        _ => Err(JsonRpcError {
+            code: METHOD_NOT_FOUND,
+            message: format!("Method not found: {method}"),
+            data: None,
+        }),
+    };
+    let (result, error) = match result {
+        Ok(result) => (Some(result), None),
+        Err(error) => (None, Some(error)),
+    };
+    JsonRpcResponse {
+        jsonrpc: JsonRpcVersion::default(),
+        id,
+        result,
+        error,
+    }
+}
+
+#[allow(clippy::borrowed_box)]
+async fn fetch_resources_and_notify_with_messenger<T>(client: &Client<T>, messenger: Option<&Box<dyn Messenger>>)
+where
+    T: Transport,
+{
+    let resources = client
+        .list_resources()
+        .await
+        .map_err(|e| eyre::eyre!("Failed to retrieve resource list for {}: {}", client.server_name, e));
+    // Templates are optional, servers without any may not implement the method.
+    let templates = client.list_resource_templates().await.map_err(|e| {
+        eyre::eyre!(
+            "Failed to retrieve resource template list for {}: {}",
+            client.server_name,
+            e
+        )
+    });
+    if let Some(messenger) = messenger {
+        let _ = messenger
+            .send_resources_list_result(resources)
+            .await
+            .map_err(|e| tracing::error!("Failed to send resource list through messenger {:?}", e));
+        let _ = messenger
+            .send_resource_templates_list_result(templates)
+            .await
+            .map_err(|e| tracing::error!("Failed to send resource template list through messenger {:?}", e));
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use std::path::PathBuf;
@@ -979,6 +1345,40 @@ mod tests {
         })
     }
 
+    #[tokio::test]
+    async fn test_handle_server_request() {
+        let request = |method: &str, params: Value| JsonRpcRequest {
+            jsonrpc: JsonRpcVersion::default(),
+            id: 7,
+            method: method.to_string(),
+            params: Some(params),
+        };
+
+        let response = handle_server_request(request("ping", serde_json::json!({})), None).await;
+        assert_eq!(response.id, 7);
+        assert_eq!(response.result, Some(serde_json::json!({})));
+
+        let response = handle_server_request(request("roots/list", serde_json::json!({})), None).await;
+        assert_eq!(response.result, Some(serde_json::json!({ "roots": [] })));
+
+        let response = handle_server_request(request("elicitation/create", serde_json::json!({})), None).await;
+        assert_eq!(response.error.map(|e| e.code), Some(INVALID_PARAMS));
+
+        let response = handle_server_request(request("roots/unknown", serde_json::json!({})), None).await;
+        assert_eq!(response.error.map(|e| e.code), Some(METHOD_NOT_FOUND));
+
+        let response = handle_server_request(request("sampling/createMessage", serde_json::json!({})), None).await;
+        assert_eq!(response.error.map(|e| e.code), Some(INVALID_PARAMS));
+
+        let params = serde_json::json!({
+            "messages": [{ "role": "user", "content": { "type": "text", "text": "Hello" } }],
+            "maxTokens": 10,
+        });
+        let response = handle_server_request(request("sampling/createMessage", params), None).await;
+        assert!(response.result.is_none());
+        assert_eq!(response.error.map(|e| e.code), Some(USER_REJECTED));
+    }
+
     #[cfg(windows)]
     mod windows_command_tests {
         use super::*;
@@ -1,3 +1,5 @@
+use std::collections::HashMap;
+
 use serde::{
     Deserialize,
     Serialize,
@@ -84,6 +86,157 @@ pub struct ResourceTemplatesListResult {
     pub next_cursor: Option<String>,
 }
 
+/// A resource offered by a server, as listed by `resources/list`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ResourceInfo {
+    pub uri: String,
+    /// Human-readable name of the resource
+    pub name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mime_type: Option<String>,
+}
+
+/// A template for resources offered by a server, as listed by `resources/templates/list`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ResourceTemplateInfo {
+    /// A URI template (RFC 6570) that expands into the URI of a resource
+    pub uri_template: String,
+    /// Human-readable name of the resources created from the template
+    pub name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mime_type: Option<String>,
+}
+
+impl ResourceTemplateInfo {
+    /// Returns the names of the variables in the template, in order of appearance.
+    pub fn variables(&self) -> Vec<String> {
+        let mut variables = Vec::<String>::new();
+        for (_, names) in template_expressions(&self.uri_template) {
+            for name in names.split(',') {
+                let name = name.trim_end_matches('*').to_string();
+                if !name.is_empty() && !variables.contains(&name) {
+                    variables.push(name);
+                }
+            }
+        }
+        variables
+    }
+
+    /// Expands the template with the given variable values. Supports simple (`{var}`), reserved
+    /// (`{+var}`), fragment (`{#var}`), path (`{/var}`) and query (`{?var}`, `{&var}`) expansion.
+    /// Variables without a value are left out.
+    pub fn expand(&self, values: &HashMap<String, String>) -> String {
+        use percent_encoding::{
+            AsciiSet,
+            NON_ALPHANUMERIC,
+            utf8_percent_encode,
+        };
+        const UNRESERVED: &AsciiSet = &NON_ALPHANUMERIC.remove(b'-').remove(b'.').remove(b'_').remove(b'~');
+        const RESERVED: &AsciiSet = &UNRESERVED
+            .remove(b':')
+            .remove(b'/')
+            .remove(b'?')
+            .remove(b'#')
+            .remove(b'[')
+            .remove(b']')
+            .remove(b'@')
+            .remove(b'!')
+            .remove(b'$')
+            .remove(b'&')
+            .remove(b'\'')
+            .remove(b'(')
+            .remove(b')')
+            .remove(b'*')
+            .remove(b'+')
+            .remove(b',')
+            .remove(b';')
+            .remove(b'=');
+
+        let mut expanded = String::new();
+        let mut rest = self.uri_template.as_str();
+        for (expression, names) in template_expressions(&self.uri_template) {
+            let (before, after) = rest.split_once(expression).expect("expression is in template");
+            expanded.push_str(before);
+            rest = after;
+
+            let operator = expression[1..].chars().next().filter(|c| "+#/?&".contains(*c));
+            let set = if matches!(operator, Some('+' | '#')) {
+                RESERVED
+            } else {
+                UNRESERVED
+            };
+            let (first, separator) = match operator {
+                Some('#') => ("#", ","),
+                Some('/') => ("/", "/"),
+                Some('?') => ("?", "&"),
+                Some('&') => ("&", "&"),
+                _ => ("", ","),
+            };
+            let parts = names
+                .split(',')
+                .filter_map(|name| {
+                    let name = name.trim_end_matches('*');
+                    let value = utf8_percent_encode(values.get(name)?, set).to_string();
+                    Some(match operator {
+                        Some('?' | '&') => format!("{name}={value}"),
+                        _ => value,
+                    })
+                })
+                .collect::<Vec<_>>();
+            if !parts.is_empty() {
+                expanded.push_str(first);
+                expanded.push_str(&parts.join(separator));
+            }
+        }
+        expanded.push_str(rest);
+        expanded
+    }
+}
+
+/// Returns the expressions of a URI template with the names of their variables, i.e.
+/// `("{?a,b}", "a,b")`.
+fn template_expressions(template: &str) -> Vec<(&str, &str)> {
+    let mut expressions = Vec::new();
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        let Some(len) = rest[start..].find('}') else {
+            break;
+        };
+        let expression = &rest[start..=start + len];
+        let names = expression[1..expression.len() - 1].trim_start_matches(['+', '#', '/', '?', '&', '.', ';']);
+        expressions.push((expression, names));
+        rest = &rest[start + len + 1..];
+    }
+    expressions
+}
+
+/// `result` field in [JsonRpcResponse] from a `resources/read` request
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ResourceReadResult {
+    pub contents: Vec<ResourceReadContents>,
+}
+
+/// Contents of a resource read with `resources/read`. Exactly one of `text` and `blob` is set.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ResourceReadContents {
+    pub uri: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mime_type: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub text: Option<String>,
+    /// base64-encoded-data
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub blob: Option<String>,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 /// Result of prompt listing query
@@ -160,6 +313,221 @@ pub struct ToolCallResult {
     pub is_error: Option<bool>,
 }
 
+/// Params of a `sampling/createMessage` request, sent by a server to request a completion from
+/// the model.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CreateMessageRequest {
+    pub messages: Vec<SamplingMessage>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub system_prompt: Option<String>,
+    /// The maximum number of tokens to sample, advisory only
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_tokens: Option<u32>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SamplingMessage {
+    pub role: Role,
+    pub content: MessageContent,
+}
+
+/// `result` field of the response to a `sampling/createMessage` request
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CreateMessageResult {
+    pub role: Role,
+    pub content: MessageContent,
+    /// The model that generated the message
+    pub model: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stop_reason: Option<String>,
+}
+
+/// `params` field of an `elicitation/create` request, see
+/// https://modelcontextprotocol.io/specification/2025-06-18/client/elicitation
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ElicitRequest {
+    /// Explains to the user what the information is needed for
+    pub message: String,
+    pub requested_schema: ElicitSchema,
+}
+
+/// A flat object schema describing the information requested from the user
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct ElicitSchema {
+    /// The fields of the object, in the order they are declared in
+    #[serde(default, deserialize_with = "deserialize_properties")]
+    pub properties: Vec<(String, PrimitiveSchema)>,
+    #[serde(default)]
+    pub required: Vec<String>,
+}
+
+fn deserialize_properties<'de, D>(deserializer: D) -> Result<Vec<(String, PrimitiveSchema)>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    use serde::de::Error as _;
+    serde_json::Map::<String, serde_json::Value>::deserialize(deserializer)?
+        .into_iter()
+        .map(|(name, schema)| {
+            let schema = serde_json::from_value(schema).map_err(D::Error::custom)?;
+            Ok((name, schema))
+        })
+        .collect()
+}
+
+/// Schema of a single field of an [ElicitSchema]
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum PrimitiveSchema {
+    #[serde(rename_all = "camelCase")]
+    String {
+        title: Option<String>,
+        description: Option<String>,
+        /// The allowed values, if restricted
+        #[serde(rename = "enum")]
+        choices: Option<Vec<String>>,
+        /// Display names of the allowed values
+        enum_names: Option<Vec<String>>,
+        min_length: Option<usize>,
+        max_length: Option<usize>,
+        /// One of `email`, `uri`, `date` or `date-time`
+        format: Option<String>,
+        default: Option<String>,
+    },
+    Number {
+        title: Option<String>,
+        description: Option<String>,
+        minimum: Option<f64>,
+        maximum: Option<f64>,
+        default: Option<f64>,
+    },
+    Integer {
+        title: Option<String>,
+        description: Option<String>,
+        minimum: Option<i64>,
+        maximum: Option<i64>,
+        default: Option<i64>,
+    },
+    Boolean {
+        title: Option<String>,
+        description: Option<String>,
+        default: Option<bool>,
+    },
+}
+
+impl PrimitiveSchema {
+    pub fn title(&self) -> Option<&str> {
+        match self {
+            Self::String { title, .. }
+            | Self::Number { title, .. }
+            | Self::Integer { title, .. }
+            | Self::Boolean { title, .. } => title.as_deref(),
+        }
+    }
+
+    pub fn description(&self) -> Option<&str> {
+        match self {
+            Self::String { description, .. }
+            | Self::Number { description, .. }
+            | Self::Integer { description, .. }
+            | Self::Boolean { description, .. } => description.as_deref(),
+        }
+    }
+}
+
+/// `result` field of the response to an `elicitation/create` request
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ElicitResult {
+    pub action: ElicitAction,
+    /// The submitted values, only present if the user accepted
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub content: Option<serde_json::Map<String, serde_json::Value>>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ElicitAction {
+    /// The user submitted the form
+    Accept,
+    /// The user explicitly refused to provide the information
+    Decline,
+    /// The user dismissed the form without making a choice
+    Cancel,
+}
+
+impl ElicitResult {
+    pub fn decline() -> Self {
+        Self {
+            action: ElicitAction::Decline,
+            content: None,
+        }
+    }
+
+    pub fn cancel() -> Self {
+        Self {
+            action: ElicitAction::Cancel,
+            content: None,
+        }
+    }
+}
+
+/// `params` field of a `notifications/progress` notification, see
+/// https://modelcontextprotocol.io/specification/2025-03-26/basic/utilities/progress
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProgressNotification {
+    /// The token sent with the request the progress is reported for
+    pub progress_token: serde_json::Value,
+    /// Increases with each notification, even if the total is unknown
+    pub progress: f64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub total: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
+}
+
+impl std::fmt::Display for ProgressNotification {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if let Some(message) = &self.message {
+            write!(f, "{message} ")?;
+        }
+        match self.total {
+            Some(total) if total > 0.0 => write!(f, "({:.0}%)", (self.progress / total * 100.0).min(100.0)),
+            _ => write!(f, "({})", self.progress),
+        }
+    }
+}
+
+/// A directory the server may operate in, see
+/// https://modelcontextprotocol.io/specification/2025-03-26/client/roots
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Root {
+    /// A `file://` uri
+    pub uri: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+}
+
+impl Root {
+    pub fn from_path(path: &std::path::Path) -> Self {
+        Self {
+            uri: url::Url::from_directory_path(path)
+                .map_or_else(|_not_absolute| format!("file://{}", path.display()), String::from),
+            name: path.file_name().map(|name| name.to_string_lossy().to_string()),
+        }
+    }
+}
+
+/// `result` field of the response to a `roots/list` request
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RootsListResult {
+    pub roots: Vec<Root>,
+}
+
 /// Content of a message
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(tag = "type", rename_all = "camelCase")]
@@ -246,3 +614,74 @@ pub struct ServerCapabilities {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub tools: Option<serde_json::Value>,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_elicit_request_deserialize() {
+        let request = serde_json::from_value::<ElicitRequest>(serde_json::json!({
+            "message": "Pick the deployment target",
+            "requestedSchema": {
+                "type": "object",
+                "properties": {
+                    "environment": { "type": "string", "enum": ["dev", "prod"], "enumNames": ["Development", "Production"] },
+                    "replicas": { "type": "integer", "minimum": 1, "default": 2 },
+                    "confirm": { "type": "boolean", "title": "Confirm" },
+                },
+                "required": ["environment"],
+            },
+        }))
+        .unwrap();
+        let names = request
+            .requested_schema
+            .properties
+            .iter()
+            .map(|(name, _)| name.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(names, vec!["environment", "replicas", "confirm"]);
+        assert_eq!(request.requested_schema.properties[1].1, PrimitiveSchema::Integer {
+            title: None,
+            description: None,
+            minimum: Some(1),
+            maximum: None,
+            default: Some(2),
+        });
+        assert_eq!(request.requested_schema.properties[2].1.title(), Some("Confirm"));
+        assert_eq!(request.requested_schema.required, vec!["environment"]);
+
+        assert_eq!(
+            serde_json::to_value(ElicitResult::decline()).unwrap(),
+            serde_json::json!({ "action": "decline" })
+        );
+    }
+
+    #[test]
+    fn test_resource_template_expand() {
+        let template = ResourceTemplateInfo {
+            uri_template: "repo://{owner}/{repo}/files{/path}{?ref,depth}".to_string(),
+            name: "files".to_string(),
+            description: None,
+            mime_type: None,
+        };
+        assert_eq!(template.variables(), vec!["owner", "repo", "path", "ref", "depth"]);
+
+        let values = HashMap::from([
+            ("owner".to_string(), "aws".to_string()),
+            ("repo".to_string(), "q cli".to_string()),
+            ("path".to_string(), "src/main.rs".to_string()),
+            ("ref".to_string(), "main".to_string()),
+        ]);
+        assert_eq!(
+            template.expand(&values),
+            "repo://aws/q%20cli/files/src%2Fmain.rs?ref=main"
+        );
+
+        let template = ResourceTemplateInfo {
+            uri_template: "file://{+path}".to_string(),
+            ..template
+        };
+        assert_eq!(template.expand(&values), "file://src/main.rs");
+    }
+}
@@ -1,9 +1,15 @@
 use thiserror::Error;
 
 use super::{
+    CreateMessageRequest,
+    CreateMessageResult,
+    ElicitRequest,
+    ElicitResult,
+    ProgressNotification,
     PromptsListResult,
     ResourceTemplatesListResult,
     ResourcesListResult,
+    Root,
     ToolsListResult,
 };
 
@@ -34,6 +40,27 @@ pub trait Messenger: std::fmt::Debug + Send + Sync + 'static {
         result: eyre::Result<ResourceTemplatesListResult>,
     ) -> Result<(), MessengerError>;
 
+    /// Signals to the consumer that a resource it subscribed to has changed
+    async fn send_resource_updated_notification(&self, uri: String) -> Result<(), MessengerError>;
+
+    /// Sends progress the server reported on a request to the consumer
+    async fn send_progress_notification(&self, progress: ProgressNotification) -> Result<(), MessengerError>;
+
+    /// Asks the consumer to sample the model on behalf of the server, see
+    /// https://modelcontextprotocol.io/specification/2025-03-26/client/sampling
+    /// Errors are reported to the server, e.g. if the user rejected the request.
+    async fn send_sampling_request(&self, request: CreateMessageRequest)
+    -> Result<CreateMessageResult, MessengerError>;
+
+    /// Retrieves the directories the server may operate in from the consumer, see
+    /// https://modelcontextprotocol.io/specification/2025-03-26/client/roots
+    async fn list_roots(&self) -> Result<Vec<Root>, MessengerError>;
+
+    /// Asks the consumer to request information from the user on behalf of the server, see
+    /// https://modelcontextprotocol.io/specification/2025-06-18/client/elicitation
+    /// Declining or cancelling are results rather than errors.
+    async fn send_elicitation_request(&self, request: ElicitRequest) -> Result<ElicitResult, MessengerError>;
+
     /// Signals to the orchestrator that a server has started initializing
     async fn send_init_msg(&self) -> Result<(), MessengerError>;
 
@@ -75,6 +102,29 @@ impl Messenger for NullMessenger {
         Ok(())
     }
 
+    async fn send_resource_updated_notification(&self, _uri: String) -> Result<(), MessengerError> {
+        Ok(())
+    }
+
+    async fn send_progress_notification(&self, _progress: ProgressNotification) -> Result<(), MessengerError> {
+        Ok(())
+    }
+
+    async fn send_sampling_request(
+        &self,
+        _request: CreateMessageRequest,
+    ) -> Result<CreateMessageResult, MessengerError> {
+        Err(MessengerError::Custom("Sampling is not supported".to_string()))
+    }
+
+    async fn list_roots(&self) -> Result<Vec<Root>, MessengerError> {
+        Ok(Vec::new())
+    }
+
+    async fn send_elicitation_request(&self, _request: ElicitRequest) -> Result<ElicitResult, MessengerError> {
+        Err(MessengerError::Custom("Elicitation is not supported".to_string()))
+    }
+
     async fn send_init_msg(&self) -> Result<(), MessengerError> {
         Ok(())
     }
@@ -0,0 +1,962 @@
+//! Client side of the Streamable HTTP transport, falling back to the legacy HTTP+SSE transport
+//! for servers that do not support it.
+//!
+//! Referencing https://modelcontextprotocol.io/specification/2025-03-26/basic/transports#streamable-http
+//! and https://modelcontextprotocol.io/specification/2024-11-05/basic/transports#http-with-sse
+use std::collections::HashMap;
+use std::sync::{
+    Arc,
+    Mutex,
+    RwLock,
+};
+use std::time::Duration;
+
+use reqwest::header::{
+    ACCEPT,
+    CONTENT_TYPE,
+    HeaderMap,
+    HeaderName,
+    HeaderValue,
+};
+use reqwest::{
+    Method,
+    RequestBuilder,
+    Response,
+    StatusCode,
+};
+use tokio::sync::{
+    broadcast,
+    watch,
+};
+use tokio::task::JoinHandle;
+use url::Url;
+
+use super::base_protocol::{
+    JsonRpcMessage,
+    JsonRpcNotification,
+    JsonRpcVersion,
+};
+use super::{
+    Listener,
+    LogListener,
+    Transport,
+    TransportError,
+};
+
+const SESSION_ID_HEADER: &str = "mcp-session-id";
+const LAST_EVENT_ID_HEADER: &str = "last-event-id";
+const EVENT_STREAM: &str = "text/event-stream";
+/// Number of consecutive failed attempts after which a connection is no longer retried.
+const MAX_RECONNECT_ATTEMPTS: u32 = 5;
+const RECONNECT_BASE_DELAY: Duration = Duration::from_millis(250);
+
+#[derive(Debug)]
+pub struct JsonRpcHttpTransport {
+    inner: Arc<HttpClientInner>,
+    receiver: broadcast::Receiver<Result<JsonRpcMessage, TransportError>>,
+    log_receiver: broadcast::Receiver<String>,
+}
+
+#[derive(Debug)]
+struct HttpClientInner {
+    url: Url,
+    headers: HeaderMap,
+    client: reqwest::Client,
+    mode: tokio::sync::RwLock<Mode>,
+    session_id: RwLock<Option<String>>,
+    /// The initialize request, sent again if the server loses the session.
+    initialize: Mutex<Option<JsonRpcMessage>>,
+    tx: broadcast::Sender<Result<JsonRpcMessage, TransportError>>,
+    log_tx: broadcast::Sender<String>,
+    tasks: Mutex<Vec<JoinHandle<()>>>,
+}
+
+#[derive(Debug)]
+enum Mode {
+    /// No message has been sent yet, the transport supported by the server is unknown.
+    Unknown,
+    Streamable,
+    /// The legacy HTTP+SSE transport, where messages are posted to the endpoint announced on
+    /// the event stream. The endpoint is [None] while the stream is reconnecting.
+    Sse {
+        endpoint: watch::Receiver<Option<Url>>,
+    },
+}
+
+impl JsonRpcHttpTransport {
+    pub fn client(url: &str, headers: &HashMap<String, String>) -> Result<Self, TransportError> {
+        let url = Url::parse(url).map_err(|e| TransportError::Custom(format!("Invalid server url '{url}': {e}")))?;
+        let mut header_map = HeaderMap::new();
+        for (name, value) in headers {
+            let name = HeaderName::try_from(name.as_str())
+                .map_err(|e| TransportError::Custom(format!("Invalid header name '{name}': {e}")))?;
+            let value = HeaderValue::try_from(value.as_str())
+                .map_err(|e| TransportError::Custom(format!("Invalid value for header '{name}': {e}")))?;
+            header_map.insert(name, value);
+        }
+        let client = crate::request::new_client().map_err(|e| TransportError::Http(e.to_string()))?;
+
+        let (tx, receiver) = broadcast::channel::<Result<JsonRpcMessage, TransportError>>(100);
+        let (log_tx, log_receiver) = broadcast::channel::<String>(100);
+        Ok(Self {
+            inner: Arc::new(HttpClientInner {
+                url,
+                headers: header_map,
+                client,
+                mode: tokio::sync::RwLock::new(Mode::Unknown),
+                session_id: RwLock::new(None),
+                initialize: Mutex::new(None),
+                tx,
+                log_tx,
+                tasks: Mutex::new(Vec::new()),
+            }),
+            receiver,
+            log_receiver,
+        })
+    }
+}
+
+impl Drop for JsonRpcHttpTransport {
+    fn drop(&mut self) {
+        for task in self.inner.tasks.lock().expect("lock poisoned").drain(..) {
+            task.abort();
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl Transport for JsonRpcHttpTransport {
+    async fn send(&self, msg: &JsonRpcMessage) -> Result<(), TransportError> {
+        let inner = &self.inner;
+        if msg.is_initialize() {
+            *inner.initialize.lock().expect("lock poisoned") = Some(msg.clone());
+        }
+
+        let mode = inner.mode.read().await;
+        if let Mode::Unknown = *mode {
+            drop(mode);
+            let mut mode = inner.mode.write().await;
+            // Servers that only support the legacy transport reject the initial post.
+            let response = inner.post(&inner.url, msg).await?;
+            if msg.is_initialize() && is_legacy_rejection(response.status()) {
+                inner.log(format!(
+                    "Server responded with {} to initialize, falling back to the HTTP+SSE transport",
+                    response.status()
+                ));
+                let endpoint = inner.connect_sse();
+                *mode = Mode::Sse {
+                    endpoint: endpoint.clone(),
+                };
+                drop(mode);
+                return inner.send_sse(endpoint, msg).await;
+            }
+            *mode = Mode::Streamable;
+            drop(mode);
+            return inner.handle_response(response, msg).await;
+        }
+
+        match &*mode {
+            Mode::Sse { endpoint } => {
+                let endpoint = endpoint.clone();
+                drop(mode);
+                inner.send_sse(endpoint, msg).await
+            },
+            _ => {
+                drop(mode);
+                inner.send_streamable(msg).await
+            },
+        }
+    }
+
+    fn get_listener(&self) -> impl Listener {
+        HttpListener {
+            receiver: self.receiver.resubscribe(),
+        }
+    }
+
+    async fn shutdown(&self) -> Result<(), TransportError> {
+        for task in self.inner.tasks.lock().expect("lock poisoned").drain(..) {
+            task.abort();
+        }
+        let session_id = self.inner.session_id.read().expect("lock poisoned").clone();
+        if let (Mode::Streamable, Some(session_id)) = (&*self.inner.mode.read().await, session_id) {
+            // Servers that do not allow clients to terminate sessions respond with 405.
+            let _ = self
+                .inner
+                .request(Method::DELETE, &self.inner.url)
+                .header(SESSION_ID_HEADER, session_id)
+                .send()
+                .await;
+        }
+        Ok(())
+    }
+
+    fn get_log_listener(&self) -> impl LogListener {
+        HttpLogListener {
+            receiver: self.log_receiver.resubscribe(),
+        }
+    }
+}
+
+impl HttpClientInner {
+    fn request(&self, method: Method, url: &Url) -> RequestBuilder {
+        self.client.request(method, url.clone()).headers(self.headers.clone())
+    }
+
+    fn log(&self, msg: String) {
+        let _ = self.log_tx.send(msg);
+    }
+
+    fn spawn(&self, task: impl Future<Output = ()> + Send + 'static) {
+        let mut tasks = self.tasks.lock().expect("lock poisoned");
+        tasks.retain(|task| !task.is_finished());
+        tasks.push(tokio::spawn(task));
+    }
+
+    fn session_id(&self) -> Option<String> {
+        self.session_id.read().expect("lock poisoned").clone()
+    }
+
+    /// Posts `msg` to `url`, retrying if the server cannot be reached.
+    async fn post(&self, url: &Url, msg: &JsonRpcMessage) -> Result<Response, TransportError> {
+        let body = serde_json::to_vec(msg)?;
+        let mut attempt = 0;
+        loop {
+            let mut request = self
+                .request(Method::POST, url)
+                .header(CONTENT_TYPE, "application/json")
+                .header(ACCEPT, format!("application/json, {EVENT_STREAM}"))
+                .body(body.clone());
+            if let Some(session_id) = self.session_id() {
+                request = request.header(SESSION_ID_HEADER, session_id);
+            }
+            match request.send().await {
+                Ok(response) => break Ok(response),
+                Err(err) if (err.is_connect() || err.is_timeout()) && attempt < MAX_RECONNECT_ATTEMPTS => {
+                    attempt += 1;
+                    self.log(format!("Failed to reach {}, retrying: {err}", self.url));
+                    tokio::time::sleep(reconnect_delay(attempt)).await;
+                },
+                Err(err) => break Err(err.into()),
+            }
+        }
+    }
+
+    async fn send_streamable(self: &Arc<Self>, msg: &JsonRpcMessage) -> Result<(), TransportError> {
+        let had_session = self.session_id().is_some();
+        let mut response = self.post(&self.url, msg).await?;
+        // The server responds with 404 once it has discarded the session, e.g. after a restart.
+        if had_session && response.status() == StatusCode::NOT_FOUND && !msg.is_initialize() {
+            self.reinitialize().await?;
+            response = self.post(&self.url, msg).await?;
+        }
+        self.handle_response(response, msg).await
+    }
+
+    async fn handle_response(self: &Arc<Self>, response: Response, msg: &JsonRpcMessage) -> Result<(), TransportError> {
+        let status = response.status();
+        if !status.is_success() {
+            let body = response.text().await.unwrap_or_default();
+            return Err(TransportError::Http(format!("Server responded with {status}: {body}")));
+        }
+        if let Some(session_id) = response.headers().get(SESSION_ID_HEADER).and_then(|v| v.to_str().ok()) {
+            *self.session_id.write().expect("lock poisoned") = Some(session_id.to_string());
+        }
+
+        let content_type = response
+            .headers()
+            .get(CONTENT_TYPE)
+            .and_then(|v| v.to_str().ok())
+            .unwrap_or_default()
+            .to_string();
+        if content_type.starts_with(EVENT_STREAM) {
+            let inner = Arc::clone(self);
+            self.spawn(async move {
+                let mut last_event_id = None;
+                if let Err(err) = inner.read_event_stream(response, &mut last_event_id, None).await {
+                    inner.log(format!("Response stream from {} was interrupted: {err}", inner.url));
+                    if last_event_id.is_some() {
+                        inner.listen(last_event_id, false).await;
+                    }
+                }
+            });
+        } else if status != StatusCode::ACCEPTED {
+            let body = response.bytes().await?;
+            if !body.is_empty() {
+                self.forward(&body);
+            }
+        }
+
+        // The server may offer a stream for messages that are not responses to a request once
+        // the session is initialized.
+        if matches!(msg, JsonRpcMessage::Notification(n) if n.method == "notifications/initialized") {
+            let inner = Arc::clone(self);
+            self.spawn(async move { inner.listen(None, true).await });
+        }
+        Ok(())
+    }
+
+    /// Starts a new session by replaying the initialize handshake.
+    async fn reinitialize(&self) -> Result<(), TransportError> {
+        let Some(initialize) = self.initialize.lock().expect("lock poisoned").clone() else {
+            return Err(TransportError::Http(
+                "Session expired before it was initialized".to_string(),
+            ));
+        };
+        self.log(format!("Session with {} expired, starting a new session", self.url));
+        *self.session_id.write().expect("lock poisoned") = None;
+
+        let response = self.post(&self.url, &initialize).await?;
+        if !response.status().is_success() {
+            return Err(TransportError::Http(format!(
+                "Failed to start a new session, server responded with {}",
+                response.status()
+            )));
+        }
+        let session_id = response
+            .headers()
+            .get(SESSION_ID_HEADER)
+            .and_then(|v| v.to_str().ok())
+            .map(str::to_string);
+        *self.session_id.write().expect("lock poisoned") = session_id;
+
+        let response = self.post(&self.url, &initialized_notification()).await?;
+        if !response.status().is_success() {
+            return Err(TransportError::Http(format!(
+                "Failed to start a new session, server responded with {}",
+                response.status()
+            )));
+        }
+        Ok(())
+    }
+
+    /// Listens to the stream of server initiated messages, reconnecting if it is interrupted.
+    ///
+    /// With `optional`, gives up without an error if the server does not offer a stream.
+    async fn listen(&self, mut last_event_id: Option<String>, optional: bool) {
+        let mut attempt = 0;
+        loop {
+            let mut request = self.request(Method::GET, &self.url).header(ACCEPT, EVENT_STREAM);
+            if let Some(session_id) = self.session_id() {
+                request = request.header(SESSION_ID_HEADER, session_id);
+            }
+            if let Some(id) = &last_event_id {
+                request = request.header(LAST_EVENT_ID_HEADER, id);
+            }
+            match request.send().await {
+                Ok(response) if response.status() == StatusCode::METHOD_NOT_ALLOWED && optional => return,
+                Ok(response) if response.status().is_success() => {
+                    attempt = 0;
+                    if let Err(err) = self.read_event_stream(response, &mut last_event_id, None).await {
+                        self.log(format!("Event stream from {} was interrupted: {err}", self.url));
+                    }
+                },
+                Ok(response) => self.log(format!(
+                    "Failed to open event stream from {}, server responded with {}",
+                    self.url,
+                    response.status()
+                )),
+                Err(err) => self.log(format!("Failed to open event stream from {}: {err}", self.url)),
+            }
+
+            attempt += 1;
+            if attempt > MAX_RECONNECT_ATTEMPTS {
+                self.log(format!("Giving up on the event stream from {}", self.url));
+                return;
+            }
+            tokio::time::sleep(reconnect_delay(attempt)).await;
+        }
+    }
+
+    /// Opens the event stream of the legacy HTTP+SSE transport in the background. The returned
+    /// channel is updated with the endpoint that messages must be posted to.
+    fn connect_sse(self: &Arc<Self>) -> watch::Receiver<Option<Url>> {
+        let (endpoint_tx, endpoint_rx) = watch::channel(None);
+        let inner = Arc::clone(self);
+        self.spawn(async move {
+            let mut attempt = 0;
+            let mut connected_before = false;
+            loop {
+                let request = inner.request(Method::GET, &inner.url).header(ACCEPT, EVENT_STREAM);
+                match request.send().await {
+                    Ok(response) if response.status().is_success() => {
+                        attempt = 0;
+                        let mut last_event_id = None;
+                        let reconnected = connected_before;
+                        connected_before = true;
+                        let result = inner
+                            .read_event_stream(response, &mut last_event_id, Some((&endpoint_tx, reconnected)))
+                            .await;
+                        let _ = endpoint_tx.send(None);
+                        if let Err(err) = result {
+                            inner.log(format!("Event stream from {} was interrupted: {err}", inner.url));
+                        }
+                    },
+                    Ok(response) => inner.log(format!(
+                        "Failed to open event stream from {}, server responded with {}",
+                        inner.url,
+                        response.status()
+                    )),
+                    Err(err) => inner.log(format!("Failed to open event stream from {}: {err}", inner.url)),
+                }
+
+                attempt += 1;
+                if attempt > MAX_RECONNECT_ATTEMPTS {
+                    inner.log(format!("Giving up on the event stream from {}", inner.url));
+                    let _ = inner
+                        .tx
+                        .send(Err(TransportError::Http(format!("Lost connection to {}", inner.url))));
+                    return;
+                }
+                tokio::time::sleep(reconnect_delay(attempt)).await;
+            }
+        });
+        endpoint_rx
+    }
+
+    async fn send_sse(
+        &self,
+        mut endpoint: watch::Receiver<Option<Url>>,
+        msg: &JsonRpcMessage,
+    ) -> Result<(), TransportError> {
+        let url = endpoint
+            .wait_for(Option::is_some)
+            .await
+            .map_err(|_closed| TransportError::Http(format!("Lost connection to {}", self.url)))?
+            .clone()
+            .expect("endpoint is some");
+        let response = self.post(&url, msg).await?;
+        if !response.status().is_success() {
+            let status = response.status();
+            let body = response.text().await.unwrap_or_default();
+            return Err(TransportError::Http(format!("Server responded with {status}: {body}")));
+        }
+        Ok(())
+    }
+
+    /// Forwards every message on the event stream until it ends.
+    ///
+    /// For the legacy transport, `endpoint` receives the endpoint announced by the server. If
+    /// the stream is a reconnection, the session is initialized again on the new endpoint.
+    async fn read_event_stream(
+        &self,
+        mut response: Response,
+        last_event_id: &mut Option<String>,
+        endpoint: Option<(&watch::Sender<Option<Url>>, bool)>,
+    ) -> Result<(), TransportError> {
+        let mut parser = SseParser::default();
+        while let Some(chunk) = response.chunk().await? {
+            for event in parser.push(&chunk) {
+                if let Some(id) = event.id {
+                    *last_event_id = Some(id);
+                }
+                match (event.event.as_deref(), endpoint) {
+                    (Some("endpoint"), Some((endpoint_tx, reconnected))) => {
+                        let url = self
+                            .url
+                            .join(event.data.trim())
+                            .map_err(|e| TransportError::Http(format!("Invalid endpoint '{}': {e}", event.data)))?;
+                        if reconnected {
+                            self.log(format!("Reconnected to {}, starting a new session", self.url));
+                            self.reinitialize_sse(&url).await?;
+                        }
+                        let _ = endpoint_tx.send(Some(url));
+                    },
+                    (None | Some("message"), _) if !event.data.trim().is_empty() => {
+                        self.forward(event.data.as_bytes());
+                    },
+                    _ => {},
+                }
+            }
+        }
+        Ok(())
+    }
+
+    async fn reinitialize_sse(&self, endpoint: &Url) -> Result<(), TransportError> {
+        let Some(initialize) = self.initialize.lock().expect("lock poisoned").clone() else {
+            return Ok(());
+        };
+        // The response is received on the event stream and ignored since nothing awaits its id.
+        self.post(endpoint, &initialize).await?;
+        self.post(endpoint, &initialized_notification()).await?;
+        Ok(())
+    }
+
+    /// Sends the messages in `body`, a single message or a batch, to the listeners.
+    fn forward(&self, body: &[u8]) {
+        match serde_json::from_slice::<serde_json::Value>(body) {
+            Ok(serde_json::Value::Array(batch)) => {
+                for msg in batch {
+                    let _ = self
+                        .tx
+                        .send(serde_json::from_value::<JsonRpcMessage>(msg).map_err(Into::into));
+                }
+            },
+            Ok(msg) => {
+                let _ = self
+                    .tx
+                    .send(serde_json::from_value::<JsonRpcMessage>(msg).map_err(Into::into));
+            },
+            Err(err) => {
+                let _ = self.tx.send(Err(err.into()));
+            },
+        }
+    }
+}
+
+/// Servers that only support the legacy transport respond to the initial post with one of these.
+fn is_legacy_rejection(status: StatusCode) -> bool {
+    matches!(
+        status,
+        StatusCode::BAD_REQUEST | StatusCode::NOT_FOUND | StatusCode::METHOD_NOT_ALLOWED
+    )
+}
+
+fn initialized_notification() -> JsonRpcMessage {
+    JsonRpcMessage::Notification(JsonRpcNotification {
+        jsonrpc: JsonRpcVersion::default(),
+        method: "notifications/initialized".to_string(),
+        params: None,
+    })
+}
+
+fn reconnect_delay(attempt: u32) -> Duration {
+    RECONNECT_BASE_DELAY * 2u32.pow(attempt.saturating_sub(1).min(5))
+}
+
+/// An event received on a `text/event-stream`.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+struct SseEvent {
+    event: Option<String>,
+    data: String,
+    id: Option<String>,
+}
+
+/// Incrementally parses a `text/event-stream`, see https://html.spec.whatwg.org/multipage/server-sent-events.html#parsing-an-event-stream
+#[derive(Debug, Default)]
+struct SseParser {
+    buffer: Vec<u8>,
+    current: Option<SseEvent>,
+}
+
+impl SseParser {
+    /// Returns the events completed by `chunk`.
+    fn push(&mut self, chunk: &[u8]) -> Vec<SseEvent> {
+        self.buffer.extend_from_slice(chunk);
+        let mut events = Vec::new();
+        while let Some(end) = self.buffer.iter().position(|b| *b == b'\n') {
+            let line = self.buffer.drain(..=end).collect::<Vec<_>>();
+            let line = String::from_utf8_lossy(&line);
+            let line = line.trim_end_matches(['\n', '\r']);
+            if line.is_empty() {
+                events.extend(self.current.take());
+                continue;
+            }
+            if line.starts_with(':') {
+                continue;
+            }
+            let (field, value) = match line.split_once(':') {
+                Some((field, value)) => (field, value.strip_prefix(' ').unwrap_or(value)),
+                None => (line, ""),
+            };
+            let event = self.current.get_or_insert_with(SseEvent::default);
+            match field {
+                "event" => event.event = Some(value.to_string()),
+                "data" => {
+                    if !event.data.is_empty() {
+                        event.data.push('\n');
+                    }
+                    event.data.push_str(value);
+                },
+                "id" => event.id = Some(value.to_string()),
+                _ => {},
+            }
+        }
+        events
+    }
+}
+
+pub struct HttpListener {
+    pub receiver: broadcast::Receiver<Result<JsonRpcMessage, TransportError>>,
+}
+
+#[async_trait::async_trait]
+impl Listener for HttpListener {
+    async fn recv(&mut self) -> Result<JsonRpcMessage, TransportError> {
+        self.receiver.recv().await?
+    }
+}
+
+pub struct HttpLogListener {
+    pub receiver: broadcast::Receiver<String>,
+}
+
+#[async_trait::async_trait]
+impl LogListener for HttpLogListener {
+    async fn recv(&mut self) -> Result<String, TransportError> {
+        Ok(self.receiver.recv().await?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::{
+        Value,
+        json,
+    };
+    use tokio::io::{
+        AsyncBufReadExt,
+        AsyncReadExt,
+        AsyncWriteExt,
+        BufReader,
+    };
+    use tokio::net::{
+        TcpListener,
+        TcpStream,
+    };
+    use tokio::sync::mpsc;
+
+    use super::*;
+    use crate::mcp_client::{
+        Client,
+        HttpClientConfig,
+        HttpTransport,
+    };
+
+    /// A minimal MCP server, speaking either the Streamable HTTP or the legacy HTTP+SSE
+    /// transport. Every request is served on its own connection.
+    #[derive(Debug, Default)]
+    struct TestServer {
+        legacy: bool,
+        /// The current session of the streamable server, sessions are numbered from 1.
+        session: Option<u32>,
+        sessions_started: u32,
+        /// Sends messages on the event stream of the legacy server.
+        stream: Option<mpsc::UnboundedSender<Value>>,
+        authorization: Vec<String>,
+        /// Messages posted to the legacy server.
+        received: Vec<Value>,
+    }
+
+    struct TestRequest {
+        method: String,
+        path: String,
+        headers: HashMap<String, String>,
+        body: Vec<u8>,
+    }
+
+    async fn start_server(legacy: bool) -> (String, Arc<Mutex<TestServer>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/mcp", listener.local_addr().unwrap());
+        let state = Arc::new(Mutex::new(TestServer {
+            legacy,
+            ..Default::default()
+        }));
+        let server = Arc::clone(&state);
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                tokio::spawn(serve(stream, Arc::clone(&server)));
+            }
+        });
+        (url, state)
+    }
+
+    async fn serve(stream: TcpStream, state: Arc<Mutex<TestServer>>) {
+        let mut stream = BufReader::new(stream);
+        let mut line = String::new();
+        stream.read_line(&mut line).await.unwrap();
+        let mut parts = line.split_whitespace();
+        let method = parts.next().unwrap_or_default().to_string();
+        let path = parts.next().unwrap_or_default().to_string();
+        let mut headers = HashMap::new();
+        loop {
+            let mut line = String::new();
+            stream.read_line(&mut line).await.unwrap();
+            let Some((name, value)) = line.trim_end().split_once(':') else {
+                break;
+            };
+            headers.insert(name.to_lowercase(), value.trim().to_string());
+        }
+        let mut body = vec![0; headers.get("content-length").map_or(0, |l| l.parse().unwrap())];
+        stream.read_exact(&mut body).await.unwrap();
+        let request = TestRequest {
+            method,
+            path,
+            headers,
+            body,
+        };
+
+        let mut stream = stream.into_inner();
+        let legacy = {
+            let mut state = state.lock().unwrap();
+            state
+                .authorization
+                .extend(request.headers.get("authorization").cloned());
+            state.legacy
+        };
+        if legacy {
+            serve_legacy(&mut stream, request, state).await;
+        } else {
+            serve_streamable(&mut stream, request, state).await;
+        }
+    }
+
+    async fn serve_streamable(stream: &mut TcpStream, request: TestRequest, state: Arc<Mutex<TestServer>>) {
+        if request.method != "POST" {
+            return respond(stream, "405 Method Not Allowed", &[], "").await;
+        }
+        let msg = serde_json::from_slice::<Value>(&request.body).unwrap();
+        let session = {
+            let mut state = state.lock().unwrap();
+            if msg["method"] == "initialize" {
+                state.sessions_started += 1;
+                state.session = Some(state.sessions_started);
+            }
+            state.session.filter(|s| {
+                msg["method"] == "initialize" || request.headers.get(SESSION_ID_HEADER) == Some(&s.to_string())
+            })
+        };
+        let Some(session) = session else {
+            return respond(stream, "404 Not Found", &[], "").await;
+        };
+
+        if msg.get("id").is_none() {
+            return respond(stream, "202 Accepted", &[], "").await;
+        }
+        let response = result_for(&msg, session);
+        if msg["method"] == "initialize" {
+            let session = session.to_string();
+            respond(
+                stream,
+                "200 OK",
+                &[("Content-Type", "application/json"), (SESSION_ID_HEADER, &session)],
+                &response.to_string(),
+            )
+            .await;
+        } else {
+            let log = json!({
+                "jsonrpc": "2.0",
+                "method": "notifications/message",
+                "params": { "level": "info", "data": "working" },
+            });
+            let body = format!("id: 1\ndata: {log}\n\nid: 2\ndata: {response}\n\n");
+            respond(stream, "200 OK", &[("Content-Type", EVENT_STREAM)], &body).await;
+        }
+    }
+
+    async fn serve_legacy(stream: &mut TcpStream, request: TestRequest, state: Arc<Mutex<TestServer>>) {
+        match (request.method.as_str(), request.path.as_str()) {
+            ("GET", "/mcp") => {
+                let (tx, mut rx) = mpsc::unbounded_channel();
+                state.lock().unwrap().stream = Some(tx);
+                let head = format!("HTTP/1.1 200 OK\r\nContent-Type: {EVENT_STREAM}\r\nConnection: close\r\n\r\n");
+                stream.write_all(head.as_bytes()).await.unwrap();
+                stream
+                    .write_all(b"event: endpoint\ndata: /messages?session=1\n\n")
+                    .await
+                    .unwrap();
+                while let Some(msg) = rx.recv().await {
+                    let event = format!("event: message\ndata: {msg}\n\n");
+                    if stream.write_all(event.as_bytes()).await.is_err() {
+                        break;
+                    }
+                }
+            },
+            ("POST", "/messages?session=1") => {
+                let msg = serde_json::from_slice::<Value>(&request.body).unwrap();
+                {
+                    let mut state = state.lock().unwrap();
+                    state.received.push(msg.clone());
+                    // Tool calls are answered by the test.
+                    if msg.get("id").is_some() && msg["method"] != "tools/call" {
+                        state.stream.as_ref().unwrap().send(result_for(&msg, 1)).unwrap();
+                    }
+                }
+                respond(stream, "202 Accepted", &[], "").await;
+            },
+            _ => respond(stream, "405 Method Not Allowed", &[], "").await,
+        }
+    }
+
+    fn result_for(msg: &Value, session: u32) -> Value {
+        let result = if msg["method"] == "initialize" {
+            json!({
+                "protocolVersion": "2025-03-26",
+                "capabilities": {},
+                "serverInfo": { "name": "test", "version": "1.0.0" },
+            })
+        } else {
+            json!({ "method": msg["method"], "session": session })
+        };
+        json!({ "jsonrpc": "2.0", "id": msg["id"], "result": result })
+    }
+
+    async fn respond(stream: &mut TcpStream, status: &str, headers: &[(&str, &str)], body: &str) {
+        let mut response = format!(
+            "HTTP/1.1 {status}\r\nContent-Length: {}\r\nConnection: close\r\n",
+            body.len()
+        );
+        for (name, value) in headers {
+            response.push_str(&format!("{name}: {value}\r\n"));
+        }
+        response.push_str("\r\n");
+        response.push_str(body);
+        stream.write_all(response.as_bytes()).await.unwrap();
+        stream.shutdown().await.unwrap();
+    }
+
+    fn client(url: String) -> Client<HttpTransport> {
+        Client::<HttpTransport>::from_config(HttpClientConfig {
+            server_name: "test".to_string(),
+            url,
+            headers: HashMap::from([("Authorization".to_string(), "Bearer token".to_string())]),
+            timeout: 5000,
+            client_info: json!({ "name": "TestClient", "version": "1.0.0" }),
+        })
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_streamable_http() {
+        let (url, state) = start_server(false).await;
+        let client = client(url);
+        client.init().await.unwrap();
+
+        let response = client.request("tools/list", None).await.unwrap();
+        assert_eq!(
+            response.result.unwrap(),
+            json!({ "method": "tools/list", "session": 1 })
+        );
+
+        // The server forgets the session, the client starts a new one and sends the request again.
+        state.lock().unwrap().session = None;
+        let response = client.request("prompts/list", None).await.unwrap();
+        assert_eq!(
+            response.result.unwrap(),
+            json!({ "method": "prompts/list", "session": 2 })
+        );
+
+        let state = state.lock().unwrap();
+        assert_eq!(state.sessions_started, 2);
+        assert!(!state.authorization.is_empty());
+        assert!(state.authorization.iter().all(|a| a == "Bearer token"));
+    }
+
+    #[tokio::test]
+    async fn test_legacy_sse_fallback() {
+        let (url, state) = start_server(true).await;
+        let client = client(url);
+        client.init().await.unwrap();
+
+        let response = client.request("tools/list", None).await.unwrap();
+        assert_eq!(
+            response.result.unwrap(),
+            json!({ "method": "tools/list", "session": 1 })
+        );
+        assert!(state.lock().unwrap().authorization.iter().all(|a| a == "Bearer token"));
+    }
+
+    #[tokio::test]
+    async fn test_progress_and_cancellation() {
+        let (url, state) = start_server(true).await;
+        let client = Client::<HttpTransport>::from_config(HttpClientConfig {
+            server_name: "test".to_string(),
+            url,
+            headers: HashMap::new(),
+            timeout: 500,
+            client_info: json!({ "name": "TestClient", "version": "1.0.0" }),
+        })
+        .unwrap();
+        client.init().await.unwrap();
+        let tool_call = || async {
+            loop {
+                let call = state
+                    .lock()
+                    .unwrap()
+                    .received
+                    .iter()
+                    .rev()
+                    .find(|msg| msg["method"] == "tools/call")
+                    .cloned();
+                if let Some(call) = call {
+                    break call;
+                }
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        };
+
+        // Progress keeps the request alive past the timeout.
+        let server = async {
+            let call = tool_call().await;
+            let token = call["params"]["_meta"]["progressToken"].clone();
+            let stream = state.lock().unwrap().stream.clone().unwrap();
+            for progress in 1..=3 {
+                tokio::time::sleep(Duration::from_millis(300)).await;
+                stream
+                    .send(json!({
+                        "jsonrpc": "2.0",
+                        "method": "notifications/progress",
+                        "params": { "progressToken": token, "progress": progress, "total": 3 },
+                    }))
+                    .unwrap();
+            }
+            stream.send(result_for(&call, 1)).unwrap();
+        };
+        let (response, ()) = tokio::join!(
+            client.request_with_progress("tools/call", Some(json!({ "name": "slow" }))),
+            server
+        );
+        assert_eq!(response.unwrap().result.unwrap()["method"], "tools/call");
+
+        // Dropping the request, e.g. on Ctrl+C, cancels it.
+        state.lock().unwrap().received.clear();
+        let request = client.request_with_progress("tools/call", Some(json!({ "name": "slow" })));
+        assert!(tokio::time::timeout(Duration::from_millis(100), request).await.is_err());
+        let id = tool_call().await["id"].clone();
+        let cancelled = async {
+            loop {
+                let cancelled = state
+                    .lock()
+                    .unwrap()
+                    .received
+                    .iter()
+                    .find(|msg| msg["method"] == "notifications/cancelled")
+                    .cloned();
+                if let Some(cancelled) = cancelled {
+                    break cancelled;
+                }
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        };
+        let cancelled = tokio::time::timeout(Duration::from_secs(5), cancelled).await.unwrap();
+        assert_eq!(cancelled["params"]["requestId"], id);
+    }
+
+    #[test]
+    fn test_sse_parser() {
+        let mut parser = SseParser::default();
+        assert!(
+            parser
+                .push(b": keep-alive\n\nevent: endpoint\r\ndata: /mess")
+                .is_empty()
+        );
+        assert_eq!(
+            parser.push(b"ages?id=1\r\n\r\nid: 7\ndata: {\"a\":\ndata: 1}\n\n"),
+            vec![
+                SseEvent {
+                    event: Some("endpoint".to_string()),
+                    data: "/messages?id=1".to_string(),
+                    id: None,
+                },
+                SseEvent {
+                    event: None,
+                    data: "{\"a\":\n1}".to_string(),
+                    id: Some("7".to_string()),
+                },
+            ]
+        );
+        // Incomplete events are kept until the blank line is received.
+        assert!(parser.push(b"data: partial\n").is_empty());
+        assert_eq!(parser.push(b"\n")[0].data, "partial");
+    }
+}
@@ -8,7 +8,10 @@ use std::ffi::{
     OsString,
 };
 use std::io;
-use std::path::PathBuf;
+use std::path::{
+    Path,
+    PathBuf,
+};
 use std::sync::{
     Arc,
     Mutex,
@@ -132,6 +135,17 @@ impl Env {
         }
     }
 
+    pub fn set_current_dir(&self, path: impl AsRef<Path>) -> Result<(), io::Error> {
+        use inner::Inner;
+        match &self.0 {
+            Inner::Real => std::env::set_current_dir(path),
+            Inner::Fake(fake) => {
+                fake.lock().unwrap().cwd = path.as_ref().to_path_buf();
+                Ok(())
+            },
+        }
+    }
+
     pub fn current_exe(&self) -> Result<PathBuf, io::Error> {
         use inner::Inner;
         match &self.0 {
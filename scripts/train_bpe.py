@@ -0,0 +1,154 @@
+"""Trains the byte-level BPE merges bundled with q chat for counting tokens offline.
+
+The merges are written one per line as the ids of the two tokens merged, where ids 0 to 255 are
+single bytes and id 256 + n is the token created by the merge on line n. Text is split into chunks
+before merging exactly like `split_chunks` in crates/chat-cli/src/cli/chat/token_counter/bpe.rs,
+so both must be changed together.
+
+The bundled merges were trained on the Rust sources and documentation of this repository, the Python
+standard library for more code, and the manual pages of a Debian system for prose in English and
+other languages:
+
+    python3 scripts/train_bpe.py -n 16384 crates docs /usr/lib/python3.11 /usr/share/man \\
+        -o crates/chat-cli/src/cli/chat/token_counter/bpe_merges.txt
+"""
+
+import argparse
+import gzip
+import heapq
+import pathlib
+import re
+from collections import Counter, defaultdict
+from typing import Iterator, List
+
+EXTENSIONS = {".rs", ".md", ".py", ".toml", ".json", ".sh", ".ts", ".txt", ".gz"}
+
+
+def char_class(c: str) -> str:
+    if c.isspace():
+        return "space"
+    if c.isalpha():
+        return "letter"
+    if c.isascii() and c.isdigit():
+        return "digit"
+    return "other"
+
+
+def split_chunks(text: str) -> Iterator[str]:
+    """Splits text into words, numbers of up to 3 digits, runs of punctuation and runs of
+    whitespace. A single space before a word or punctuation is kept with it."""
+    i = 0
+    n = len(text)
+    while i < n:
+        start = i
+        kind = char_class(text[i])
+        if text[i] == " " and i + 1 < n and char_class(text[i + 1]) in ("letter", "other"):
+            i += 1
+            kind = char_class(text[i])
+            while i < n and char_class(text[i]) == kind:
+                i += 1
+        elif kind == "space":
+            while i < n and char_class(text[i]) == "space":
+                i += 1
+            # Leave the last space to the word or punctuation that follows.
+            if i - start > 1 and i < n and text[i - 1] == " " and char_class(text[i]) in ("letter", "other"):
+                i -= 1
+        elif kind == "digit":
+            while i < n and i - start < 3 and char_class(text[i]) == "digit":
+                i += 1
+        else:
+            while i < n and char_class(text[i]) == kind:
+                i += 1
+        yield text[start:i]
+
+
+def read_man_page(file: pathlib.Path) -> str:
+    """Reads the text of a compressed manual page, leaving out the roff requests and escapes."""
+    with gzip.open(file, "rt", encoding="utf-8") as f:
+        lines = [line for line in f if not line.startswith((".", "'"))]
+    return re.sub(r"\\f[BIRP]|\\\(..|\\[-&e]", "", "".join(lines))
+
+
+def read_corpus(paths: List[str]) -> Counter:
+    words = Counter()
+    for path in paths:
+        root = pathlib.Path(path)
+        files = [root] if root.is_file() else sorted(p for p in root.rglob("*") if p.suffix in EXTENSIONS)
+        for file in files:
+            if "target" in file.parts or not file.is_file():
+                continue
+            try:
+                if file.suffix == ".gz":
+                    text = read_man_page(file)
+                else:
+                    text = file.read_text(encoding="utf-8")
+            except (UnicodeDecodeError, OSError, EOFError):
+                continue
+            words.update(split_chunks(text))
+    return words
+
+
+def train(words: Counter, merges: int) -> List[tuple]:
+    vocab = [list(word.encode("utf-8")) for word in words]
+    freqs = list(words.values())
+
+    pair_counts = Counter()
+    pair_words = defaultdict(set)
+    for i, symbols in enumerate(vocab):
+        for pair in zip(symbols, symbols[1:]):
+            pair_counts[pair] += freqs[i]
+            pair_words[pair].add(i)
+
+    heap = [(-count, pair) for pair, count in pair_counts.items()]
+    heapq.heapify(heap)
+
+    result = []
+    while len(result) < merges and heap:
+        count, pair = heapq.heappop(heap)
+        if pair_counts.get(pair, 0) != -count:
+            continue
+        new_id = 256 + len(result)
+        result.append(pair)
+
+        changed = set()
+        for i in pair_words.pop(pair):
+            symbols = vocab[i]
+            for old in zip(symbols, symbols[1:]):
+                pair_counts[old] -= freqs[i]
+                changed.add(old)
+            merged = []
+            j = 0
+            while j < len(symbols):
+                if j + 1 < len(symbols) and (symbols[j], symbols[j + 1]) == pair:
+                    merged.append(new_id)
+                    j += 2
+                else:
+                    merged.append(symbols[j])
+                    j += 1
+            vocab[i] = merged
+            for new in zip(merged, merged[1:]):
+                pair_counts[new] += freqs[i]
+                pair_words[new].add(i)
+                changed.add(new)
+
+        pair_counts.pop(pair, None)
+        for changed_pair in changed:
+            count = pair_counts.get(changed_pair, 0)
+            if count > 0:
+                heapq.heappush(heap, (-count, changed_pair))
+    return result
+
+
+def main():
+    parser = argparse.ArgumentParser(description="Trains the byte-level BPE merges used to count tokens")
+    parser.add_argument("paths", nargs="+", help="Files or directories of text to train on")
+    parser.add_argument("-n", "--merges", type=int, default=8192, help="The number of merges to learn")
+    parser.add_argument("-o", "--output", required=True, help="The file to write the merges to")
+    args = parser.parse_args()
+
+    merges = train(read_corpus(args.paths), args.merges)
+    pathlib.Path(args.output).write_text("".join(f"{a} {b}\n" for a, b in merges))
+
+
+if __name__ == "__main__":
+    main()